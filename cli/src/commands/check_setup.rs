@@ -27,6 +27,10 @@ pub struct ZiskCheckSetup {
     /// Verbosity (-v, -vv)
     #[arg(short, long, action = clap::ArgAction::Count, help = "Increase verbosity level")]
     pub verbose: u8, // Using u8 to hold the number of `-v`
+
+    /// Also run the self-test of the host crypto routines used to compute the witnesses
+    #[clap(long, default_value_t = false)]
+    pub self_test: bool,
 }
 
 impl ZiskCheckSetup {
@@ -44,6 +48,14 @@ impl ZiskCheckSetup {
         )
         .map_err(|e| anyhow::anyhow!("Error checking setup: {}", e))?;
 
+        if self.self_test {
+            let report = precompiles_common::self_test();
+            println!("{report}");
+            if !report.passed() {
+                anyhow::bail!("Host crypto self-test failed: {}", report.failures().join(", "));
+            }
+        }
+
         Ok(())
     }
 }
@@ -6,7 +6,7 @@ use zisk_build::ZISK_VERSION_MESSAGE;
 use zisk_sdk::{ProverClient, ZiskExecuteResult};
 
 use crate::{commands::cli_fail_if_gpu_mode, ux::print_banner};
-use zisk_common::io::ZiskStdin;
+use zisk_common::io::{TransportSpec, ZiskStdin};
 
 #[derive(Parser)]
 #[command(author, about, long_about = None, version = ZISK_VERSION_MESSAGE)]
@@ -37,9 +37,9 @@ pub struct ZiskExecute {
     #[clap(short = 'l', long, action = clap::ArgAction::SetTrue)]
     pub emulator: bool,
 
-    /// Input path
+    /// Input path, or input transport URI: `file:<path>`, `manifest:<path>` or `null:`
     #[clap(short = 'i', long)]
-    pub input: Option<PathBuf>,
+    pub input: Option<TransportSpec>,
 
     /// Setup folder path
     #[clap(short = 'k', long)]
@@ -89,15 +89,7 @@ impl ZiskExecute {
     }
 
     fn create_stdin(&mut self) -> Result<ZiskStdin> {
-        let stdin = if let Some(input) = &self.input {
-            if !input.exists() {
-                return Err(anyhow::anyhow!("Input file not found at {:?}", input.display()));
-            }
-            ZiskStdin::from_file(input)?
-        } else {
-            ZiskStdin::null()
-        };
-        Ok(stdin)
+        ZiskStdin::from_spec(self.input.clone().unwrap_or(TransportSpec::Null))
     }
 
     pub fn run_emu(&mut self, stdin: ZiskStdin) -> Result<ZiskExecuteResult> {
@@ -5,9 +5,12 @@ use colored::Colorize;
 use proofman_common::ParamsGPU;
 use std::path::PathBuf;
 use zisk_build::ZISK_VERSION_MESSAGE;
-use zisk_common::io::ZiskStdin;
 #[cfg(feature = "stats")]
 use zisk_common::ExecutorStatsEvent;
+use zisk_common::{
+    io::{TransportSpec, ZiskStdin},
+    DETERMINISTIC_TRACE_BATCHING_ENV,
+};
 use zisk_sdk::{ProverClient, ZiskProveResult};
 
 // Structure representing the 'prove' subcommand of cargo.
@@ -40,9 +43,9 @@ pub struct ZiskProve {
     #[clap(short = 'l', long, action = clap::ArgAction::SetTrue)]
     pub emulator: bool,
 
-    /// Input path
+    /// Input path, or input transport URI: `file:<path>`, `manifest:<path>` or `null:`
     #[clap(short = 'i', long)]
-    pub input: Option<PathBuf>,
+    pub input: Option<TransportSpec>,
 
     /// Setup folder path
     #[clap(short = 'k', long)]
@@ -104,12 +107,22 @@ pub struct ZiskProve {
 
     #[clap(short = 'r', long, default_value_t = false)]
     pub rma: bool,
+
+    /// Split the inputs of the trace builders in a fixed number of batches, independent of the
+    /// number of threads, so that debugging runs are reproducible
+    #[clap(long, default_value_t = false)]
+    pub deterministic_batching: bool,
 }
 
 impl ZiskProve {
     pub fn run(&mut self) -> Result<()> {
         print_banner();
 
+        if self.deterministic_batching {
+            // Set through the environment to reach the witness computation library
+            std::env::set_var(DETERMINISTIC_TRACE_BATCHING_ENV, "1");
+        }
+
         let mut gpu_params = ParamsGPU::new(self.preallocate);
 
         if self.max_streams.is_some() {
@@ -154,15 +167,7 @@ impl ZiskProve {
     }
 
     fn create_stdin(&mut self) -> Result<ZiskStdin> {
-        let stdin = if let Some(input) = &self.input {
-            if !input.exists() {
-                return Err(anyhow::anyhow!("Input file not found at {:?}", input.display()));
-            }
-            ZiskStdin::from_file(input)?
-        } else {
-            ZiskStdin::null()
-        };
-        Ok(stdin)
+        ZiskStdin::from_spec(self.input.clone().unwrap_or(TransportSpec::Null))
     }
 
     pub fn run_emu(
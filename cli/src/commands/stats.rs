@@ -3,7 +3,7 @@ use clap::Parser;
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, fs, path::PathBuf, time::Instant};
 use zisk_build::ZISK_VERSION_MESSAGE;
-use zisk_common::io::ZiskStdin;
+use zisk_common::io::{TransportSpec, ZiskStdin};
 use zisk_common::{ExecutorStats, Stats};
 use zisk_pil::*;
 use zisk_sdk::ProverClient;
@@ -39,9 +39,9 @@ pub struct ZiskStats {
     #[clap(short = 'l', long, action = clap::ArgAction::SetTrue)]
     pub emulator: bool,
 
-    /// Input path
+    /// Input path, or input transport URI: `file:<path>`, `manifest:<path>` or `null:`
     #[clap(short = 'i', long)]
-    pub input: Option<PathBuf>,
+    pub input: Option<TransportSpec>,
 
     /// Setup folder path
     #[clap(short = 'k', long)]
@@ -116,15 +116,7 @@ impl ZiskStats {
     }
 
     fn create_stdin(&mut self) -> Result<ZiskStdin> {
-        let stdin = if let Some(input) = &self.input {
-            if !input.exists() {
-                return Err(anyhow::anyhow!("Input file not found at {:?}", input.display()));
-            }
-            ZiskStdin::from_file(input)?
-        } else {
-            ZiskStdin::null()
-        };
-        Ok(stdin)
+        ZiskStdin::from_spec(self.input.clone().unwrap_or(TransportSpec::Null))
     }
 
     pub fn run_emu(&mut self, stdin: ZiskStdin) -> Result<(i32, i32, Option<ExecutorStats>)> {
@@ -0,0 +1,222 @@
+//! Test helpers to assert the memory bus messages produced by a component, e.g. a precompile,
+//! against a list of expectations written in terms of the operation, address, value and main
+//! step, instead of positional payloads:
+//!
+//! ```ignore
+//! assert_mem_bus(
+//!     &pending,
+//!     &[expect_mem_read(0xa0001000).value(7).at_step(3), expect_mem_write(0xa0002000).value(8)],
+//! );
+//! ```
+//!
+//! Only the fields set in an expectation are checked, and the messages of other buses are
+//! ignored. On mismatch, the assertion panics with a line by line comparison of every expected
+//! and captured message.
+
+use std::{collections::VecDeque, fmt};
+
+use crate::{
+    BusId, BusPayload, MemBusPayload, MEM_BUS_ID, MEM_BUS_LOAD_OP, MEM_BUS_OPS_BY_MAIN_STEP,
+    MEM_BUS_STEP_BASE, MEM_BUS_STORE_OP,
+};
+
+/// Expected memory bus message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExpectedMemOp {
+    pub is_write: bool,
+    pub addr: u64,
+    /// Value read or written
+    pub value: Option<u64>,
+    /// Main step of the operation
+    pub step: Option<u64>,
+    /// Width of the operation, in bytes
+    pub bytes: Option<u64>,
+}
+
+/// Returns the expectation of a memory read from `addr`
+pub fn expect_mem_read(addr: u64) -> ExpectedMemOp {
+    ExpectedMemOp { is_write: false, addr, value: None, step: None, bytes: None }
+}
+
+/// Returns the expectation of a memory write to `addr`
+pub fn expect_mem_write(addr: u64) -> ExpectedMemOp {
+    ExpectedMemOp { is_write: true, addr, value: None, step: None, bytes: None }
+}
+
+impl ExpectedMemOp {
+    /// Expects the value read or written
+    pub fn value(mut self, value: u64) -> Self {
+        self.value = Some(value);
+        self
+    }
+
+    /// Expects the main step of the operation
+    pub fn at_step(mut self, step: u64) -> Self {
+        self.step = Some(step);
+        self
+    }
+
+    /// Expects the width of the operation, in bytes
+    pub fn bytes(mut self, bytes: u64) -> Self {
+        self.bytes = Some(bytes);
+        self
+    }
+
+    /// Returns true if the captured message matches every field set in the expectation
+    pub fn matches(&self, op: &CapturedMemOp) -> bool {
+        (self.is_write == op.is_write)
+            && (self.addr == op.addr)
+            && self.value.is_none_or(|value| value == op.value)
+            && self.step.is_none_or(|step| step == op.step)
+            && self.bytes.is_none_or(|bytes| bytes == op.bytes)
+    }
+}
+
+impl fmt::Display for ExpectedMemOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = if self.is_write { "write" } else { "read" };
+        write!(f, "{op} addr={:#x}", self.addr)?;
+        let fields = [("value", self.value), ("step", self.step), ("bytes", self.bytes)];
+        for (name, value) in fields {
+            match value {
+                Some(value) => write!(f, " {name}={value:#x}")?,
+                None => write!(f, " {name}=*")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Memory bus message, decoded from its payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapturedMemOp {
+    pub is_write: bool,
+    pub addr: u64,
+    /// Value read or written
+    pub value: u64,
+    /// Main step of the operation
+    pub step: u64,
+    /// Width of the operation, in bytes
+    pub bytes: u64,
+}
+
+impl CapturedMemOp {
+    /// Decodes a memory bus payload
+    pub fn from_payload(data: &[u64]) -> Self {
+        let payload = MemBusPayload::from_payload(data);
+        let is_write = payload.op == MEM_BUS_STORE_OP;
+        debug_assert!(is_write || payload.op == MEM_BUS_LOAD_OP);
+        Self {
+            is_write,
+            addr: payload.addr,
+            value: if is_write { payload.value } else { payload.mem_value_0 },
+            step: payload.step.saturating_sub(MEM_BUS_STEP_BASE) / MEM_BUS_OPS_BY_MAIN_STEP,
+            bytes: payload.bytes,
+        }
+    }
+}
+
+impl fmt::Display for CapturedMemOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = if self.is_write { "write" } else { "read" };
+        write!(
+            f,
+            "{op} addr={:#x} value={:#x} step={:#x} bytes={:#x}",
+            self.addr, self.value, self.step, self.bytes
+        )
+    }
+}
+
+/// Returns the memory bus messages of the captured bus messages, in order
+pub fn captured_mem_ops(captured: &VecDeque<(BusId, Vec<u64>)>) -> Vec<CapturedMemOp> {
+    captured
+        .iter()
+        .filter(|(bus_id, _)| *bus_id == MEM_BUS_ID)
+        .map(|(_, data)| CapturedMemOp::from_payload(data))
+        .collect()
+}
+
+/// Compares the captured memory bus messages with the expectations, returning a line by line
+/// report if they do not match
+pub fn check_mem_bus(
+    captured: &VecDeque<(BusId, Vec<u64>)>,
+    expected: &[ExpectedMemOp],
+) -> Result<(), String> {
+    let captured = captured_mem_ops(captured);
+    let ok = (captured.len() == expected.len())
+        && expected.iter().zip(&captured).all(|(expected, op)| expected.matches(op));
+    if ok {
+        return Ok(());
+    }
+
+    let mut report = format!(
+        "memory bus mismatch: expected {} messages, captured {}\n",
+        expected.len(),
+        captured.len()
+    );
+    for i in 0..expected.len().max(captured.len()) {
+        match (expected.get(i), captured.get(i)) {
+            (Some(expected), Some(op)) if expected.matches(op) => {
+                report += &format!("   #{i}: {op}\n");
+            }
+            (Some(expected), Some(op)) => {
+                report += &format!("-  #{i}: {expected}\n+  #{i}: {op}\n");
+            }
+            (Some(expected), None) => report += &format!("-  #{i}: {expected}\n"),
+            (None, Some(op)) => report += &format!("+  #{i}: {op}\n"),
+            (None, None) => unreachable!(),
+        }
+    }
+    Err(report)
+}
+
+/// Asserts that the captured memory bus messages match the expectations, in order
+#[track_caller]
+pub fn assert_mem_bus(captured: &VecDeque<(BusId, Vec<u64>)>, expected: &[ExpectedMemOp]) {
+    if let Err(report) = check_mem_bus(captured, expected) {
+        panic!("{report}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_mem_bus() {
+        let mut captured = VecDeque::new();
+        let read = MemBusPayload {
+            op: MEM_BUS_LOAD_OP,
+            addr: 0xa0001000,
+            step: MEM_BUS_STEP_BASE + MEM_BUS_OPS_BY_MAIN_STEP * 3 + 2,
+            bytes: 8,
+            mem_value_0: 7,
+            ..Default::default()
+        };
+        let write = MemBusPayload {
+            op: MEM_BUS_STORE_OP,
+            addr: 0xa0002000,
+            step: MEM_BUS_STEP_BASE + MEM_BUS_OPS_BY_MAIN_STEP * 3 + 3,
+            bytes: 8,
+            value: 8,
+            ..Default::default()
+        };
+        captured.push_back((MEM_BUS_ID, read.to_payload()));
+        captured.push_back((BusId(0), vec![1, 2, 3]));
+        captured.push_back((MEM_BUS_ID, write.to_payload()));
+
+        assert_mem_bus(
+            &captured,
+            &[expect_mem_read(0xa0001000).value(7).at_step(3), expect_mem_write(0xa0002000)],
+        );
+
+        let report = check_mem_bus(&captured, &[expect_mem_read(0xa0001000).value(6)]).unwrap_err();
+        assert_eq!(
+            report,
+            "memory bus mismatch: expected 1 messages, captured 2\n\
+             -  #0: read addr=0xa0001000 value=0x6 step=* bytes=*\n\
+             +  #0: read addr=0xa0001000 value=0x7 step=0x3 bytes=0x8\n\
+             +  #1: write addr=0xa0002000 value=0x8 step=0x3 bytes=0x8\n"
+        );
+    }
+}
@@ -0,0 +1,127 @@
+//! The `BusPayload` trait maps a struct to and from the positional `Vec<u64>` representation used
+//! to send data through the bus, so that the position of every value is defined once, by the order
+//! of the struct fields, instead of by magic indexes spread across producers and consumers.
+
+/// Struct that can be sent through the bus as a positional payload.
+pub trait BusPayload: Sized {
+    /// Number of `u64` values of the payload.
+    const SIZE: usize;
+
+    /// Writes the payload values into `data`, which must be at least `SIZE` long.
+    fn write_payload(&self, data: &mut [u64]);
+
+    /// Reads the payload values from `data`, which must be at least `SIZE` long.
+    fn from_payload(data: &[u64]) -> Self;
+
+    /// Returns the payload as a vector of `SIZE` values.
+    fn to_payload(&self) -> Vec<u64> {
+        let mut data = vec![0; Self::SIZE];
+        self.write_payload(&mut data);
+        data
+    }
+}
+
+/// Macro to define a struct implementing `BusPayload`.
+///
+/// Every field is a `u64` and is mapped to the payload position given by its declaration order.
+/// The number of fields is checked at compile time against the expected payload size.
+///
+/// # Parameters
+/// * `size` - The expected payload size, e.g. the `*_BUS_DATA_SIZE` constant of the bus.
+/// * The struct definition, with named `u64` fields.
+#[macro_export]
+macro_rules! bus_payload {
+    (
+        size = $size:expr;
+        $(#[$meta:meta])*
+        $vis:vis struct $Name:ident {
+            $($(#[$field_meta:meta])* $field_vis:vis $field:ident: u64),+ $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $Name {
+            $($(#[$field_meta])* $field_vis $field: u64),+
+        }
+
+        const _: () = assert!(
+            [$(stringify!($field)),+].len() == $size,
+            concat!("bus_payload! field count of ", stringify!($Name), " does not match its size")
+        );
+
+        impl $crate::BusPayload for $Name {
+            const SIZE: usize = $size;
+
+            #[inline(always)]
+            fn write_payload(&self, data: &mut [u64]) {
+                let mut _index = 0;
+                $(
+                    data[_index] = self.$field;
+                    _index += 1;
+                )+
+            }
+
+            #[inline(always)]
+            fn from_payload(data: &[u64]) -> Self {
+                let mut _index = 0;
+                $(
+                    let $field = data[_index];
+                    _index += 1;
+                )+
+                Self { $($field),+ }
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemBusData, MemBusPayload, MEM_BUS_DATA_SIZE, MEM_BUS_STORE_OP};
+
+    crate::bus_payload! {
+        size = 3;
+        /// Payload with attributes and visibilities, which must be kept
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        struct TestPayload {
+            /// First value
+            first: u64,
+            pub second: u64,
+            pub(crate) third: u64,
+        }
+    }
+
+    #[test]
+    fn test_bus_payload_round_trip() {
+        let payload = TestPayload { first: 1, second: u64::MAX, third: 3 };
+        assert_eq!(TestPayload::SIZE, 3);
+        let data = payload.to_payload();
+        assert_eq!(data, [1, u64::MAX, 3]);
+        assert_eq!(TestPayload::from_payload(&data), payload);
+
+        // Longer buffers are only written and read up to SIZE
+        let mut data = [7; 5];
+        payload.write_payload(&mut data);
+        assert_eq!(data, [1, u64::MAX, 3, 7, 7]);
+        assert_eq!(TestPayload::from_payload(&data), payload);
+
+        // The memory bus payload positions agree with the MemBusData accessors
+        let mem = MemBusPayload {
+            op: MEM_BUS_STORE_OP,
+            addr: 0xa0000008,
+            step: 5,
+            bytes: 8,
+            mem_value_0: 0x11,
+            mem_value_1: 0x22,
+            value: 0x33,
+        };
+        let data = mem.to_payload();
+        assert_eq!(data.len(), MEM_BUS_DATA_SIZE);
+        assert_eq!(MemBusPayload::from_payload(&data), mem);
+        assert_eq!(MemBusData::get_op(&data), MEM_BUS_STORE_OP as u8);
+        assert_eq!(MemBusData::get_addr(&data), 0xa0000008);
+        assert_eq!(MemBusData::get_step(&data), 5);
+        assert_eq!(MemBusData::get_bytes(&data), 8);
+        assert_eq!(MemBusData::get_mem_values(&data), [0x11, 0x22]);
+        assert_eq!(MemBusData::get_value(&data), 0x33);
+    }
+}
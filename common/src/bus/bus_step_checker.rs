@@ -0,0 +1,247 @@
+//! Checker of the ordering invariants of the memory bus steps
+//!
+//! * The memory AIRs assume that the memory bus messages of a segment, emitted by the main state
+//!   machine and by the precompiles, satisfy some global invariants that no single producer can
+//!   check on its own.  `BusStepChecker` consumes all the messages of a segment, in emission
+//!   order, and checks them:
+//!   * The steps of the messages accessing the same aligned 8-bytes address are strictly
+//!     increasing.
+//!   * Every message uses the main step slot of its producer and kind: slots 0 and 1 for the loads
+//!     and slot 2 for the stores of the main state machine, slot 2 for the loads and slot 3 for
+//!     the stores of the precompiles.
+//!   * No step is used twice for the same address.
+//! * A message accessing two aligned addresses, i.e. a misaligned one crossing 8 bytes, is checked
+//!   against both of them.
+//! * The first violation is returned with the offending pair of messages, their index in the
+//!   segment and their producers, since later violations are usually a consequence of it.
+
+use std::{collections::HashMap, collections::VecDeque, fmt};
+
+use zisk_core::precompile_name;
+
+use crate::{
+    BusId, BusPayload, MemBusPayload, MEM_BUS_ID, MEM_BUS_OPS_BY_MAIN_STEP, MEM_BUS_STEP_BASE,
+    MEM_BUS_STORE_OP,
+};
+
+/// Component that emitted a memory bus message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusProducer {
+    /// The main state machine, i.e. the interpreter
+    Main,
+    /// The precompile with the provided code
+    Precompile(u16),
+}
+
+impl BusProducer {
+    /// Returns true if the producer may use the main step slot for the kind of operation
+    pub fn uses_slot(&self, slot: u64, is_write: bool) -> bool {
+        match (self, is_write) {
+            (BusProducer::Main, false) => slot <= 1,
+            (BusProducer::Main, true) => slot == 2,
+            (BusProducer::Precompile(_), false) => slot == 2,
+            (BusProducer::Precompile(_), true) => slot == 3,
+        }
+    }
+}
+
+impl fmt::Display for BusProducer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BusProducer::Main => write!(f, "main"),
+            BusProducer::Precompile(code) => match precompile_name(*code) {
+                Some(name) => write!(f, "precompile {name}(0x{code:x})"),
+                None => write!(f, "precompile unknown(0x{code:x})"),
+            },
+        }
+    }
+}
+
+/// Memory bus message, as seen by `BusStepChecker`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BusStepOp {
+    /// Index of the message among the memory bus messages of the segment
+    pub index: usize,
+    pub producer: BusProducer,
+    pub is_write: bool,
+    pub addr: u64,
+    /// Memory step, i.e. including the main step slot
+    pub step: u64,
+}
+
+impl BusStepOp {
+    /// Returns the main step of the message
+    pub fn main_step(&self) -> u64 {
+        (self.step - MEM_BUS_STEP_BASE) / MEM_BUS_OPS_BY_MAIN_STEP
+    }
+
+    /// Returns the main step slot of the message
+    pub fn slot(&self) -> u64 {
+        (self.step - MEM_BUS_STEP_BASE) % MEM_BUS_OPS_BY_MAIN_STEP
+    }
+}
+
+impl fmt::Display for BusStepOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = if self.is_write { "write" } else { "read" };
+        write!(
+            f,
+            "#{} {} {op} addr={:#x} step={:#x}",
+            self.index, self.producer, self.addr, self.step
+        )?;
+        if self.step >= MEM_BUS_STEP_BASE {
+            write!(f, " (main step {:#x} slot {})", self.main_step(), self.slot())?;
+        }
+        Ok(())
+    }
+}
+
+/// Invariant broken by a memory bus message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusStepViolation {
+    /// The step is lower than the first memory step
+    InvalidStep { op: BusStepOp },
+    /// The main step slot does not match the producer and kind of the message
+    WrongSlot { op: BusStepOp },
+    /// The step is lower than the step of a previous message to the same address
+    OutOfOrder { addr: u64, previous: BusStepOp, op: BusStepOp },
+    /// The step is the step of a previous message to the same address
+    StepReuse { addr: u64, previous: BusStepOp, op: BusStepOp },
+}
+
+impl fmt::Display for BusStepViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BusStepViolation::InvalidStep { op } => write!(f, "invalid step: {op}"),
+            BusStepViolation::WrongSlot { op } => write!(f, "wrong slot: {op}"),
+            BusStepViolation::OutOfOrder { addr, previous, op } => {
+                write!(f, "steps out of order at addr={addr:#x}: {previous}, then {op}")
+            }
+            BusStepViolation::StepReuse { addr, previous, op } => {
+                write!(f, "step reused at addr={addr:#x}: {previous}, then {op}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BusStepViolation {}
+
+/// Checker of the memory bus messages of a segment, see the module documentation
+#[derive(Debug, Default)]
+pub struct BusStepChecker {
+    /// Last message to every aligned address
+    last_ops: HashMap<u64, BusStepOp>,
+    /// Number of memory bus messages checked
+    count: usize,
+}
+
+impl BusStepChecker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of memory bus messages checked
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Checks the next message of the segment, ignoring the messages of other buses
+    pub fn check(
+        &mut self,
+        producer: BusProducer,
+        bus_id: &BusId,
+        data: &[u64],
+    ) -> Result<(), BusStepViolation> {
+        if *bus_id != MEM_BUS_ID {
+            return Ok(());
+        }
+        let payload = MemBusPayload::from_payload(data);
+        let op = BusStepOp {
+            index: self.count,
+            producer,
+            is_write: payload.op == MEM_BUS_STORE_OP,
+            addr: payload.addr,
+            step: payload.step,
+        };
+        self.count += 1;
+
+        if op.step < MEM_BUS_STEP_BASE {
+            return Err(BusStepViolation::InvalidStep { op });
+        }
+        if !producer.uses_slot(op.slot(), op.is_write) {
+            return Err(BusStepViolation::WrongSlot { op });
+        }
+
+        let first_addr = op.addr & !7;
+        let last_addr = (op.addr + payload.bytes.max(1) - 1) & !7;
+        for addr in (first_addr..=last_addr).step_by(8) {
+            if let Some(previous) = self.last_ops.insert(addr, op) {
+                if op.step == previous.step {
+                    return Err(BusStepViolation::StepReuse { addr, previous, op });
+                }
+                if op.step < previous.step {
+                    return Err(BusStepViolation::OutOfOrder { addr, previous, op });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the next messages of the segment, all of them emitted by the same producer
+    pub fn check_pending(
+        &mut self,
+        producer: BusProducer,
+        pending: &VecDeque<(BusId, Vec<u64>)>,
+    ) -> Result<(), BusStepViolation> {
+        pending.iter().try_for_each(|(bus_id, data)| self.check(producer, bus_id, data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MEM_BUS_LOAD_OP;
+    use zisk_core::PRECOMPILE_KECCAK;
+
+    fn mem_op(is_write: bool, addr: u64, main_step: u64, slot: u64) -> Vec<u64> {
+        MemBusPayload {
+            op: if is_write { MEM_BUS_STORE_OP } else { MEM_BUS_LOAD_OP },
+            addr,
+            step: MEM_BUS_STEP_BASE + MEM_BUS_OPS_BY_MAIN_STEP * main_step + slot,
+            bytes: 8,
+            ..Default::default()
+        }
+        .to_payload()
+    }
+
+    #[test]
+    fn test_bus_step_checker() {
+        let keccak = BusProducer::Precompile(PRECOMPILE_KECCAK);
+        let mut checker = BusStepChecker::new();
+        checker.check(BusProducer::Main, &MEM_BUS_ID, &mem_op(false, 0xa0001000, 1, 0)).unwrap();
+        checker.check(BusProducer::Main, &MEM_BUS_ID, &mem_op(true, 0xa0001000, 1, 2)).unwrap();
+        checker.check(BusProducer::Main, &BusId(0), &[0; 4]).unwrap();
+        checker.check(keccak, &MEM_BUS_ID, &mem_op(false, 0xa0001000, 2, 2)).unwrap();
+        checker.check(keccak, &MEM_BUS_ID, &mem_op(true, 0xa0001000, 2, 3)).unwrap();
+        assert_eq!(checker.count(), 4);
+
+        // Slots of the other producer
+        let err = checker.check(keccak, &MEM_BUS_ID, &mem_op(false, 0xa0002000, 3, 0));
+        assert!(matches!(err, Err(BusStepViolation::WrongSlot { .. })));
+        let err = checker.check(BusProducer::Main, &MEM_BUS_ID, &mem_op(true, 0xa0002000, 3, 3));
+        assert!(matches!(err, Err(BusStepViolation::WrongSlot { .. })));
+
+        // Same step, and lower step, of a misaligned message crossing to the next address
+        let mut misaligned = mem_op(false, 0xa0000ffc, 2, 2);
+        misaligned[3] = 8;
+        let err = checker.check(keccak, &MEM_BUS_ID, &misaligned).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "steps out of order at addr=0xa0001000: #3 precompile keccak(0x800) write \
+             addr=0xa0001000 step=0xc (main step 0x2 slot 3), then #6 precompile keccak(0x800) \
+             read addr=0xa0000ffc step=0xb (main step 0x2 slot 2)"
+        );
+        let err = checker.check(BusProducer::Main, &MEM_BUS_ID, &mem_op(true, 0xa0000ff8, 2, 2));
+        assert!(matches!(err, Err(BusStepViolation::StepReuse { addr: 0xa0000ff8, .. })));
+    }
+}
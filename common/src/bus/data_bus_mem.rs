@@ -6,6 +6,15 @@ pub const MEM_BUS_ID: BusId = BusId(2);
 
 pub const MEM_BUS_DATA_SIZE: usize = 7;
 
+/// Memory bus operation of the loads
+pub const MEM_BUS_LOAD_OP: u64 = 1;
+/// Memory bus operation of the stores
+pub const MEM_BUS_STORE_OP: u64 = 2;
+/// Memory step of the first memory operation of main step 0
+pub const MEM_BUS_STEP_BASE: u64 = 1;
+/// Memory steps of every main step, i.e. maximum memory operations by main step
+pub const MEM_BUS_OPS_BY_MAIN_STEP: u64 = 4;
+
 const OP: usize = 0;
 const ADDR: usize = 1;
 const STEP: usize = 2;
@@ -14,6 +23,21 @@ const MEM_VALUE_0: usize = 4;
 const MEM_VALUE_1: usize = 5;
 const VALUE: usize = 6;
 
+crate::bus_payload! {
+    size = MEM_BUS_DATA_SIZE;
+    /// Memory bus payload, in the positional order expected by the memory state machines.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct MemBusPayload {
+        pub op: u64,
+        pub addr: u64,
+        pub step: u64,
+        pub bytes: u64,
+        pub mem_value_0: u64,
+        pub mem_value_1: u64,
+        pub value: u64,
+    }
+}
+
 /// Type representing a memory data payload consisting of four `PayloadType` values.
 pub type MemData = [PayloadType; 4];
 
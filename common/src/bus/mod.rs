@@ -1,9 +1,13 @@
 mod bus_device;
 mod bus_device_metrics;
+mod bus_expect;
 mod bus_id;
+mod bus_payload;
+mod bus_step_checker;
 mod data_bus_mem;
 mod data_bus_operation;
 mod data_bus_rom;
+mod pending_ops;
 
 use std::{
     fmt::Display,
@@ -12,10 +16,14 @@ use std::{
 
 pub use bus_device::*;
 pub use bus_device_metrics::*;
+pub use bus_expect::*;
 pub use bus_id::*;
+pub use bus_payload::*;
+pub use bus_step_checker::*;
 pub use data_bus_mem::*;
 pub use data_bus_operation::*;
 pub use data_bus_rom::*;
+pub use pending_ops::*;
 
 use std::fmt::{Formatter, Result};
 
@@ -0,0 +1,163 @@
+//! The `PendingOps` queue stores the bus operations derived while processing bus data, as an
+//! allocation-free alternative to `VecDeque<(BusId, Vec<u64>)>` on the hot path.
+//!
+//! Payloads up to `PENDING_OP_INLINE_SIZE` values, e.g. memory bus payloads, are stored inline in
+//! a ring of fixed capacity, and longer payloads are stored in a shared arena, so the queue can be
+//! reused across steps without allocating once it has warmed up. If the ring is full, operations
+//! are stored in an overflow queue, and counted, so that the capacity can be tuned.
+
+use std::collections::VecDeque;
+
+use crate::{BusId, MEM_BUS_DATA_SIZE};
+
+/// Maximum number of payload values stored inline, i.e. without using the arena.
+pub const PENDING_OP_INLINE_SIZE: usize = MEM_BUS_DATA_SIZE;
+
+/// Slot of the ring, storing one operation.
+#[derive(Clone, Copy)]
+struct PendingOpSlot {
+    bus_id: BusId,
+    len: usize,
+    /// Inline payload, or the arena offset of the longer payloads in `data[0]`.
+    data: [u64; PENDING_OP_INLINE_SIZE],
+}
+
+/// Usage counters of a `PendingOps` queue.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PendingOpsStats {
+    /// Number of operations pushed.
+    pub pushed: u64,
+    /// Highest number of operations queued at the same time.
+    pub peak_depth: usize,
+    /// Number of operations that did not fit in the ring.
+    pub overflows: u64,
+}
+
+/// Fixed-capacity FIFO queue of pending bus operations.
+pub struct PendingOps {
+    ring: Vec<PendingOpSlot>,
+    head: usize,
+    len: usize,
+    arena: Vec<u64>,
+    overflow: VecDeque<(BusId, Vec<u64>)>,
+    stats: PendingOpsStats,
+}
+
+impl PendingOps {
+    /// Creates a queue able to store `capacity` operations without overflowing.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "PendingOps::new() capacity must be greater than zero");
+        let empty = PendingOpSlot { bus_id: BusId(0), len: 0, data: [0; PENDING_OP_INLINE_SIZE] };
+        Self {
+            ring: vec![empty; capacity],
+            head: 0,
+            len: 0,
+            arena: Vec::new(),
+            overflow: VecDeque::new(),
+            stats: PendingOpsStats::default(),
+        }
+    }
+
+    /// Returns the number of queued operations.
+    pub fn len(&self) -> usize {
+        self.len + self.overflow.len()
+    }
+
+    /// Returns true if no operation is queued.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the usage counters.
+    pub fn stats(&self) -> PendingOpsStats {
+        self.stats
+    }
+
+    /// Queues an operation.
+    pub fn push_back(&mut self, bus_id: BusId, payload: &[u64]) {
+        self.stats.pushed += 1;
+
+        // Once overflowed, keep using the overflow queue until it drains, to preserve the order
+        if (self.len == self.ring.len()) || !self.overflow.is_empty() {
+            self.stats.overflows += 1;
+            self.overflow.push_back((bus_id, payload.to_vec()));
+        } else {
+            let index = (self.head + self.len) % self.ring.len();
+            let slot = &mut self.ring[index];
+            slot.bus_id = bus_id;
+            slot.len = payload.len();
+            if payload.len() <= PENDING_OP_INLINE_SIZE {
+                slot.data[..payload.len()].copy_from_slice(payload);
+            } else {
+                slot.data[0] = self.arena.len() as u64;
+                self.arena.extend_from_slice(payload);
+            }
+            self.len += 1;
+        }
+
+        self.stats.peak_depth = self.stats.peak_depth.max(self.len());
+    }
+
+    /// Dequeues the oldest operation, copying its payload into `payload`, which is cleared first.
+    pub fn pop_front_into(&mut self, payload: &mut Vec<u64>) -> Option<BusId> {
+        payload.clear();
+        if self.len == 0 {
+            let (bus_id, data) = self.overflow.pop_front()?;
+            payload.extend_from_slice(&data);
+            return Some(bus_id);
+        }
+
+        let slot = &self.ring[self.head];
+        if slot.len <= PENDING_OP_INLINE_SIZE {
+            payload.extend_from_slice(&slot.data[..slot.len]);
+        } else {
+            let offset = slot.data[0] as usize;
+            payload.extend_from_slice(&self.arena[offset..offset + slot.len]);
+        }
+        let bus_id = slot.bus_id;
+        self.head = (self.head + 1) % self.ring.len();
+        self.len -= 1;
+
+        // The arena can be reused once no queued operation refers to it
+        if self.len == 0 {
+            self.arena.clear();
+        }
+        Some(bus_id)
+    }
+
+    /// Removes all the queued operations, keeping the allocated memory for reuse.
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+        self.arena.clear();
+        self.overflow.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pending_ops() {
+        let mut pending = PendingOps::new(2);
+        let long: Vec<u64> = (0..20).collect();
+        pending.push_back(BusId(1), &[1, 2, 3]);
+        pending.push_back(BusId(2), &long);
+        pending.push_back(BusId(3), &[4]);
+
+        let mut payload = Vec::new();
+        assert_eq!(pending.pop_front_into(&mut payload), Some(BusId(1)));
+        assert_eq!(payload, vec![1, 2, 3]);
+        // The ring has room again, but the order must be kept behind the overflowed operation
+        pending.push_back(BusId(4), &[5]);
+        assert_eq!(pending.pop_front_into(&mut payload), Some(BusId(2)));
+        assert_eq!(payload, long);
+        assert_eq!(pending.pop_front_into(&mut payload), Some(BusId(3)));
+        assert_eq!(pending.pop_front_into(&mut payload), Some(BusId(4)));
+        assert_eq!(payload, vec![5]);
+        assert_eq!(pending.pop_front_into(&mut payload), None);
+
+        assert_eq!(pending.stats(), PendingOpsStats { pushed: 4, peak_depth: 3, overflows: 2 });
+    }
+}
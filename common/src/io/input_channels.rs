@@ -0,0 +1,165 @@
+//! Named input channels.
+//!
+//! Guests that consume several artifacts (e.g. a block, state proofs and a config) receive them as
+//! named channels, read with `ziskos::zisk_input("state_proof")`, instead of a single blob with
+//! manual offsets.  The channels are encoded into the guest stdin as a container:
+//! * Magic word `INPUT_CHANNELS_MAGIC` and number of channels
+//! * For every channel: name length, name bytes padded to 8 bytes, data offset from the beginning
+//!   of the container, and data length
+//! * The data of every channel, padded to 8 bytes
+//!
+//! All the words are little-endian u64s.  The channels can be described by a JSON manifest, e.g.
+//! `{ "channels": [ { "name": "block", "path": "block.bin" } ] }`, where relative paths are resolved
+//! from the manifest directory.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+
+/// Magic word at the beginning of an input channels container ("ZISKCHAN")
+pub const INPUT_CHANNELS_MAGIC: u64 = u64::from_le_bytes(*b"ZISKCHAN");
+
+/// Manifest entry: channel name and file containing its data.
+#[derive(Debug, Clone, Deserialize)]
+pub struct InputChannelEntry {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// Manifest describing the input channels of a guest.
+#[derive(Debug, Clone, Deserialize)]
+pub struct InputChannelsManifest {
+    pub channels: Vec<InputChannelEntry>,
+}
+
+/// Set of named input channels, in insertion order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputChannels {
+    channels: Vec<(String, Vec<u8>)>,
+}
+
+impl InputChannels {
+    /// Create an empty set of channels.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a channel; the name must be unique.
+    pub fn add(&mut self, name: &str, data: Vec<u8>) -> Result<&mut Self> {
+        if self.get(name).is_some() {
+            bail!("Duplicated input channel '{name}'");
+        }
+        self.channels.push((name.to_string(), data));
+        Ok(self)
+    }
+
+    /// Get the data of a channel.
+    pub fn get(&self, name: &str) -> Option<&[u8]> {
+        self.channels.iter().find(|(n, _)| n == name).map(|(_, data)| data.as_slice())
+    }
+
+    /// Names of the channels, in insertion order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.channels.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Load the channels described by a JSON manifest file.
+    pub fn from_manifest<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let manifest_data = fs::read_to_string(path)
+            .with_context(|| format!("Could not read input manifest {}", path.display()))?;
+        let manifest: InputChannelsManifest = serde_json::from_str(&manifest_data)
+            .with_context(|| format!("Invalid input manifest {}", path.display()))?;
+
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+        let mut channels = Self::new();
+        for entry in manifest.channels {
+            let data_path = base_dir.join(&entry.path);
+            let data = fs::read(&data_path).with_context(|| {
+                format!("Could not read input channel '{}' at {}", entry.name, data_path.display())
+            })?;
+            channels.add(&entry.name, data)?;
+        }
+        Ok(channels)
+    }
+
+    /// Encode the channels into a stdin container.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let padded = |len: usize| len.div_ceil(8) * 8;
+        let header_len =
+            16 + self.channels.iter().map(|(name, _)| 8 + padded(name.len()) + 16).sum::<usize>();
+
+        let mut header = Vec::with_capacity(header_len);
+        header.extend_from_slice(&INPUT_CHANNELS_MAGIC.to_le_bytes());
+        header.extend_from_slice(&(self.channels.len() as u64).to_le_bytes());
+
+        let mut data = Vec::new();
+        for (name, channel_data) in &self.channels {
+            header.extend_from_slice(&(name.len() as u64).to_le_bytes());
+            header.extend_from_slice(name.as_bytes());
+            header.resize(padded(header.len()), 0);
+            header.extend_from_slice(&((header_len + data.len()) as u64).to_le_bytes());
+            header.extend_from_slice(&(channel_data.len() as u64).to_le_bytes());
+
+            data.extend_from_slice(channel_data);
+            data.resize(padded(data.len()), 0);
+        }
+
+        header.extend_from_slice(&data);
+        header
+    }
+
+    /// Decode a stdin container.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let word = |pos: usize| -> Result<u64> {
+            let word_bytes = bytes.get(pos..pos + 8).ok_or_else(|| anyhow!("Truncated input"))?;
+            Ok(u64::from_le_bytes(word_bytes.try_into().unwrap()))
+        };
+        if word(0)? != INPUT_CHANNELS_MAGIC {
+            bail!("Input is not an input channels container");
+        }
+
+        let mut channels = Self::new();
+        let mut pos = 16;
+        for _ in 0..word(8)? {
+            let name_len = word(pos)? as usize;
+            let name =
+                bytes.get(pos + 8..pos + 8 + name_len).ok_or_else(|| anyhow!("Truncated input"))?;
+            let name = std::str::from_utf8(name).context("Invalid input channel name")?;
+            pos += 8 + name_len.div_ceil(8) * 8;
+
+            let offset = word(pos)? as usize;
+            let len = word(pos + 8)? as usize;
+            pos += 16;
+            let data = bytes.get(offset..offset + len).ok_or_else(|| anyhow!("Truncated input"))?;
+            channels.add(name, data.to_vec())?;
+        }
+        Ok(channels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_channels() {
+        let mut channels = InputChannels::new();
+        channels.add("block", vec![1, 2, 3]).unwrap();
+        channels.add("state_proof", (0..20).collect()).unwrap();
+        channels.add("config", Vec::new()).unwrap();
+        assert!(channels.add("block", Vec::new()).is_err());
+
+        let bytes = channels.to_bytes();
+        assert_eq!(bytes.len() % 8, 0);
+        let decoded = InputChannels::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, channels);
+        assert_eq!(decoded.get("state_proof").unwrap()[19], 19);
+        assert_eq!(decoded.names().collect::<Vec<_>>(), vec!["block", "state_proof", "config"]);
+
+        assert!(InputChannels::from_bytes(&bytes[..40]).is_err());
+        assert!(InputChannels::from_bytes(&[0u8; 16]).is_err());
+    }
+}
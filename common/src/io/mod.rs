@@ -1,9 +1,15 @@
 mod file_stdin;
+mod input_channels;
 mod memory_stdin;
 mod null_stdin;
+mod stream_stats;
+mod transport;
 mod zisk_stdin;
 
 pub use file_stdin::*;
+pub use input_channels::*;
 pub use memory_stdin::*;
 pub use null_stdin::*;
+pub use stream_stats::*;
+pub use transport::*;
 pub use zisk_stdin::*;
@@ -0,0 +1,133 @@
+//! Statistics and health of the input transports.
+//!
+//! * Every transport front-end implements `StreamStats`, returning the bytes and messages
+//!   transferred in each direction and the time of its last activity.  `retransmits` is only
+//!   reported by the transports that retry, so it is `None` for the local ones.
+//! * `TransportHealth` is derived from the statistics: a transport without activity for longer
+//!   than the stall timeout is stalled, so orchestration code can restart it based on data instead
+//!   of a global timeout.  `aggregate_health()` returns the worst health of several transports.
+
+use std::time::{Duration, Instant};
+
+/// Data transferred by a transport
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransportStats {
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub messages_in: u64,
+    pub messages_out: u64,
+    /// Number of retransmissions, for the transports that retry
+    pub retransmits: Option<u64>,
+    /// Time of the last read or write
+    pub last_activity: Option<Instant>,
+}
+
+impl TransportStats {
+    /// Records a message of `bytes` read from the transport
+    pub fn record_in(&mut self, bytes: usize) {
+        self.bytes_in += bytes as u64;
+        self.messages_in += 1;
+        self.last_activity = Some(Instant::now());
+    }
+
+    /// Records a message of `bytes` written to the transport
+    pub fn record_out(&mut self, bytes: usize) {
+        self.bytes_out += bytes as u64;
+        self.messages_out += 1;
+        self.last_activity = Some(Instant::now());
+    }
+
+    /// Returns the health at the provided time
+    pub fn health_at(&self, now: Instant, stall_timeout: Duration) -> TransportHealth {
+        match self.last_activity {
+            None => TransportHealth::Idle,
+            Some(last) => {
+                let idle = now.saturating_duration_since(last);
+                if idle > stall_timeout {
+                    TransportHealth::Stalled { idle }
+                } else {
+                    TransportHealth::Active
+                }
+            }
+        }
+    }
+}
+
+/// Health of a transport, from the best to the worst
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TransportHealth {
+    /// Data transferred within the stall timeout
+    Active,
+    /// No data transferred yet
+    Idle,
+    /// No data transferred for longer than the stall timeout
+    Stalled { idle: Duration },
+}
+
+/// Statistics of a transport reader or writer
+pub trait StreamStats {
+    /// Returns the data transferred so far
+    fn stats(&self) -> TransportStats;
+
+    /// Returns the current health of the transport
+    fn health(&self, stall_timeout: Duration) -> TransportHealth {
+        self.stats().health_at(Instant::now(), stall_timeout)
+    }
+}
+
+/// Returns the worst health of the transports, or `TransportHealth::Idle` if there are none
+pub fn aggregate_health<'a>(
+    transports: impl IntoIterator<Item = &'a dyn StreamStats>,
+    stall_timeout: Duration,
+) -> TransportHealth {
+    transports
+        .into_iter()
+        .map(|transport| transport.health(stall_timeout))
+        .max()
+        .unwrap_or(TransportHealth::Idle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transport_health() {
+        let mut stats = TransportStats::default();
+        let timeout = Duration::from_secs(10);
+        assert_eq!(stats.health_at(Instant::now(), timeout), TransportHealth::Idle);
+
+        stats.record_in(16);
+        stats.record_in(8);
+        stats.record_out(4);
+        assert_eq!(
+            (stats.bytes_in, stats.messages_in, stats.bytes_out, stats.messages_out),
+            (24, 2, 4, 1)
+        );
+        let last = stats.last_activity.unwrap();
+        assert_eq!(
+            stats.health_at(last + Duration::from_secs(5), timeout),
+            TransportHealth::Active
+        );
+        assert_eq!(
+            stats.health_at(last + Duration::from_secs(11), timeout),
+            TransportHealth::Stalled { idle: Duration::from_secs(11) }
+        );
+
+        struct Fixed(TransportStats);
+        impl StreamStats for Fixed {
+            fn stats(&self) -> TransportStats {
+                self.0
+            }
+        }
+        let (idle, active) = (Fixed(TransportStats::default()), Fixed(stats));
+        assert_eq!(
+            aggregate_health([&idle as &dyn StreamStats, &active], timeout),
+            TransportHealth::Idle
+        );
+        assert_eq!(
+            aggregate_health([&active as &dyn StreamStats], timeout),
+            TransportHealth::Active
+        );
+    }
+}
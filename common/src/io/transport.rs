@@ -0,0 +1,178 @@
+//! Transports of the guest input.
+//!
+//! * `TransportSpec` describes where the input comes from, and is parsed from a URI:
+//!   * `null:` no input
+//!   * `file:<path>`, or a plain path, the content of a file
+//!   * `manifest:<path>` the named input channels described by a JSON manifest file
+//!   * `TransportSpec::Memory` in-memory data, which has no URI
+//! * `ZiskTransport` is the transport opened from a spec, implementing `ZiskIO` for all of them,
+//!   so the front-ends only build a spec, and adding a transport is a change to this module.
+
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Result};
+
+use crate::io::{InputChannels, ZiskFileStdin, ZiskIO, ZiskMemoryStdin, ZiskNullStdin};
+
+/// Description of a guest input transport
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransportSpec {
+    Null,
+    File(PathBuf),
+    Manifest(PathBuf),
+    Memory(Vec<u8>),
+}
+
+impl FromStr for TransportSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(uri: &str) -> Result<Self> {
+        let spec = match uri.split_once(':') {
+            Some(("null", "")) => TransportSpec::Null,
+            Some(("file", path)) => TransportSpec::File(PathBuf::from(path)),
+            Some(("manifest", path)) => TransportSpec::Manifest(PathBuf::from(path)),
+            Some((scheme, _)) if is_scheme(scheme) => {
+                bail!("Unsupported input transport {scheme:?} in {uri:?}")
+            }
+            _ => TransportSpec::File(PathBuf::from(uri)),
+        };
+        if let TransportSpec::File(path) | TransportSpec::Manifest(path) = &spec {
+            if path.as_os_str().is_empty() {
+                bail!("Missing path in input transport {uri:?}");
+            }
+        }
+        Ok(spec)
+    }
+}
+
+/// Returns true if the URI prefix looks like a scheme rather than a path, e.g. not `C` in `C:\`
+fn is_scheme(prefix: &str) -> bool {
+    (prefix.len() > 1)
+        && prefix.starts_with(|c: char| c.is_ascii_alphabetic())
+        && prefix.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+}
+
+impl From<Option<PathBuf>> for TransportSpec {
+    /// Input path of the command line, if any
+    fn from(path: Option<PathBuf>) -> Self {
+        path.map_or(TransportSpec::Null, TransportSpec::File)
+    }
+}
+
+impl fmt::Display for TransportSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransportSpec::Null => write!(f, "null:"),
+            TransportSpec::File(path) => write!(f, "file:{}", path.display()),
+            TransportSpec::Manifest(path) => write!(f, "manifest:{}", path.display()),
+            TransportSpec::Memory(data) => write!(f, "memory ({} bytes)", data.len()),
+        }
+    }
+}
+
+/// Guest input transport, opened from a `TransportSpec`
+pub enum ZiskTransport {
+    File(ZiskFileStdin),
+    Null(ZiskNullStdin),
+    Memory(ZiskMemoryStdin),
+}
+
+/// Former name of `ZiskTransport`, which has the same variants
+#[deprecated(note = "use `ZiskTransport`, opened from a `TransportSpec`")]
+pub type ZiskIOVariant = ZiskTransport;
+
+impl ZiskTransport {
+    /// Opens the transport described by the spec
+    pub fn open(spec: TransportSpec) -> Result<Self> {
+        Ok(match spec {
+            TransportSpec::Null => ZiskTransport::Null(ZiskNullStdin),
+            TransportSpec::File(path) => {
+                if !path.exists() {
+                    bail!("Input file not found at {:?}", path.display());
+                }
+                let file = ZiskFileStdin::new(&path)
+                    .with_context(|| format!("Could not open input file {:?}", path.display()))?;
+                ZiskTransport::File(file)
+            }
+            TransportSpec::Manifest(path) => ZiskTransport::Memory(ZiskMemoryStdin::new(
+                InputChannels::from_manifest(path)?.to_bytes(),
+            )),
+            TransportSpec::Memory(data) => ZiskTransport::Memory(ZiskMemoryStdin::new(data)),
+        })
+    }
+}
+
+impl ZiskIO for ZiskTransport {
+    fn read(&mut self) -> Vec<u8> {
+        match self {
+            ZiskTransport::File(file_stdin) => file_stdin.read(),
+            ZiskTransport::Null(null_stdin) => null_stdin.read(),
+            ZiskTransport::Memory(memory_stdin) => memory_stdin.read(),
+        }
+    }
+
+    fn read_slice(&mut self, slice: &mut [u8]) {
+        match self {
+            ZiskTransport::File(file_stdin) => file_stdin.read_slice(slice),
+            ZiskTransport::Null(null_stdin) => null_stdin.read_slice(slice),
+            ZiskTransport::Memory(memory_stdin) => memory_stdin.read_slice(slice),
+        }
+    }
+
+    fn read_into(&mut self, buffer: &mut [u8]) {
+        match self {
+            ZiskTransport::File(file_stdin) => file_stdin.read_into(buffer),
+            ZiskTransport::Null(null_stdin) => null_stdin.read_into(buffer),
+            ZiskTransport::Memory(memory_stdin) => memory_stdin.read_into(buffer),
+        }
+    }
+
+    fn write_serialized(&mut self, data: &[u8]) {
+        match self {
+            ZiskTransport::File(file_stdin) => file_stdin.write_serialized(data),
+            ZiskTransport::Null(null_stdin) => null_stdin.write_serialized(data),
+            ZiskTransport::Memory(memory_stdin) => memory_stdin.write_serialized(data),
+        }
+    }
+
+    fn write_bytes(&mut self, data: &[u8]) {
+        match self {
+            ZiskTransport::File(file_stdin) => file_stdin.write_bytes(data),
+            ZiskTransport::Null(null_stdin) => null_stdin.write_bytes(data),
+            ZiskTransport::Memory(memory_stdin) => memory_stdin.write_bytes(data),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transport_spec() {
+        let parse = |uri: &str| uri.parse::<TransportSpec>();
+        assert_eq!(parse("null:").unwrap(), TransportSpec::Null);
+        assert_eq!(parse("file:in.bin").unwrap(), TransportSpec::File("in.bin".into()));
+        assert_eq!(parse("/tmp/in.bin").unwrap(), TransportSpec::File("/tmp/in.bin".into()));
+        assert_eq!(parse("C:\\in.bin").unwrap(), TransportSpec::File("C:\\in.bin".into()));
+        assert_eq!(parse("manifest:m.json").unwrap(), TransportSpec::Manifest("m.json".into()));
+        assert!(parse("tcp:localhost:1234").is_err());
+        assert!(parse("file:").is_err());
+        for uri in ["null:", "file:in.bin", "manifest:m.json"] {
+            assert_eq!(parse(uri).unwrap().to_string(), uri);
+        }
+        assert_eq!(TransportSpec::from(None), TransportSpec::Null);
+
+        let mut transport = ZiskTransport::open(TransportSpec::Memory(vec![1, 2, 3])).unwrap();
+        let mut buffer = [0u8; 2];
+        transport.read_slice(&mut buffer);
+        assert_eq!(buffer, [1, 2]);
+        assert!(ZiskTransport::open(parse("file:/nonexistent/in.bin").unwrap()).is_err());
+
+        #[allow(deprecated)]
+        let legacy: ZiskIOVariant = ZiskIOVariant::Null(ZiskNullStdin);
+        assert!(matches!(legacy, ZiskTransport::Null(_)));
+    }
+}
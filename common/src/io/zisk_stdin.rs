@@ -1,4 +1,7 @@
-use crate::io::{ZiskFileStdin, ZiskMemoryStdin, ZiskNullStdin};
+use crate::io::{
+    InputChannels, StreamStats, TransportSpec, TransportStats, ZiskMemoryStdin, ZiskNullStdin,
+    ZiskTransport,
+};
 use std::path::Path;
 
 use anyhow::Result;
@@ -20,92 +23,76 @@ pub trait ZiskIO: Send + Sync {
     fn write_bytes(&mut self, data: &[u8]);
 }
 
-pub enum ZiskIOVariant {
-    File(ZiskFileStdin),
-    Null(ZiskNullStdin),
-    Memory(ZiskMemoryStdin),
+pub struct ZiskStdin {
+    io: ZiskTransport,
+    stats: TransportStats,
 }
 
-impl ZiskIO for ZiskIOVariant {
+impl ZiskIO for ZiskStdin {
     fn read(&mut self) -> Vec<u8> {
-        match self {
-            ZiskIOVariant::File(file_stdin) => file_stdin.read(),
-            ZiskIOVariant::Null(null_stdin) => null_stdin.read(),
-            ZiskIOVariant::Memory(memory_stdin) => memory_stdin.read(),
-        }
+        let data = self.io.read();
+        self.stats.record_in(data.len());
+        data
     }
 
     fn read_slice(&mut self, slice: &mut [u8]) {
-        match self {
-            ZiskIOVariant::File(file_stdin) => file_stdin.read_slice(slice),
-            ZiskIOVariant::Null(null_stdin) => null_stdin.read_slice(slice),
-            ZiskIOVariant::Memory(memory_stdin) => memory_stdin.read_slice(slice),
-        }
+        self.io.read_slice(slice);
+        self.stats.record_in(slice.len());
     }
 
     fn read_into(&mut self, buffer: &mut [u8]) {
-        match self {
-            ZiskIOVariant::File(file_stdin) => file_stdin.read_into(buffer),
-            ZiskIOVariant::Null(null_stdin) => null_stdin.read_into(buffer),
-            ZiskIOVariant::Memory(memory_stdin) => memory_stdin.read_into(buffer),
-        }
+        self.io.read_into(buffer);
+        self.stats.record_in(buffer.len());
     }
 
     fn write_serialized(&mut self, data: &[u8]) {
-        match self {
-            ZiskIOVariant::File(file_stdin) => file_stdin.write_serialized(data),
-            ZiskIOVariant::Null(null_stdin) => null_stdin.write_serialized(data),
-            ZiskIOVariant::Memory(memory_stdin) => memory_stdin.write_serialized(data),
-        }
+        self.io.write_serialized(data);
+        self.stats.record_out(data.len());
     }
 
     fn write_bytes(&mut self, data: &[u8]) {
-        match self {
-            ZiskIOVariant::File(file_stdin) => file_stdin.write_bytes(data),
-            ZiskIOVariant::Null(null_stdin) => null_stdin.write_bytes(data),
-            ZiskIOVariant::Memory(memory_stdin) => memory_stdin.write_bytes(data),
-        }
+        self.io.write_bytes(data);
+        self.stats.record_out(data.len());
     }
 }
 
-pub struct ZiskStdin {
-    io: ZiskIOVariant,
-}
-
-impl ZiskIO for ZiskStdin {
-    fn read(&mut self) -> Vec<u8> {
-        self.io.read()
-    }
-
-    fn read_slice(&mut self, slice: &mut [u8]) {
-        self.io.read_slice(slice)
-    }
-
-    fn read_into(&mut self, buffer: &mut [u8]) {
-        self.io.read_into(buffer)
+impl StreamStats for ZiskStdin {
+    fn stats(&self) -> TransportStats {
+        self.stats
     }
+}
 
-    fn write_serialized(&mut self, data: &[u8]) {
-        self.io.write_serialized(data)
+impl ZiskStdin {
+    fn new(io: ZiskTransport) -> Self {
+        Self { io, stats: TransportStats::default() }
     }
 
-    fn write_bytes(&mut self, data: &[u8]) {
-        self.io.write_bytes(data)
+    /// Create a stdin from the transport described by the spec
+    pub fn from_spec(spec: TransportSpec) -> Result<Self> {
+        Ok(Self::new(ZiskTransport::open(spec)?))
     }
-}
 
-impl ZiskStdin {
     /// Create a null stdin (no input)
     pub fn null() -> Self {
-        Self { io: ZiskIOVariant::Null(ZiskNullStdin) }
+        Self::new(ZiskTransport::Null(ZiskNullStdin))
     }
 
     /// Create a file-based stdin
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
-        Ok(Self { io: ZiskIOVariant::File(ZiskFileStdin::new(path)?) })
+        Self::from_spec(TransportSpec::File(path.as_ref().to_path_buf()))
     }
 
     pub fn from_vec(data: Vec<u8>) -> Self {
-        Self { io: ZiskIOVariant::Memory(ZiskMemoryStdin::new(data)) }
+        Self::new(ZiskTransport::Memory(ZiskMemoryStdin::new(data)))
+    }
+
+    /// Create a stdin containing named input channels
+    pub fn from_channels(channels: &InputChannels) -> Self {
+        Self::from_vec(channels.to_bytes())
+    }
+
+    /// Create a stdin containing the named input channels described by a JSON manifest file
+    pub fn from_manifest<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_spec(TransportSpec::Manifest(path.as_ref().to_path_buf()))
     }
 }
@@ -4,12 +4,14 @@ mod emu_minimal_trace;
 mod executor_stats;
 mod instance_context;
 pub mod io;
+pub mod memmap;
 mod mpi_context;
 mod planner_helpers;
 mod proof;
 mod proof_log;
 mod regular_counters;
 mod regular_planner;
+mod trace_batching;
 mod types;
 mod utils;
 mod zisk_lib_init;
@@ -25,6 +27,7 @@ pub use proof::*;
 pub use proof_log::*;
 pub use regular_counters::*;
 pub use regular_planner::*;
+pub use trace_batching::*;
 pub use types::*;
 pub use utils::*;
 pub use zisk_lib_init::*;
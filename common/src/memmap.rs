@@ -0,0 +1,173 @@
+//! Guest memory map
+//!
+//! Single description of the guest address space, built on the memory constants of `zisk_core`,
+//! so that the crates that need to classify an address (precompiles, memory state machines,
+//! emulator) do not repeat the region bounds as magic numbers.
+//!
+//! Regions, in address order, as [start, end):
+//! * `Bios`: [`ROM_ENTRY`, `ROM_ADDR`), code
+//! * `Rom`: [`ROM_ADDR`, `FLOAT_LIB_ROM_ADDR`), code and read-only data
+//! * `FloatLibRom`: [`FLOAT_LIB_ROM_ADDR`, `ROM_END`), code
+//! * `Input`: [`INPUT_ADDR`, `INPUT_END`), read-only
+//! * `Sys`: [`SYS_ADDR`, `OUTPUT_ADDR`), read-write
+//! * `Output`: [`OUTPUT_ADDR`, `OUTPUT_END`), read-write
+//! * `Ram`: [`OUTPUT_END`, `FLOAT_LIB_RAM_ADDR`), read-write
+//! * `FloatLibRam`: [`FLOAT_LIB_RAM_ADDR`, `RAM_END`), read-write
+
+pub use zisk_core::{
+    FLOAT_LIB_RAM_ADDR, FLOAT_LIB_ROM_ADDR, INPUT_ADDR, MAX_INPUT_SIZE, OUTPUT_ADDR,
+    OUTPUT_MAX_SIZE, RAM_ADDR, RAM_SIZE, ROM_ADDR, ROM_ADDR_MAX, ROM_ENTRY, SYS_ADDR,
+};
+
+/// End (exclusive) of the program ROM, including the float library
+pub const ROM_END: u64 = ROM_ADDR_MAX + 1;
+/// End (exclusive) of the input data region
+pub const INPUT_END: u64 = INPUT_ADDR + MAX_INPUT_SIZE;
+/// End (exclusive) of the output data region
+pub const OUTPUT_END: u64 = OUTPUT_ADDR + OUTPUT_MAX_SIZE;
+/// End (exclusive) of the RW memory, including the float library memory
+pub const RAM_END: u64 = RAM_ADDR + RAM_SIZE;
+
+/// Region of the guest address space
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemRegion {
+    /// BIOS instructions, executed before and after the program
+    Bios,
+    /// Program instructions and read-only data
+    Rom,
+    /// Float library instructions
+    FloatLibRom,
+    /// Input data, read-only
+    Input,
+    /// System memory: registers, UART, guest log, float registers and CSRs
+    Sys,
+    /// Public output data
+    Output,
+    /// General purpose memory: program data, heap and stack
+    Ram,
+    /// Float library memory
+    FloatLibRam,
+    /// Any address outside the previous regions
+    Unmapped,
+}
+
+impl MemRegion {
+    /// Returns the region name
+    pub const fn name(&self) -> &'static str {
+        match self {
+            MemRegion::Bios => "bios",
+            MemRegion::Rom => "rom",
+            MemRegion::FloatLibRom => "float_lib_rom",
+            MemRegion::Input => "input",
+            MemRegion::Sys => "sys",
+            MemRegion::Output => "output",
+            MemRegion::Ram => "ram",
+            MemRegion::FloatLibRam => "float_lib_ram",
+            MemRegion::Unmapped => "unmapped",
+        }
+    }
+
+    /// Returns the address range of the region, as [start, end), or None if it is not mapped
+    pub const fn range(&self) -> Option<(u64, u64)> {
+        match self {
+            MemRegion::Bios => Some((ROM_ENTRY, ROM_ADDR)),
+            MemRegion::Rom => Some((ROM_ADDR, FLOAT_LIB_ROM_ADDR)),
+            MemRegion::FloatLibRom => Some((FLOAT_LIB_ROM_ADDR, ROM_END)),
+            MemRegion::Input => Some((INPUT_ADDR, INPUT_END)),
+            MemRegion::Sys => Some((SYS_ADDR, OUTPUT_ADDR)),
+            MemRegion::Output => Some((OUTPUT_ADDR, OUTPUT_END)),
+            MemRegion::Ram => Some((OUTPUT_END, FLOAT_LIB_RAM_ADDR)),
+            MemRegion::FloatLibRam => Some((FLOAT_LIB_RAM_ADDR, RAM_END)),
+            MemRegion::Unmapped => None,
+        }
+    }
+
+    /// Returns true if the guest can write to the region
+    pub const fn is_writable(&self) -> bool {
+        matches!(self, MemRegion::Sys | MemRegion::Output | MemRegion::Ram | MemRegion::FloatLibRam)
+    }
+}
+
+/// Returns the region that contains the address
+pub const fn region_of(addr: u64) -> MemRegion {
+    if addr >= ROM_ENTRY && addr < ROM_ADDR {
+        MemRegion::Bios
+    } else if addr >= ROM_ADDR && addr < FLOAT_LIB_ROM_ADDR {
+        MemRegion::Rom
+    } else if addr >= FLOAT_LIB_ROM_ADDR && addr < ROM_END {
+        MemRegion::FloatLibRom
+    } else if addr >= INPUT_ADDR && addr < INPUT_END {
+        MemRegion::Input
+    } else if addr >= SYS_ADDR && addr < OUTPUT_ADDR {
+        MemRegion::Sys
+    } else if addr >= OUTPUT_ADDR && addr < OUTPUT_END {
+        MemRegion::Output
+    } else if addr >= OUTPUT_END && addr < FLOAT_LIB_RAM_ADDR {
+        MemRegion::Ram
+    } else if addr >= FLOAT_LIB_RAM_ADDR && addr < RAM_END {
+        MemRegion::FloatLibRam
+    } else {
+        MemRegion::Unmapped
+    }
+}
+
+/// Returns true if the address is in the program ROM, including the BIOS and the float library
+pub const fn is_rom(addr: u64) -> bool {
+    addr >= ROM_ENTRY && addr < ROM_END
+}
+
+/// Returns true if the address is in the input data region
+pub const fn is_input(addr: u64) -> bool {
+    addr >= INPUT_ADDR && addr < INPUT_END
+}
+
+/// Returns true if the address is in the RW memory, including the system and output regions
+pub const fn is_ram(addr: u64) -> bool {
+    addr >= RAM_ADDR && addr < RAM_END
+}
+
+/// Returns true if the address belongs to any region
+pub const fn is_mapped(addr: u64) -> bool {
+    !matches!(region_of(addr), MemRegion::Unmapped)
+}
+
+/// Returns true if the guest can write to the address
+pub const fn is_writable(addr: u64) -> bool {
+    region_of(addr).is_writable()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memmap() {
+        assert_eq!(region_of(0x1000), MemRegion::Bios);
+        assert_eq!(region_of(0x8000_0000), MemRegion::Rom);
+        assert_eq!(region_of(0x87f0_0000), MemRegion::FloatLibRom);
+        assert_eq!(region_of(0x9000_0000), MemRegion::Input);
+        assert_eq!(region_of(0xa000_0200), MemRegion::Sys);
+        assert_eq!(region_of(0xa001_0000), MemRegion::Output);
+        assert_eq!(region_of(0xa002_0000), MemRegion::Ram);
+        assert_eq!(region_of(0xbfff_fff8), MemRegion::FloatLibRam);
+        assert_eq!(region_of(0xc000_0000), MemRegion::Unmapped);
+        assert!(is_rom(0x87ff_ffff) && !is_rom(0x8800_0000));
+        assert!(!is_writable(0x9000_0000) && is_writable(0xa002_0000));
+
+        // Both ends of every region range are classified into it
+        for region in [
+            MemRegion::Bios,
+            MemRegion::Rom,
+            MemRegion::FloatLibRom,
+            MemRegion::Input,
+            MemRegion::Sys,
+            MemRegion::Output,
+            MemRegion::Ram,
+            MemRegion::FloatLibRam,
+        ] {
+            let (start, end) = region.range().unwrap();
+            assert_eq!(region_of(start), region, "{}", region.name());
+            assert_eq!(region_of(end - 1), region, "{}", region.name());
+        }
+    }
+}
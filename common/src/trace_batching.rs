@@ -0,0 +1,81 @@
+//! Batching of the inputs of the parallel trace builders
+//!
+//! The trace builders that process their inputs in parallel split them in one batch per worker
+//! thread, so the batch boundaries, and with them the order in which the per-batch multiplicities
+//! and logs are produced, depend on the size of the thread pool.  In deterministic mode the inputs
+//! are split in a fixed number of batches instead, so that two runs over the same inputs produce
+//! the same batches regardless of the number of threads, which makes debugging runs reproducible.
+//!
+//! * Only the Arith and Add256 trace builders batch their inputs per thread.  The rest of the
+//!   state machines process their inputs per row or per collected input vector, whose boundaries
+//!   do not depend on the thread pool, so they do not use `trace_batch_size()`.
+//! * The deterministic mode is enabled by `set_deterministic_trace_batching()`, or by setting the
+//!   `ZISK_DETERMINISTIC_TRACE_BATCHING` environment variable to 1, which also reaches the copy of
+//!   this crate linked in the witness computation library, e.g. through the
+//!   `--deterministic-batching` option of `cargo-zisk prove` and `verify-constraints`.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    OnceLock,
+};
+
+/// Number of batches used in deterministic mode
+pub const DETERMINISTIC_TRACE_BATCHES: usize = 64;
+
+/// Environment variable that enables the deterministic mode when set to 1
+pub const DETERMINISTIC_TRACE_BATCHING_ENV: &str = "ZISK_DETERMINISTIC_TRACE_BATCHING";
+
+static DETERMINISTIC_TRACE_BATCHING: AtomicBool = AtomicBool::new(false);
+
+/// Deterministic mode requested through the environment, read once per process
+static DETERMINISTIC_TRACE_BATCHING_FROM_ENV: OnceLock<bool> = OnceLock::new();
+
+/// Enables or disables the deterministic mode, for all the trace builders of the process
+pub fn set_deterministic_trace_batching(enabled: bool) {
+    DETERMINISTIC_TRACE_BATCHING.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns true if the deterministic mode is enabled, either by the process or by the environment
+pub fn is_deterministic_trace_batching() -> bool {
+    DETERMINISTIC_TRACE_BATCHING.load(Ordering::Relaxed)
+        || *DETERMINISTIC_TRACE_BATCHING_FROM_ENV.get_or_init(|| {
+            std::env::var(DETERMINISTIC_TRACE_BATCHING_ENV).is_ok_and(|value| value == "1")
+        })
+}
+
+/// Returns the number of inputs of every batch (the last one may be shorter), never zero
+pub fn trace_batch_size(total_inputs: usize) -> usize {
+    let batches = if is_deterministic_trace_batching() {
+        DETERMINISTIC_TRACE_BATCHES
+    } else {
+        rayon::current_num_threads()
+    };
+    total_inputs.div_ceil(batches).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_batch_size() {
+        set_deterministic_trace_batching(true);
+        let pool = |threads| rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        let sizes: Vec<usize> =
+            [1, 3, 16].iter().map(|&n| pool(n).install(|| trace_batch_size(1000))).collect();
+        assert_eq!(sizes, [16, 16, 16]);
+        assert_eq!(trace_batch_size(0), 1);
+
+        set_deterministic_trace_batching(false);
+        assert_eq!(pool(4).install(|| trace_batch_size(1000)), 250);
+
+        // Inputs not divisible by the number of threads are split in one batch per thread, with
+        // a shorter last one, instead of the extra batch of the remainder produced by a floor
+        // division, e.g. 3 batches of 334, 334 and 332 inputs instead of 4 with 333, 333, 333 and 1
+        let batches = |threads, inputs: usize| {
+            pool(threads).install(|| inputs.div_ceil(trace_batch_size(inputs)))
+        };
+        assert_eq!(pool(3).install(|| trace_batch_size(1000)), 334);
+        assert_eq!([batches(3, 1000), batches(3, 2), batches(4, 4096)], [3, 2, 4]);
+    }
+}
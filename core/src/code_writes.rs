@@ -0,0 +1,147 @@
+//! Self-modifying code detection
+//!
+//! * Zisk assumes that the program code is immutable: the code is transpiled into the ROM, so a
+//!   write into the text segment (e.g. because of a mis-set linker script) does not modify the
+//!   executed instructions, and it makes the proof fail in a way that is hard to explain.
+//! * `verify_no_text_writes()` is a static pre-check that scans the executable sections of the
+//!   program looking for stores whose address can be computed from constants (`lui`, `auipc`,
+//!   `addi`, `c.li`, etc. within the same basic block), and reports the ones that target the code.
+//! * The emulator complements it at runtime: the memory model panics on the writes into the code
+//!   ranges of the ROM, or, if the emulator is instrumented (e.g. with an ebreak policy or the
+//!   shadow stack), records them so that the emulator reports the pc of the writing instruction.
+
+use core::fmt;
+
+use riscv::{decode_bytes, DecodedItem, RiscvInstruction};
+
+use crate::elf_extraction::ElfPayload;
+
+/// Write into the program code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodeWrite {
+    /// Address of the instruction that writes into the code
+    pub pc: u64,
+    /// Written address
+    pub addr: u64,
+    /// Width of the write, in bytes
+    pub width: u64,
+}
+
+impl fmt::Display for CodeWrite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "instruction at pc=0x{:x} writes {} bytes to code address 0x{:x}",
+            self.pc, self.width, self.addr
+        )
+    }
+}
+
+/// Returns the width of the store instructions, or None if the instruction is not a store
+fn store_width(inst: &str) -> Option<u64> {
+    match inst {
+        "sb" => Some(1),
+        "sh" => Some(2),
+        "sw" | "fsw" | "c.sw" | "c.swsp" | "sc.w" => Some(4),
+        "sd" | "fsd" | "c.sd" | "c.sdsp" | "c.fsd" | "c.fsdsp" | "sc.d" => Some(8),
+        _ => None,
+    }
+}
+
+/// Returns true if the instruction may transfer the control, ending the basic block
+fn is_control_transfer(inst: &str) -> bool {
+    inst.starts_with('b')
+        || inst.starts_with("c.b")
+        || inst.starts_with('j')
+        || inst.starts_with("c.j")
+        || (inst == "ecall")
+}
+
+/// Scans the executable sections of the program, and returns the list of writes into the code
+/// whose address is known statically.  Stores whose address depends on runtime values are not
+/// reported, so passing this check does not guarantee the absence of code writes.
+pub fn verify_no_text_writes(program: &ElfPayload) -> Result<(), Vec<CodeWrite>> {
+    let code_ranges: Vec<(u64, u64)> = program
+        .exec
+        .iter()
+        .map(|section| (section.addr, section.addr + section.data.len() as u64))
+        .collect();
+    let is_code = |addr: u64, width: u64| {
+        code_ranges.iter().any(|(start, end)| (addr < *end) && (addr + width > *start))
+    };
+
+    let mut code_writes = Vec::new();
+    for section in &program.exec {
+        // Registers whose value is a known constant, reset at every basic block
+        let mut constants: [Option<u64>; 32] = [None; 32];
+        constants[0] = Some(0);
+
+        let instructions: Vec<RiscvInstruction> =
+            decode_bytes(section.addr, &section.data, &program.data_in_text)
+                .into_iter()
+                .filter_map(|item| match item {
+                    DecodedItem::Instruction(i) => Some(i),
+                    _ => None,
+                })
+                .collect();
+        for i in &instructions {
+            let imm = i.imm as i64 as u64;
+            if let Some(width) = store_width(&i.inst) {
+                if let Some(base) = constants[i.rs1 as usize] {
+                    let addr = base.wrapping_add(imm);
+                    if is_code(addr, width) {
+                        code_writes.push(CodeWrite { pc: i.rom_address, addr, width });
+                    }
+                }
+                // Store conditional instructions also write rd
+                if i.inst.starts_with("sc.") && (i.rd != 0) {
+                    constants[i.rd as usize] = None;
+                }
+                continue;
+            }
+            if is_control_transfer(&i.inst) {
+                constants = [None; 32];
+                constants[0] = Some(0);
+                continue;
+            }
+            let value = match i.inst.as_str() {
+                "lui" | "c.lui" => Some(imm),
+                "auipc" => Some(i.rom_address.wrapping_add(imm)),
+                "addi" | "c.addi" | "c.li" => {
+                    constants[i.rs1 as usize].map(|v| v.wrapping_add(imm))
+                }
+                _ => None,
+            };
+            if i.rd != 0 {
+                constants[i.rd as usize] = value;
+            }
+        }
+    }
+
+    if code_writes.is_empty() {
+        Ok(())
+    } else {
+        Err(code_writes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elf_extraction::DataSection;
+
+    const CODE_ADDR: u64 = 0x80000000;
+
+    #[test]
+    fn test_verify_no_text_writes() {
+        // auipc t0, 0; sw zero, 8(t0); addi a0, zero, 1
+        let code: [u32; 3] = [0x00000297, 0x0002a423, 0x00100513];
+        let data = code.iter().flat_map(|inst| inst.to_le_bytes()).collect();
+        let program =
+            ElfPayload { exec: vec![DataSection { addr: CODE_ADDR, data }], ..Default::default() };
+        assert_eq!(
+            verify_no_text_writes(&program),
+            Err(vec![CodeWrite { pc: CODE_ADDR + 4, addr: CODE_ADDR + 8, width: 4 }])
+        );
+    }
+}
@@ -5,14 +5,32 @@ use crate::{
     elf_extraction::{
         collect_elf_payload, collect_elf_payload_from_bytes, merge_adjacent_ro_sections, ElfPayload,
     },
-    riscv2zisk_context::{add_entry_exit_jmp, add_zisk_code, add_zisk_init_data},
+    riscv2zisk_context::{add_entry_exit_jmp, add_zisk_init_data},
+    rom_pipeline::{add_zisk_code_pipelined, RomPipelineConfig, RomPipelineCounters},
     AsmGenerationMethod, RoData, ZiskInst, ZiskRom, ZiskRom2Asm, ROM_ADDR, ROM_ADDR_MAX, ROM_ENTRY,
 };
 use rayon::prelude::*;
 use std::{error::Error, path::Path};
 
+/// Options of the ROM transpilation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Elf2RomOptions {
+    /// Keep the data marked by the `$d` mapping symbols inside the code sections, e.g. jump
+    /// tables, as read-only data instead of transpiling it as code.  Disabled by default, so that
+    /// the ROM only depends on the sections of the ELF file.
+    pub data_in_text: bool,
+}
+
 /// Executes the ROM transpilation process: from ELF to Zisk
 pub fn elf2rom(elf_file: &Path) -> Result<ZiskRom, Box<dyn Error>> {
+    elf2rom_with_options(elf_file, &Elf2RomOptions::default())
+}
+
+/// Executes the ROM transpilation process with the provided options
+pub fn elf2rom_with_options(
+    elf_file: &Path,
+    options: &Elf2RomOptions,
+) -> Result<ZiskRom, Box<dyn Error>> {
     // Load the embedded float library
     const FLOAT_LIB_DATA: &[u8] = include_bytes!("../../lib-float/c/lib/ziskfloat.elf");
 
@@ -26,10 +44,25 @@ pub fn elf2rom(elf_file: &Path) -> Result<ZiskRom, Box<dyn Error>> {
     // Add the end instruction, jumping over it
     add_end_and_lib(&mut rom);
 
+    // Decode the code sections in parallel chunks
+    let pipeline_config = RomPipelineConfig::default();
+    let pipeline_counters = RomPipelineCounters::default();
+
     for (i, payload) in payloads.into_iter().enumerate() {
-        // 1. Add executable code sections
+        // 1. Add executable code sections, keeping the data embedded in them if requested
+        let data_in_text: &[(u64, u64)] =
+            if options.data_in_text { &payload.data_in_text } else { &[] };
+        let mut embedded_data = Vec::new();
         for section in &payload.exec {
-            add_zisk_code(&mut rom, section.addr, &section.data);
+            embedded_data.extend(add_zisk_code_pipelined(
+                &mut rom,
+                section.addr,
+                &section.data,
+                data_in_text,
+                &pipeline_config,
+                &pipeline_counters,
+            ));
+            rom.code_ranges.push((section.addr, section.addr + section.data.len() as u64));
         }
 
         // 2. Add read-write data sections (will be copied to RAM)
@@ -37,9 +70,10 @@ pub fn elf2rom(elf_file: &Path) -> Result<ZiskRom, Box<dyn Error>> {
             add_zisk_init_data(&mut rom, section.addr, &section.data, true);
         }
 
-        // 3. Add read-only data sections
+        // 3. Add read-only data sections, including the data embedded in the code
         // Merge adjacent read-only sections for efficiency
-        let merged_ro = merge_adjacent_ro_sections(&payload.ro);
+        embedded_data.extend(payload.ro.iter().cloned());
+        let merged_ro = merge_adjacent_ro_sections(&embedded_data);
         for section in &merged_ro {
             rom.ro_data.push(RoData::new(section.addr, section.data.len(), section.data.clone()));
         }
@@ -466,4 +500,33 @@ mod tests {
         assert_eq!(stored.b_src, 2);
         assert_eq!(stored.store, 3);
     }
+
+    /// Returns the full contents of the ROM instructions and read-only data, in address order
+    fn rom_contents(rom: &ZiskRom) -> (Vec<String>, Vec<String>) {
+        let mut insts: Vec<_> = rom.insts.iter().collect();
+        insts.sort_unstable_by_key(|(addr, _)| **addr);
+        let insts = insts.iter().map(|(addr, builder)| format!("{addr:x} {:?}", builder.i));
+        let ro_data = rom.ro_data.iter().map(|ro| format!("{ro:?}"));
+        (insts.collect(), ro_data.collect())
+    }
+
+    #[test]
+    fn test_data_in_text_keeps_ordinary_roms() {
+        // Guests without `$d` mapping symbols in their code sections, e.g. with the absolute ones
+        // of the debug info only, get the same ROM with and without the option
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        for elf in [
+            "../emulator/benches/data/my.elf",
+            "../elf-regressions/prebuilt-elfs/go-program-hello-world.elf",
+        ] {
+            let elf_file = manifest_dir.join(elf);
+            assert!(collect_elf_payload(&elf_file).unwrap().data_in_text.is_empty(), "{elf}");
+
+            let rom = elf2rom(&elf_file).unwrap();
+            let options = Elf2RomOptions { data_in_text: true };
+            let rom_with_data = elf2rom_with_options(&elf_file, &options).unwrap();
+            assert!(!rom.insts.is_empty());
+            assert_eq!(rom_contents(&rom), rom_contents(&rom_with_data), "{elf}");
+        }
+    }
 }
@@ -30,6 +30,10 @@ pub struct ElfPayload {
     pub rw: Vec<DataSection>,
     /// `SHF_ALLOC` but not `SHF_WRITE` - read-only data
     pub ro: Vec<DataSection>,
+    /// `[start, end)` address ranges of data embedded in the executable sections, e.g. jump
+    /// tables, sorted and non-overlapping; see `data_in_text_ranges()`.  Only used to build the
+    /// ROM if `Elf2RomOptions::data_in_text` is enabled
+    pub data_in_text: Vec<(u64, u64)>,
 }
 
 /// Extracts the relevant sections from the ELF file for `ZiskRom`
@@ -61,8 +65,9 @@ pub fn collect_elf_payload_from_bytes(file_data: &[u8]) -> Result<ElfPayload, Bo
     let mut out = ElfPayload { entry_point: elf.ehdr.e_entry, ..Default::default() };
 
     // Process all section headers
+    let mut exec_indexes: Vec<usize> = Vec::new();
     if let Some(shdrs) = elf.section_headers() {
-        for sh in shdrs {
+        for (index, sh) in shdrs.iter().enumerate() {
             // Must be allocated at runtime
             //
             // Essentially all sections that we need to load into memory when the program is loaded.
@@ -107,6 +112,7 @@ pub fn collect_elf_payload_from_bytes(file_data: &[u8]) -> Result<ElfPayload, Bo
             if is_exec {
                 // Executable code section
                 out.exec.push(DataSection { addr: sh.sh_addr, data });
+                exec_indexes.push(index);
             } else if is_write && in_ram {
                 // Read-write data that needs to be copied to RAM
                 out.rw.push(DataSection { addr: sh.sh_addr, data });
@@ -126,9 +132,73 @@ pub fn collect_elf_payload_from_bytes(file_data: &[u8]) -> Result<ElfPayload, Bo
         }
     }
 
+    // Find the data embedded in the executable sections, from the mapping symbols defined in
+    // them; the ones of other sections, e.g. the absolute ones of the debug info, are ignored
+    if let Some((symtab, strtab)) = elf.symbol_table()? {
+        let mut mapping_symbols: Vec<(u64, bool)> = Vec::new();
+        for symbol in symtab.iter() {
+            if !exec_indexes.contains(&(symbol.st_shndx as usize)) {
+                continue;
+            }
+            let name = strtab.get(symbol.st_name as usize)?;
+            if (name == "$d") || name.starts_with("$d.") {
+                mapping_symbols.push((symbol.st_value, true));
+            } else if name.starts_with("$x") {
+                mapping_symbols.push((symbol.st_value, false));
+            }
+        }
+        out.data_in_text = data_in_text_ranges(&out.exec, &mapping_symbols);
+    }
+
     Ok(out)
 }
 
+/// Returns the sorted, non-overlapping `[start, end)` ranges of data inside the executable
+/// sections.  The RISC-V psABI mapping symbols mark the start of data (`$d`) and code (`$x`,
+/// optionally followed by the ISA string) inside a section, so a `$d` range lasts until the next
+/// `$x` or the end of its section.
+pub fn data_in_text_ranges(
+    exec: &[DataSection],
+    mapping_symbols: &[(u64, bool)],
+) -> Vec<(u64, u64)> {
+    let mut mapping_symbols = mapping_symbols.to_vec();
+    mapping_symbols.sort_unstable();
+
+    let mut ranges: Vec<(u64, u64)> = Vec::new();
+    for section in exec {
+        let section_end = section.addr + section.data.len() as u64;
+        let mut data_start: Option<u64> = None;
+        for (addr, is_data) in mapping_symbols.iter().copied() {
+            if (addr < section.addr) || (addr >= section_end) {
+                continue;
+            }
+            match (data_start, is_data) {
+                (None, true) => data_start = Some(addr),
+                (Some(start), false) => {
+                    ranges.push((start, addr));
+                    data_start = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(start) = data_start {
+            ranges.push((start, section_end));
+        }
+    }
+
+    // Merge the overlapping and adjacent ranges
+    ranges.retain(|(start, end)| start < end);
+    ranges.sort_unstable();
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
 /// Helper function to merge adjacent read-only sections
 ///
 ///   Example: If you have:
@@ -171,6 +241,18 @@ pub fn merge_adjacent_ro_sections(sections: &[DataSection]) -> Vec<DataSection>
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_data_in_text_ranges() {
+        let exec = vec![DataSection { addr: 0x1000, data: vec![0; 0x100] }];
+        // $d at 0x1010 until $x at 0x1020, and $d at 0x10f0 until the end of the section;
+        // symbols outside the section are ignored
+        let mapping_symbols = [(0x1020, false), (0x1010, true), (0x10f0, true), (0x2000, true)];
+        assert_eq!(
+            data_in_text_ranges(&exec, &mapping_symbols),
+            vec![(0x1010, 0x1020), (0x10f0, 0x1100)]
+        );
+    }
+
     #[test]
     fn test_merge_adjacent_empty() {
         let sections = vec![];
@@ -0,0 +1,259 @@
+//! Guest binary size report
+//!
+//! * `elf_size_report()` reports the size of the text, read-only data, data and bss sections of a
+//!   guest ELF, and the size of all its symbols, so that the guest build workflow does not need
+//!   external binutils (`size`, `nm --size-sort`) to find what makes a program big.
+//! * For the function symbols it also reports their number of RISC-V instructions, decoded with
+//!   the same decoder used by the transpiler, which is closer to their proving cost than their
+//!   size in bytes.
+
+use elf::{
+    abi::{SHF_ALLOC, SHF_EXECINSTR, SHF_WRITE, SHT_NOBITS, STT_FUNC, STT_OBJECT},
+    endian::AnyEndian,
+    ElfBytes,
+};
+use riscv::riscv_interpreter;
+use std::error::Error;
+
+use crate::{convert_vector, is_elf_file};
+
+/// Size in bytes of the allocated sections of an ELF, by kind
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SectionSizes {
+    /// `SHF_EXECINSTR` sections, i.e. code
+    pub text: u64,
+    /// Sections not `SHF_WRITE`, i.e. read-only data
+    pub rodata: u64,
+    /// `SHF_WRITE` sections with data
+    pub data: u64,
+    /// `SHT_NOBITS` sections, i.e. zero-initialized data
+    pub bss: u64,
+}
+
+impl SectionSizes {
+    /// Returns the total size of the allocated sections
+    pub fn total(&self) -> u64 {
+        self.text + self.rodata + self.data + self.bss
+    }
+}
+
+/// Size of an ELF symbol
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolSize {
+    /// Symbol name, as found in the ELF, i.e. mangled
+    pub name: String,
+    /// Symbol address
+    pub addr: u64,
+    /// Symbol size in bytes
+    pub size: u64,
+    /// Number of RISC-V instructions, only for function symbols
+    pub instructions: Option<usize>,
+}
+
+/// Size report of a guest ELF
+#[derive(Debug, Clone, Default)]
+pub struct ElfSizeReport {
+    /// Size of the allocated sections
+    pub sections: SectionSizes,
+    /// Function and data symbols with a non-zero size, from biggest to smallest
+    pub symbols: Vec<SymbolSize>,
+}
+
+impl ElfSizeReport {
+    /// Returns the `n` biggest symbols
+    pub fn largest(&self, n: usize) -> &[SymbolSize] {
+        &self.symbols[..n.min(self.symbols.len())]
+    }
+
+    /// Returns the `n` functions with the highest number of instructions
+    pub fn largest_functions(&self, n: usize) -> Vec<&SymbolSize> {
+        let mut functions: Vec<&SymbolSize> =
+            self.symbols.iter().filter(|s| s.instructions.is_some()).collect();
+        functions.sort_by(|a, b| b.instructions.cmp(&a.instructions).then(a.addr.cmp(&b.addr)));
+        functions.truncate(n);
+        functions
+    }
+}
+
+/// Builds the size report of the provided ELF file bytes
+pub fn elf_size_report(file_data: &[u8]) -> Result<ElfSizeReport, Box<dyn Error>> {
+    if !is_elf_file(file_data).map_err(|_| "Error reading ELF file")? {
+        return Err("File is not a valid ELF file".into());
+    }
+    let elf = ElfBytes::<AnyEndian>::minimal_parse(file_data)?;
+
+    // Classify the allocated sections, keeping the code ones to decode the functions
+    let mut report = ElfSizeReport::default();
+    let mut code: Vec<(u64, &[u8])> = Vec::new();
+    if let Some(shdrs) = elf.section_headers() {
+        for sh in shdrs {
+            if (sh.sh_flags & SHF_ALLOC as u64) == 0 {
+                continue;
+            }
+            if sh.sh_type == SHT_NOBITS {
+                report.sections.bss += sh.sh_size;
+            } else if (sh.sh_flags & SHF_EXECINSTR as u64) != 0 {
+                report.sections.text += sh.sh_size;
+                code.push((sh.sh_addr, elf.section_data(&sh)?.0));
+            } else if (sh.sh_flags & SHF_WRITE as u64) != 0 {
+                report.sections.data += sh.sh_size;
+            } else {
+                report.sections.rodata += sh.sh_size;
+            }
+        }
+    }
+
+    if let Some((symtab, strtab)) = elf.symbol_table()? {
+        for symbol in symtab.iter() {
+            let symtype = symbol.st_symtype();
+            if (symbol.st_size == 0) || ((symtype != STT_FUNC) && (symtype != STT_OBJECT)) {
+                continue;
+            }
+            let instructions = if symtype == STT_FUNC {
+                count_instructions(&code, symbol.st_value, symbol.st_size)
+            } else {
+                None
+            };
+            report.symbols.push(SymbolSize {
+                name: strtab.get(symbol.st_name as usize)?.to_string(),
+                addr: symbol.st_value,
+                size: symbol.st_size,
+                instructions,
+            });
+        }
+    }
+    report.symbols.sort_by(|a, b| b.size.cmp(&a.size).then(a.addr.cmp(&b.addr)));
+
+    Ok(report)
+}
+
+/// Returns the number of instructions of the function at `addr`, or None if it is not inside a
+/// code section
+fn count_instructions(code: &[(u64, &[u8])], addr: u64, size: u64) -> Option<usize> {
+    let (start, data) = code
+        .iter()
+        .find(|(start, data)| (addr >= *start) && (addr + size <= *start + data.len() as u64))?;
+    let offset = (addr - start) as usize;
+    // Instructions are 2-bytes aligned
+    let bytes = &data[offset..offset + (size as usize & !1)];
+    Some(riscv_interpreter(addr, &convert_vector(bytes)).len())
+}
+
+#[cfg(test)]
+mod tests {
+    use elf::abi::{SHT_PROGBITS, SHT_STRTAB, SHT_SYMTAB, STT_NOTYPE};
+
+    use super::*;
+
+    /// Builds a RISC-V ELF with a function, two data objects and a section of every kind
+    fn elf_fixture() -> Vec<u8> {
+        // main: addi a0, a0, 1; c.nop; addi a0, a0, 1
+        let mut text = 0x00150513u32.to_le_bytes().to_vec();
+        text.extend([0x01, 0x00]);
+        text.extend(0x00150513u32.to_le_bytes());
+
+        let mut strtab = vec![0];
+        let mut symtab = vec![0; 24];
+        for (name, symtype, shndx, value, size) in [
+            ("main", STT_FUNC, 1u16, 0x80000000u64, text.len() as u64),
+            ("TABLE", STT_OBJECT, 2, 0x80001000, 8),
+            ("COUNTER", STT_OBJECT, 3, 0xa0000000, 16),
+            // Skipped, since they have no size or are neither functions nor objects
+            ("EMPTY", STT_OBJECT, 3, 0xa0000010, 0),
+            ("label", STT_NOTYPE, 1, 0x80000004, 4),
+        ] {
+            symtab.extend((strtab.len() as u32).to_le_bytes());
+            symtab.extend([symtype, 0]);
+            symtab.extend(shndx.to_le_bytes());
+            symtab.extend(value.to_le_bytes());
+            symtab.extend(size.to_le_bytes());
+            strtab.extend(name.as_bytes());
+            strtab.push(0);
+        }
+
+        // Name, type, flags, address, contents (only its size for .bss) and linked section
+        let alloc = SHF_ALLOC as u64;
+        let mut sections = vec![
+            (".text", SHT_PROGBITS, alloc | SHF_EXECINSTR as u64, 0x80000000u64, text, 0u32),
+            (".rodata", SHT_PROGBITS, alloc, 0x80001000, vec![0; 8], 0),
+            (".data", SHT_PROGBITS, alloc | SHF_WRITE as u64, 0xa0000000, vec![0; 16], 0),
+            (".bss", SHT_NOBITS, alloc | SHF_WRITE as u64, 0xa0000010, vec![0; 0x100], 0),
+            (".symtab", SHT_SYMTAB, 0, 0, symtab, 6),
+            (".strtab", SHT_STRTAB, 0, 0, strtab, 0),
+        ];
+        let mut shstrtab = vec![0];
+        let mut names = Vec::new();
+        for name in sections.iter().map(|s| s.0).chain([".shstrtab"]) {
+            names.push(shstrtab.len() as u32);
+            shstrtab.extend(name.as_bytes());
+            shstrtab.push(0);
+        }
+        sections.push((".shstrtab", SHT_STRTAB, 0, 0, shstrtab, 0));
+
+        // Header, with e_shoff patched below, followed by the contents and the section headers
+        let shnum = sections.len() as u16 + 1;
+        let mut elf = vec![0x7f, b'E', b'L', b'F', 2, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        elf.extend(2u16.to_le_bytes()); // ET_EXEC
+        elf.extend(0xf3u16.to_le_bytes()); // EM_RISCV
+        elf.extend(1u32.to_le_bytes());
+        elf.extend(0x80000000u64.to_le_bytes());
+        elf.extend([0; 16]); // e_phoff and e_shoff
+        elf.extend(0u32.to_le_bytes());
+        elf.extend([64, 0, 56, 0, 0, 0, 64, 0]);
+        elf.extend(shnum.to_le_bytes());
+        elf.extend((shnum - 1).to_le_bytes());
+
+        let mut headers = vec![0; 64];
+        for ((_, sh_type, flags, addr, data, link), name) in sections.iter().zip(names) {
+            let offset = elf.len() as u64;
+            if *sh_type != SHT_NOBITS {
+                elf.extend(data);
+            }
+            let entsize = if *sh_type == SHT_SYMTAB { 24u64 } else { 0 };
+            headers.extend(name.to_le_bytes());
+            headers.extend(sh_type.to_le_bytes());
+            headers.extend(flags.to_le_bytes());
+            headers.extend(addr.to_le_bytes());
+            headers.extend(offset.to_le_bytes());
+            headers.extend((data.len() as u64).to_le_bytes());
+            headers.extend(link.to_le_bytes());
+            headers.extend(0u32.to_le_bytes());
+            headers.extend(1u64.to_le_bytes());
+            headers.extend(entsize.to_le_bytes());
+        }
+        elf.resize(elf.len().next_multiple_of(8), 0);
+        let shoff = elf.len() as u64;
+        elf[40..48].copy_from_slice(&shoff.to_le_bytes());
+        elf.extend(headers);
+        elf
+    }
+
+    #[test]
+    fn test_elf_size_report() {
+        let report = elf_size_report(&elf_fixture()).unwrap();
+        let expected = SectionSizes { text: 10, rodata: 8, data: 16, bss: 0x100 };
+        assert_eq!(report.sections, expected);
+        assert_eq!(report.sections.total(), 290);
+
+        // From biggest to smallest, with the instructions of the functions only
+        let symbols: Vec<_> = report
+            .symbols
+            .iter()
+            .map(|s| (s.name.as_str(), s.addr, s.size, s.instructions))
+            .collect();
+        assert_eq!(
+            symbols,
+            [
+                ("COUNTER", 0xa0000000, 16, None),
+                ("main", 0x80000000, 10, Some(3)),
+                ("TABLE", 0x80001000, 8, None)
+            ]
+        );
+        assert_eq!(report.largest(1), &report.symbols[..1]);
+        let functions = report.largest_functions(5);
+        assert_eq!(functions.len(), 1);
+        assert_eq!(functions[0].name, "main");
+
+        assert!(elf_size_report(b"not an ELF file").is_err());
+    }
+}
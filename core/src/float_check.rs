@@ -0,0 +1,77 @@
+//! Hardware floating point instructions check
+//!
+//! * Guests built for a soft-float target, optionally with the ziskos `soft-float` shims, should
+//!   not contain any floating point instruction, but precompiled objects or hand-written assembly
+//!   pulled in by third-party crates can still bring them in.  Zisk executes them through its
+//!   float library, which is slower than the soft-float code and has its own rounding behavior.
+//! * `find_float_instructions()` decodes the executable sections of the program with the RISC-V
+//!   decoder, and reports every instruction that reads or writes a floating point register or
+//!   accesses a floating point CSR (`fflags`, `frm`, `fcsr`).
+//! * The data embedded in the sections and marked by the symbol table is skipped, but unmarked
+//!   data can still be reported; every site is reported with its decoded text to ease the review.
+
+use riscv::RiscvInstruction;
+
+use crate::{elf_extraction::ElfPayload, program_instructions};
+
+/// Floating point CSRs: fflags, frm and fcsr
+const FLOAT_CSRS: [u32; 3] = [0x001, 0x002, 0x003];
+
+/// Floating point instruction found in the program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FloatInstructionSite {
+    /// Address of the instruction
+    pub addr: u64,
+    /// Instruction name, e.g. `fadd.d`
+    pub inst: String,
+    /// Decoded instruction text
+    pub text: String,
+}
+
+/// Returns true if the instruction uses the floating point unit
+pub fn is_float_instruction(instruction: &RiscvInstruction) -> bool {
+    let semantics = instruction.semantics();
+    let float_regs = (semantics.reads.bits | semantics.writes.bits) >> 32;
+    let float_csr = instruction.inst.starts_with("csrr") && FLOAT_CSRS.contains(&instruction.csr);
+    (float_regs != 0) || float_csr
+}
+
+/// Returns the floating point instructions of the executable sections of the program
+pub fn find_float_instructions(program: &ElfPayload) -> Vec<FloatInstructionSite> {
+    program_instructions(program)
+        .into_iter()
+        .map(|(_, i)| i)
+        .filter(is_float_instruction)
+        .map(|i| FloatInstructionSite {
+            addr: i.rom_address,
+            inst: i.inst.clone(),
+            text: i.to_text(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elf_extraction::DataSection;
+
+    #[test]
+    fn test_find_float_instructions() {
+        let code: [u32; 5] = [
+            0x00b50533, // add a0, a0, a1
+            0x02b57553, // fadd.d fa0, fa0, fa1
+            0x00053507, // fld fa0, 0(a0)
+            0x00302573, // csrr a0, fcsr
+            0xf1202573, // csrr a0, marchid
+        ];
+        let data = code.iter().flat_map(|inst| inst.to_le_bytes()).collect();
+        let program = ElfPayload {
+            entry_point: 0x80000000,
+            exec: vec![DataSection { addr: 0x80000000, data }],
+            ..Default::default()
+        };
+        let sites = find_float_instructions(&program);
+        let found: Vec<(u64, &str)> = sites.iter().map(|s| (s.addr, s.inst.as_str())).collect();
+        assert_eq!(found, vec![(0x80000004, "fadd.d"), (0x80000008, "fld"), (0x8000000c, "csrrs")]);
+    }
+}
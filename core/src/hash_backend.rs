@@ -0,0 +1,226 @@
+//! Host hashing backends
+//!
+//! * The emulator computes the SHA-256 and Keccak-f precompiles on the host, which run on
+//!   anything from cloud ARM machines to AVX-512 servers.  The SHA-256 compression is the one of
+//!   `sha2`, which selects at runtime its hardware-accelerated implementation if the CPU has the
+//!   SHA extensions (SHA-NI) on x86_64 or the cryptography extensions on aarch64, and its
+//!   portable one otherwise.
+//! * `sha256_backend()` reports the implementation `sha2` selects on this CPU, checking the same
+//!   features, so that it can be logged with the metrics of a run or with a self-test failure.
+//! * `force_backend()` overrides the detected SHA-256 backend for the whole process, e.g. to
+//!   reproduce on an accelerated host a run of a software one; `sha256_compress()` computes the
+//!   compression with the backend in use, which is the portable implementation of this module for
+//!   `HashBackend::Software`.
+//! * Keccak-f has only the software implementation of `tiny-keccak`.
+//! * `hash_backends()` returns the backends in use.
+
+use std::{
+    fmt,
+    str::FromStr,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+/// Implementation of a host hashing routine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashBackend {
+    /// Portable implementation
+    Software,
+    /// x86_64 SHA extensions
+    ShaNi,
+    /// aarch64 cryptography extensions
+    ArmCrypto,
+}
+
+impl HashBackend {
+    /// Returns the accelerated backend of the CPU, if it has the required features
+    pub fn detect() -> Option<HashBackend> {
+        #[cfg(target_arch = "x86_64")]
+        if std::arch::is_x86_feature_detected!("sha")
+            && std::arch::is_x86_feature_detected!("sse4.1")
+            && std::arch::is_x86_feature_detected!("ssse3")
+        {
+            return Some(HashBackend::ShaNi);
+        }
+        #[cfg(target_arch = "aarch64")]
+        if std::arch::is_aarch64_feature_detected!("sha2") {
+            return Some(HashBackend::ArmCrypto);
+        }
+        None
+    }
+
+    /// Returns true if this backend can run on the CPU
+    pub fn is_available(&self) -> bool {
+        match self {
+            HashBackend::Software => true,
+            _ => HashBackend::detect() == Some(*self),
+        }
+    }
+}
+
+impl fmt::Display for HashBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashBackend::Software => write!(f, "software"),
+            HashBackend::ShaNi => write!(f, "sha-ni"),
+            HashBackend::ArmCrypto => write!(f, "armv8-crypto"),
+        }
+    }
+}
+
+impl FromStr for HashBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "software" => Ok(HashBackend::Software),
+            "sha-ni" => Ok(HashBackend::ShaNi),
+            "armv8-crypto" => Ok(HashBackend::ArmCrypto),
+            _ => {
+                Err(format!("unknown hash backend {s}, expected software, sha-ni or armv8-crypto"))
+            }
+        }
+    }
+}
+
+/// SHA-256 backend forced by `force_backend()`, encoded as 1 + its discriminant, or 0 if none
+static FORCED_SHA256_BACKEND: AtomicU8 = AtomicU8::new(0);
+
+/// Forces the SHA-256 backend used by the process, or restores the detected one if `None`.  It
+/// fails if the backend cannot run on this CPU.
+pub fn force_backend(backend: Option<HashBackend>) -> Result<(), String> {
+    let encoded = match backend {
+        Some(backend) if !backend.is_available() => {
+            return Err(format!("force_backend() hash backend {backend} is not available"));
+        }
+        Some(backend) => 1 + backend as u8,
+        None => 0,
+    };
+    FORCED_SHA256_BACKEND.store(encoded, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Returns the SHA-256 backend in use: the forced one, if any, or the one `sha2` selects on this
+/// CPU
+pub fn sha256_backend() -> HashBackend {
+    match FORCED_SHA256_BACKEND.load(Ordering::Relaxed) {
+        1 => HashBackend::Software,
+        2 => HashBackend::ShaNi,
+        3 => HashBackend::ArmCrypto,
+        _ => HashBackend::detect().unwrap_or(HashBackend::Software),
+    }
+}
+
+/// Applies the SHA-256 compression function to the state, using the backend in use
+pub fn sha256_compress(state: &mut [u32; 8], block: &[u8; 64]) {
+    match sha256_backend() {
+        HashBackend::Software => sha256_compress_software(state, block),
+        #[allow(deprecated)]
+        _ => sha2::compress256(state, &[(*block).into()]),
+    }
+}
+
+/// Round constants of SHA-256
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// Portable SHA-256 compression function
+fn sha256_compress_software(state: &mut [u32; 8], block: &[u8; 64]) {
+    let mut w = [0u32; 64];
+    for (i, word) in block.chunks_exact(4).enumerate() {
+        w[i] = u32::from_be_bytes(word.try_into().unwrap());
+    }
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for i in 0..64 {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(SHA256_K[i]).wrapping_add(w[i]);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(maj);
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+
+    for (word, value) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *word = word.wrapping_add(value);
+    }
+}
+
+/// Returns the Keccak-f backend in use
+pub fn keccak_backend() -> HashBackend {
+    HashBackend::Software
+}
+
+/// Backends of the hashing routines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HashBackends {
+    pub sha256: HashBackend,
+    pub keccak: HashBackend,
+}
+
+impl fmt::Display for HashBackends {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sha256={} keccak={}", self.sha256, self.keccak)
+    }
+}
+
+/// Returns the backends in use
+pub fn hash_backends() -> HashBackends {
+    HashBackends { sha256: sha256_backend(), keccak: keccak_backend() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_backends() {
+        let backends = hash_backends();
+        assert_eq!(backends.sha256, HashBackend::detect().unwrap_or(HashBackend::Software));
+        assert_eq!(backends.keccak, HashBackend::Software);
+        let sha256 = ["software", "sha-ni", "armv8-crypto"].map(|name| format!("sha256={name}"));
+        assert!(sha256.iter().any(|prefix| backends.to_string().starts_with(prefix.as_str())));
+        assert!(backends.to_string().ends_with(" keccak=software"));
+
+        // The software backend matches the one selected by sha2
+        let block: [u8; 64] = std::array::from_fn(|i| i as u8);
+        let mut expected = [0x6a09e667u32, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0, 1, 2, 3];
+        let mut state = expected;
+        #[allow(deprecated)]
+        sha2::compress256(&mut expected, &[block.into()]);
+        force_backend(Some(HashBackend::Software)).unwrap();
+        assert_eq!(sha256_backend(), HashBackend::Software);
+        sha256_compress(&mut state, &block);
+        assert_eq!(state, expected);
+
+        // Only the available backends can be forced
+        let accelerated = [HashBackend::ShaNi, HashBackend::ArmCrypto];
+        let unavailable = accelerated.into_iter().find(|backend| !backend.is_available()).unwrap();
+        assert!(force_backend(Some(unavailable)).is_err());
+        assert_eq!(sha256_backend(), HashBackend::Software);
+        force_backend(None).unwrap();
+        assert_eq!(sha256_backend(), backends.sha256);
+        assert_eq!("sha-ni".parse(), Ok(HashBackend::ShaNi));
+        assert!("avx512".parse::<HashBackend>().is_err());
+    }
+}
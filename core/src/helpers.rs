@@ -1,12 +1,15 @@
-use sha2::compress256;
+use crate::sha256_compress;
 #[allow(deprecated)]
 use sha2::digest::generic_array::{typenum::U64, GenericArray};
 
 pub fn sha256f(state: &mut [u64; 4], input: &[u64; 8]) {
     // Convert both the state and the input to appropriate types
     let mut state_u32: [u32; 8] = convert_u64_to_u32(state).try_into().unwrap();
-    let block = convert_u64_to_generic_array_bytes(input);
-    compress256(&mut state_u32, &[block]);
+    let mut block = [0u8; 64];
+    for (bytes, word) in block.chunks_exact_mut(8).zip(input) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    sha256_compress(&mut state_u32, &block);
 
     // Convert the state back to u64 and write it to the memory address
     *state = convert_u32_to_u64(&state_u32);
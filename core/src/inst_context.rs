@@ -39,7 +39,7 @@ pub struct PrecompiledInstContext {
 
 /// Zisk fcall instruction context.
 /// Stores the fcall arguments data and the result data.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FcallInstContext {
     /// Fcall parameters data
     /// Maximum size is FCALL_PARAMS_MAX_SIZE u64's
@@ -146,6 +146,17 @@ impl InstContext {
         let s = format! {"a={:x} b={:x} c={:x} flag={} sp={} pc={} step={} end={}", self.a, self.b, self.c, self.flag, self.sp, self.pc, self.step, self.end};
         s
     }
+
+    /// Copies the 8-bytes words of memory starting at `addr` into `words`; see
+    /// `Mem::read_mem_slice()`
+    pub fn read_mem_slice(&self, addr: u64, words: &mut [u64]) {
+        self.mem.read_mem_slice(addr, words)
+    }
+
+    /// Copies `words` to memory starting at `addr`; see `Mem::write_mem_slice()`
+    pub fn write_mem_slice(&mut self, addr: u64, words: &[u64]) {
+        self.mem.write_mem_slice(addr, words)
+    }
 }
 
 impl Default for InstContext {
@@ -51,14 +51,26 @@
 //!
 //! The zisk_core crate contains basic structures and functionality used by several other modules:
 //! opcodes, instructions and transpilation
+pub mod code_writes;
 pub mod elf2rom;
 pub mod elf_extraction;
+pub mod elf_size_report;
 pub mod fcall;
+pub mod float_check;
+pub mod hash_backend;
 pub mod helpers;
 pub mod inst_context;
 pub mod mem;
+pub mod mmio;
+pub mod patcher;
+pub mod precompile_codes;
+pub mod program_cache;
+pub mod program_commitment;
+pub mod program_diff;
 pub mod riscv2zisk;
 pub mod riscv2zisk_context;
+pub mod rom_pipeline;
+pub mod stack_depth;
 mod utils;
 pub mod zisk_definitions;
 pub mod zisk_inst;
@@ -69,13 +81,25 @@ pub mod zisk_required_operation;
 pub mod zisk_rom;
 pub mod zisk_rom_2_asm;
 
+pub use code_writes::*;
 pub use elf2rom::*;
+pub use elf_size_report::*;
 pub use fcall::*;
+pub use float_check::*;
+pub use hash_backend::*;
 pub use helpers::*;
 pub use inst_context::*;
 pub use mem::*;
+pub use mmio::*;
+pub use patcher::*;
+pub use precompile_codes::*;
+pub use program_cache::*;
+pub use program_commitment::*;
+pub use program_diff::*;
 pub use riscv2zisk::*;
 pub use riscv2zisk_context::*;
+pub use rom_pipeline::*;
+pub use stack_depth::*;
 pub use utils::*;
 pub use zisk_definitions::*;
 pub use zisk_inst::*;
@@ -36,6 +36,7 @@
 //! `| Contains system address.`
 //! `| The first 256 bytes contain 32 8-byte registers`
 //! `| The address UART_ADDR is used as a stdout at addr = 0xa0000200`
+//! `| The address LOG_ADDR is used as a guest log at addr = 0xa0000208`
 //! `| The first float register is at         FREG_FIRST = 0xa0001000`
 //! `| The first CSR register is at             CSR_ADDR = 0xa0008000`
 //! `|`
@@ -98,7 +99,7 @@
 //! * The third RW memory region going from `AVAILABLE_MEM_ADDR` onwards can be used during the
 //!   program execution as general purpose memory.
 
-use crate::{M16, M3, M32, M8, REG_FIRST, REG_LAST};
+use crate::{MmioDevice, MmioRegion, M16, M3, M32, M8, REG_FIRST, REG_LAST};
 use core::fmt;
 
 /// Fist input data memory address
@@ -141,6 +142,8 @@ pub const FLOAT_LIB_SP: u64 = 0xc0000000 - 16; // 0xbffffff0
 pub const ARCH_ID_ZISK: u64 = 0xFFFEEEE;
 /// UART memory address; single bytes written here will be copied to the standard output
 pub const UART_ADDR: u64 = SYS_ADDR + 0x200;
+/// Guest log memory address; 8-byte words written here form the guest log records
+pub const LOG_ADDR: u64 = SYS_ADDR + 0x208;
 /// Float registers first address
 pub const FREG_FIRST: u64 = SYS_ADDR + 0x1000;
 /// CSR memory address; contains control and status registers
@@ -196,19 +199,112 @@ impl MemSection {
     }
 }
 
-/// Memory structure, containing several read sections and one single write section
+/// Memory write record, storing the content of the written address before the write
+#[derive(Debug, Clone, Copy)]
+pub struct MemWriteRecord {
+    pub addr: u64,
+    pub width: u64,
+    pub previous_value: u64,
+}
+
+/// Memory structure, containing several read sections, one single write section and, optionally,
+/// several MMIO regions
 #[derive(Debug, Default)]
 pub struct Mem {
     pub read_sections: Vec<MemSection>,
     pub write_section: MemSection,
     pub free_input: u64,
+    pub mmio_regions: Vec<MmioRegion>,
+    /// If present, every write done through `write()` or `write_silent()` to a memory section
+    /// is recorded here, allowing to undo it later; writes to MMIO regions are not recorded
+    pub write_journal: Option<Vec<MemWriteRecord>>,
+    /// Address ranges [start, end) of the program code, which must never be written
+    pub code_ranges: Vec<(u64, u64)>,
+    /// First (address, width) write into the code ranges, pending to be reported by the emulator
+    pub code_write: Option<(u64, u64)>,
+    /// If true, writes into the code ranges are recorded in `code_write`, for the emulator to
+    /// report them with the pc of the writing instruction, instead of panicking here
+    pub defer_code_writes: bool,
+    /// Input too big to fit in the input section, served on demand through the input page fcall
+    pub paged_input: Vec<u8>,
 }
 
 impl Mem {
     /// Memory structure constructor
     pub fn new() -> Mem {
         //println!("Mem::new()");
-        Mem { read_sections: Vec::new(), write_section: MemSection::new(), free_input: 0 }
+        Mem {
+            read_sections: Vec::new(),
+            write_section: MemSection::new(),
+            free_input: 0,
+            mmio_regions: Vec::new(),
+            write_journal: None,
+            code_ranges: Vec::new(),
+            code_write: None,
+            defer_code_writes: false,
+            paged_input: Vec::new(),
+        }
+    }
+
+    /// Adds an MMIO region to the memory structure; reads and writes inside the [start, start +
+    /// size) range will be forwarded to the provided device instead of the memory sections
+    pub fn add_mmio_region(&mut self, start: u64, size: u64, device: Box<dyn MmioDevice>) {
+        // Check the size is not zero
+        if size == 0 {
+            panic!("Mem::add_mmio_region() got invalid size={size} at start={start:x}");
+        }
+
+        // Check that the region does not overlap any existing MMIO region
+        if let Some(existing_region) =
+            self.mmio_regions.iter().find(|region| region.overlaps(start, size))
+        {
+            panic!(
+                "Mem::add_mmio_region() region start={:x} size={:x} overlaps existing region start={:x} end={:x}",
+                start, size, existing_region.start, existing_region.end
+            );
+        }
+
+        self.mmio_regions.push(MmioRegion { start, end: start + size, device });
+    }
+
+    /// Returns the index of the MMIO region that contains the address and width, if any
+    #[inline(always)]
+    fn mmio_region(&self, addr: u64, width: u64) -> Option<usize> {
+        let index = self.mmio_regions.iter().position(|region| region.overlaps(addr, width))?;
+
+        // Accesses crossing a region boundary cannot be served by the device nor by the memory
+        if !self.mmio_regions[index].contains(addr, width) {
+            panic!(
+                "Mem::mmio_region() access addr={:x} width={} crosses MMIO region start={:x} end={:x}",
+                addr, width, self.mmio_regions[index].start, self.mmio_regions[index].end
+            );
+        }
+
+        Some(index)
+    }
+
+    /// Returns the index of the MMIO region that contains the address and width, if any, testing
+    /// first whether there is any MMIO region, so that the accesses pay only for this test
+    #[inline(always)]
+    fn mmio_access(&self, addr: u64, width: u64) -> Option<usize> {
+        if self.mmio_regions.is_empty() {
+            return None;
+        }
+        self.mmio_region(addr, width)
+    }
+
+    /// Returns the aligned 8-bytes values that an access to an MMIO device requires, as the ones
+    /// returned by `read_required()` and `write_silent_required()` for the memory sections
+    fn mmio_required(device: &dyn MmioDevice, addr: u64, width: u64) -> Vec<u64> {
+        if Self::is_full_aligned(addr, width) {
+            return Vec::new();
+        }
+        let (addr_req_1, addr_req_2) = Self::required_addresses(addr, width);
+        if addr_req_1 == addr_req_2 {
+            vec![device.read(addr_req_1, 8)]
+        } else {
+            vec![device.read(addr_req_1, 8), device.read(addr_req_2, 8)]
+        }
     }
 
     /// Adds a read section to the memory structure
@@ -271,6 +367,60 @@ impl Mem {
         self.read_sections.push(new_section);
     }
 
+    /// Replaces the input data read sections, i.e. the free input, the input length and the input
+    /// data, with the ones of the provided input
+    pub fn replace_input(&mut self, input: &[u8]) {
+        // Check the input data size is inside the proper range
+        if input.len() > (MAX_INPUT_SIZE - 16) as usize {
+            panic!("Mem::replace_input() input size too big size={}", input.len());
+        }
+
+        // Remove the current input sections, if any
+        self.read_sections.retain(|section| {
+            (section.start < INPUT_ADDR) || (section.start >= INPUT_ADDR + MAX_INPUT_SIZE)
+        });
+
+        // Add the free input, length and input data read sections
+        let input_len = input.len() as u64;
+        let free_input = 0u64;
+        self.add_read_section(INPUT_ADDR, &free_input.to_le_bytes());
+        self.add_read_section(INPUT_ADDR + 8, &input_len.to_le_bytes());
+        self.add_read_section(INPUT_ADDR + 16, input);
+        self.paged_input = Vec::new();
+
+        // Keep read sections sorted by start address, as required by the binary search
+        self.read_sections.sort_by_key(|section| section.start);
+    }
+
+    /// Replaces the input with one that does not need to fit in the input section: it is kept out
+    /// of the guest address space, whose input section is left empty, and it can only be read
+    /// through the input page fcall
+    pub fn replace_paged_input(&mut self, input: Vec<u8>) {
+        self.replace_input(&[]);
+        self.paged_input = input;
+    }
+
+    /// Returns true if the input fits in the input section
+    pub fn input_fits(input_len: usize) -> bool {
+        input_len <= (MAX_INPUT_SIZE - 16) as usize
+    }
+
+    /// Returns the input bytes, wherever they are stored
+    pub fn input_slice(&self) -> &[u8] {
+        if !self.paged_input.is_empty() {
+            return &self.paged_input;
+        }
+
+        // The free input, the input length and the input data share the same read section
+        match self.read_sections.iter().find(|section| section.start == INPUT_ADDR) {
+            Some(section) if section.buffer.len() >= 16 => {
+                let len = u64::from_le_bytes(section.buffer[8..16].try_into().unwrap()) as usize;
+                &section.buffer[16..16 + len]
+            }
+            _ => &[],
+        }
+    }
+
     /// Adds a write section to the memory structure, which cannot be written twice
     pub fn add_write_section(&mut self, start: u64, size: u64) {
         //println!("Mem::add_write_section() start={:x}={} size={:x}={}", start, start, size,
@@ -312,6 +462,11 @@ impl Mem {
     pub fn read(&self, addr: u64, width: u64) -> u64 {
         debug_assert!(!Mem::address_is_register(addr));
 
+        // Forward the read to the MMIO device, if any
+        if let Some(index) = self.mmio_access(addr, width) {
+            return self.mmio_regions[index].device.read(addr, width);
+        }
+
         // First try to read in the write section
         if (addr >= self.write_section.start) && (addr <= (self.write_section.end - width)) {
             // Calculate the read position
@@ -402,6 +557,12 @@ impl Mem {
         let is_single_not_aligned = !is_full_aligned && (addr_req_1 == addr_req_2);
         let is_double_not_aligned = !is_full_aligned && !is_single_not_aligned;
 
+        // Forward the read to the MMIO device, if any
+        if let Some(index) = self.mmio_access(addr, width) {
+            let device = self.mmio_regions[index].device.as_ref();
+            return (device.read(addr, width), Self::mmio_required(device, addr, width));
+        }
+
         // First try to read in the write section
         if (addr >= self.write_section.start) && (addr <= (self.write_section.end - width)) {
             // Calculate the read position
@@ -564,6 +725,12 @@ impl Mem {
     pub fn write_silent(&mut self, addr: u64, val: u64, width: u64) {
         debug_assert!(!Mem::address_is_register(addr));
 
+        // Forward the write to the MMIO device, if any
+        if let Some(index) = self.mmio_access(addr, width) {
+            self.mmio_regions[index].device.write(addr, val, width);
+            return;
+        }
+
         //println!("Mem::write() addr={:x}={} width={} value={:x}={}", addr, addr, width, val,
         // val);
 
@@ -588,6 +755,16 @@ impl Mem {
 
         // Check that the address and width fall into this section address range
         if (addr < section.start) || ((addr + width) > section.end) {
+            // Writes into the code are not done, but recorded to be reported by the emulator
+            // together with the pc of the writing instruction, if requested
+            if self.code_ranges.iter().any(|(start, end)| (addr < *end) && (addr + width > *start))
+            {
+                if !self.defer_code_writes {
+                    panic!("Mem::write_silent() code write at addr={addr:x} width={width}");
+                }
+                self.code_write.get_or_insert((addr, width));
+                return;
+            }
             panic!(
                 "Mem::write_silent() invalid addr={}={:x} write section start={:x} end={:x}",
                 addr, addr, section.start, section.end
@@ -597,6 +774,18 @@ impl Mem {
         // Calculate the write position
         let write_position: usize = (addr - section.start) as usize;
 
+        // Record the previous content, if requested
+        if let Some(write_journal) = &mut self.write_journal {
+            let mut previous_bytes = [0u8; 8];
+            previous_bytes[..width as usize]
+                .copy_from_slice(&section.buffer[write_position..write_position + width as usize]);
+            write_journal.push(MemWriteRecord {
+                addr,
+                width,
+                previous_value: u64::from_le_bytes(previous_bytes),
+            });
+        }
+
         // Write the value based on the provided width
         match width {
             1 => section.buffer[write_position] = val as u8,
@@ -613,6 +802,14 @@ impl Mem {
     /// Write a u64 value to the memory write section, based on the provided address and width
     #[inline(always)]
     pub fn write_silent_required(&mut self, addr: u64, val: u64, width: u64) -> Vec<u64> {
+        // Forward the write to the MMIO device, if any, after reading the previous values
+        if let Some(index) = self.mmio_access(addr, width) {
+            let device = self.mmio_regions[index].device.as_mut();
+            let additional_data = Self::mmio_required(device, addr, width);
+            device.write(addr, val, width);
+            return additional_data;
+        }
+
         //println!("Mem::write() addr={:x}={} width={} value={:x}={}", addr, addr, width, val,
         // val);
 
@@ -714,6 +911,85 @@ impl Mem {
         additional_data
     }
 
+    /// Copies `words.len()` 8-bytes words of memory starting at `addr` into `words`, looking up
+    /// the section only once, e.g. to read the large operands of a precompile.  The address must
+    /// be aligned to 8 bytes, and the range must be inside one single memory section, out of any
+    /// MMIO region.
+    pub fn read_mem_slice(&self, addr: u64, words: &mut [u64]) {
+        let (section, position) = self.slice_section(addr, words.len(), "Mem::read_mem_slice()");
+        let section = match section {
+            None => &self.write_section,
+            Some(index) => &self.read_sections[index],
+        };
+        let bytes = &section.buffer[position..position + words.len() * 8];
+        for (word, chunk) in words.iter_mut().zip(bytes.chunks_exact(8)) {
+            *word = u64::from_le_bytes(chunk.try_into().unwrap());
+        }
+    }
+
+    /// Copies `words` to memory starting at `addr`, looking up the section only once, e.g. to
+    /// write the large results of a precompile.  The range must be inside the write section; if
+    /// the write journal is enabled, the previous content of the words is recorded.
+    pub fn write_mem_slice(&mut self, addr: u64, words: &[u64]) {
+        let len = words.len();
+        let (section, position) = self.slice_section(addr, len, "Mem::write_mem_slice()");
+        if section.is_some() {
+            panic!(
+                "Mem::write_mem_slice() addr={addr:x} len={len} is not inside the write section"
+            );
+        }
+
+        // Record the previous content, if requested
+        if let Some(write_journal) = &mut self.write_journal {
+            for i in 0..len {
+                let offset = position + i * 8;
+                write_journal.push(MemWriteRecord {
+                    addr: addr + (i * 8) as u64,
+                    width: 8,
+                    previous_value: u64::from_le_bytes(
+                        self.write_section.buffer[offset..offset + 8].try_into().unwrap(),
+                    ),
+                });
+            }
+        }
+
+        let bytes = &mut self.write_section.buffer[position..position + len * 8];
+        for (chunk, word) in bytes.chunks_exact_mut(8).zip(words) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+    }
+
+    /// Returns the section containing the slice, i.e. None for the write section or the index of
+    /// the read section, and the position of the slice in the section buffer
+    fn slice_section(&self, addr: u64, len: usize, caller: &str) -> (Option<usize>, usize) {
+        if (addr & 0x07) != 0 {
+            panic!("{caller} addr={addr:x} not aligned to 8 bytes");
+        }
+        let size = len as u64 * 8;
+        if self.mmio_regions.iter().any(|region| region.overlaps(addr, size)) {
+            panic!("{caller} addr={addr:x} len={len} overlaps an MMIO region");
+        }
+
+        // First try the write section
+        if (addr >= self.write_section.start) && (addr + size <= self.write_section.end) {
+            return (None, (addr - self.write_section.start) as usize);
+        }
+
+        // Search for the read section that contains the address, since they are sorted by start
+        let index = self.read_sections.partition_point(|section| section.start <= addr);
+        if index > 0 {
+            let section = &self.read_sections[index - 1];
+            if addr + size <= section.end {
+                // The free input pointer is not stored in the section buffer
+                if (addr <= INPUT_ADDR) && (addr + size > INPUT_ADDR) {
+                    panic!("{caller} addr={addr:x} len={len} includes the free input pointer");
+                }
+                return (Some(index - 1), (addr - section.start) as usize);
+            }
+        }
+        panic!("{caller} section not found for addr={addr:x} len={len}");
+    }
+
     #[inline(always)]
     pub fn address_is_register(address: u64) -> bool {
         ((address & 0x7) == 0) && (REG_FIRST..=REG_LAST).contains(&address)
@@ -797,3 +1073,25 @@ impl Mem {
 
     //pub fn get_non_aligned_data_from_required(address: u64, width: u8,)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mem_slice() {
+        let mut mem = Mem::new();
+        mem.add_write_section(RAM_ADDR, RAM_SIZE);
+        mem.add_read_section(ROM_ADDR, &[1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0]);
+        let mut words = [0u64; 3];
+        mem.read_mem_slice(ROM_ADDR, &mut words[..2]);
+        assert_eq!(words[..2], [1, 2]);
+
+        mem.write_journal = Some(Vec::new());
+        mem.write_mem_slice(AVAILABLE_MEM_ADDR + 8, &[3, 4]);
+        assert_eq!(mem.read(AVAILABLE_MEM_ADDR + 16, 8), 4);
+        mem.read_mem_slice(AVAILABLE_MEM_ADDR, &mut words);
+        assert_eq!(words, [0, 3, 4]);
+        assert_eq!(mem.write_journal.as_ref().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,150 @@
+//! Memory-mapped I/O (MMIO) regions
+//!
+//! * An MMIO region is a range of addresses whose accesses are not served by the memory sections,
+//!   but forwarded to a device implementing the `MmioDevice` trait.
+//! * This allows emulating the devices that replace ecalls in Zisk (e.g. the input data region or
+//!   an output region) and experimenting with new device interfaces without modifying the memory
+//!   model.
+//! * MMIO regions are honored by all the `Mem` accesses: `read()`, `write()`, `write_silent()`,
+//!   and the `read_required()` and `write_silent_required()` ones used to generate the traces,
+//!   which return the aligned values read from the device.  Only the slice accesses reject them.
+//!   The device values are not part of the proven execution, so they should only be used for
+//!   emulation and experimentation purposes.
+
+use core::fmt;
+
+/// Device attached to an MMIO region.
+/// Addresses are absolute, i.e. they are not relative to the region start address, and width is
+/// always 1, 2, 4 or 8 bytes.
+pub trait MmioDevice: Send + Sync {
+    /// Returns the 1, 2, 4 or 8 bytes value read from the provided address
+    fn read(&self, addr: u64, width: u64) -> u64;
+
+    /// Handles the write of a 1, 2, 4 or 8 bytes value to the provided address
+    fn write(&mut self, addr: u64, val: u64, width: u64);
+}
+
+/// MMIO device built from a pair of read and write callbacks
+pub struct MmioCallbacks<R, W>
+where
+    R: Fn(u64, u64) -> u64 + Send + Sync,
+    W: FnMut(u64, u64, u64) + Send + Sync,
+{
+    /// Read callback, called with (address, width), returns the read value
+    pub on_read: R,
+    /// Write callback, called with (address, value, width)
+    pub on_write: W,
+}
+
+impl<R, W> MmioCallbacks<R, W>
+where
+    R: Fn(u64, u64) -> u64 + Send + Sync,
+    W: FnMut(u64, u64, u64) + Send + Sync,
+{
+    /// MMIO callbacks constructor
+    pub fn new(on_read: R, on_write: W) -> Self {
+        Self { on_read, on_write }
+    }
+}
+
+impl<R, W> MmioDevice for MmioCallbacks<R, W>
+where
+    R: Fn(u64, u64) -> u64 + Send + Sync,
+    W: FnMut(u64, u64, u64) + Send + Sync,
+{
+    fn read(&self, addr: u64, width: u64) -> u64 {
+        (self.on_read)(addr, width)
+    }
+
+    fn write(&mut self, addr: u64, val: u64, width: u64) {
+        (self.on_write)(addr, val, width)
+    }
+}
+
+/// MMIO region, covering the addresses in the range [start, end)
+pub struct MmioRegion {
+    pub start: u64,
+    pub end: u64,
+    pub device: Box<dyn MmioDevice>,
+}
+
+impl MmioRegion {
+    /// Returns true if the region contains the whole [addr, addr + width) range
+    #[inline(always)]
+    pub fn contains(&self, addr: u64, width: u64) -> bool {
+        (addr >= self.start) && (addr + width <= self.end)
+    }
+
+    /// Returns true if the region contains any address of the [addr, addr + width) range
+    #[inline(always)]
+    pub fn overlaps(&self, addr: u64, width: u64) -> bool {
+        (addr < self.end) && (addr + width > self.start)
+    }
+}
+
+impl fmt::Debug for MmioRegion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MmioRegion {{ start={:x} end={:x} }}", self.start, self.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Mem, OUTPUT_ADDR};
+    use std::sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    };
+
+    const DEVICE_ADDR: u64 = 0x30000000;
+
+    #[test]
+    fn test_mmio_read_write() {
+        let mut mem = Mem::new();
+        mem.add_write_section(OUTPUT_ADDR, 0x100);
+
+        let last_write = Arc::new(AtomicU64::new(0));
+        let last_write_clone = last_write.clone();
+        mem.add_mmio_region(
+            DEVICE_ADDR,
+            0x10,
+            Box::new(MmioCallbacks::new(
+                |addr, width| addr - DEVICE_ADDR + width,
+                move |_, val, _| last_write_clone.store(val, Ordering::Relaxed),
+            )),
+        );
+
+        // Accesses inside the region are forwarded to the device
+        assert_eq!(mem.read(DEVICE_ADDR + 8, 8), 16);
+        mem.write(DEVICE_ADDR + 4, 0x1234, 4);
+        assert_eq!(last_write.load(Ordering::Relaxed), 0x1234);
+
+        // Also the accesses that return the aligned values required by the traces
+        assert_eq!(mem.read_required(DEVICE_ADDR + 8, 8), (16, vec![]));
+        assert_eq!(mem.read_required(DEVICE_ADDR + 2, 2), (4, vec![8]));
+        assert_eq!(mem.write_silent_required(DEVICE_ADDR + 6, 0x99, 4), vec![8, 16]);
+        assert_eq!(last_write.load(Ordering::Relaxed), 0x99);
+
+        // Accesses outside the region are still served by the memory sections
+        mem.write(OUTPUT_ADDR, 0x5678, 8);
+        assert_eq!(mem.read(OUTPUT_ADDR, 8), 0x5678);
+        assert_eq!(last_write.load(Ordering::Relaxed), 0x99);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_mmio_overlapping_regions() {
+        let mut mem = Mem::new();
+        mem.add_mmio_region(
+            DEVICE_ADDR,
+            0x10,
+            Box::new(MmioCallbacks::new(|_, _| 0, |_, _, _| {})),
+        );
+        mem.add_mmio_region(
+            DEVICE_ADDR + 8,
+            0x10,
+            Box::new(MmioCallbacks::new(|_, _| 0, |_, _, _| {})),
+        );
+    }
+}
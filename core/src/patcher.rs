@@ -0,0 +1,276 @@
+//! Guest code patcher
+//!
+//! * `Patcher` instruments a prebuilt guest program by replacing the instruction at a given pc
+//!   with a jump to a trampoline, e.g. to collect coverage of binaries built without
+//!   instrumentation for fuzzing.
+//! * The trampolines are placed in a new executable section.  Every trampoline contains the
+//!   instrumentation instructions, the relocated original instruction, and a jump back to the
+//!   instruction following the patched one.
+//! * The relocated instruction is rewritten when it depends on its own pc: branches, `jal` and
+//!   `auipc` are rewritten to keep their original targets and results.  Sites that cannot be
+//!   patched, e.g. compressed instructions (too short to hold a jump) or `jalr` with a link
+//!   register (that would return into the trampoline), are reported with a `PatchError`.
+
+use core::fmt;
+use std::collections::BTreeSet;
+
+use riscv::{riscv_interpreter, RiscvInstruction};
+
+use crate::elf_extraction::{DataSection, ElfPayload};
+
+/// Reason why an instruction cannot be patched
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchError {
+    /// The pc is not the address of an instruction of an executable section
+    NotCode { pc: u64 },
+    /// The instruction is compressed, so a 4-bytes jump does not fit in its place
+    Compressed { pc: u64 },
+    /// The trampoline or the original target is out of the range of a jump
+    OutOfRange { pc: u64 },
+    /// The instruction is an indirect jump that stores a return address
+    IndirectLink { pc: u64 },
+    /// The instruction has already been patched
+    AlreadyPatched { pc: u64 },
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PatchError::NotCode { pc } => write!(f, "pc=0x{pc:x} is not an instruction address"),
+            PatchError::Compressed { pc } => {
+                write!(f, "pc=0x{pc:x} is a compressed instruction, too short for a jump")
+            }
+            PatchError::OutOfRange { pc } => {
+                write!(f, "pc=0x{pc:x} is out of the jump range of its trampoline or target")
+            }
+            PatchError::IndirectLink { pc } => {
+                write!(f, "pc=0x{pc:x} is an indirect jump storing a return address")
+            }
+            PatchError::AlreadyPatched { pc } => write!(f, "pc=0x{pc:x} is already patched"),
+        }
+    }
+}
+
+/// Encodes `jal rd, offset`, or returns None if the offset is out of range
+fn encode_jal(rd: u32, offset: i64) -> Option<u32> {
+    if (offset & 1 != 0) || !(-(1 << 20)..(1 << 20)).contains(&offset) {
+        return None;
+    }
+    let imm = offset as u32;
+    Some(
+        (((imm >> 20) & 0x1) << 31)
+            | (((imm >> 1) & 0x3ff) << 21)
+            | (((imm >> 11) & 0x1) << 20)
+            | (((imm >> 12) & 0xff) << 12)
+            | (rd << 7)
+            | 0x6f,
+    )
+}
+
+/// Replaces the offset of a branch instruction, which must be in range
+fn encode_branch_offset(inst: u32, offset: i64) -> u32 {
+    let imm = offset as u32;
+    (inst & 0x01fff07f)
+        | (((imm >> 12) & 0x1) << 31)
+        | (((imm >> 5) & 0x3f) << 25)
+        | (((imm >> 1) & 0xf) << 8)
+        | (((imm >> 11) & 0x1) << 7)
+}
+
+/// Encodes `auipc rd, hi; addi rd, rd, lo` so that `rd = pc + offset`, or returns None if the
+/// offset is out of range
+fn encode_pc_offset(rd: u32, offset: i64) -> Option<[u32; 2]> {
+    if !(i32::MIN as i64..=i32::MAX as i64 - 0x800).contains(&offset) {
+        return None;
+    }
+    let hi = (offset + 0x800) >> 12;
+    let lo = offset - (hi << 12);
+    Some([
+        ((hi as u32) << 12) | (rd << 7) | 0x17,
+        ((lo as u32) << 20) | (rd << 15) | (rd << 7) | 0x13,
+    ])
+}
+
+/// Instruments a program by redirecting some of its instructions to trampolines
+pub struct Patcher {
+    /// Program being patched
+    program: ElfPayload,
+    /// Address of the trampolines section
+    trampoline_addr: u64,
+    /// Instructions of the trampolines section
+    trampolines: Vec<u32>,
+    /// Patched pcs
+    patched: BTreeSet<u64>,
+}
+
+impl Patcher {
+    /// Creates a patcher of the program, placing the trampolines at `trampoline_addr`, which must
+    /// be 4-bytes aligned and not overlap the program sections
+    pub fn new(program: ElfPayload, trampoline_addr: u64) -> Self {
+        if trampoline_addr & 0x3 != 0 {
+            panic!("Patcher::new() trampoline_addr=0x{trampoline_addr:x} is not 4-bytes aligned");
+        }
+        Self { program, trampoline_addr, trampolines: Vec::new(), patched: BTreeSet::new() }
+    }
+
+    /// Returns the instruction at `pc`, and the section and offset where it is stored
+    fn instruction_at(&self, pc: u64) -> Result<(RiscvInstruction, usize, usize), PatchError> {
+        let (index, section) = self
+            .program
+            .exec
+            .iter()
+            .enumerate()
+            .find(|(_, s)| (pc >= s.addr) && (pc + 2 <= s.addr + s.data.len() as u64))
+            .ok_or(PatchError::NotCode { pc })?;
+        let offset = (pc - section.addr) as usize;
+        let low = u16::from_le_bytes([section.data[offset], section.data[offset + 1]]);
+        if low & 0x3 != 0x3 {
+            return Err(PatchError::Compressed { pc });
+        }
+        if offset + 4 > section.data.len() {
+            return Err(PatchError::NotCode { pc });
+        }
+        let high = u16::from_le_bytes([section.data[offset + 2], section.data[offset + 3]]);
+        let i = riscv_interpreter(pc, &[low, high]).remove(0);
+        Ok((i, index, offset))
+    }
+
+    /// Builds the instructions of the trampoline of the instruction `i`, placed at `addr`
+    fn build_trampoline(
+        i: &RiscvInstruction,
+        addr: u64,
+        instrumentation: &[u32],
+    ) -> Result<Vec<u32>, PatchError> {
+        let pc = i.rom_address;
+        let next = pc + 4;
+        let target = pc.wrapping_add(i.imm as i64 as u64);
+        let out_of_range = PatchError::OutOfRange { pc };
+        let mut code = instrumentation.to_vec();
+        // Returns the offset from the next trampoline instruction to `to`
+        let offset =
+            |code: &Vec<u32>, to: u64| to.wrapping_sub(addr + 4 * code.len() as u64) as i64;
+
+        match i.inst.as_str() {
+            "beq" | "bne" | "blt" | "bge" | "bltu" | "bgeu" => {
+                // Taken: skip the jump back to the next instruction, and jump to the target
+                code.push(encode_branch_offset(i.rvinst, 8));
+                code.push(encode_jal(0, offset(&code, next)).ok_or(out_of_range)?);
+                code.push(encode_jal(0, offset(&code, target)).ok_or(out_of_range)?);
+                return Ok(code);
+            }
+            "jal" => {
+                // Store the original return address, then jump to the target
+                if i.rd != 0 {
+                    code.extend(encode_pc_offset(i.rd, offset(&code, next)).ok_or(out_of_range)?);
+                }
+                code.push(encode_jal(0, offset(&code, target)).ok_or(out_of_range)?);
+                return Ok(code);
+            }
+            "jalr" => {
+                if i.rd != 0 {
+                    return Err(PatchError::IndirectLink { pc });
+                }
+                code.push(i.rvinst);
+                return Ok(code);
+            }
+            "auipc" => {
+                if i.rd != 0 {
+                    code.extend(encode_pc_offset(i.rd, offset(&code, target)).ok_or(out_of_range)?);
+                }
+            }
+            _ => code.push(i.rvinst),
+        }
+        code.push(encode_jal(0, offset(&code, next)).ok_or(out_of_range)?);
+        Ok(code)
+    }
+
+    /// Replaces the instruction at `pc` with a jump to a trampoline that executes the
+    /// instrumentation instructions, which must not depend on their pc, before the original one
+    pub fn patch(&mut self, pc: u64, instrumentation: &[u32]) -> Result<(), PatchError> {
+        if self.patched.contains(&pc) {
+            return Err(PatchError::AlreadyPatched { pc });
+        }
+        let (i, section, offset) = self.instruction_at(pc)?;
+
+        let addr = self.trampoline_addr + 4 * self.trampolines.len() as u64;
+        let trampoline = Self::build_trampoline(&i, addr, instrumentation)?;
+        let jump =
+            encode_jal(0, addr.wrapping_sub(pc) as i64).ok_or(PatchError::OutOfRange { pc })?;
+
+        self.program.exec[section].data[offset..offset + 4].copy_from_slice(&jump.to_le_bytes());
+        self.trampolines.extend(trampoline);
+        self.patched.insert(pc);
+        Ok(())
+    }
+
+    /// Patches all the provided pcs with the same instrumentation, and returns the errors of the
+    /// sites that could not be patched
+    pub fn patch_all(&mut self, pcs: &[u64], instrumentation: &[u32]) -> Vec<PatchError> {
+        pcs.iter().filter_map(|pc| self.patch(*pc, instrumentation).err()).collect()
+    }
+
+    /// Returns the patched program, including the trampolines section
+    pub fn finish(mut self) -> ElfPayload {
+        if !self.trampolines.is_empty() {
+            let data = self.trampolines.iter().flat_map(|inst| inst.to_le_bytes()).collect();
+            self.program.exec.push(DataSection { addr: self.trampoline_addr, data });
+        }
+        self.program
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE_ADDR: u64 = 0x80000000;
+    const TRAMPOLINE_ADDR: u64 = 0x80001000;
+    const NOP: u32 = 0x00000013;
+
+    #[test]
+    fn test_patcher() {
+        // addi a0, zero, 1; beq a0, zero, +8; c.nop; c.nop
+        let mut data: Vec<u8> =
+            [0x00100513u32, 0x00050463].iter().flat_map(|i| i.to_le_bytes()).collect();
+        data.extend([0x01, 0x00, 0x01, 0x00]);
+        let program =
+            ElfPayload { exec: vec![DataSection { addr: CODE_ADDR, data }], ..Default::default() };
+
+        let mut patcher = Patcher::new(program, TRAMPOLINE_ADDR);
+        let errors = patcher.patch_all(&[CODE_ADDR, CODE_ADDR + 4, CODE_ADDR + 8], &[NOP]);
+        assert_eq!(errors, vec![PatchError::Compressed { pc: CODE_ADDR + 8 }]);
+        assert_eq!(
+            patcher.patch(CODE_ADDR, &[NOP]),
+            Err(PatchError::AlreadyPatched { pc: CODE_ADDR })
+        );
+        let program = patcher.finish();
+
+        let word = |section: &DataSection, addr: u64| {
+            let offset = (addr - section.addr) as usize;
+            u32::from_le_bytes(section.data[offset..offset + 4].try_into().unwrap())
+        };
+        let (code, trampolines) = (&program.exec[0], &program.exec[1]);
+        assert_eq!(word(code, CODE_ADDR), encode_jal(0, 0x1000).unwrap());
+        assert_eq!(word(code, CODE_ADDR + 4), encode_jal(0, 0x1000 - 4 + 12).unwrap());
+        let expected = [
+            // addi trampoline: nop; addi a0, zero, 1; j CODE_ADDR + 4
+            NOP,
+            0x00100513,
+            encode_jal(0, CODE_ADDR as i64 + 4 - (TRAMPOLINE_ADDR as i64 + 8)).unwrap(),
+            // beq trampoline: nop; beq a0, zero, +8; j CODE_ADDR + 8; j CODE_ADDR + 12
+            NOP,
+            encode_branch_offset(0x00050463, 8),
+            encode_jal(0, CODE_ADDR as i64 + 8 - (TRAMPOLINE_ADDR as i64 + 20)).unwrap(),
+            encode_jal(0, CODE_ADDR as i64 + 12 - (TRAMPOLINE_ADDR as i64 + 24)).unwrap(),
+        ];
+        for (n, inst) in expected.iter().enumerate() {
+            assert_eq!(word(trampolines, TRAMPOLINE_ADDR + 4 * n as u64), *inst);
+        }
+
+        // The relocated instructions are decoded as the original ones, with the new offsets
+        let decoded = riscv_interpreter(TRAMPOLINE_ADDR, &crate::convert_vector(&trampolines.data));
+        assert_eq!(decoded[4].inst, "beq");
+        assert_eq!(decoded[4].imm, 8);
+        assert_eq!(decoded[2].imm as i64, CODE_ADDR as i64 + 4 - (TRAMPOLINE_ADDR as i64 + 8));
+    }
+}
@@ -0,0 +1,141 @@
+//! Precompile codes registry
+//!
+//! * Every precompile is invoked by the guest writing to a CSR whose address is its precompile
+//!   code, e.g. `csrw 0x800, a0` calls keccak.  The guest side of these codes is defined in
+//!   `ziskos/entrypoint/src/syscalls/syscall.rs`, and must match this registry.
+//! * `PRECOMPILE_CODES` is the single list of assigned codes and names.  A compile-time check
+//!   rejects duplicated codes or names, and overlaps with the CSR ranges reserved for fcall.
+//! * The names are the ones of the Zisk operations the precompiles are transpiled into.
+
+/// Code of the keccak-f permutation precompile
+pub const PRECOMPILE_KECCAK: u16 = 0x800;
+/// Code of the 256-bit arithmetic precompile
+pub const PRECOMPILE_ARITH256: u16 = 0x801;
+/// Code of the 256-bit modular arithmetic precompile
+pub const PRECOMPILE_ARITH256_MOD: u16 = 0x802;
+/// Code of the secp256k1 point addition precompile
+pub const PRECOMPILE_SECP256K1_ADD: u16 = 0x803;
+/// Code of the secp256k1 point doubling precompile
+pub const PRECOMPILE_SECP256K1_DBL: u16 = 0x804;
+/// Code of the sha256 extend and compress precompile
+pub const PRECOMPILE_SHA256: u16 = 0x805;
+/// Code of the bn254 point addition precompile
+pub const PRECOMPILE_BN254_CURVE_ADD: u16 = 0x806;
+/// Code of the bn254 point doubling precompile
+pub const PRECOMPILE_BN254_CURVE_DBL: u16 = 0x807;
+/// Code of the bn254 complex addition precompile
+pub const PRECOMPILE_BN254_COMPLEX_ADD: u16 = 0x808;
+/// Code of the bn254 complex subtraction precompile
+pub const PRECOMPILE_BN254_COMPLEX_SUB: u16 = 0x809;
+/// Code of the bn254 complex multiplication precompile
+pub const PRECOMPILE_BN254_COMPLEX_MUL: u16 = 0x80A;
+/// Code of the 384-bit modular arithmetic precompile
+pub const PRECOMPILE_ARITH384_MOD: u16 = 0x80B;
+/// Code of the bls12-381 point addition precompile
+pub const PRECOMPILE_BLS12_381_CURVE_ADD: u16 = 0x80C;
+/// Code of the bls12-381 point doubling precompile
+pub const PRECOMPILE_BLS12_381_CURVE_DBL: u16 = 0x80D;
+/// Code of the bls12-381 complex addition precompile
+pub const PRECOMPILE_BLS12_381_COMPLEX_ADD: u16 = 0x80E;
+/// Code of the bls12-381 complex subtraction precompile
+pub const PRECOMPILE_BLS12_381_COMPLEX_SUB: u16 = 0x80F;
+/// Code of the bls12-381 complex multiplication precompile
+pub const PRECOMPILE_BLS12_381_COMPLEX_MUL: u16 = 0x810;
+/// Code of the 256-bit addition precompile
+pub const PRECOMPILE_ADD256: u16 = 0x811;
+
+/// All the assigned precompile codes, with their names
+pub const PRECOMPILE_CODES: [(u16, &str); 18] = [
+    (PRECOMPILE_KECCAK, "keccak"),
+    (PRECOMPILE_ARITH256, "arith256"),
+    (PRECOMPILE_ARITH256_MOD, "arith256_mod"),
+    (PRECOMPILE_SECP256K1_ADD, "secp256k1_add"),
+    (PRECOMPILE_SECP256K1_DBL, "secp256k1_dbl"),
+    (PRECOMPILE_SHA256, "sha256"),
+    (PRECOMPILE_BN254_CURVE_ADD, "bn254_curve_add"),
+    (PRECOMPILE_BN254_CURVE_DBL, "bn254_curve_dbl"),
+    (PRECOMPILE_BN254_COMPLEX_ADD, "bn254_complex_add"),
+    (PRECOMPILE_BN254_COMPLEX_SUB, "bn254_complex_sub"),
+    (PRECOMPILE_BN254_COMPLEX_MUL, "bn254_complex_mul"),
+    (PRECOMPILE_ARITH384_MOD, "arith384_mod"),
+    (PRECOMPILE_BLS12_381_CURVE_ADD, "bls12_381_curve_add"),
+    (PRECOMPILE_BLS12_381_CURVE_DBL, "bls12_381_curve_dbl"),
+    (PRECOMPILE_BLS12_381_COMPLEX_ADD, "bls12_381_complex_add"),
+    (PRECOMPILE_BLS12_381_COMPLEX_SUB, "bls12_381_complex_sub"),
+    (PRECOMPILE_BLS12_381_COMPLEX_MUL, "bls12_381_complex_mul"),
+    (PRECOMPILE_ADD256, "add256"),
+];
+
+/// CSR address ranges [start, end] reserved for fcall, which precompile codes must not use
+pub const PRECOMPILE_RESERVED_RANGES: [(u16, u16); 3] =
+    [(0x8C0, 0x8DF), (0x8F0, 0x8FF), (0xFFE, 0xFFE)];
+
+/// Compares two strings at compile time
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Returns true if no code or name is duplicated, and no code is in a reserved range
+const fn check_precompile_codes(codes: &[(u16, &str)]) -> bool {
+    let mut i = 0;
+    while i < codes.len() {
+        let mut j = i + 1;
+        while j < codes.len() {
+            if (codes[i].0 == codes[j].0) || str_eq(codes[i].1, codes[j].1) {
+                return false;
+            }
+            j += 1;
+        }
+        let mut r = 0;
+        while r < PRECOMPILE_RESERVED_RANGES.len() {
+            let (start, end) = PRECOMPILE_RESERVED_RANGES[r];
+            if (codes[i].0 >= start) && (codes[i].0 <= end) {
+                return false;
+            }
+            r += 1;
+        }
+        i += 1;
+    }
+    true
+}
+
+const _: () = assert!(
+    check_precompile_codes(&PRECOMPILE_CODES),
+    "PRECOMPILE_CODES contains a duplicated code or name, or a reserved code"
+);
+
+/// Returns the name of the precompile with the provided code, if assigned
+pub fn precompile_name(code: u16) -> Option<&'static str> {
+    PRECOMPILE_CODES.iter().find(|(c, _)| *c == code).map(|(_, name)| *name)
+}
+
+/// Returns the code of the precompile with the provided name, if assigned
+pub fn precompile_code(name: &str) -> Option<u16> {
+    PRECOMPILE_CODES.iter().find(|(_, n)| *n == name).map(|(code, _)| *code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_precompile_codes() {
+        assert_eq!(precompile_name(PRECOMPILE_SHA256), Some("sha256"));
+        assert_eq!(precompile_code("add256"), Some(PRECOMPILE_ADD256));
+        assert_eq!(precompile_name(0x8C0), None);
+        assert!(!check_precompile_codes(&[(0x800, "a"), (0x800, "b")]));
+        assert!(!check_precompile_codes(&[(0x800, "a"), (0x801, "a")]));
+        assert!(!check_precompile_codes(&[(0x8F0, "a")]));
+    }
+}
@@ -0,0 +1,238 @@
+//! Persistent cache of decoded programs
+//!
+//! * Proving jobs of the same guest decode the same code again and again.  `ProgramCache` stores
+//!   the decoded instructions of a program in a directory, so that they are decoded only once.
+//! * The entries are keyed by the program commitment, see `program_commitment()`, and by
+//!   `DECODER_VERSION`, so that a decoder change never returns instructions decoded by an older
+//!   one.  The commitment is computed once per guest, e.g. when it is registered, and provided by
+//!   the caller.
+//! * Every entry stores its key and the SHA-256 of its payload, which are verified when it is
+//!   read.  A missing, truncated or corrupted entry is a cache miss: `instructions()` then decodes
+//!   the program and replaces the entry.
+//! * Entries are written to a temporary file that is renamed, so that concurrent jobs never read a
+//!   partially written entry.
+
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+use riscv::RiscvInstruction;
+use sha2::{Digest, Sha256};
+
+use crate::{elf_extraction::ElfPayload, program_instructions};
+
+/// Version of the decoded instructions format, to be increased whenever the decoder or
+/// `RiscvInstruction` change
+pub const DECODER_VERSION: u32 = 2;
+
+/// Magic bytes at the start of every cache entry
+const CACHE_MAGIC: &[u8; 8] = b"ZISKPRGC";
+
+/// Directory of decoded programs
+#[derive(Debug, Clone)]
+pub struct ProgramCache {
+    dir: PathBuf,
+}
+
+impl ProgramCache {
+    /// Opens the cache stored in `dir`, creating the directory if it does not exist
+    pub fn open(dir: &Path) -> Result<Self, Box<dyn Error>> {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Error creating cache dir={}: {e}", dir.display()))?;
+        Ok(Self { dir: dir.to_path_buf() })
+    }
+
+    /// Returns the path of the entry of the program with the provided commitment
+    pub fn entry_path(&self, commitment: &[u8; 32]) -> PathBuf {
+        let name: String = commitment.iter().map(|b| format!("{b:02x}")).collect();
+        self.dir.join(format!("{name}.v{DECODER_VERSION}.bin"))
+    }
+
+    /// Returns the cached instructions of the program with the provided commitment, or None if
+    /// they are not cached or their entry is not valid
+    pub fn get(&self, commitment: &[u8; 32]) -> Option<Vec<(u64, RiscvInstruction)>> {
+        let bytes = fs::read(self.entry_path(commitment)).ok()?;
+        decode_entry(&bytes, commitment)
+    }
+
+    /// Stores the instructions of the program with the provided commitment
+    pub fn put(
+        &self,
+        commitment: &[u8; 32],
+        instructions: &[(u64, RiscvInstruction)],
+    ) -> Result<(), Box<dyn Error>> {
+        let path = self.entry_path(commitment);
+        let tmp_path = path.with_extension(format!("tmp{}", process::id()));
+        fs::write(&tmp_path, encode_entry(instructions, commitment))
+            .map_err(|e| format!("Error writing cache entry={}: {e}", tmp_path.display()))?;
+        fs::rename(&tmp_path, &path)
+            .map_err(|e| format!("Error writing cache entry={}: {e}", path.display()))?;
+        Ok(())
+    }
+
+    /// Returns the instructions of the program with the provided commitment, from the cache if
+    /// they are there, or decoding them and storing them otherwise.  A failure to store them is
+    /// not an error, since the cache is only an optimization.
+    pub fn instructions(
+        &self,
+        commitment: &[u8; 32],
+        program: &ElfPayload,
+    ) -> Vec<(u64, RiscvInstruction)> {
+        if let Some(instructions) = self.get(commitment) {
+            return instructions;
+        }
+        let instructions = program_instructions(program);
+        if let Err(e) = self.put(commitment, &instructions) {
+            eprintln!("ProgramCache::instructions() failed to store the program: {e}");
+        }
+        instructions
+    }
+}
+
+/// Serializes a cache entry: the magic bytes, the decoder version, the commitment, the payload
+/// length and the payload, followed by the SHA-256 of the payload
+fn encode_entry(instructions: &[(u64, RiscvInstruction)], commitment: &[u8; 32]) -> Vec<u8> {
+    let mut payload = Vec::new();
+    payload.extend((instructions.len() as u64).to_le_bytes());
+    for (pc, i) in instructions {
+        payload.extend(pc.to_le_bytes());
+        payload.extend(i.rom_address.to_le_bytes());
+        for field in [i.rvinst, i.funct2, i.funct3, i.funct5, i.funct7, i.rd, i.rs1, i.rs2, i.rs3] {
+            payload.extend(field.to_le_bytes());
+        }
+        payload.extend(i.imm.to_le_bytes());
+        for field in [i.imme, i.aq, i.rl, i.csr, i.pred, i.succ] {
+            payload.extend(field.to_le_bytes());
+        }
+        for text in [&i.t, &i.inst] {
+            payload.push(text.len() as u8);
+            payload.extend(text.as_bytes());
+        }
+    }
+
+    let mut entry = Vec::with_capacity(payload.len() + 84);
+    entry.extend(CACHE_MAGIC);
+    entry.extend(DECODER_VERSION.to_le_bytes());
+    entry.extend(commitment);
+    entry.extend((payload.len() as u64).to_le_bytes());
+    entry.extend(&payload);
+    entry.extend(Sha256::digest(&payload));
+    entry
+}
+
+/// Deserializes a cache entry, returning None if it is not a valid entry of the commitment for the
+/// current decoder version
+fn decode_entry(entry: &[u8], commitment: &[u8; 32]) -> Option<Vec<(u64, RiscvInstruction)>> {
+    let mut reader = EntryReader { bytes: entry };
+    if (reader.bytes(8)? != CACHE_MAGIC)
+        || (reader.u32()? != DECODER_VERSION)
+        || (reader.bytes(32)? != commitment)
+    {
+        return None;
+    }
+    let payload_len = reader.u64()? as usize;
+    let payload = reader.bytes(payload_len)?;
+    if (reader.bytes(32)? != Sha256::digest(payload).as_slice()) || !reader.bytes.is_empty() {
+        return None;
+    }
+
+    let mut reader = EntryReader { bytes: payload };
+    let count = reader.u64()? as usize;
+    let mut instructions = Vec::with_capacity(count.min(payload_len));
+    for _ in 0..count {
+        let pc = reader.u64()?;
+        let mut i = RiscvInstruction { rom_address: reader.u64()?, ..Default::default() };
+        for field in [
+            &mut i.rvinst,
+            &mut i.funct2,
+            &mut i.funct3,
+            &mut i.funct5,
+            &mut i.funct7,
+            &mut i.rd,
+            &mut i.rs1,
+            &mut i.rs2,
+            &mut i.rs3,
+        ] {
+            *field = reader.u32()?;
+        }
+        i.imm = reader.u32()? as i32;
+        for field in [&mut i.imme, &mut i.aq, &mut i.rl, &mut i.csr, &mut i.pred, &mut i.succ] {
+            *field = reader.u32()?;
+        }
+        for text in [&mut i.t, &mut i.inst] {
+            let len = reader.bytes(1)?[0] as usize;
+            *text = String::from_utf8(reader.bytes(len)?.to_vec()).ok()?;
+        }
+        instructions.push((pc, i));
+    }
+    reader.bytes.is_empty().then_some(instructions)
+}
+
+/// Little-endian reader of a cache entry, returning None at its end
+struct EntryReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> EntryReader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < len {
+            return None;
+        }
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Some(bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elf_extraction::DataSection;
+
+    #[test]
+    fn test_program_cache() {
+        let dir = std::env::temp_dir().join(format!("zisk-program-cache-{}", process::id()));
+        let cache = ProgramCache::open(&dir).unwrap();
+
+        // addi a0, a0, 1; c.nop
+        let mut data = 0x00150513u32.to_le_bytes().to_vec();
+        data.extend([0x01, 0x00]);
+        let program =
+            ElfPayload { exec: vec![DataSection { addr: 0x1000, data }], ..Default::default() };
+        let commitment = [7u8; 32];
+
+        assert!(cache.get(&commitment).is_none());
+        let decoded = cache.instructions(&commitment, &program);
+        let cached = cache.get(&commitment).unwrap();
+        assert_eq!(cached.len(), 2);
+        for ((pc, i), (cached_pc, cached_i)) in decoded.iter().zip(&cached) {
+            assert_eq!(pc, cached_pc);
+            assert_eq!(i.to_text(), cached_i.to_text());
+            assert_eq!((i.rvinst, i.imm, &i.t), (cached_i.rvinst, cached_i.imm, &cached_i.t));
+        }
+
+        // A corrupted entry is a miss, and is replaced
+        let path = cache.entry_path(&commitment);
+        let mut entry = fs::read(&path).unwrap();
+        let last = entry.len() - 40;
+        entry[last] ^= 1;
+        fs::write(&path, &entry).unwrap();
+        assert!(cache.get(&commitment).is_none());
+        assert!(cache.get(&[8u8; 32]).is_none());
+        assert_eq!(cache.instructions(&commitment, &program).len(), 2);
+        assert!(cache.get(&commitment).is_some());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
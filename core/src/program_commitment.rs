@@ -0,0 +1,130 @@
+//! Program commitment
+//!
+//! * `program_commitment()` computes a 32-byte hash of the code of a guest ELF, so that a prover
+//!   and a verifier can check that they are using the same guest before running anything.
+//! * The hash is computed over a canonical form of the decoded instruction stream, not over the
+//!   file bytes, so it does not change with the ELF headers, debug information, symbols, section
+//!   order or the way the code is split in sections.  The all-zeros encodings, used as padding
+//!   between functions and sections, and the data embedded in the code are skipped.
+//! * The canonical form is the SHA-256 of:
+//!   * the domain tag `PROGRAM_COMMITMENT_TAG`
+//!   * the target triple length as a little-endian u64, followed by the triple
+//!   * the entry point as a little-endian u64
+//!   * for every instruction, by increasing pc: the pc as a little-endian u64, the encoding length
+//!     in bytes as a u8, and the encoding as a little-endian u32
+//! * Only the code is committed; the data sections are not part of the commitment.
+
+use std::error::Error;
+
+use riscv::{decode_bytes, DecodedItem};
+use sha2::{Digest, Sha256};
+
+use crate::elf_extraction::{collect_elf_payload_from_bytes, ElfPayload};
+
+/// Domain separation tag of the commitment, to be changed if the canonical form changes
+pub const PROGRAM_COMMITMENT_TAG: &[u8] = b"zisk-program-commitment-v1";
+
+/// Target triple of the Zisk guests
+pub const ZISK_TARGET_TRIPLE: &str = "riscv64ima-zisk-zkvm-elf";
+
+/// Execution target a program is committed for; programs with the same code but different targets
+/// have different commitments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    /// Target triple the guest was built for
+    pub triple: String,
+}
+
+impl Target {
+    pub fn new(triple: &str) -> Self {
+        Self { triple: triple.to_string() }
+    }
+}
+
+impl Default for Target {
+    fn default() -> Self {
+        Self::new(ZISK_TARGET_TRIPLE)
+    }
+}
+
+/// Computes the commitment of the code of the provided ELF file bytes
+pub fn program_commitment(bytes: &[u8], target: &Target) -> Result<[u8; 32], Box<dyn Error>> {
+    let program = collect_elf_payload_from_bytes(bytes)?;
+    Ok(program_commitment_from_payload(&program, target))
+}
+
+/// Computes the commitment of the code of an already extracted ELF payload
+pub fn program_commitment_from_payload(program: &ElfPayload, target: &Target) -> [u8; 32] {
+    let mut instructions: Vec<(u64, u8, u32)> = program
+        .exec
+        .iter()
+        .flat_map(|section| decode_bytes(section.addr, &section.data, &program.data_in_text))
+        .filter_map(|item| match item {
+            DecodedItem::Instruction(i) if i.rvinst != 0 => Some(i),
+            _ => None,
+        })
+        .map(|i| (i.rom_address, i.len_bytes() as u8, i.rvinst))
+        .collect();
+    instructions.sort_unstable_by_key(|(pc, _, _)| *pc);
+
+    let mut hasher = Sha256::new();
+    hasher.update(PROGRAM_COMMITMENT_TAG);
+    hasher.update((target.triple.len() as u64).to_le_bytes());
+    hasher.update(target.triple.as_bytes());
+    hasher.update(program.entry_point.to_le_bytes());
+    for (pc, len, rvinst) in instructions {
+        hasher.update(pc.to_le_bytes());
+        hasher.update([len]);
+        hasher.update(rvinst.to_le_bytes());
+    }
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elf_extraction::DataSection;
+
+    const CODE_ADDR: u64 = 0x80000000;
+
+    fn payload(sections: &[(u64, &[u32])]) -> ElfPayload {
+        ElfPayload {
+            entry_point: CODE_ADDR,
+            exec: sections
+                .iter()
+                .map(|(addr, code)| DataSection {
+                    addr: *addr,
+                    data: code.iter().flat_map(|inst| inst.to_le_bytes()).collect(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_program_commitment() {
+        // addi a0, a0, 1; ret; padding; addi a0, zero, 5; ret
+        let code = [0x00150513, 0x00008067, 0, 0x00500513, 0x00008067];
+        let target = Target::default();
+        let commitment = program_commitment_from_payload(&payload(&[(CODE_ADDR, &code)]), &target);
+
+        // Same code split in two sections, in reverse order, without the padding
+        let split = payload(&[(CODE_ADDR + 12, &code[3..]), (CODE_ADDR, &code[..2])]);
+        assert_eq!(program_commitment_from_payload(&split, &target), commitment);
+
+        // Different immediate, address or target
+        let mut changed = code;
+        changed[3] = 0x00600513;
+        let changed = payload(&[(CODE_ADDR, &changed)]);
+        assert_ne!(program_commitment_from_payload(&changed, &target), commitment);
+        let moved = payload(&[(CODE_ADDR + 4, &code)]);
+        assert_ne!(program_commitment_from_payload(&moved, &target), commitment);
+        let other = Target::new("riscv64imac-unknown-none-elf");
+        assert_ne!(
+            program_commitment_from_payload(&payload(&[(CODE_ADDR, &code)]), &other),
+            commitment
+        );
+
+        assert!(program_commitment(&[0u8; 16], &target).is_err());
+    }
+}
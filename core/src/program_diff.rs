@@ -0,0 +1,355 @@
+//! Instruction stream diff of two guest builds
+//!
+//! * `diff_programs()` compares the decoded instructions of two builds of a guest, e.g. before and
+//!   after a compiler or ziskos upgrade, to review how the change affects the code to be proven.
+//! * The instructions are grouped by function symbol, and the functions of both builds are paired
+//!   by name, so that a function moved to a different address is still compared with itself.  The
+//!   instructions not covered by any symbol are compared as a single anonymous function.
+//! * Inside a function, the instructions are aligned with a longest common subsequence of their
+//!   shape, i.e. the instruction without its immediates.  Aligned instructions with different
+//!   immediates, typically branch offsets and constants shifted by the change, are reported as
+//!   immediate-only changes, and the non-aligned ones as added, removed or modified.
+//! * `function_churn()` summarizes the hunks by function, from the most to the least changed.
+
+use std::collections::BTreeMap;
+
+use riscv::{decode_bytes, DecodedItem, RiscvInstruction};
+
+use crate::{elf_extraction::ElfPayload, SymbolSize};
+
+/// Functions with more instruction pairs than this are aligned by position instead of with a
+/// longest common subsequence, to bound the memory used by the alignment table
+const MAX_ALIGNMENT_CELLS: usize = 1 << 26;
+
+/// Kind of change of an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChangeKind {
+    /// Instruction only present in the new build
+    Added,
+    /// Instruction only present in the old build
+    Removed,
+    /// Instruction replaced by a different one
+    Modified,
+    /// Same instruction with different immediates
+    ImmediateOnly,
+}
+
+/// Instruction of one of the builds, as reported in a diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffInstruction {
+    /// Program counter of the instruction
+    pub pc: u64,
+    /// Original instruction encoding
+    pub rvinst: u32,
+    /// Instruction mnemonic
+    pub inst: String,
+}
+
+impl DiffInstruction {
+    fn new(pc: u64, i: &RiscvInstruction) -> Self {
+        Self { pc, rvinst: i.rvinst, inst: i.inst.clone() }
+    }
+}
+
+/// Change of a single instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstructionChange {
+    pub kind: ChangeKind,
+    /// Instruction of the old build, None if added
+    pub old: Option<DiffInstruction>,
+    /// Instruction of the new build, None if removed
+    pub new: Option<DiffInstruction>,
+}
+
+/// Run of consecutive changed instructions of a function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffHunk {
+    /// Function symbol name, empty for the instructions not covered by any symbol
+    pub function: String,
+    pub changes: Vec<InstructionChange>,
+}
+
+impl DiffHunk {
+    /// Returns the number of changes of the provided kind
+    pub fn count(&self, kind: ChangeKind) -> usize {
+        self.changes.iter().filter(|c| c.kind == kind).count()
+    }
+}
+
+/// Changes of a function, summarized
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FunctionChurn {
+    /// Function symbol name, empty for the instructions not covered by any symbol
+    pub function: String,
+    pub added: usize,
+    pub removed: usize,
+    pub modified: usize,
+    pub immediate_only: usize,
+}
+
+impl FunctionChurn {
+    /// Returns the total number of changed instructions
+    pub fn total(&self) -> usize {
+        self.added + self.removed + self.modified + self.immediate_only
+    }
+}
+
+/// Decodes the executable sections of the program into `(pc, instruction)` pairs, skipping the
+/// data embedded in them
+pub fn program_instructions(program: &ElfPayload) -> Vec<(u64, RiscvInstruction)> {
+    program
+        .exec
+        .iter()
+        .flat_map(|section| decode_bytes(section.addr, &section.data, &program.data_in_text))
+        .filter_map(|item| match item {
+            DecodedItem::Instruction(i) => Some((i.rom_address, i)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Compares two instruction streams, aligning them by pc order, and returns the changed hunks
+pub fn diff_programs(
+    a: &[(u64, RiscvInstruction)],
+    b: &[(u64, RiscvInstruction)],
+) -> Vec<DiffHunk> {
+    diff_programs_by_symbol(a, &[], b, &[])
+}
+
+/// Compares two instruction streams, pairing their functions by symbol name, and returns the
+/// changed hunks, grouped by function name.  The symbols are the ones of `ElfSizeReport`; only the
+/// first one of every name is used.
+pub fn diff_programs_by_symbol(
+    a: &[(u64, RiscvInstruction)],
+    a_symbols: &[SymbolSize],
+    b: &[(u64, RiscvInstruction)],
+    b_symbols: &[SymbolSize],
+) -> Vec<DiffHunk> {
+    let a_functions = split_by_symbol(a, a_symbols);
+    let mut b_functions = split_by_symbol(b, b_symbols);
+
+    let mut hunks = Vec::new();
+    for (function, old) in a_functions {
+        let new = b_functions.remove(&function).unwrap_or_default();
+        diff_function(&function, &old, &new, &mut hunks);
+    }
+    for (function, new) in b_functions {
+        diff_function(&function, &[], &new, &mut hunks);
+    }
+    hunks
+}
+
+/// Summarizes the hunks by function, sorted from the most to the least changed
+pub fn function_churn(hunks: &[DiffHunk]) -> Vec<FunctionChurn> {
+    let mut churn: BTreeMap<&str, FunctionChurn> = BTreeMap::new();
+    for hunk in hunks {
+        let entry = churn.entry(&hunk.function).or_insert_with(|| FunctionChurn {
+            function: hunk.function.clone(),
+            ..Default::default()
+        });
+        entry.added += hunk.count(ChangeKind::Added);
+        entry.removed += hunk.count(ChangeKind::Removed);
+        entry.modified += hunk.count(ChangeKind::Modified);
+        entry.immediate_only += hunk.count(ChangeKind::ImmediateOnly);
+    }
+    let mut churn: Vec<FunctionChurn> = churn.into_values().collect();
+    churn.sort_by_key(|c| std::cmp::Reverse(c.total()));
+    churn
+}
+
+/// Groups the instructions by the name of the symbol that contains them, in pc order
+fn split_by_symbol<'a>(
+    instructions: &'a [(u64, RiscvInstruction)],
+    symbols: &[SymbolSize],
+) -> BTreeMap<String, Vec<(u64, &'a RiscvInstruction)>> {
+    let mut ranges: Vec<&SymbolSize> = Vec::new();
+    for symbol in symbols {
+        if (symbol.size > 0) && !ranges.iter().any(|r| r.name == symbol.name) {
+            ranges.push(symbol);
+        }
+    }
+    ranges.sort_by_key(|r| r.addr);
+
+    let mut sorted: Vec<(u64, &RiscvInstruction)> =
+        instructions.iter().map(|(pc, i)| (*pc, i)).collect();
+    sorted.sort_by_key(|(pc, _)| *pc);
+
+    let mut functions: BTreeMap<String, Vec<(u64, &RiscvInstruction)>> = BTreeMap::new();
+    for (pc, i) in sorted {
+        let index = ranges.partition_point(|r| r.addr <= pc);
+        let name = match index.checked_sub(1).map(|index| ranges[index]) {
+            Some(r) if pc < r.addr + r.size => r.name.as_str(),
+            _ => "",
+        };
+        functions.entry(name.to_string()).or_default().push((pc, i));
+    }
+    functions
+}
+
+/// Returns the instruction without its immediates, so that instructions that only differ in their
+/// immediates compare equal
+fn shape(i: &RiscvInstruction) -> (&str, [u32; 10]) {
+    (
+        i.inst.as_str(),
+        [
+            i.rd,
+            i.rs1,
+            i.rs2,
+            i.rs3,
+            i.funct2,
+            i.funct3,
+            i.funct5,
+            i.funct7,
+            i.csr,
+            i.aq << 1 | i.rl,
+        ],
+    )
+}
+
+/// Aligns the instructions of a function in both builds, appending its changed hunks
+fn diff_function(
+    function: &str,
+    old: &[(u64, &RiscvInstruction)],
+    new: &[(u64, &RiscvInstruction)],
+    hunks: &mut Vec<DiffHunk>,
+) {
+    let mut changes: Vec<InstructionChange> = Vec::new();
+    let mut removed: Vec<DiffInstruction> = Vec::new();
+    let mut added: Vec<DiffInstruction> = Vec::new();
+
+    // Pairs the pending non-aligned instructions as modified, and the rest as removed or added
+    let flush_unaligned = |changes: &mut Vec<InstructionChange>,
+                           removed: &mut Vec<DiffInstruction>,
+                           added: &mut Vec<DiffInstruction>| {
+        let pairs = removed.len().max(added.len());
+        let mut removed = removed.drain(..);
+        let mut added = added.drain(..);
+        for _ in 0..pairs {
+            let (old, new) = (removed.next(), added.next());
+            let kind = match (&old, &new) {
+                (Some(_), Some(_)) => ChangeKind::Modified,
+                (Some(_), None) => ChangeKind::Removed,
+                _ => ChangeKind::Added,
+            };
+            changes.push(InstructionChange { kind, old, new });
+        }
+    };
+
+    for step in align(old, new) {
+        match step {
+            (Some(o), Some(n)) => {
+                flush_unaligned(&mut changes, &mut removed, &mut added);
+                let (old_pc, old_i) = old[o];
+                let (new_pc, new_i) = new[n];
+                if (old_i.imm != new_i.imm) || (old_i.imme != new_i.imme) {
+                    changes.push(InstructionChange {
+                        kind: ChangeKind::ImmediateOnly,
+                        old: Some(DiffInstruction::new(old_pc, old_i)),
+                        new: Some(DiffInstruction::new(new_pc, new_i)),
+                    });
+                } else if !changes.is_empty() {
+                    hunks.push(DiffHunk {
+                        function: function.to_string(),
+                        changes: std::mem::take(&mut changes),
+                    });
+                }
+            }
+            (Some(o), None) => removed.push(DiffInstruction::new(old[o].0, old[o].1)),
+            (None, Some(n)) => added.push(DiffInstruction::new(new[n].0, new[n].1)),
+            (None, None) => unreachable!(),
+        }
+    }
+    flush_unaligned(&mut changes, &mut removed, &mut added);
+    if !changes.is_empty() {
+        hunks.push(DiffHunk { function: function.to_string(), changes });
+    }
+}
+
+/// Aligns two instruction sequences, returning the steps of the alignment as pairs of indices,
+/// where an index is None if the instruction has no counterpart in the other sequence
+fn align(
+    old: &[(u64, &RiscvInstruction)],
+    new: &[(u64, &RiscvInstruction)],
+) -> Vec<(Option<usize>, Option<usize>)> {
+    let (n, m) = (old.len(), new.len());
+    if (n + 1).saturating_mul(m + 1) > MAX_ALIGNMENT_CELLS {
+        return (0..n.max(m)).map(|i| ((i < n).then_some(i), (i < m).then_some(i))).collect();
+    }
+
+    // lcs[i * (m + 1) + j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let width = m + 1;
+    let mut lcs = vec![0u32; (n + 1) * width];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i * width + j] = if shape(old[i].1) == shape(new[j].1) {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut steps = Vec::with_capacity(n.max(m));
+    let (mut i, mut j) = (0, 0);
+    while (i < n) || (j < m) {
+        if (i < n) && (j < m) && (shape(old[i].1) == shape(new[j].1)) {
+            steps.push((Some(i), Some(j)));
+            i += 1;
+            j += 1;
+        } else if (j == m) || ((i < n) && (lcs[(i + 1) * width + j] >= lcs[i * width + j + 1])) {
+            steps.push((Some(i), None));
+            i += 1;
+        } else {
+            steps.push((None, Some(j)));
+            j += 1;
+        }
+    }
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE_ADDR: u64 = 0x80000000;
+
+    fn decode(code: &[u32]) -> Vec<(u64, RiscvInstruction)> {
+        let data: Vec<u8> = code.iter().flat_map(|inst| inst.to_le_bytes()).collect();
+        let program = ElfPayload {
+            entry_point: CODE_ADDR,
+            exec: vec![crate::elf_extraction::DataSection { addr: CODE_ADDR, data }],
+            ..Default::default()
+        };
+        program_instructions(&program)
+    }
+
+    fn symbol(name: &str, addr: u64, size: u64) -> SymbolSize {
+        SymbolSize { name: name.to_string(), addr, size, instructions: None }
+    }
+
+    #[test]
+    fn test_diff_programs() {
+        // f: addi sp, sp, -16; addi a0, a0, 1; ret
+        // g: addi a0, zero, 5; ret
+        let a = decode(&[0xff010113, 0x00150513, 0x00008067, 0x00500513, 0x00008067]);
+        // g: addi a0, zero, 5; ret
+        // f: addi sp, sp, -32; addi a0, a0, 1; add a0, a0, a1; ret
+        let b = decode(&[0x00500513, 0x00008067, 0xfe010113, 0x00150513, 0x00b50533, 0x00008067]);
+
+        // Without symbols the moved function g is seen as changed
+        assert!(!diff_programs(&a, &b).is_empty());
+
+        let a_symbols = [symbol("f", CODE_ADDR, 12), symbol("g", CODE_ADDR + 12, 8)];
+        let b_symbols = [symbol("g", CODE_ADDR, 8), symbol("f", CODE_ADDR + 8, 16)];
+        let hunks = diff_programs_by_symbol(&a, &a_symbols, &b, &b_symbols);
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(hunks[0].function, "f");
+        assert_eq!(hunks[0].changes[0].kind, ChangeKind::ImmediateOnly);
+        assert_eq!(hunks[1].changes[0].kind, ChangeKind::Added);
+        assert_eq!(hunks[1].changes[0].new.as_ref().unwrap().pc, CODE_ADDR + 16);
+
+        let churn = function_churn(&hunks);
+        assert_eq!(churn.len(), 1);
+        assert_eq!((churn[0].added, churn[0].immediate_only, churn[0].total()), (1, 1, 2));
+    }
+}
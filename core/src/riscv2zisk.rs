@@ -3,7 +3,7 @@
 //! The input parameter is an ELF RISC-V file name, and the output parameter is a JSON Zisk ROM
 //! file.  Optionally, the Zisk ROM can also be saved in x84-64 NASM assembly format.
 
-use crate::{elf2rom, elf2romfile, ZiskRom};
+use crate::{elf2rom_with_options, Elf2RomOptions, ZiskRom, ZiskRom2Asm};
 use std::{error::Error, path::PathBuf};
 
 /// ZisK Emulator can be executed in assembly to get the maximum performance
@@ -65,15 +65,23 @@ pub enum AsmGenerationMethod {
 pub struct Riscv2zisk {
     /// ELF RISC-V file name (input)
     pub elf_file: PathBuf,
+    /// Options of the transpilation
+    pub options: Elf2RomOptions,
 }
 
 impl Riscv2zisk {
     /// Creates a new Riscv2zisk struct with the provided input and output file names
     pub fn new<P: Into<PathBuf>>(elf_file: P) -> Riscv2zisk {
-        Riscv2zisk { elf_file: elf_file.into() }
+        Riscv2zisk { elf_file: elf_file.into(), options: Elf2RomOptions::default() }
     }
 
-    /// Executes the file conversion process by calling elf2romfile()
+    /// Sets the options of the transpilation
+    pub fn with_options(mut self, options: Elf2RomOptions) -> Riscv2zisk {
+        self.options = options;
+        self
+    }
+
+    /// Executes the file conversion process, saving the ROM as assembly
     pub fn runfile<P: Into<PathBuf>>(
         &self,
         asm_file: P,
@@ -81,12 +89,19 @@ impl Riscv2zisk {
         log_output: bool,
         comments: bool,
     ) -> Result<(), Box<dyn Error>> {
-        elf2romfile(&self.elf_file, &asm_file.into(), generation_method, log_output, comments)
-            .map_err(|e| format!("Error converting elf to assembly: {e}").into())
+        let rom = self.run().map_err(|e| format!("Error converting elf to assembly: {e}"))?;
+        ZiskRom2Asm::save_to_asm_file(
+            &rom,
+            &asm_file.into(),
+            generation_method,
+            log_output,
+            comments,
+        );
+        Ok(())
     }
 
-    /// Executes the file conversion process by calling elf2rom()
+    /// Executes the file conversion process by calling elf2rom_with_options()
     pub fn run(&self) -> Result<ZiskRom, Box<dyn Error>> {
-        elf2rom(&self.elf_file)
+        elf2rom_with_options(&self.elf_file, &self.options)
     }
 }
@@ -2,41 +2,19 @@
 //! instances of ZiskInstBuilder, and accumulates these instances in a hash map as a public
 //! attribute.
 
-use riscv::{riscv_interpreter, RiscvInstruction};
+use riscv::{decode_bytes, DecodedItem, Mnemonic, RiscvInstruction};
 
 use crate::{
-    convert_vector, ZiskInstBuilder, ZiskRom, ARCH_ID_CSR_ADDR, ARCH_ID_ZISK, CSR_ADDR,
-    FLOAT_LIB_ROM_ADDR, FLOAT_LIB_SP, FREG_F0, FREG_INST, FREG_RA, FREG_X0, INPUT_ADDR, MTVEC,
-    OUTPUT_ADDR, REG_X0, ROM_ENTRY, ROM_EXIT,
+    elf_extraction::DataSection, precompile_name, ZiskInstBuilder, ZiskRom, ARCH_ID_CSR_ADDR,
+    ARCH_ID_ZISK, CSR_ADDR, FLOAT_LIB_ROM_ADDR, FLOAT_LIB_SP, FREG_F0, FREG_INST, FREG_RA, FREG_X0,
+    INPUT_ADDR, MTVEC, OUTPUT_ADDR, PRECOMPILE_ADD256, REG_X0, ROM_ENTRY, ROM_EXIT,
 };
 
 use std::collections::HashMap;
-// The CSR precompiled addresses are defined in the `ZiskOS` `ziskos/entrypoint/src` files
-// because legacy versions of Rust do not support constant parameters in `asm!` macros.
-
-const CSR_PRECOMPILED: [&str; 18] = [
-    "keccak",
-    "arith256",
-    "arith256_mod",
-    "secp256k1_add",
-    "secp256k1_dbl",
-    "sha256",
-    "bn254_curve_add",
-    "bn254_curve_dbl",
-    "bn254_complex_add",
-    "bn254_complex_sub",
-    "bn254_complex_mul",
-    "arith384_mod",
-    "bls12_381_curve_add",
-    "bls12_381_curve_dbl",
-    "bls12_381_complex_add",
-    "bls12_381_complex_sub",
-    "bls12_381_complex_mul",
-    "add256",
-];
-const CSR_PRECOMPILED_ADDR_START: u32 = 0x800;
-const CSR_PRECOMPILED_ADDR_END: u32 = CSR_PRECOMPILED_ADDR_START + CSR_PRECOMPILED.len() as u32;
-const CSR_PRECOMPILED_ADD256: u32 = CSR_PRECOMPILED_ADDR_START + 17;
+// The CSR precompiled addresses are defined in the `precompile_codes` module, and duplicated in the
+// `ZiskOS` `ziskos/entrypoint/src` files because legacy versions of Rust do not support constant
+// parameters in `asm!` macros.
+
 const CSR_FCALL_ADDR_START: u32 = 0x8C0;
 const CSR_FCALL_ADDR_END: u32 = 0x8DF;
 const CSR_FCALL_GET_ADDR: u32 = 0xFFE;
@@ -665,12 +643,14 @@ impl Riscv2ZiskContext<'_> {
         zib.ind_width(w);
         zib.src_b("ind", i.imm as u64, false);
         zib.op(op).unwrap();
-        let reg_offset: i64 =
-            if i.inst == "fld" || i.inst == "flw" || i.inst == "c.fld" || i.inst == "c.fldsp" {
-                ((FREG_F0 - REG_X0) >> 3) as i64
-            } else {
-                0
-            };
+        let reg_offset: i64 = if matches!(
+            i.mnemonic(),
+            Mnemonic::Fld | Mnemonic::Flw | Mnemonic::CFld | Mnemonic::CFldsp
+        ) {
+            ((FREG_F0 - REG_X0) >> 3) as i64
+        } else {
+            0
+        };
         zib.store("reg", i.rd as i64 + reg_offset, false, false);
         zib.j(inst_size as i64, inst_size as i64);
         zib.verbose(&format!("{} r{}, 0x{:x}(r{})", i.inst, i.rd, i.imm, i.rs1));
@@ -685,12 +665,14 @@ impl Riscv2ZiskContext<'_> {
     /// and stores the result in memory
     pub fn store_op(&mut self, i: &RiscvInstruction, op: &str, w: u64, inst_size: u64) {
         assert!(inst_size == 2 || inst_size == 4);
-        let reg_offset: u64 =
-            if i.inst == "fsd" || i.inst == "fsw" || i.inst == "c.fsd" || i.inst == "c.fsdsp" {
-                (FREG_F0 - REG_X0) >> 3
-            } else {
-                0
-            };
+        let reg_offset: u64 = if matches!(
+            i.mnemonic(),
+            Mnemonic::Fsd | Mnemonic::Fsw | Mnemonic::CFsd | Mnemonic::CFsdsp
+        ) {
+            (FREG_F0 - REG_X0) >> 3
+        } else {
+            0
+        };
         let mut zib = ZiskInstBuilder::new_from_riscv(i.rom_address, i.inst.clone());
         zib.src_a("reg", i.rs1 as u64, false);
         zib.src_b("reg", i.rs2 as u64 + reg_offset, false);
@@ -1151,9 +1133,8 @@ impl Riscv2ZiskContext<'_> {
             let mut zib = ZiskInstBuilder::new_from_riscv(rom_address, i.inst.clone());
             zib.src_b("reg", i.rs1 as u64, false);
             zib.j(4, 4);
-            if (CSR_PRECOMPILED_ADDR_START..=CSR_PRECOMPILED_ADDR_END).contains(&i.csr) {
+            if let Some(precompiled) = precompile_name(i.csr as u16) {
                 zib.src_a("step", 0, false);
-                let precompiled = CSR_PRECOMPILED[(i.csr - CSR_PRECOMPILED_ADDR_START) as usize];
                 zib.op(precompiled).unwrap();
                 zib.verbose(precompiled);
             } else if (CSR_FCALL_PARAM_ADDR_START..=CSR_FCALL_PARAM_ADDR_END).contains(&i.csr) {
@@ -1192,7 +1173,7 @@ impl Riscv2ZiskContext<'_> {
             zib.j(4, 4);
             zib.build();
             self.insts.insert(rom_address, zib);
-        } else if i.csr == CSR_PRECOMPILED_ADD256 {
+        } else if i.csr == PRECOMPILE_ADD256 as u32 {
             let mut zib = ZiskInstBuilder::new_from_riscv(rom_address, i.inst.clone());
             zib.src_a("step", 0, false);
             zib.src_b("reg", i.rs1 as u64, false);
@@ -1685,27 +1666,54 @@ impl Riscv2ZiskContext<'_> {
 } // impl Riscv2ZiskContext
 
 /// Converts a buffer with RISC-V data into a vector of Zisk instructions, using the
-/// Riscv2ZiskContext to perform the instruction transpilation
-pub fn add_zisk_code(rom: &mut ZiskRom, addr: u64, data: &[u8]) {
+/// Riscv2ZiskContext to perform the instruction transpilation.  The `data_ranges` inside the buffer
+/// are not transpiled but returned, so that they can be added as read-only data.
+pub fn add_zisk_code(
+    rom: &mut ZiskRom,
+    addr: u64,
+    data: &[u8],
+    data_ranges: &[(u64, u64)],
+) -> Vec<DataSection> {
     //print!("add_zisk_code() addr={}\n", addr);
 
-    // Convert input data to a u32 vector
-    let code_vector: Vec<u16> = convert_vector(data);
+    // Convert data vector to RISCV instructions, skipping the data embedded in the code
+    let items = decode_bytes(addr, data, data_ranges);
 
-    // Convert data vector to RISCV instructions
-    let riscv_instructions = riscv_interpreter(addr, &code_vector);
+    let mut embedded_data = Vec::new();
+    add_decoded_zisk_code(rom, items, &mut embedded_data);
+    embedded_data
+}
 
+/// Converts the decoded items of a code section to ZisK instructions, stored in rom.insts, and
+/// appends the data embedded in the code to `embedded_data`
+pub fn add_decoded_zisk_code(
+    rom: &mut ZiskRom,
+    items: Vec<DecodedItem>,
+    embedded_data: &mut Vec<DataSection>,
+) {
     // Create a context to convert RISCV instructions to ZisK instructions, using rom.insts
     let mut ctx = Riscv2ZiskContext { insts: &mut rom.insts };
 
-    // For all RISCV instructions
-    for riscv_instruction in riscv_instructions {
-        //print!("add_zisk_code() converting RISCV instruction={}\n",
-        // riscv_instruction.to_string());
+    // For all RISCV instructions, returning the embedded data to be added as read-only data
+    for item in items {
+        match item {
+            DecodedItem::Instruction(riscv_instruction) => {
+                //print!("add_zisk_code() converting RISCV instruction={}\n",
+                // riscv_instruction.to_string());
 
-        // Convert RICV instruction to ZisK instruction and store it in rom.insts
-        ctx.convert(&riscv_instruction);
-        //print!("   to: {}", ctx.insts.iter().last().)
+                // Convert RICV instruction to ZisK instruction and store it in rom.insts
+                ctx.convert(&riscv_instruction);
+                //print!("   to: {}", ctx.insts.iter().last().)
+            }
+            DecodedItem::Data { addr, bytes } => {
+                embedded_data.push(DataSection { addr, data: bytes });
+            }
+            DecodedItem::Illegal { addr, raw } => {
+                panic!(
+                    "add_zisk_code() found undecodable instruction 0x{raw:x} at addr=0x{addr:x}"
+                );
+            }
+        }
     }
 }
 
@@ -0,0 +1,261 @@
+//! Parallel decoding and translation of large code sections
+//!
+//! * `add_zisk_code()` decodes a code section and translates it to ZisK instructions in a single
+//!   thread, which dominates the ROM setup time of guests with hundreds of MB of code.
+//! * `add_zisk_code_pipelined()` builds the same ROM instructions in two stages:
+//!   * Stage 1 decodes chunks of the section in parallel, in `RomPipelineConfig::threads` worker
+//!     threads.
+//!   * Stage 2 translates the decoded chunks to ZisK instructions in the calling thread, in
+//!     address order, since the translation writes to the ROM.
+//! * The stages are connected by a bounded channel with backpressure: a worker only starts
+//!   decoding a chunk if it is less than `RomPipelineConfig::max_chunks_in_flight` chunks ahead of
+//!   the translation, so the memory used by the decoded items does not grow with the section size.
+//! * The chunks are cut by `chunk_offsets()` at instruction boundaries outside of the embedded
+//!   data, found by a sequential scan of the instruction lengths, which is much cheaper than
+//!   decoding them.
+//! * Sections smaller than `RomPipelineConfig::min_pipelined_size`, or a configuration with a
+//!   single worker, are decoded and translated serially in the calling thread, as
+//!   `add_zisk_code()` does, since the threads would cost more than they save.
+//! * `RomPipelineCounters` counts the decoded bytes and chunks, the translated instructions and the
+//!   times a worker had to wait for the translation, and can be read while the pipeline runs.
+
+use std::{
+    collections::BTreeMap,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        mpsc, Condvar, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use riscv::{chunk_offsets, decode_bytes, DecodedItem};
+
+use crate::{add_decoded_zisk_code, elf_extraction::DataSection, ZiskRom};
+
+/// Configuration of `add_zisk_code_pipelined()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RomPipelineConfig {
+    /// Minimum size in bytes of the chunks decoded by the workers
+    pub chunk_size: usize,
+    /// Maximum number of chunks decoded, or being decoded, ahead of the translation
+    pub max_chunks_in_flight: usize,
+    /// Number of decoding worker threads
+    pub threads: usize,
+    /// Minimum size in bytes of the sections decoded in parallel; smaller ones are decoded serially
+    pub min_pipelined_size: usize,
+}
+
+impl Default for RomPipelineConfig {
+    fn default() -> Self {
+        let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        Self {
+            chunk_size: 1 << 20,
+            max_chunks_in_flight: 2 * threads,
+            threads,
+            min_pipelined_size: 4 << 20,
+        }
+    }
+}
+
+/// Throughput counters of `add_zisk_code_pipelined()`
+#[derive(Debug, Default)]
+pub struct RomPipelineCounters {
+    /// Number of bytes decoded by the workers
+    pub decoded_bytes: AtomicU64,
+    /// Number of chunks decoded by the workers
+    pub decoded_chunks: AtomicU64,
+    /// Number of RISC-V instructions translated to ZisK instructions
+    pub translated_instructions: AtomicU64,
+    /// Number of times a worker waited for the translation before decoding its chunk
+    pub decode_stalls: AtomicU64,
+}
+
+impl RomPipelineCounters {
+    /// Returns the number of bytes decoded per second, if they were decoded in `elapsed`
+    pub fn decode_throughput(&self, elapsed: Duration) -> f64 {
+        self.decoded_bytes.load(Ordering::Relaxed) as f64 / elapsed.as_secs_f64()
+    }
+
+    /// Returns the number of instructions translated per second, if they were translated in
+    /// `elapsed`
+    pub fn translate_throughput(&self, elapsed: Duration) -> f64 {
+        self.translated_instructions.load(Ordering::Relaxed) as f64 / elapsed.as_secs_f64()
+    }
+}
+
+/// Adds a code section to the ROM as `add_zisk_code()` does, decoding it in parallel chunks, and
+/// returns the data embedded in it
+pub fn add_zisk_code_pipelined(
+    rom: &mut ZiskRom,
+    addr: u64,
+    data: &[u8],
+    data_ranges: &[(u64, u64)],
+    config: &RomPipelineConfig,
+    counters: &RomPipelineCounters,
+) -> Vec<DataSection> {
+    // Decode small sections, or with a single worker, serially in the calling thread
+    if data.len() < config.min_pipelined_size || config.threads <= 1 {
+        let items = decode_bytes(addr, data, data_ranges);
+        counters.decoded_bytes.fetch_add(data.len() as u64, Ordering::Relaxed);
+        counters.decoded_chunks.fetch_add(1, Ordering::Relaxed);
+        let instructions = items.iter().filter(|item| item.instruction().is_some());
+        counters.translated_instructions.fetch_add(instructions.count() as u64, Ordering::Relaxed);
+
+        let mut embedded_data = Vec::new();
+        add_decoded_zisk_code(rom, items, &mut embedded_data);
+        return embedded_data;
+    }
+
+    let offsets = chunk_offsets(addr, data, data_ranges, config.chunk_size);
+    let chunks: Vec<(usize, usize)> = offsets.windows(2).map(|w| (w[0], w[1])).collect();
+    let max_in_flight = config.max_chunks_in_flight.max(1);
+    let threads = config.threads.clamp(1, chunks.len().max(1));
+
+    // Next chunk to decode, and number of chunks translated, waited for by the workers ahead
+    let next_chunk = AtomicUsize::new(0);
+    let translated = (Mutex::new(0usize), Condvar::new());
+    let (sender, receiver) = mpsc::sync_channel(max_in_flight);
+
+    let mut embedded_data = Vec::new();
+    let result = thread::scope(|scope| {
+        for _ in 0..threads {
+            let sender = sender.clone();
+            let (next_chunk, translated, chunks) = (&next_chunk, &translated, &chunks);
+            scope.spawn(move || loop {
+                let index = next_chunk.fetch_add(1, Ordering::Relaxed);
+                if index >= chunks.len() {
+                    break;
+                }
+
+                // Wait until the chunk is inside the window of chunks in flight
+                let (lock, condvar) = translated;
+                let mut done = lock.lock().unwrap();
+                if index >= *done + max_in_flight {
+                    counters.decode_stalls.fetch_add(1, Ordering::Relaxed);
+                    while index >= *done + max_in_flight {
+                        done = condvar.wait(done).unwrap();
+                    }
+                }
+                drop(done);
+
+                // Send the decoding panics to the translation, to be resumed there
+                let (start, end) = chunks[index];
+                let items = panic::catch_unwind(|| {
+                    decode_bytes(addr + start as u64, &data[start..end], data_ranges)
+                });
+                counters.decoded_bytes.fetch_add((end - start) as u64, Ordering::Relaxed);
+                counters.decoded_chunks.fetch_add(1, Ordering::Relaxed);
+                if sender.send((index, items)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        // Translate the chunks in order, keeping the ones decoded ahead of their turn
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut pending: BTreeMap<usize, thread::Result<Vec<DecodedItem>>> = BTreeMap::new();
+            for index in 0..chunks.len() {
+                let items = loop {
+                    if let Some(items) = pending.remove(&index) {
+                        break items;
+                    }
+                    let (i, items) = receiver.recv().expect("decoding workers ended early");
+                    pending.insert(i, items);
+                };
+                let items = items.unwrap_or_else(|payload| panic::resume_unwind(payload));
+                let instructions = items.iter().filter(|item| item.instruction().is_some());
+                counters
+                    .translated_instructions
+                    .fetch_add(instructions.count() as u64, Ordering::Relaxed);
+                add_decoded_zisk_code(rom, items, &mut embedded_data);
+
+                let (lock, condvar) = &translated;
+                *lock.lock().unwrap() += 1;
+                condvar.notify_all();
+            }
+        }));
+
+        // On a panic, release the waiting workers, which stop when their chunk cannot be sent
+        drop(receiver);
+        let (lock, condvar) = &translated;
+        *lock.lock().unwrap() = chunks.len();
+        condvar.notify_all();
+        result
+    });
+    if let Err(payload) = result {
+        panic::resume_unwind(payload);
+    }
+    embedded_data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::add_zisk_code;
+
+    /// Returns the full contents of the ROM instructions and of the embedded data, in address
+    /// order
+    fn contents(rom: &ZiskRom, embedded_data: &[DataSection]) -> (Vec<String>, Vec<String>) {
+        let mut insts: Vec<_> = rom.insts.iter().collect();
+        insts.sort_unstable_by_key(|(addr, _)| **addr);
+        let insts = insts.iter().map(|(addr, builder)| format!("{addr:x} {:?}", builder.i));
+        let data = embedded_data.iter().map(|section| format!("{section:?}"));
+        (insts.collect(), data.collect())
+    }
+
+    #[test]
+    fn test_add_zisk_code_pipelined() {
+        // addi a0, a0, 1 and c.nop, repeated, with a jump table in the middle
+        let mut data = Vec::new();
+        for _ in 0..1000 {
+            data.extend(0x00150513u32.to_le_bytes());
+            data.extend([0x01, 0x00]);
+        }
+        let data_ranges = [(0x80000600, 0x8000060c)];
+
+        let mut rom = ZiskRom::default();
+        let expected = add_zisk_code(&mut rom, 0x80000000, &data, &data_ranges);
+        assert_eq!(expected.len(), 1);
+        let expected = contents(&rom, &expected);
+
+        let items = decode_bytes(0x80000000, &data, &data_ranges);
+        let instructions = items.iter().filter(|item| item.instruction().is_some()).count();
+
+        // Pipelined, serial with a single worker, and serial below the minimum size
+        let pipelined = RomPipelineConfig {
+            chunk_size: 64,
+            max_chunks_in_flight: 2,
+            threads: 4,
+            min_pipelined_size: 0,
+        };
+        let single_worker = RomPipelineConfig { threads: 1, ..pipelined };
+        let small_section = RomPipelineConfig { min_pipelined_size: data.len() + 1, ..pipelined };
+        for (config, chunks) in
+            [(pipelined, None), (single_worker, Some(1)), (small_section, Some(1))]
+        {
+            let counters = RomPipelineCounters::default();
+            let mut pipelined_rom = ZiskRom::default();
+            let embedded_data = add_zisk_code_pipelined(
+                &mut pipelined_rom,
+                0x80000000,
+                &data,
+                &data_ranges,
+                &config,
+                &counters,
+            );
+            assert_eq!(contents(&pipelined_rom, &embedded_data), expected, "{config:?}");
+
+            let decoded_chunks = counters.decoded_chunks.load(Ordering::Relaxed);
+            match chunks {
+                Some(chunks) => assert_eq!(decoded_chunks, chunks),
+                None => assert!(decoded_chunks > 1),
+            }
+            assert_eq!(counters.decoded_bytes.load(Ordering::Relaxed), data.len() as u64);
+            let translated = counters.translated_instructions.load(Ordering::Relaxed);
+            assert_eq!(translated, instructions as u64);
+        }
+    }
+}
@@ -0,0 +1,220 @@
+//! Static stack depth analysis
+//!
+//! * The guest stack grows down towards the heap, and the Zisk memory map does not detect their
+//!   collision, so a program that exceeds its stack silently corrupts its own data.
+//! * `analyze_stack_depth()` finds the functions of the program starting from the entry point and
+//!   following the direct calls (`jal` with a link register), walks the control flow graph of
+//!   every function, and computes its frame size as the sum of its stack pointer decrements
+//!   (`addi sp, sp, -N`, `c.addi16sp`, etc.).
+//! * The worst case stack depth of a function is its frame size plus the worst case depth of its
+//!   callees.  Recursive calls and indirect calls (`jalr` with a link register) cannot be bounded
+//!   statically, so they are reported, and in their presence the computed depth is a lower bound.
+//! * Jumps without a link register are considered part of the current function, so the frames of
+//!   tail called functions are added to the one of their caller, which is conservative.
+//! * The reachable `ebreak` and `c.ebreak` instructions are reported as well, since guests built
+//!   with debug assertions can contain them, and the emulator can be configured to trap on them.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use riscv::RiscvInstruction;
+
+use crate::{elf_extraction::ElfPayload, program_instructions};
+
+/// Stack pointer register index
+const SP: u32 = 2;
+
+/// Stack usage of a function
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FunctionStack {
+    /// Address of the first instruction of the function
+    pub entry: u64,
+    /// Sum of the stack pointer decrements of the function, in bytes
+    pub frame_size: u64,
+    /// Entry addresses of the directly called functions
+    pub callees: BTreeSet<u64>,
+    /// Addresses of the indirect call instructions of the function
+    pub indirect_calls: Vec<u64>,
+    /// Addresses of the ebreak instructions of the function, i.e. debug traps
+    pub ebreaks: Vec<u64>,
+    /// True if the function is part of a recursive call chain
+    pub recursive: bool,
+    /// Worst case stack depth of the function, including its callees, in bytes
+    pub worst_depth: u64,
+}
+
+/// Result of the static stack depth analysis
+#[derive(Debug, Clone, Default)]
+pub struct StackDepthReport {
+    /// Stack usage of every function found, by entry address
+    pub functions: BTreeMap<u64, FunctionStack>,
+    /// Worst case stack depth of the program, i.e. of its entry point function, in bytes
+    pub worst_depth: u64,
+    /// True if the worst case depth is a lower bound, because of recursive or indirect calls
+    pub unbounded: bool,
+}
+
+impl StackDepthReport {
+    /// Returns the addresses of the reachable ebreak instructions, whose behavior depends on the
+    /// emulator ebreak policy and which are executed as a nop by the proven execution
+    pub fn ebreak_sites(&self) -> Vec<u64> {
+        let sites: BTreeSet<u64> =
+            self.functions.values().flat_map(|f| f.ebreaks.iter().copied()).collect();
+        sites.into_iter().collect()
+    }
+}
+
+/// Returns the target of a relative branch or jump instruction
+fn relative_target(i: &RiscvInstruction) -> u64 {
+    i.rom_address.wrapping_add(i.imm as i64 as u64)
+}
+
+/// Returns true if the instruction is a conditional branch
+fn is_branch(inst: &str) -> bool {
+    inst.starts_with('b') || (inst == "c.beqz") || (inst == "c.bnez")
+}
+
+/// Walks the control flow graph of the function starting at `entry`, and returns its stack usage,
+/// without the worst case depth
+fn analyze_function(entry: u64, instructions: &HashMap<u64, &RiscvInstruction>) -> FunctionStack {
+    let mut function = FunctionStack { entry, ..Default::default() };
+    let mut visited: BTreeSet<u64> = BTreeSet::new();
+    let mut pending: Vec<u64> = vec![entry];
+
+    while let Some(pc) = pending.pop() {
+        if !visited.insert(pc) {
+            continue;
+        }
+        let Some(i) = instructions.get(&pc) else {
+            continue;
+        };
+        let next = pc + i.len_bytes();
+
+        match i.inst.as_str() {
+            "addi" | "c.addi" | "c.addi16sp" if (i.rd == SP) && (i.rs1 == SP) && (i.imm < 0) => {
+                function.frame_size += i.imm.unsigned_abs() as u64;
+                pending.push(next);
+            }
+            "jal" | "c.j" | "c.jal" => {
+                if i.rd == 0 {
+                    pending.push(relative_target(i));
+                } else {
+                    function.callees.insert(relative_target(i));
+                    pending.push(next);
+                }
+            }
+            "jalr" | "c.jr" | "c.jalr" => {
+                if i.rd != 0 {
+                    function.indirect_calls.push(pc);
+                    pending.push(next);
+                }
+            }
+            "ebreak" | "c.ebreak" => {
+                function.ebreaks.push(pc);
+                pending.push(next);
+            }
+            "c.halt" | "c.reserved" => {}
+            inst if is_branch(inst) => {
+                pending.push(relative_target(i));
+                pending.push(next);
+            }
+            _ => pending.push(next),
+        }
+    }
+    function
+}
+
+/// Computes the worst case depth of the function and its callees, marking the recursive ones.
+/// `state` is 1 for the functions being computed, i.e. in the current call chain, and 2 for the
+/// computed ones.
+fn compute_worst_depth(
+    entry: u64,
+    functions: &mut BTreeMap<u64, FunctionStack>,
+    state: &mut HashMap<u64, u8>,
+    chain: &mut Vec<u64>,
+) -> u64 {
+    state.insert(entry, 1);
+    chain.push(entry);
+
+    let callees = functions[&entry].callees.clone();
+    let mut callees_depth = 0;
+    for callee in callees {
+        match state.get(&callee) {
+            Some(1) => {
+                // Recursion: mark all the functions of the call chain from the callee
+                let start = chain.iter().position(|f| *f == callee).unwrap();
+                for f in &chain[start..] {
+                    functions.get_mut(f).unwrap().recursive = true;
+                }
+            }
+            Some(_) => callees_depth = callees_depth.max(functions[&callee].worst_depth),
+            None => {
+                let depth = compute_worst_depth(callee, functions, state, chain);
+                callees_depth = callees_depth.max(depth);
+            }
+        }
+    }
+
+    chain.pop();
+    state.insert(entry, 2);
+    let function = functions.get_mut(&entry).unwrap();
+    function.worst_depth = function.frame_size + callees_depth;
+    function.worst_depth
+}
+
+/// Analyzes the executable sections of the program, and returns the stack usage of its functions
+/// and the worst case stack depth of the program
+pub fn analyze_stack_depth(program: &ElfPayload) -> StackDepthReport {
+    let decoded = program_instructions(program);
+    let instructions: HashMap<u64, &RiscvInstruction> =
+        decoded.iter().map(|(pc, i)| (*pc, i)).collect();
+
+    // Find the functions, following the direct calls from the entry point
+    let mut functions: BTreeMap<u64, FunctionStack> = BTreeMap::new();
+    let mut pending: Vec<u64> = vec![program.entry_point];
+    while let Some(entry) = pending.pop() {
+        if functions.contains_key(&entry) {
+            continue;
+        }
+        let function = analyze_function(entry, &instructions);
+        pending.extend(function.callees.iter().copied());
+        functions.insert(entry, function);
+    }
+
+    let mut state: HashMap<u64, u8> = HashMap::new();
+    let worst_depth =
+        compute_worst_depth(program.entry_point, &mut functions, &mut state, &mut Vec::new());
+    let unbounded = functions.values().any(|f| f.recursive || !f.indirect_calls.is_empty());
+
+    StackDepthReport { functions, worst_depth, unbounded }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elf_extraction::DataSection;
+
+    const CODE_ADDR: u64 = 0x80000000;
+
+    #[test]
+    fn test_analyze_stack_depth() {
+        let code: [u32; 8] = [
+            // main: addi sp, sp, -16; jal ra, f; addi sp, sp, 16; ret
+            0xff010113, 0x00c000ef, 0x01010113, 0x00008067,
+            // f: addi sp, sp, -32; jalr ra, 0(a0); addi sp, sp, 32; ret
+            0xfe010113, 0x000500e7, 0x02010113, 0x00008067,
+        ];
+        let data = code.iter().flat_map(|inst| inst.to_le_bytes()).collect();
+        let program = ElfPayload {
+            entry_point: CODE_ADDR,
+            exec: vec![DataSection { addr: CODE_ADDR, data }],
+            ..Default::default()
+        };
+        let report = analyze_stack_depth(&program);
+        assert_eq!(report.functions.len(), 2);
+        assert_eq!(report.functions[&CODE_ADDR].frame_size, 16);
+        assert_eq!(report.functions[&(CODE_ADDR + 16)].frame_size, 32);
+        assert_eq!(report.functions[&(CODE_ADDR + 16)].indirect_calls, vec![CODE_ADDR + 20]);
+        assert_eq!(report.worst_depth, 48);
+        assert!(report.unbounded);
+    }
+}
@@ -9,7 +9,7 @@
 
 #![allow(unused)]
 
-use ziskos::zisklib::fcall_proxy;
+use ziskos::zisklib::{fcall_input_page_from, fcall_proxy, FCALL_INPUT_PAGE_ID};
 
 use std::{
     collections::HashMap,
@@ -2195,7 +2195,12 @@ pub fn opc_fcall(ctx: &mut InstContext) {
     // Get function id from a
     let function_id = ctx.a;
 
-    let iresult = fcall_proxy(function_id, &ctx.fcall.parameters, &mut ctx.fcall.result);
+    // The input pages are served from the input owned by the memory
+    let iresult = if function_id == FCALL_INPUT_PAGE_ID as u64 {
+        fcall_input_page_from(ctx.mem.input_slice(), &ctx.fcall.parameters, &mut ctx.fcall.result)
+    } else {
+        fcall_proxy(function_id, &ctx.fcall.parameters, &mut ctx.fcall.result)
+    };
 
     if iresult < 0 {
         panic!("opc_fcall() failed calling Fcall() function_id={function_id} iresult={iresult}");
@@ -9,7 +9,7 @@ use std::fs;
 use std::sync::Arc;
 use tokio::sync::{mpsc, Mutex};
 use tokio::task::JoinHandle;
-use zisk_common::io::ZiskStdin;
+use zisk_common::io::{TransportSpec, ZiskStdin};
 use zisk_distributed_common::{AggregationParams, DataCtx, InputSourceDto, JobPhase, WorkerState};
 use zisk_distributed_common::{ComputeCapacity, JobId, WorkerId};
 use zisk_sdk::{Asm, Emu, ProverClient, ZiskBackend, ZiskProver};
@@ -532,20 +532,12 @@ impl<T: ZiskBackend + 'static> Worker<T> {
     ) -> Result<Vec<ContributionsInfo>> {
         let phase = proofman::ProvePhase::Contributions;
 
-        match input_source {
-            InputSourceDto::InputPath(input_path) => {
-                let stdin = ZiskStdin::from_file(input_path)?;
-                prover.set_stdin(stdin);
-            }
-            InputSourceDto::InputData(input_data) => {
-                let stdin = ZiskStdin::from_vec(input_data);
-                prover.set_stdin(stdin);
-            }
-            InputSourceDto::InputNull => {
-                let stdin = ZiskStdin::null();
-                prover.set_stdin(stdin);
-            }
-        }
+        let spec = match input_source {
+            InputSourceDto::InputPath(input_path) => TransportSpec::File(input_path.into()),
+            InputSourceDto::InputData(input_data) => TransportSpec::Memory(input_data),
+            InputSourceDto::InputNull => TransportSpec::Null,
+        };
+        prover.set_stdin(ZiskStdin::from_spec(spec)?);
 
         let challenge = match prover.prove_phase(phase_inputs, options, phase) {
             Ok(proofman::ProvePhaseResult::Contributions(challenge)) => {
@@ -8,7 +8,10 @@ use std::sync::atomic::{fence, Ordering};
 use std::time::Duration;
 use tracing::error;
 
-use crate::{AsmMOChunk, AsmMOHeader, AsmRunError, AsmService, AsmServices, AsmSharedMemory};
+use crate::{
+    AsmMOChunk, AsmMOHeader, AsmRunError, AsmService, AsmServices, AsmSharedMemory,
+    CancellationToken,
+};
 use mem_planner_cpp::MemPlanner;
 
 use anyhow::{Context, Result};
@@ -23,6 +26,7 @@ pub struct PreloadedMO {
     pub output_shmem: AsmSharedMemory<AsmMOHeader>,
     mem_planner: Option<MemPlanner>,
     handle_mo: Option<std::thread::JoinHandle<MemPlanner>>,
+    cancel_token: CancellationToken,
 }
 
 impl PreloadedMO {
@@ -47,8 +51,14 @@ impl PreloadedMO {
             output_shmem: output_shared_memory,
             mem_planner: Some(MemPlanner::new()),
             handle_mo: None,
+            cancel_token: CancellationToken::new(),
         })
     }
+
+    /// Returns a token to abort the running memory operations request from another thread
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel_token.clone()
+    }
 }
 
 impl Drop for PreloadedMO {
@@ -152,8 +162,25 @@ impl AsmRunnerMO {
             preloaded.output_shmem.mapped_ptr().add(threshold_bytes) as *const AsmMOChunk
         };
 
+        let mut chunks = 0u64;
         let exit_code = loop {
-            match sem_chunk_done.timed_wait(Duration::from_secs(10)) {
+            let wait_result = sem_chunk_done.timed_wait(Duration::from_secs(10));
+
+            // Discard the pending chunks if the request has been aborted; the planner is closed
+            // with the chunks already added and the service thread is detached
+            if let Some(reason) = preloaded.cancel_token.reason() {
+                preloaded.cancel_token.reset();
+                mem_planner.set_completed();
+                mem_planner.wait();
+                preloaded.handle_mo = Some(std::thread::spawn(move || {
+                    drop(mem_planner);
+                    MemPlanner::new()
+                }));
+                return Err(AsmRunError::Cancelled { reason, discarded_chunks: chunks })
+                    .context("Memory operations request aborted");
+            }
+
+            match wait_result {
                 Ok(()) => {
                     // Synchronize with memory changes from the C++ side
                     fence(Ordering::Acquire);
@@ -189,6 +216,7 @@ impl AsmRunnerMO {
                     }
 
                     mem_planner.add_chunk(chunk.mem_ops_size, data_ptr as *const c_void);
+                    chunks += 1;
 
                     if chunk.end == 1 {
                         break 0;
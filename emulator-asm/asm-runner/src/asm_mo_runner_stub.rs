@@ -2,10 +2,18 @@ use zisk_common::{ExecutorStatsHandle, Plan};
 
 use std::fmt::Debug;
 
+use crate::CancellationToken;
 use anyhow::Result;
 
 pub struct PreloadedMO {}
 
+impl PreloadedMO {
+    /// Returns a token to abort the running memory operations request from another thread
+    pub fn cancellation_token(&self) -> CancellationToken {
+        CancellationToken::new()
+    }
+}
+
 // This struct is used to run the assembly code in a separate process and generate minimal traces.
 #[derive(Debug)]
 pub struct AsmRunnerMO {
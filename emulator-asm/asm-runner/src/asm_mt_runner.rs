@@ -9,7 +9,10 @@ use std::time::{Duration, Instant};
 
 use tracing::{error, info};
 
-use crate::{AsmMTChunk, AsmMTHeader, AsmRunError, AsmService, AsmServices, AsmSharedMemory};
+use crate::{
+    AsmMTChunk, AsmMTHeader, AsmRunError, AsmService, AsmServices, AsmSharedMemory,
+    CancellationToken,
+};
 
 use anyhow::{Context, Result};
 
@@ -31,6 +34,7 @@ pub enum MinimalTraces {
 
 pub struct PreloadedMT {
     pub output_shmem: AsmSharedMemory<AsmMTHeader>,
+    cancel_token: CancellationToken,
 }
 
 impl PreloadedMT {
@@ -51,7 +55,12 @@ impl PreloadedMT {
         let output_shared_memory =
             AsmSharedMemory::<AsmMTHeader>::open_and_map(&output_name, unlock_mapped_memory)?;
 
-        Ok(Self { output_shmem: output_shared_memory })
+        Ok(Self { output_shmem: output_shared_memory, cancel_token: CancellationToken::new() })
+    }
+
+    /// Returns a token to abort the running minimal trace request from another thread
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel_token.clone()
     }
 }
 
@@ -132,7 +141,20 @@ impl AsmRunnerMT {
         };
 
         let exit_code = loop {
-            match sem_chunk_done.timed_wait(Duration::from_secs(10)) {
+            let wait_result = sem_chunk_done.timed_wait(Duration::from_secs(10));
+
+            // Discard the pending chunks if the request has been aborted; the service thread and
+            // the tasks are detached
+            if let Some(reason) = preloaded.cancel_token.reason() {
+                preloaded.cancel_token.reset();
+                return Err(AsmRunError::Cancelled {
+                    reason,
+                    discarded_chunks: emu_traces.len() as u64,
+                })
+                .context("Minimal trace request aborted");
+            }
+
+            match wait_result {
                 Ok(()) => {
                     #[cfg(feature = "stats")]
                     {
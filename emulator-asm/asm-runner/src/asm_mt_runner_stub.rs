@@ -4,6 +4,7 @@ use std::ffi::c_void;
 use std::fmt::Debug;
 use std::sync::Arc;
 
+use crate::CancellationToken;
 use anyhow::Result;
 pub trait Task: Send + Sync + 'static {
     type Output: Send + 'static;
@@ -21,6 +22,13 @@ pub enum MinimalTraces {
 
 pub struct PreloadedMT {}
 
+impl PreloadedMT {
+    /// Returns a token to abort the running minimal trace request from another thread
+    pub fn cancellation_token(&self) -> CancellationToken {
+        CancellationToken::new()
+    }
+}
+
 // This struct is used to run the assembly code in a separate process and generate minimal traces.
 #[derive(Debug)]
 pub struct AsmRunnerMT {
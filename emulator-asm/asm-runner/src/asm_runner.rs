@@ -20,6 +20,8 @@ pub enum AsmRunError {
     ServiceError(#[source] anyhow::Error),
     #[error("Arc unwrap failed")]
     ArcUnwrap,
+    #[error("Cancelled: {reason} ({discarded_chunks} chunks discarded)")]
+    Cancelled { reason: String, discarded_chunks: u64 },
 }
 
 #[derive(Debug, Clone)]
@@ -0,0 +1,75 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+/// Shared flag to abort a running assembly emulator request from another thread, e.g. when the
+/// orchestrator gives up on a hung child process.
+///
+/// The runners check the token every time they wake up from a semaphore wait, and return
+/// `AsmRunError::Cancelled` with the number of chunks they discarded.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<CancellationInner>,
+}
+
+#[derive(Debug, Default)]
+struct CancellationInner {
+    cancelled: AtomicBool,
+    reason: Mutex<Option<String>>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests cancellation; only the reason of the first request is kept
+    pub fn cancel(&self, reason: &str) {
+        let mut current = self.inner.reason.lock().unwrap();
+        if current.is_none() {
+            *current = Some(reason.to_string());
+        }
+        self.inner.cancelled.store(true, Ordering::Release);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Returns the reason of the cancellation, if cancelled
+    pub fn reason(&self) -> Option<String> {
+        self.inner.reason.lock().unwrap().clone()
+    }
+
+    /// Clears the cancellation, so that the token can be used for the next request
+    pub fn reset(&self) {
+        let mut current = self.inner.reason.lock().unwrap();
+        *current = None;
+        self.inner.cancelled.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancellation_token() {
+        let token = CancellationToken::new();
+        let remote = token.clone();
+        assert!(!token.is_cancelled());
+
+        std::thread::spawn(move || {
+            remote.cancel("peer gone");
+            remote.cancel("timeout");
+        })
+        .join()
+        .unwrap();
+        assert!(token.is_cancelled());
+        assert_eq!(token.reason().as_deref(), Some("peer gone"));
+
+        token.reset();
+        assert!(!token.is_cancelled() && token.reason().is_none());
+    }
+}
@@ -2570,6 +2570,9 @@ void server_setup (void)
             exit(-1);
         }
         if (verbose) printf("mmap(input) mapped %lu B and returned address %p in %lu us\n", MAX_INPUT_SIZE, pInput, duration);
+
+        // Serve the input page fcall from the mapped input section
+        FcallSetInputSection((const uint8_t *)INPUT_ADDR);
     }
 
     /*******/
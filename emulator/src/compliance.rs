@@ -0,0 +1,295 @@
+//! RISC-V compliance runner
+//!
+//! * Runs the upstream RISC-V test suites (riscv-tests and riscv-arch-test/riscof) on the Zisk
+//!   emulator, and builds a pass/fail matrix per extension.
+//! * The test ELF files must have been built for the Zisk memory map, i.e. with the code in the
+//!   ROM region and the data (including `tohost` and the signature) in the RAM region.
+//! * A test ends when it writes a non-zero value to the `tohost` symbol: 1 means pass, and any
+//!   other value means that the test number `value >> 1` failed.  `tohost` is served as an MMIO
+//!   region, so that the emulation stops as soon as it is written.
+//! * If the ELF file defines the `begin_signature` and `end_signature` symbols, and a reference
+//!   signature file (riscof format, one 32-bit hexadecimal word per line) named `<test>.signature`
+//!   or `<test>.reference_output` is found next to it, the signature region is compared against it.
+//! * The extension of a test is the first directory of its path relative to the suite directory,
+//!   e.g. `I/add-01.elf` belongs to the `I` extension.
+//! * The suites are not vendored: the inline test runs them only if the `ZISK_ARCH_TEST_DIR`
+//!   environment variable points to a directory containing the built ELF files.
+
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use object::{Object, ObjectSymbol};
+use zisk_core::{elf2rom, MmioCallbacks};
+
+use crate::Emu;
+
+/// Default maximum number of steps executed per test
+pub const COMPLIANCE_MAX_STEPS: u64 = 10_000_000;
+
+/// Test of a compliance suite
+#[derive(Debug, Clone)]
+pub struct ComplianceTest {
+    /// Test name, i.e. the ELF file name without extension
+    pub name: String,
+    /// Extension the test belongs to
+    pub extension: String,
+    /// Path of the test ELF file
+    pub elf: PathBuf,
+    /// Path of the reference signature file, if any
+    pub reference_signature: Option<PathBuf>,
+}
+
+/// Outcome of a compliance test
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ComplianceOutcome {
+    /// The test passed
+    Pass,
+    /// The test wrote a failure code to `tohost`
+    Fail { test_number: u64 },
+    /// The signature does not match the reference one
+    SignatureMismatch { offset: u64, expected: u32, actual: u32 },
+    /// The test did not write `tohost` within the maximum number of steps
+    Timeout { steps: u64 },
+    /// The test could not be executed
+    Error(String),
+}
+
+impl ComplianceOutcome {
+    /// Returns true if the test passed
+    pub fn passed(&self) -> bool {
+        *self == ComplianceOutcome::Pass
+    }
+}
+
+impl fmt::Display for ComplianceOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ComplianceOutcome::Pass => write!(f, "PASS"),
+            ComplianceOutcome::Fail { test_number } => write!(f, "FAIL test={test_number}"),
+            ComplianceOutcome::SignatureMismatch { offset, expected, actual } => write!(
+                f,
+                "FAIL signature offset=0x{offset:x} expected=0x{expected:08x} actual=0x{actual:08x}"
+            ),
+            ComplianceOutcome::Timeout { steps } => write!(f, "TIMEOUT steps={steps}"),
+            ComplianceOutcome::Error(e) => write!(f, "ERROR {e}"),
+        }
+    }
+}
+
+/// Results of a compliance suite, grouped by extension
+#[derive(Debug, Default)]
+pub struct ComplianceMatrix {
+    /// Map of extension to the list of (test name, outcome)
+    pub results: BTreeMap<String, Vec<(String, ComplianceOutcome)>>,
+}
+
+impl ComplianceMatrix {
+    /// Returns the number of (passed, total) tests of an extension
+    pub fn extension_summary(&self, extension: &str) -> (usize, usize) {
+        self.results.get(extension).map_or((0, 0), |tests| {
+            (tests.iter().filter(|(_, outcome)| outcome.passed()).count(), tests.len())
+        })
+    }
+
+    /// Returns true if all the tests passed
+    pub fn all_passed(&self) -> bool {
+        self.results.values().flatten().all(|(_, outcome)| outcome.passed())
+    }
+
+    /// Returns the (extension, test name, outcome) of the tests that did not pass
+    pub fn failures(&self) -> Vec<(&str, &str, &ComplianceOutcome)> {
+        self.results
+            .iter()
+            .flat_map(|(extension, tests)| {
+                tests
+                    .iter()
+                    .map(move |(name, outcome)| (extension.as_str(), name.as_str(), outcome))
+            })
+            .filter(|(_, _, outcome)| !outcome.passed())
+            .collect()
+    }
+}
+
+impl fmt::Display for ComplianceMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<16} {:>8} {:>8} {:>8}", "EXTENSION", "PASSED", "FAILED", "TOTAL")?;
+        for extension in self.results.keys() {
+            let (passed, total) = self.extension_summary(extension);
+            writeln!(f, "{:<16} {:>8} {:>8} {:>8}", extension, passed, total - passed, total)?;
+        }
+        for (extension, name, outcome) in self.failures() {
+            writeln!(f, "{extension}/{name}: {outcome}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Finds all the test ELF files (`*.elf`) under the suite directory, sorted by path
+pub fn discover_compliance_tests(suite_dir: &Path) -> io::Result<Vec<ComplianceTest>> {
+    let mut elfs = Vec::new();
+    let mut pending = vec![suite_dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "elf") {
+                elfs.push(path);
+            }
+        }
+    }
+    elfs.sort();
+
+    Ok(elfs
+        .into_iter()
+        .map(|elf| {
+            let name = elf.file_stem().unwrap().to_string_lossy().into_owned();
+            let relative = elf.strip_prefix(suite_dir).unwrap();
+            let extension = match relative.components().count() {
+                1 => "default".to_string(),
+                _ => relative.components().next().unwrap().as_os_str().to_string_lossy().into(),
+            };
+            let reference_signature = ["signature", "reference_output"]
+                .iter()
+                .map(|ext| elf.with_extension(ext))
+                .find(|path| path.is_file());
+            ComplianceTest { name, extension, elf, reference_signature }
+        })
+        .collect())
+}
+
+/// Returns the address of a symbol of the ELF file, if defined
+fn find_symbol(elf: &object::File, name: &str) -> Option<u64> {
+    elf.symbols().find(|symbol| symbol.name() == Ok(name)).map(|symbol| symbol.address())
+}
+
+/// Parses a riscof signature file, i.e. one 32-bit hexadecimal word per line
+fn parse_signature(text: &str) -> Result<Vec<u32>, String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            u32::from_str_radix(line.trim_start_matches("0x"), 16)
+                .map_err(|e| format!("invalid signature word {line}: {e}"))
+        })
+        .collect()
+}
+
+/// Runs a single compliance test, executing up to `max_steps` steps
+pub fn run_compliance_test(test: &ComplianceTest, max_steps: u64) -> ComplianceOutcome {
+    let data = match fs::read(&test.elf) {
+        Ok(data) => data,
+        Err(e) => return ComplianceOutcome::Error(e.to_string()),
+    };
+    let elf = match object::File::parse(&*data) {
+        Ok(elf) => elf,
+        Err(e) => return ComplianceOutcome::Error(e.to_string()),
+    };
+    let Some(tohost) = find_symbol(&elf, "tohost") else {
+        return ComplianceOutcome::Error("tohost symbol not found".to_string());
+    };
+    let signature = find_symbol(&elf, "begin_signature").zip(find_symbol(&elf, "end_signature"));
+
+    let rom = match elf2rom(&test.elf) {
+        Ok(rom) => rom,
+        Err(e) => return ComplianceOutcome::Error(e.to_string()),
+    };
+
+    // Serve tohost as an MMIO region, to detect the end of the test
+    let tohost_value = Arc::new(AtomicU64::new(0));
+    let tohost_value_clone = tohost_value.clone();
+    let mut emu = Emu::new(&rom);
+    emu.add_mmio_region(
+        tohost,
+        8,
+        Box::new(MmioCallbacks::new(
+            |_, _| 0,
+            move |_, val, _| {
+                if val != 0 {
+                    tohost_value_clone.store(val, Ordering::Relaxed)
+                }
+            },
+        )),
+    );
+    emu.ctx = emu.create_emu_context(Vec::new());
+
+    while tohost_value.load(Ordering::Relaxed) == 0 {
+        if emu.terminated() {
+            return ComplianceOutcome::Error("program ended without writing tohost".to_string());
+        }
+        if emu.number_of_steps() >= max_steps {
+            return ComplianceOutcome::Timeout { steps: max_steps };
+        }
+        emu.step_fast();
+    }
+
+    let value = tohost_value.load(Ordering::Relaxed);
+    if value != 1 {
+        return ComplianceOutcome::Fail { test_number: value >> 1 };
+    }
+
+    // Compare the signature against the reference one, if both are available
+    if let (Some((begin, end)), Some(reference)) = (signature, &test.reference_signature) {
+        let expected = match fs::read_to_string(reference).map_err(|e| e.to_string()) {
+            Ok(text) => match parse_signature(&text) {
+                Ok(words) => words,
+                Err(e) => return ComplianceOutcome::Error(e),
+            },
+            Err(e) => return ComplianceOutcome::Error(e),
+        };
+        let words = (end - begin) / 4;
+        if words != expected.len() as u64 {
+            return ComplianceOutcome::Error(format!(
+                "signature has {words} words, reference has {}",
+                expected.len()
+            ));
+        }
+        for (i, expected) in expected.into_iter().enumerate() {
+            let offset = i as u64 * 4;
+            let actual = emu.ctx.inst_ctx.mem.read(begin + offset, 4) as u32;
+            if actual != expected {
+                return ComplianceOutcome::SignatureMismatch { offset, expected, actual };
+            }
+        }
+    }
+
+    ComplianceOutcome::Pass
+}
+
+/// Runs all the tests of a compliance suite, returning the pass/fail matrix
+pub fn run_compliance_suite(suite_dir: &Path, max_steps: u64) -> io::Result<ComplianceMatrix> {
+    let mut matrix = ComplianceMatrix::default();
+    for test in discover_compliance_tests(suite_dir)? {
+        let outcome = run_compliance_test(&test, max_steps);
+        matrix.results.entry(test.extension).or_default().push((test.name, outcome));
+    }
+    Ok(matrix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_signature() {
+        assert_eq!(parse_signature("00000001\n0xdeadbeef\n\n"), Ok(vec![1, 0xdeadbeef]));
+        assert!(parse_signature("zz").is_err());
+    }
+
+    #[test]
+    fn test_arch_test_suite() {
+        let Ok(suite_dir) = std::env::var("ZISK_ARCH_TEST_DIR") else {
+            return;
+        };
+        let matrix = run_compliance_suite(Path::new(&suite_dir), COMPLIANCE_MAX_STEPS).unwrap();
+        println!("{matrix}");
+        assert!(matrix.all_passed());
+    }
+}
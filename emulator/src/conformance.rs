@@ -0,0 +1,274 @@
+//! Conformance runner
+//!
+//! * Executes the same program and input on two machines implementing the `ReferenceMachine`
+//!   trait, comparing their architectural state (pc and the 32 integer registers) every N RISC-V
+//!   instructions, and reports the first divergence found, including the instruction executed by
+//!   each machine in the last compared window.
+//! * It is the acceptance test for decoder and executor changes: any alternative implementation
+//!   (e.g. a new decoder, a fast interpreter, or a modified transpilation) can be checked against
+//!   the Zisk emulator, which implements the trait through `EmuMachine`.
+//! * Comparing every instruction (N = 1) pinpoints the exact diverging instruction; larger values
+//!   are faster, but only report the window where the divergence happened.
+
+use std::fmt;
+
+use riscv::RiscVRegisters;
+
+use crate::Emu;
+
+/// Architectural state of a RISC-V machine, as compared by the conformance runner
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchState {
+    /// Address of the next RISC-V instruction to execute
+    pub pc: u64,
+    /// Integer registers x0 to x31
+    pub regs: [u64; 32],
+    /// True if the program execution ended
+    pub ended: bool,
+}
+
+/// Machine that can execute a RISC-V program one instruction at a time
+pub trait ReferenceMachine {
+    /// Name of the machine, used in the divergence reports
+    fn name(&self) -> &str;
+
+    /// Executes one RISC-V instruction; returns false if the program had already ended
+    fn step_instruction(&mut self) -> bool;
+
+    /// Returns the current architectural state
+    fn arch_state(&self) -> ArchState;
+
+    /// Returns a human-readable description of the instruction at the provided address
+    fn describe_instruction(&self, pc: u64) -> String;
+}
+
+/// Zisk emulator adapter implementing `ReferenceMachine`
+pub struct EmuMachine<'a> {
+    emu: Emu<'a>,
+}
+
+impl<'a> EmuMachine<'a> {
+    /// Creates a Zisk emulator machine, ready to execute the program with the provided input.
+    /// The BIOS setup instructions, which have no RISC-V counterpart, are executed before
+    /// returning, so that the pc points to the program entry.
+    pub fn new(mut emu: Emu<'a>, inputs: Vec<u8>) -> Self {
+        emu.ctx = emu.create_emu_context(inputs);
+        if !emu.is_riscv_boundary() {
+            emu.step_riscv_instruction();
+        }
+        Self { emu }
+    }
+
+    /// Returns the wrapped emulator
+    pub fn emu(&self) -> &Emu<'a> {
+        &self.emu
+    }
+}
+
+impl ReferenceMachine for EmuMachine<'_> {
+    fn name(&self) -> &str {
+        "ziskemu"
+    }
+
+    fn step_instruction(&mut self) -> bool {
+        self.emu.step_riscv_instruction()
+    }
+
+    fn arch_state(&self) -> ArchState {
+        ArchState {
+            pc: self.emu.ctx.inst_ctx.pc,
+            regs: self.emu.get_regs_array(),
+            ended: self.emu.terminated(),
+        }
+    }
+
+    fn describe_instruction(&self, pc: u64) -> String {
+        let instruction = self.emu.rom.get_instruction(pc);
+        match &instruction.riscv_inst {
+            Some(riscv_inst) => format!("{riscv_inst} ({})", instruction.verbose),
+            None => instruction.verbose.clone(),
+        }
+    }
+}
+
+/// Part of the architectural state that diverged
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DivergenceKind {
+    /// Different pc values
+    Pc { a: u64, b: u64 },
+    /// Different values of the integer register x<index>
+    Register { index: usize, a: u64, b: u64 },
+    /// Only one of the machines ended the program execution
+    End { a: bool, b: bool },
+}
+
+/// First divergence found between two machines
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    /// Number of RISC-V instructions executed by both machines when the divergence was detected
+    pub instructions: u64,
+    /// Number of instructions executed since the previous (matching) comparison
+    pub window: u64,
+    /// What diverged
+    pub kind: DivergenceKind,
+    /// Name, pc and description of the last instruction executed by machine a
+    pub a: (String, u64, String),
+    /// Name, pc and description of the last instruction executed by machine b
+    pub b: (String, u64, String),
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "divergence after {} instructions (window of {}): ",
+            self.instructions, self.window
+        )?;
+        match &self.kind {
+            DivergenceKind::Pc { a, b } => write!(f, "pc a=0x{a:x} b=0x{b:x}")?,
+            DivergenceKind::Register { index, a, b } => write!(
+                f,
+                "register x{index}({}) a=0x{a:x} b=0x{b:x}",
+                RiscVRegisters::name_from_usize(*index).unwrap()
+            )?,
+            DivergenceKind::End { a, b } => write!(f, "end a={a} b={b}")?,
+        }
+        write!(
+            f,
+            "; last instruction {} at pc=0x{:x}: {}; last instruction {} at pc=0x{:x}: {}",
+            self.a.0, self.a.1, self.a.2, self.b.0, self.b.1, self.b.2
+        )
+    }
+}
+
+/// Result of a conformance run that did not diverge
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConformanceSummary {
+    /// Number of RISC-V instructions executed by each machine
+    pub instructions: u64,
+    /// Number of state comparisons performed
+    pub comparisons: u64,
+}
+
+/// Compares two architectural states, returning the first difference found
+fn compare_states(a: &ArchState, b: &ArchState) -> Option<DivergenceKind> {
+    if a.ended != b.ended {
+        return Some(DivergenceKind::End { a: a.ended, b: b.ended });
+    }
+    if a.pc != b.pc {
+        return Some(DivergenceKind::Pc { a: a.pc, b: b.pc });
+    }
+    (0..32).find(|i| a.regs[*i] != b.regs[*i]).map(|index| DivergenceKind::Register {
+        index,
+        a: a.regs[index],
+        b: b.regs[index],
+    })
+}
+
+/// Executes both machines until both end or `max_instructions` are executed, comparing their
+/// architectural state every `compare_every` instructions.  Both machines must be already loaded
+/// with the same program and input, and positioned at the same entry point.
+pub fn run_conformance(
+    a: &mut dyn ReferenceMachine,
+    b: &mut dyn ReferenceMachine,
+    compare_every: u64,
+    max_instructions: u64,
+) -> Result<ConformanceSummary, Box<Divergence>> {
+    assert!(compare_every > 0, "run_conformance() compare_every must be greater than zero");
+
+    let mut instructions: u64 = 0;
+    let mut comparisons: u64 = 0;
+    let mut window: u64 = 0;
+    let mut last_pc = (a.arch_state().pc, b.arch_state().pc);
+
+    loop {
+        let state_a = a.arch_state();
+        let state_b = b.arch_state();
+        let finished = (state_a.ended && state_b.ended) || (instructions >= max_instructions);
+
+        // Compare the states at every checkpoint, and before finishing
+        if (window == compare_every) || finished {
+            comparisons += 1;
+            if let Some(kind) = compare_states(&state_a, &state_b) {
+                return Err(Box::new(Divergence {
+                    instructions,
+                    window,
+                    kind,
+                    a: (a.name().to_string(), last_pc.0, a.describe_instruction(last_pc.0)),
+                    b: (b.name().to_string(), last_pc.1, b.describe_instruction(last_pc.1)),
+                }));
+            }
+            window = 0;
+        }
+        if finished {
+            return Ok(ConformanceSummary { instructions, comparisons });
+        }
+
+        last_pc = (state_a.pc, state_b.pc);
+        a.step_instruction();
+        b.step_instruction();
+        instructions += 1;
+        window += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Toy machine that increments x1 at every instruction, optionally diverging at one of them
+    struct CounterMachine {
+        state: ArchState,
+        length: u64,
+        diverge_at: Option<u64>,
+    }
+
+    impl CounterMachine {
+        fn new(length: u64, diverge_at: Option<u64>) -> Self {
+            Self { state: ArchState { pc: 0, regs: [0; 32], ended: false }, length, diverge_at }
+        }
+    }
+
+    impl ReferenceMachine for CounterMachine {
+        fn name(&self) -> &str {
+            "counter"
+        }
+
+        fn step_instruction(&mut self) -> bool {
+            if self.state.ended {
+                return false;
+            }
+            let increment = if Some(self.state.pc / 4) == self.diverge_at { 2 } else { 1 };
+            self.state.regs[1] += increment;
+            self.state.pc += 4;
+            self.state.ended = self.state.pc / 4 == self.length;
+            true
+        }
+
+        fn arch_state(&self) -> ArchState {
+            self.state.clone()
+        }
+
+        fn describe_instruction(&self, pc: u64) -> String {
+            format!("addi ra, ra, 1 @ {pc}")
+        }
+    }
+
+    #[test]
+    fn test_conformance_equal_machines() {
+        let mut a = CounterMachine::new(100, None);
+        let mut b = CounterMachine::new(100, None);
+        let summary = run_conformance(&mut a, &mut b, 10, u64::MAX).unwrap();
+        assert_eq!(summary, ConformanceSummary { instructions: 100, comparisons: 10 });
+    }
+
+    #[test]
+    fn test_conformance_divergence() {
+        let mut a = CounterMachine::new(100, None);
+        let mut b = CounterMachine::new(100, Some(42));
+        let divergence = run_conformance(&mut a, &mut b, 1, u64::MAX).unwrap_err();
+        assert_eq!(divergence.instructions, 43);
+        assert_eq!(divergence.kind, DivergenceKind::Register { index: 1, a: 43, b: 44 });
+        assert_eq!(divergence.b.1, 42 * 4);
+    }
+}
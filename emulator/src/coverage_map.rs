@@ -0,0 +1,125 @@
+//! Edge coverage map for fuzzing feedback
+//!
+//! * `CoverageMap` records the control flow edges executed by the emulator into a fixed-size
+//!   bitmap of 8-bit hit counters, in the AFL style: the index of the edge `prev_pc -> pc` is
+//!   `hash(pc) ^ (hash(prev_pc) >> 1)`, so that `a -> b` and `b -> a` are different edges.
+//! * The bitmap layout is the one expected by AFL/libFuzzer-style feedback, so it can be handed
+//!   directly to a coverage-guided fuzzer driving guest inputs.
+//! * The edges are recorded between consecutive executed Zisk instructions, which is finer than
+//!   the RISC-V control flow but equivalent for coverage purposes.
+
+use crate::Emu;
+
+/// Default number of counters of the coverage bitmap, as in AFL
+pub const COVERAGE_MAP_SIZE: usize = 1 << 16;
+
+/// Edge coverage bitmap
+#[derive(Debug, Clone)]
+pub struct CoverageMap {
+    /// Hit counters, indexed by edge hash; they wrap around as in AFL
+    pub bitmap: Vec<u8>,
+    /// Hash of the previous pc, already shifted
+    prev: usize,
+}
+
+impl Default for CoverageMap {
+    fn default() -> Self {
+        Self::new(COVERAGE_MAP_SIZE)
+    }
+}
+
+impl CoverageMap {
+    /// Creates a coverage map of `size` counters, which must be a power of two
+    pub fn new(size: usize) -> Self {
+        if !size.is_power_of_two() {
+            panic!("CoverageMap::new() size={size} is not a power of two");
+        }
+        Self { bitmap: vec![0; size], prev: 0 }
+    }
+
+    /// Hashes a pc into a bitmap index
+    #[inline(always)]
+    fn hash(&self, pc: u64) -> usize {
+        // Fibonacci hashing spreads the aligned pcs over the whole bitmap
+        (pc.wrapping_mul(0x9e3779b97f4a7c15) >> 32) as usize & (self.bitmap.len() - 1)
+    }
+
+    /// Records the execution of `pc`, i.e. the edge from the previously recorded pc
+    #[inline(always)]
+    pub fn record(&mut self, pc: u64) {
+        let current = self.hash(pc);
+        let index = current ^ self.prev;
+        self.bitmap[index] = self.bitmap[index].wrapping_add(1);
+        self.prev = current >> 1;
+    }
+
+    /// Clears all the counters, e.g. before running a new input
+    pub fn reset(&mut self) {
+        self.bitmap.fill(0);
+        self.prev = 0;
+    }
+
+    /// Merges the coverage of another map of the same size, keeping the highest counters
+    pub fn merge(&mut self, other: &CoverageMap) {
+        if self.bitmap.len() != other.bitmap.len() {
+            panic!(
+                "CoverageMap::merge() size mismatch {} != {}",
+                self.bitmap.len(),
+                other.bitmap.len()
+            );
+        }
+        for (counter, other) in self.bitmap.iter_mut().zip(&other.bitmap) {
+            *counter = (*counter).max(*other);
+        }
+    }
+
+    /// Returns the number of covered edges
+    pub fn covered_edges(&self) -> usize {
+        self.bitmap.iter().filter(|counter| **counter != 0).count()
+    }
+
+    /// Returns true if this map covers any edge not covered by `total`
+    pub fn has_new_coverage(&self, total: &CoverageMap) -> bool {
+        self.bitmap
+            .iter()
+            .zip(&total.bitmap)
+            .any(|(counter, total)| (*counter != 0) && (*total == 0))
+    }
+
+    /// Runs the program up to its end, or up to `max_steps` steps, recording its edge coverage
+    pub fn run(&mut self, emu: &mut Emu, max_steps: u64) {
+        while !emu.ctx.inst_ctx.end && (emu.ctx.inst_ctx.step < max_steps) {
+            self.record(emu.ctx.inst_ctx.pc);
+            emu.step_fast();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coverage_map() {
+        let mut total = CoverageMap::new(1024);
+
+        let mut map = CoverageMap::new(1024);
+        for pc in [0x1000, 0x1004, 0x1008, 0x1004, 0x1008] {
+            map.record(pc);
+        }
+        // Edges: start -> 0x1000, 0x1000 -> 0x1004, 0x1004 -> 0x1008, 0x1008 -> 0x1004
+        assert_eq!(map.covered_edges(), 4);
+        assert!(map.has_new_coverage(&total));
+        total.merge(&map);
+        assert!(!map.has_new_coverage(&total));
+
+        map.reset();
+        assert_eq!(map.covered_edges(), 0);
+        for pc in [0x1000, 0x1004, 0x100c] {
+            map.record(pc);
+        }
+        assert!(map.has_new_coverage(&total));
+        total.merge(&map);
+        assert_eq!(total.covered_edges(), 5);
+    }
+}
@@ -102,7 +102,7 @@ impl ElfSymbolReader {
     }
 
     /// Returns an iterator over all functions
-    pub fn functions(&self) -> impl Iterator<Item = &SymbolInfo> {
+    pub fn functions(&self) -> impl Iterator<Item = &SymbolInfo> + Clone {
         self.functions.iter()
     }
 }
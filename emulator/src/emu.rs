@@ -1,7 +1,9 @@
 use std::mem;
 
 use crate::{
-    ElfSymbolReader, EmuContext, EmuFullTraceStep, EmuOptions, EmuRegTrace, ParEmuOptions,
+    EbreakHook, EbreakPolicy, ElfSymbolReader, EmuContext, EmuFullTraceStep, EmuOptions,
+    EmuRegTrace, MisalignedPolicy, ParEmuOptions, RegionProfiler, ShadowStack, Watch,
+    SHADOW_STACK_MAX_DEPTH,
 };
 use fields::PrimeField64;
 use mem_common::MemHelpers;
@@ -15,9 +17,9 @@ use data_bus::DataBusTrait;
 use zisk_common::{EmuTrace, EmuTraceStart};
 use zisk_core::zisk_ops::ZiskOp;
 use zisk_core::{
-    EmulationMode, InstContext, Mem, ZiskInst, ZiskOperationType, ZiskRom, FREG_F0, FREG_INST,
-    FREG_RA, FREG_X0, OUTPUT_ADDR, ROM_ENTRY, SRC_C, SRC_IMM, SRC_IND, SRC_MEM, SRC_REG, SRC_STEP,
-    STORE_IND, STORE_MEM, STORE_NONE, STORE_REG,
+    CodeWrite, EmulationMode, InstContext, Mem, MmioDevice, ZiskInst, ZiskOperationType, ZiskRom,
+    FREG_F0, FREG_INST, FREG_RA, FREG_X0, OUTPUT_ADDR, ROM_ENTRY, SRC_C, SRC_IMM, SRC_IND, SRC_MEM,
+    SRC_REG, SRC_STEP, STORE_IND, STORE_MEM, STORE_NONE, STORE_REG,
 };
 
 /// ZisK emulator structure, containing the ZisK rom, the list of ZisK operations, and the
@@ -31,6 +33,21 @@ pub struct Emu<'a> {
     // This array is used to store static data to avoid heap allocations and speed up the
     // conversion of data to be written to the bus
     static_array: [u64; MAX_OPERATION_DATA_SIZE],
+    /// Policy applied to the ebreak instructions
+    pub(crate) ebreak_policy: EbreakPolicy,
+    /// Hook invoked by the hook ebreak policy
+    pub(crate) ebreak_hook: Option<EbreakHook>,
+    /// Policy applied to the misaligned RISC-V loads and stores
+    pub(crate) misaligned_policy: MisalignedPolicy,
+    /// Watchpoints checked by `run_until_watch()`, indexed by id; removed ones are None
+    pub(crate) watchpoints: Vec<Option<Watch>>,
+    /// Guest call stack, tracked only if enabled
+    pub(crate) shadow_stack: Option<ShadowStack>,
+    /// Profiler of the guest regions, enabled only if requested
+    pub(crate) region_profiler: Option<RegionProfiler>,
+    /// True if any per-step check is enabled, see `update_instrumented()`, so that the default
+    /// steps test this flag instead of every check
+    pub(crate) instrumented: bool,
 }
 
 /// ZisK emulator structure implementation
@@ -76,7 +93,18 @@ pub struct Emu<'a> {
 ///                             Emu::source_a_mem_reads_generate(instruction, &mut emu_full_trace_vec.mem_reads);
 impl<'a> Emu<'a> {
     pub fn new(rom: &ZiskRom) -> Emu<'_> {
-        Emu { rom, ctx: EmuContext::default(), static_array: [0; MAX_OPERATION_DATA_SIZE] }
+        Emu {
+            rom,
+            ctx: EmuContext::default(),
+            static_array: [0; MAX_OPERATION_DATA_SIZE],
+            ebreak_policy: EbreakPolicy::Nop,
+            ebreak_hook: None,
+            misaligned_policy: MisalignedPolicy::Decompose,
+            watchpoints: Vec::new(),
+            shadow_stack: None,
+            region_profiler: None,
+            instrumented: false,
+        }
     }
 
     pub fn from_emu_trace_start(rom: &'a ZiskRom, trace_start: &'a EmuTraceStart) -> Emu<'a> {
@@ -91,8 +119,14 @@ impl<'a> Emu<'a> {
     }
 
     pub fn create_emu_context(&mut self, inputs: Vec<u8>) -> EmuContext {
+        self.create_paged_emu_context(inputs, false)
+    }
+
+    /// Creates the emulator context; if `paged_input` is set, the inputs can be too big to fit in
+    /// the input section, and then they are only readable through the input page fcall
+    pub fn create_paged_emu_context(&mut self, inputs: Vec<u8>, paged_input: bool) -> EmuContext {
         // Initialize an empty instance
-        let mut ctx = EmuContext::new(inputs);
+        let mut ctx = EmuContext::with_input(inputs, paged_input);
 
         // Create a new read section for every RO data entry of the rom
         for i in 0..self.rom.ro_data.len() {
@@ -102,12 +136,25 @@ impl<'a> Emu<'a> {
         // Sort read sections by start address to improve performance when using binary search
         ctx.inst_ctx.mem.read_sections.sort_by(|a, b| a.start.cmp(&b.start));
 
+        // Detect writes into the program code, reported with their pc only if instrumented
+        ctx.inst_ctx.mem.code_ranges = self.rom.code_ranges.clone();
+        ctx.inst_ctx.mem.defer_code_writes = self.instrumented;
+
+        // Keep the MMIO regions registered before the context was created
+        ctx.inst_ctx.mem.mmio_regions = mem::take(&mut self.ctx.inst_ctx.mem.mmio_regions);
+
         // Get registers
         //emu.get_regs(); // TODO: ask Jordi
 
         ctx
     }
 
+    /// Registers an MMIO region whose reads and writes will be forwarded to the provided device.
+    /// Regions registered before calling `run()` or `par_run()` are kept in the new context.
+    pub fn add_mmio_region(&mut self, start: u64, size: u64, device: Box<dyn MmioDevice>) {
+        self.ctx.inst_ctx.mem.add_mmio_region(start, size, device);
+    }
+
     /// Calculate the 'a' register value based on the source specified by the current instruction
     #[inline(always)]
     pub fn source_a(&mut self, instruction: &ZiskInst) {
@@ -1442,9 +1489,53 @@ impl<'a> Emu<'a> {
         }
     }
 
+    /// Reports the writes into the code done by the current instruction, if any, before pc is
+    /// set to the next one
+    #[inline(always)]
+    fn check_code_write(&mut self, instruction: &ZiskInst) {
+        if self.ctx.inst_ctx.mem.code_write.is_some() {
+            self.report_code_write(instruction);
+        }
+    }
+
+    /// Panics reporting the write into the code done by the current instruction
+    #[cold]
+    fn report_code_write(&mut self, instruction: &ZiskInst) {
+        let (addr, width) = self.ctx.inst_ctx.mem.code_write.take().unwrap();
+        let code_write = CodeWrite { pc: self.ctx.inst_ctx.pc, addr, width };
+        panic!(
+            "Emu::report_code_write() self-modifying code detected at step={}: {code_write} ({})",
+            self.ctx.inst_ctx.step, instruction.verbose
+        );
+    }
+
+    /// Recomputes the `instrumented` flag, after a change of the ebreak or misaligned policies,
+    /// the watchpoints, the shadow stack or the region profiler
+    pub(crate) fn update_instrumented(&mut self) {
+        self.instrumented = (self.ebreak_policy != EbreakPolicy::Nop)
+            || (self.misaligned_policy != MisalignedPolicy::Decompose)
+            || self.watchpoints.iter().any(Option::is_some)
+            || self.shadow_stack.is_some()
+            || self.region_profiler.is_some();
+        self.ctx.inst_ctx.mem.defer_code_writes = self.instrumented;
+    }
+
     /// Run the whole program, fast
     #[inline(always)]
     pub fn run_fast(&mut self, options: &EmuOptions) {
+        if let Some(policy) = options.ebreak {
+            self.ebreak_policy = policy;
+        }
+        if let Some(policy) = options.misaligned {
+            self.misaligned_policy = policy;
+        }
+        if options.shadow_stack {
+            self.enable_shadow_stack(SHADOW_STACK_MAX_DEPTH);
+        }
+        if options.regions {
+            self.enable_region_profiler();
+        }
+        self.update_instrumented();
         while !self.ctx.inst_ctx.end && (self.ctx.inst_ctx.step < options.max_steps) {
             self.step_fast();
         }
@@ -1455,12 +1546,19 @@ impl<'a> Emu<'a> {
                 "Emu::run_fast() finished with error at step={} pc=0x{:x}",
                 self.ctx.inst_ctx.step, self.ctx.inst_ctx.pc
             );
+            if let Some(backtrace) = self.current_backtrace() {
+                eprintln!("{backtrace}");
+            }
         }
     }
 
     /// Performs one single step of the emulation
     #[inline(always)]
     pub fn step_fast(&mut self) {
+        if self.instrumented {
+            self.step_fast_instrumented();
+            return;
+        }
         let instruction = self.rom.get_instruction(self.ctx.inst_ctx.pc);
         // let debug = instruction.op >= 0xf6;
         // let initial_regs = if debug {
@@ -1517,6 +1615,52 @@ impl<'a> Emu<'a> {
         // }
     }
 
+    /// Same as `step_fast()`, also applying the ebreak and misaligned policies, reporting the
+    /// writes into the code and tracking the shadow stack and the regions
+    #[inline(never)]
+    fn step_fast_instrumented(&mut self) {
+        let instruction = self.rom.get_instruction(self.ctx.inst_ctx.pc);
+        if self.ebreak_stops(instruction) {
+            return;
+        }
+        self.source_a(instruction);
+        if self.misaligned_stops(instruction) {
+            return;
+        }
+        self.source_b(instruction);
+        (instruction.func)(&mut self.ctx.inst_ctx);
+        self.store_c(instruction);
+
+        let pc = self.ctx.inst_ctx.pc;
+        self.check_code_write(instruction);
+        self.set_pc(instruction);
+        self.track_shadow_stack(instruction, pc);
+        self.track_regions(instruction);
+        self.ctx.inst_ctx.end = instruction.end;
+        self.ctx.inst_ctx.step += 1;
+    }
+
+    /// Returns true if the current pc is the first Zisk instruction of a transpiled RISC-V
+    /// instruction, i.e. if the previous RISC-V instruction has been completely executed
+    #[inline(always)]
+    pub fn is_riscv_boundary(&self) -> bool {
+        self.rom.get_instruction(self.ctx.inst_ctx.pc).riscv_inst.is_some()
+    }
+
+    /// Executes Zisk instructions up to the beginning of the next RISC-V instruction, or up to the
+    /// end of the program; returns false if the program had already ended
+    pub fn step_riscv_instruction(&mut self) -> bool {
+        if self.ctx.inst_ctx.end {
+            return false;
+        }
+        loop {
+            self.step_fast();
+            if self.ctx.inst_ctx.end || self.is_riscv_boundary() {
+                return true;
+            }
+        }
+    }
+
     /// Run the whole program
     pub fn run(
         &mut self,
@@ -1525,7 +1669,7 @@ impl<'a> Emu<'a> {
         callback: Option<impl Fn(EmuTrace)>,
     ) {
         // Context, where the state of the execution is stored and modified at every execution step
-        self.ctx = self.create_emu_context(inputs.clone());
+        self.ctx = self.create_paged_emu_context(inputs.clone(), options.paged_input);
 
         let mut elf = ElfSymbolReader::new();
         if options.read_symbols {
@@ -1622,6 +1766,21 @@ impl<'a> Emu<'a> {
         // Store the stats option into the emulator context
         self.ctx.do_stats = options.stats || options.legacy_stats;
 
+        // Apply the requested ebreak and misaligned access policies, if any
+        if let Some(policy) = options.ebreak {
+            self.ebreak_policy = policy;
+        }
+        if let Some(policy) = options.misaligned {
+            self.misaligned_policy = policy;
+        }
+        if options.shadow_stack {
+            self.enable_shadow_stack(SHADOW_STACK_MAX_DEPTH);
+        }
+        if options.regions {
+            self.enable_region_profiler();
+        }
+        self.update_instrumented();
+
         // While not done
         while !self.ctx.inst_ctx.end {
             if options.verbose {
@@ -1693,6 +1852,13 @@ impl<'a> Emu<'a> {
                 "Emu::run() finished with error at step={} pc=0x{:x}",
                 self.ctx.inst_ctx.step, self.ctx.inst_ctx.pc
             );
+            if let Some(backtrace) = self.current_backtrace() {
+                if elf.functions().next().is_some() {
+                    eprintln!("{}", backtrace.to_text_with_symbols(elf.functions()));
+                } else {
+                    eprintln!("{backtrace}");
+                }
+            }
         }
 
         // Print stats report
@@ -1714,7 +1880,7 @@ impl<'a> Emu<'a> {
         par_options: &ParEmuOptions,
     ) -> Vec<EmuTrace> {
         // Context, where the state of the execution is stored and modified at every execution step
-        self.ctx = self.create_emu_context(inputs);
+        self.ctx = self.create_paged_emu_context(inputs, options.paged_input);
 
         // Init pc to the rom entry address
         self.ctx.trace.start_state.pc = ROM_ENTRY;
@@ -1823,6 +1989,11 @@ impl<'a> Emu<'a> {
         let pc = self.ctx.inst_ctx.pc;
         let instruction = self.rom.get_instruction(self.ctx.inst_ctx.pc);
 
+        // Apply the ebreak policy
+        if self.instrumented && self.ebreak_stops(instruction) {
+            return;
+        }
+
         // println!(
         //     "Emu::step() executing step={} pc={:x} inst={}",
         //     self.ctx.inst_ctx.step,
@@ -1835,6 +2006,11 @@ impl<'a> Emu<'a> {
         // Build the 'a' register value  based on the source specified by the current instruction
         self.source_a(instruction);
 
+        // Apply the misaligned access policy, once the address base is known
+        if self.instrumented && self.misaligned_stops(instruction) {
+            return;
+        }
+
         // Build the 'b' register value  based on the source specified by the current instruction
         self.source_b(instruction);
 
@@ -1864,9 +2040,20 @@ impl<'a> Emu<'a> {
         // #[cfg(feature = "sp")]
         // self.set_sp(instruction);
 
+        // Report the writes into the code done by this instruction, while pc still points to it
+        if self.instrumented {
+            self.check_code_write(instruction);
+        }
+
         // Set PC, based on current PC, current flag and current instruction
         self.set_pc(instruction);
 
+        // Track the guest call stack and regions, if requested
+        if self.instrumented {
+            self.track_shadow_stack(instruction, pc);
+            self.track_regions(instruction);
+        }
+
         // If this is the last instruction, stop executing
         if instruction.end {
             self.ctx.inst_ctx.end = true;
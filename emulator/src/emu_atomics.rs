@@ -0,0 +1,323 @@
+//! Load-reserved/store-conditional reservation modeling
+//!
+//! * Zisk transpiles `lr.w`/`lr.d` into plain loads and `sc.w`/`sc.d` into plain stores that
+//!   always succeed, i.e. that always write 0 into rd.  This is valid for single-hart programs,
+//!   but it hides the retry paths of the guest code using atomics, e.g. through `core::sync`.
+//! * `EmuAtomics` drives the emulation modeling a reservation set, as the hardware does: `lr`
+//!   reserves the loaded address, and `sc` only succeeds, storing its value and writing 0 into rd,
+//!   if the address is still reserved.  Otherwise it writes 1 into rd and does not store anything.
+//! * The reservation is invalidated by any `sc`, by any intervening store overlapping the reserved
+//!   bytes, detected through the memory write journal, and by context switches, i.e. `ecall` or
+//!   an explicit call to `invalidate()`.  Spurious failures can be configured, so that the retry
+//!   paths can be exercised.
+//! * The AMO instructions need no modeling: they are transpiled into a load, an operation and a
+//!   store, with the word variants sign-extending the loaded value, as the hardware does.
+//! * A failed `sc` is not part of the proven execution, so a program that depends on it can be
+//!   emulated but not proven.
+
+use crate::Emu;
+
+/// Reserved memory range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reservation {
+    pub addr: u64,
+    pub width: u64,
+}
+
+/// Host-side state of the load-reserved/store-conditional instructions
+#[derive(Debug, Default)]
+pub struct EmuAtomics {
+    /// Current reservation, if any
+    pub reservation: Option<Reservation>,
+    /// If set, every n-th `sc` fails even if the address is still reserved
+    pub spurious_failure_period: Option<u64>,
+    /// Number of `sc` executed
+    pub sc_count: u64,
+    /// Number of `sc` that failed
+    pub sc_failures: u64,
+    /// True if the memory write journal was enabled by this instance, and can be cleared
+    owns_journal: bool,
+    /// Number of journal records already checked, if the journal is being tracked
+    journal_pos: Option<usize>,
+}
+
+impl EmuAtomics {
+    /// Creates an instance without spurious failures
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an instance where every `period`-th `sc` fails spuriously
+    pub fn with_spurious_failures(period: u64) -> Self {
+        assert!(
+            period > 0,
+            "EmuAtomics::with_spurious_failures() period must be greater than zero"
+        );
+        Self { spurious_failure_period: Some(period), ..Default::default() }
+    }
+
+    /// Invalidates the current reservation, e.g. on a context switch
+    pub fn invalidate(&mut self) {
+        self.reservation = None;
+    }
+
+    /// Executes one step of the emulation, modeling the reservation set if the current pc is the
+    /// beginning of a `lr` or `sc` instruction; returns false if the program already ended
+    pub fn step(&mut self, emu: &mut Emu) -> bool {
+        if emu.ctx.inst_ctx.end {
+            return false;
+        }
+
+        // Detect the intervening stores through the memory write journal
+        let mem = &mut emu.ctx.inst_ctx.mem;
+        match &mem.write_journal {
+            None => {
+                mem.write_journal = Some(Vec::new());
+                self.owns_journal = true;
+                self.journal_pos = Some(0);
+            }
+            Some(write_journal) if self.journal_pos.is_none() => {
+                self.journal_pos = Some(write_journal.len());
+            }
+            Some(_) => {}
+        }
+
+        let instruction = emu.rom.get_instruction(emu.ctx.inst_ctx.pc);
+        match instruction.riscv_inst.as_deref() {
+            Some("lr.w") | Some("lr.d") => {
+                // The address is rs1, since lr has no offset
+                let addr = emu.ctx.inst_ctx.regs[instruction.a_offset_imm0 as usize];
+                let width = instruction.ind_width;
+                emu.step_fast();
+                self.reservation = Some(Reservation { addr, width });
+            }
+            Some("sc.w") | Some("sc.d") => {
+                let addr = emu.ctx.inst_ctx.regs[instruction.a_offset_imm0 as usize];
+                self.sc_count += 1;
+                let reserved = self
+                    .reservation
+                    .take()
+                    .is_some_and(|r| (r.addr == addr) && (instruction.ind_width <= r.width));
+                let spurious = self
+                    .spurious_failure_period
+                    .is_some_and(|period| self.sc_count.is_multiple_of(period));
+                if reserved && !spurious {
+                    emu.step_fast();
+                } else {
+                    self.fail_sc(emu);
+                }
+            }
+            Some("ecall") => {
+                self.invalidate();
+                emu.step_fast();
+            }
+            _ => emu.step_fast(),
+        }
+
+        self.check_journal(emu);
+        true
+    }
+
+    /// Runs the program up to its end, or up to `max_steps` steps, modeling the reservation set
+    pub fn run(&mut self, emu: &mut Emu, max_steps: u64) {
+        while emu.ctx.inst_ctx.step < max_steps && self.step(emu) {}
+        if self.owns_journal {
+            emu.ctx.inst_ctx.mem.write_journal = None;
+            self.owns_journal = false;
+        }
+        self.journal_pos = None;
+    }
+
+    /// Skips the `sc` at the current pc, writing 1 into its rd
+    fn fail_sc(&mut self, emu: &mut Emu) {
+        self.sc_failures += 1;
+        let pc = emu.ctx.inst_ctx.pc;
+        let instruction = emu.rom.get_instruction(pc);
+
+        // If rd is not x0, the store is followed by the instruction that writes 0 into rd
+        if instruction.jmp_offset1 == 1 {
+            let rd = emu.rom.get_instruction(pc + 1).store_offset as usize;
+            emu.ctx.inst_ctx.regs[rd] = 1;
+        }
+
+        // sc is never compressed, so the next RISC-V instruction is 4 bytes ahead
+        emu.ctx.inst_ctx.pc = pc + 4;
+        emu.ctx.inst_ctx.step += 1;
+    }
+
+    /// Invalidates the reservation if any of the new journal records overlaps it
+    fn check_journal(&mut self, emu: &mut Emu) {
+        let Some(write_journal) = &mut emu.ctx.inst_ctx.mem.write_journal else {
+            return;
+        };
+        let journal_pos = self.journal_pos.unwrap_or(0).min(write_journal.len());
+        if let Some(r) = self.reservation {
+            let overlaps = write_journal[journal_pos..].iter().any(|record| {
+                (record.addr < r.addr + r.width) && (r.addr < record.addr + record.width)
+            });
+            if overlaps {
+                self.reservation = None;
+            }
+        }
+        if self.owns_journal {
+            write_journal.clear();
+        }
+        self.journal_pos = Some(write_journal.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zisk_core::{add_zisk_code, ZiskInst, ZiskInstBuilder, ZiskRom, RAM_ADDR, ROM_ADDR};
+
+    use super::*;
+
+    const LR: u32 = 0b00010;
+    const SC: u32 = 0b00011;
+    const W: u32 = 2;
+    const D: u32 = 3;
+
+    /// Encodes an atomic instruction `funct5` of the word (`W`) or double word (`D`) width
+    fn amo(funct5: u32, width: u32, rd: u32, rs1: u32, rs2: u32) -> u32 {
+        (funct5 << 27) | (rs2 << 20) | (rs1 << 15) | (width << 12) | (rd << 7) | 0x2f
+    }
+
+    /// Encodes `sd rs2, 0(rs1)`
+    fn sd(rs1: u32, rs2: u32) -> u32 {
+        (rs2 << 20) | (rs1 << 15) | (D << 12) | 0x23
+    }
+
+    /// Transpiles the code at ROM_ADDR, followed by an instruction that ends the program
+    fn program(code: &[u32]) -> ZiskRom {
+        let data: Vec<u8> = code.iter().flat_map(|inst| inst.to_le_bytes()).collect();
+        let mut rom = ZiskRom::default();
+        add_zisk_code(&mut rom, ROM_ADDR, &data, &[]);
+        let end_pc = ROM_ADDR + data.len() as u64;
+        let mut zib = ZiskInstBuilder::new(end_pc);
+        zib.op("flag").unwrap();
+        zib.src_a("imm", 0, false);
+        zib.src_b("imm", 0, false);
+        zib.j(0, 0);
+        zib.end();
+        rom.insts.insert(end_pc, zib);
+
+        // Lay out the instructions as the emulator fetches them
+        let len = data.len() + 4;
+        rom.min_program_pc = ROM_ADDR;
+        rom.offset_rom_na_unstructions = ROM_ADDR;
+        rom.rom_instructions = vec![ZiskInst::default(); len / 4];
+        rom.rom_na_instructions = vec![ZiskInst::default(); len];
+        for (addr, zib) in &rom.insts {
+            let index = (addr - ROM_ADDR) as usize;
+            if index % 4 == 0 {
+                rom.rom_instructions[index / 4] = zib.i.clone();
+            } else {
+                rom.rom_na_instructions[index] = zib.i.clone();
+            }
+        }
+        rom
+    }
+
+    /// Creates an emulator starting at ROM_ADDR, with a0 pointing to RAM_ADDR, which holds
+    /// `value`, and a1 and a2 set to `a1` and `a2`
+    fn new_emu(rom: &ZiskRom, value: u64, a1: u64, a2: u64) -> Emu<'_> {
+        let mut emu = Emu::new(rom);
+        emu.ctx = emu.create_emu_context(vec![0; 8]);
+        emu.ctx.inst_ctx.pc = ROM_ADDR;
+        emu.ctx.inst_ctx.regs[10] = RAM_ADDR;
+        emu.ctx.inst_ctx.regs[11] = a1;
+        emu.ctx.inst_ctx.regs[12] = a2;
+        emu.ctx.inst_ctx.mem.write(RAM_ADDR, value, 8);
+        emu
+    }
+
+    #[test]
+    fn test_reservation_loss() {
+        let rom = program(&[
+            amo(LR, D, 13, 10, 0),  // lr.d a3, (a0)
+            amo(SC, D, 14, 10, 11), // sc.d a4, a1, (a0): reserved, succeeds
+            amo(LR, D, 13, 10, 0),  // lr.d a3, (a0)
+            sd(10, 12),             // sd a2, 0(a0): overlaps the reservation
+            amo(SC, D, 15, 10, 11), // sc.d a5, a1, (a0): fails
+            amo(SC, D, 16, 10, 11), // sc.d a6, a1, (a0): not reserved, fails
+            amo(LR, W, 13, 10, 0),  // lr.w a3, (a0)
+            amo(SC, D, 17, 10, 11), // sc.d a7, a1, (a0): wider than the reservation, fails
+        ]);
+        let mut emu = new_emu(&rom, 0, 0x1111, 0x2222);
+        let mut atomics = EmuAtomics::new();
+        atomics.run(&mut emu, 100);
+
+        assert!(emu.ctx.inst_ctx.end);
+        let regs = &emu.ctx.inst_ctx.regs;
+        assert_eq!(regs[13..18], [0x2222, 0, 1, 1, 1]);
+        assert_eq!(emu.ctx.inst_ctx.mem.read(RAM_ADDR, 8), 0x2222);
+        assert_eq!((atomics.sc_count, atomics.sc_failures), (4, 3));
+        assert_eq!(atomics.reservation, None);
+        assert!(emu.ctx.inst_ctx.mem.write_journal.is_none());
+
+        // Every second sc fails spuriously, and an invalidation drops the reservation
+        let rom = program(&[
+            amo(LR, D, 13, 10, 0),  // lr.d a3, (a0)
+            amo(SC, D, 14, 10, 11), // sc.d a4, a1, (a0): succeeds
+            amo(LR, D, 13, 10, 0),  // lr.d a3, (a0)
+            amo(SC, D, 15, 10, 12), // sc.d a5, a2, (a0): fails spuriously
+            amo(LR, D, 13, 10, 0),  // lr.d a3, (a0), followed by a context switch
+            amo(SC, D, 16, 10, 12), // sc.d a6, a2, (a0): fails
+        ]);
+        let mut emu = new_emu(&rom, 0, 0x1111, 0x2222);
+        let mut atomics = EmuAtomics::with_spurious_failures(2);
+        while emu.ctx.inst_ctx.pc != ROM_ADDR + 16 {
+            assert!(atomics.step(&mut emu));
+        }
+        assert!(atomics.step(&mut emu));
+        assert_eq!(atomics.reservation, Some(Reservation { addr: RAM_ADDR, width: 8 }));
+        atomics.invalidate();
+        atomics.run(&mut emu, 100);
+
+        assert!(emu.ctx.inst_ctx.end);
+        assert_eq!(emu.ctx.inst_ctx.regs[14..17], [0, 1, 1]);
+        assert_eq!(emu.ctx.inst_ctx.mem.read(RAM_ADDR, 8), 0x1111);
+        assert_eq!((atomics.sc_count, atomics.sc_failures), (3, 2));
+    }
+
+    #[test]
+    fn test_amo_ops() {
+        type OpD = fn(u64, u64) -> u64;
+        type OpW = fn(i32, i32) -> i32;
+        let ops: [(&str, u32, OpD, OpW); 9] = [
+            ("add", 0b00000, |a, b| a.wrapping_add(b), |a, b| a.wrapping_add(b)),
+            ("swap", 0b00001, |_, b| b, |_, b| b),
+            ("xor", 0b00100, |a, b| a ^ b, |a, b| a ^ b),
+            ("or", 0b01000, |a, b| a | b, |a, b| a | b),
+            ("and", 0b01100, |a, b| a & b, |a, b| a & b),
+            ("min", 0b10000, |a, b| (a as i64).min(b as i64) as u64, |a, b| a.min(b)),
+            ("max", 0b10100, |a, b| (a as i64).max(b as i64) as u64, |a, b| a.max(b)),
+            ("minu", 0b11000, |a, b| a.min(b), |a, b| (a as u32).min(b as u32) as i32),
+            ("maxu", 0b11100, |a, b| a.max(b), |a, b| (a as u32).max(b as u32) as i32),
+        ];
+
+        // The low words have different signs, and the high words differ from the low words'
+        let value = 0x1234_5678_8000_0005;
+        let a2 = 0xffff_ffff_0000_0007;
+        for (name, funct5, op_d, op_w) in ops {
+            // amo<op>.d a3, a2, (a0)
+            let rom = program(&[amo(funct5, D, 13, 10, 12)]);
+            let mut emu = new_emu(&rom, value, 0, a2);
+            EmuAtomics::new().run(&mut emu, 100);
+            assert_eq!(emu.ctx.inst_ctx.regs[13], value, "amo{name}.d");
+            assert_eq!(emu.ctx.inst_ctx.mem.read(RAM_ADDR, 8), op_d(value, a2), "amo{name}.d");
+
+            // amo<op>.w a3, a2, (a0) sign-extends the loaded word and only stores a word
+            let rom = program(&[amo(funct5, W, 13, 10, 12)]);
+            let mut emu = new_emu(&rom, value, 0, a2);
+            EmuAtomics::new().run(&mut emu, 100);
+            let stored = op_w(value as i32, a2 as i32) as u32 as u64;
+            assert_eq!(emu.ctx.inst_ctx.regs[13], value as i32 as u64, "amo{name}.w");
+            assert_eq!(
+                emu.ctx.inst_ctx.mem.read(RAM_ADDR, 8),
+                (value & 0xffff_ffff_0000_0000) | stored,
+                "amo{name}.w"
+            );
+        }
+    }
+}
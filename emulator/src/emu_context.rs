@@ -1,8 +1,8 @@
 use crate::Stats;
 use zisk_common::EmuTrace;
 use zisk_core::{
-    EmulationMode, FcallInstContext, InstContext, Mem, PrecompiledInstContext, INPUT_ADDR,
-    MAX_INPUT_SIZE, RAM_ADDR, RAM_SIZE, REGS_IN_MAIN_TOTAL_NUMBER, ROM_ENTRY,
+    EmulationMode, FcallInstContext, InstContext, Mem, PrecompiledInstContext, RAM_ADDR, RAM_SIZE,
+    REGS_IN_MAIN_TOTAL_NUMBER, ROM_ENTRY,
 };
 
 /// ZisK emulator context data container, storing the state of the emulation
@@ -23,8 +23,14 @@ pub struct EmuContext {
 
 /// RisK emulator context implementation
 impl EmuContext {
-    /// RisK emulator context constructor
+    /// RisK emulator context constructor; panics if the input does not fit in the input section
     pub fn new(input: Vec<u8>) -> EmuContext {
+        Self::with_input(input, false)
+    }
+
+    /// RisK emulator context constructor; if `paged_input` is set, an input too big to fit in the
+    /// input section is accepted, and it can only be read through the input page fcall
+    pub fn with_input(input: Vec<u8>, paged_input: bool) -> EmuContext {
         let mut ctx = EmuContext {
             inst_ctx: InstContext {
                 mem: Mem::default(),
@@ -54,18 +60,14 @@ impl EmuContext {
             stats: Stats::default(),
         };
 
-        // Check the input data size is inside the proper range
-        if input.len() > (MAX_INPUT_SIZE - 16) as usize {
-            panic!("EmuContext::new() input size too big size={}", input.len());
+        // Add the length and input data read sections, unless the input is too big and the caller
+        // opted in to paged input
+        if !paged_input || Mem::input_fits(input.len()) {
+            ctx.inst_ctx.mem.replace_input(&input);
+        } else {
+            ctx.inst_ctx.mem.replace_paged_input(input);
         }
 
-        // Add the length and input data read sections
-        let input_len = input.len() as u64;
-        let free_input = 0u64;
-        ctx.inst_ctx.mem.add_read_section(INPUT_ADDR, &free_input.to_le_bytes());
-        ctx.inst_ctx.mem.add_read_section(INPUT_ADDR + 8, &input_len.to_le_bytes());
-        ctx.inst_ctx.mem.add_read_section(INPUT_ADDR + 16, &input);
-
         // Add the write section
         ctx.inst_ctx.mem.add_write_section(RAM_ADDR, RAM_SIZE);
 
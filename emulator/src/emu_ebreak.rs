@@ -0,0 +1,144 @@
+//! ebreak handling policy
+//!
+//! * Zisk transpiles `ebreak` and `c.ebreak` into a nop, so they are ignored by the proven
+//!   execution, but guests built with debug assertions can reach them, e.g. in panic paths.
+//! * `EbreakPolicy` configures what the emulator does when it is about to execute one of them:
+//!   ignore it, as the proven execution does (the default), trap, i.e. end the emulation with
+//!   error at the ebreak pc, or invoke a registered hook, e.g. a debugger, which can inspect or
+//!   modify the context, and stop the emulation by setting `end`.
+//! * The policy is applied by `Emu::step()` and `Emu::step_fast()`, i.e. when running the program
+//!   with `Emu::run()`; the trace generation paths always ignore ebreak, as the proven execution.
+
+use clap::ValueEnum;
+use zisk_core::{InstContext, ZiskInst};
+
+use crate::Emu;
+
+/// What the emulator does when it reaches an ebreak instruction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum EbreakPolicy {
+    /// Execute it as a nop
+    #[default]
+    Nop,
+    /// End the emulation with error, without executing it
+    Trap,
+    /// Invoke the hook registered with `Emu::set_ebreak_hook()`, and then execute it as a nop
+    /// unless the hook ended the emulation
+    Hook,
+}
+
+/// Hook invoked when the emulator reaches an ebreak instruction, whose pc is the current one
+pub type EbreakHook = Box<dyn FnMut(&mut InstContext)>;
+
+/// Returns true if the instruction is the transpilation of an ebreak
+pub fn is_ebreak(instruction: &ZiskInst) -> bool {
+    matches!(instruction.riscv_inst.as_deref(), Some("ebreak") | Some("c.ebreak"))
+}
+
+impl Emu<'_> {
+    /// Sets the policy applied to the ebreak instructions
+    pub fn set_ebreak_policy(&mut self, policy: EbreakPolicy) {
+        self.ebreak_policy = policy;
+        self.update_instrumented();
+    }
+
+    /// Registers a hook to be invoked on every ebreak instruction, and sets the hook policy
+    pub fn set_ebreak_hook(&mut self, hook: EbreakHook) {
+        self.ebreak_hook = Some(hook);
+        self.ebreak_policy = EbreakPolicy::Hook;
+        self.update_instrumented();
+    }
+
+    /// Applies the ebreak policy if the instruction is an ebreak, and returns true if the
+    /// emulation must stop before executing it
+    #[inline(always)]
+    pub(crate) fn ebreak_stops(&mut self, instruction: &ZiskInst) -> bool {
+        if (self.ebreak_policy == EbreakPolicy::Nop) || !is_ebreak(instruction) {
+            return false;
+        }
+        match self.ebreak_policy {
+            EbreakPolicy::Nop => false,
+            EbreakPolicy::Trap => {
+                self.ctx.inst_ctx.end = true;
+                self.ctx.inst_ctx.error = true;
+                true
+            }
+            EbreakPolicy::Hook => {
+                let hook = self
+                    .ebreak_hook
+                    .as_mut()
+                    .expect("Emu::ebreak_stops() ebreak policy is hook but no hook was registered");
+                hook(&mut self.ctx.inst_ctx);
+                self.ctx.inst_ctx.end
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zisk_core::{ZiskInstBuilder, ZiskRom, ROM_ADDR, ROM_ENTRY};
+
+    use super::*;
+
+    /// Builds an instruction that continues with the next one, unless `build` changes it
+    fn inst(riscv: &str, build: impl FnOnce(&mut ZiskInstBuilder)) -> ZiskInst {
+        let mut zib = ZiskInstBuilder::new_from_riscv(0, riscv.to_string());
+        zib.op("flag").unwrap();
+        zib.src_a("imm", 0, false);
+        zib.src_b("imm", 0, false);
+        zib.j(4, 4);
+        build(&mut zib);
+        zib.i
+    }
+
+    /// Runs `ebreak; li a0, 1; end` after `setup` configures the emulator, and returns the final
+    /// pc, step, error, a0 and a1
+    fn run(setup: impl FnOnce(&mut Emu)) -> (u64, u64, bool, u64, u64) {
+        let rom = ZiskRom {
+            rom_entry_instructions: vec![
+                inst("ebreak", |_| {}),
+                inst("li", |zib| {
+                    zib.op("copyb").unwrap();
+                    zib.src_b("imm", 1, false);
+                    zib.store("reg", 10, false, false);
+                }),
+                inst("end", |zib| {
+                    zib.j(0, 0);
+                    zib.end();
+                }),
+            ],
+            min_program_pc: ROM_ADDR,
+            ..Default::default()
+        };
+        let mut emu = Emu::new(&rom);
+        emu.ctx = emu.create_emu_context(vec![0; 8]);
+        setup(&mut emu);
+        while !emu.ctx.inst_ctx.end {
+            emu.step_fast();
+        }
+        let inst_ctx = &emu.ctx.inst_ctx;
+        (inst_ctx.pc, inst_ctx.step, inst_ctx.error, inst_ctx.regs[10], inst_ctx.regs[11])
+    }
+
+    #[test]
+    fn test_ebreak_policies() {
+        // Nop, the default, executes the ebreak and continues
+        assert_eq!(EbreakPolicy::default(), EbreakPolicy::Nop);
+        assert_eq!(run(|_| {}), (ROM_ENTRY + 8, 3, false, 1, 0));
+        let nop = run(|emu| emu.set_ebreak_policy(EbreakPolicy::Nop));
+        assert_eq!(nop, (ROM_ENTRY + 8, 3, false, 1, 0));
+
+        // Trap ends with error at the ebreak, without executing it
+        let trap = run(|emu| emu.set_ebreak_policy(EbreakPolicy::Trap));
+        assert_eq!(trap, (ROM_ENTRY, 0, true, 0, 0));
+
+        // Hook can modify the context and continue, or end the emulation without error
+        let hook =
+            run(|emu| emu.set_ebreak_hook(Box::new(|ctx: &mut InstContext| ctx.regs[11] = ctx.pc)));
+        assert_eq!(hook, (ROM_ENTRY + 8, 3, false, 1, ROM_ENTRY));
+        let hook_end =
+            run(|emu| emu.set_ebreak_hook(Box::new(|ctx: &mut InstContext| ctx.end = true)));
+        assert_eq!(hook_end, (ROM_ENTRY, 0, false, 0, 0));
+    }
+}
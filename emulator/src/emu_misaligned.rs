@@ -0,0 +1,156 @@
+//! Misaligned memory access policy
+//!
+//! * RISC-V allows loads and stores whose address is not a multiple of their width, and the Zisk
+//!   memory state machine proves them by decomposition: the access is split into accesses to the
+//!   one or two aligned 8-bytes chunks that contain it, which the emulator models by default.
+//! * `MisalignedPolicy::Trap` ends the emulation with error, at the instruction pc, when an
+//!   indirect load or store, i.e. a RISC-V memory access, is misaligned, so that the guests that
+//!   rely on misaligned accesses can be found.
+//! * `decompose_mem_access()` returns the aligned operations that prove an access, in the layout
+//!   produced by `MemBusHelpers`, so that the trace-consistency tests can compare them op-for-op:
+//!   a load of every chunk and, for writes, a store of every updated chunk.
+
+use clap::ValueEnum;
+use mem_common::MemHelpers;
+use zisk_common::MEM_BUS_DATA_SIZE;
+use zisk_core::{Mem, ZiskInst, SRC_IND, STORE_IND};
+
+use crate::Emu;
+
+/// Main step slot of the aligned loads, as used by `MemBusHelpers`
+const ALIGNED_LOAD_SLOT: u8 = 2;
+/// Main step slot of the aligned stores, as used by `MemBusHelpers`
+const ALIGNED_STORE_SLOT: u8 = 3;
+
+/// What the emulator does when a RISC-V load or store is misaligned
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum MisalignedPolicy {
+    /// End the emulation with error, without executing it
+    Trap,
+    /// Execute it as the memory state machine does, i.e. by decomposition
+    #[default]
+    Decompose,
+}
+
+/// Returns true if the address is not a multiple of the access width
+#[inline(always)]
+pub fn is_misaligned(addr: u64, width: u64) -> bool {
+    (addr & (width - 1)) != 0
+}
+
+/// Returns the aligned memory bus operations that prove an access of `width` bytes at `addr`,
+/// done at the main step `step`.  `mem_values` contains the content of the one or two aligned
+/// chunks before the access, and `value` the written value, if `is_write`.
+pub fn decompose_mem_access(
+    addr: u64,
+    step: u64,
+    width: u64,
+    is_write: bool,
+    value: u64,
+    mem_values: [u64; 2],
+) -> Vec<[u64; MEM_BUS_DATA_SIZE]> {
+    let (chunk_1, chunk_2) = Mem::required_addresses(addr, width);
+    let chunks = if chunk_1 == chunk_2 { 1 } else { 2 };
+
+    let mut ops = Vec::with_capacity(2 * chunks);
+    for (i, &mem_value) in mem_values.iter().enumerate().take(chunks) {
+        let chunk_addr = chunk_1 + 8 * i as u64;
+        ops.push(MemHelpers::mem_load(
+            chunk_addr as u32,
+            step,
+            ALIGNED_LOAD_SLOT,
+            8,
+            [mem_value, 0],
+        ));
+    }
+    if is_write {
+        for (i, &mem_value) in mem_values.iter().enumerate().take(chunks) {
+            let chunk_addr = chunk_1 + 8 * i as u64;
+            let mut bytes = mem_value.to_le_bytes();
+            for j in 0..width {
+                let byte_addr = addr + j;
+                if (byte_addr >= chunk_addr) && (byte_addr < chunk_addr + 8) {
+                    bytes[(byte_addr - chunk_addr) as usize] = (value >> (8 * j)) as u8;
+                }
+            }
+            ops.push(MemHelpers::mem_write(
+                chunk_addr as u32,
+                step,
+                ALIGNED_STORE_SLOT,
+                8,
+                u64::from_le_bytes(bytes),
+                [0, 0],
+            ));
+        }
+    }
+    ops
+}
+
+impl Emu<'_> {
+    /// Sets the policy applied to the misaligned RISC-V loads and stores
+    pub fn set_misaligned_policy(&mut self, policy: MisalignedPolicy) {
+        self.misaligned_policy = policy;
+        self.update_instrumented();
+    }
+
+    /// Applies the misaligned policy to the indirect accesses of the instruction, once its 'a'
+    /// register has been calculated, and returns true if the emulation must stop before
+    /// executing it
+    #[inline(always)]
+    pub(crate) fn misaligned_stops(&mut self, instruction: &ZiskInst) -> bool {
+        if (self.misaligned_policy == MisalignedPolicy::Decompose)
+            || ((instruction.b_src != SRC_IND) && (instruction.store != STORE_IND))
+        {
+            return false;
+        }
+
+        let inst_ctx = &mut self.ctx.inst_ctx;
+        let sp = |use_sp: bool| if use_sp { inst_ctx.sp } else { 0 };
+        let mut misaligned = false;
+        if instruction.b_src == SRC_IND {
+            let addr = (inst_ctx.a as i64 + instruction.b_offset_imm0 as i64) as u64
+                + sp(instruction.b_use_sp_imm1 != 0);
+            misaligned |= is_misaligned(addr, instruction.ind_width);
+        }
+        if instruction.store == STORE_IND {
+            let addr = (inst_ctx.a as i64 + instruction.store_offset) as u64
+                + sp(instruction.store_use_sp);
+            misaligned |= is_misaligned(addr, instruction.ind_width);
+        }
+
+        if misaligned {
+            inst_ctx.end = true;
+            inst_ctx.error = true;
+        }
+        misaligned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decompose_mem_access() {
+        assert!(!is_misaligned(0x1008, 8) && is_misaligned(0x1006, 4) && !is_misaligned(0x1007, 1));
+
+        // Load of 4 bytes crossing two chunks
+        let ops = decompose_mem_access(0x1006, 10, 4, false, 0, [0x1111, 0x2222]);
+        assert_eq!(ops.len(), 2);
+        assert_eq!((ops[0][1], ops[0][4]), (0x1000, 0x1111));
+        assert_eq!((ops[1][1], ops[1][4]), (0x1008, 0x2222));
+
+        // Store of 2 bytes inside one chunk
+        let ops = decompose_mem_access(0x1003, 10, 2, true, 0xabcd, [0x8877665544332211, 0]);
+        assert_eq!(ops.len(), 2);
+        assert_eq!(ops[1][6], 0x887766abcd332211);
+        assert_eq!(ops[1][2], ops[0][2] + 1);
+
+        // Store of 8 bytes crossing two chunks
+        let ops =
+            decompose_mem_access(0x1004, 10, 8, true, 0x0807060504030201, [u64::MAX, u64::MAX]);
+        assert_eq!(ops.len(), 4);
+        assert_eq!(ops[2][6], 0x04030201ffffffff);
+        assert_eq!(ops[3][6], 0xffffffff08070605);
+    }
+}
@@ -2,7 +2,9 @@
 
 use clap::Parser;
 use std::fmt;
-use zisk_core::DEFAULT_MAX_STEPS_STR;
+use zisk_core::{HashBackend, DEFAULT_MAX_STEPS_STR};
+
+use crate::{EbreakPolicy, MisalignedPolicy};
 
 pub const ZISK_VERSION_MESSAGE: &str = concat!(
     env!("CARGO_PKG_VERSION"),
@@ -93,6 +95,31 @@ pub struct EmuOptions {
     /// Requires option: -X
     #[clap(long, value_name = "COVERAGE", default_value = "false")]
     pub coverage: bool,
+    /// Serves the emulation to gdb through the GDB remote serial protocol on this TCP port.
+    #[clap(long, value_name = "GDB_PORT")]
+    pub gdb: Option<u16>,
+    /// Policy applied to the ebreak instructions, which are executed as a nop by default.
+    #[clap(long, value_enum, value_name = "EBREAK_POLICY")]
+    pub ebreak: Option<EbreakPolicy>,
+    /// Policy applied to the misaligned loads and stores, which are decomposed by default.
+    #[clap(long, value_enum, value_name = "MISALIGNED_POLICY")]
+    pub misaligned: Option<MisalignedPolicy>,
+    /// Track the guest call stack, to print a backtrace if the program ends with error.
+    #[clap(long, default_value = "false")]
+    pub shadow_stack: bool,
+    /// Attribute the steps to the guest regions marked with zisk_region!, and print them.
+    #[clap(long, default_value = "false")]
+    pub regions: bool,
+    /// Accept inputs too big for the input section, readable only through the input page fcall.
+    #[clap(long, default_value = "false")]
+    pub paged_input: bool,
+    /// Forces the SHA-256 backend: software, sha-ni or armv8-crypto.  Detected by default.
+    #[clap(long, value_name = "HASH_BACKEND")]
+    pub hash_backend: Option<HashBackend>,
+    /// Serve the guest ecall syscalls (read, write and hint) on the host, failing if the program
+    /// exits with a non-zero code.  They are not part of the proven execution.
+    #[clap(long, default_value = "false")]
+    pub syscalls: bool,
 }
 
 impl Default for EmuOptions {
@@ -122,6 +149,14 @@ impl Default for EmuOptions {
             legacy_stats: false,
             coverage: false,
             main_name: "main".to_string(),
+            gdb: None,
+            ebreak: None,
+            misaligned: None,
+            shadow_stack: false,
+            regions: false,
+            paged_input: false,
+            hash_backend: None,
+            syscalls: false,
         }
     }
 }
@@ -149,6 +184,14 @@ impl fmt::Display for EmuOptions {
         writeln!(f, "TOP_ROI: {:?}", self.top_roi)?;
         writeln!(f, "ROI_CALLERS: {:?}", self.roi_callers)?;
         writeln!(f, "TOP_ROI_DETAIL: {:?}", self.top_roi_detail)?;
+        writeln!(f, "GDB: {:?}", self.gdb)?;
+        writeln!(f, "EBREAK: {:?}", self.ebreak)?;
+        writeln!(f, "MISALIGNED: {:?}", self.misaligned)?;
+        writeln!(f, "SHADOW_STACK: {:?}", self.shadow_stack)?;
+        writeln!(f, "REGIONS: {:?}", self.regions)?;
+        writeln!(f, "PAGED_INPUT: {:?}", self.paged_input)?;
+        writeln!(f, "HASH_BACKEND: {:?}", self.hash_backend)?;
+        writeln!(f, "SYSCALLS: {:?}", self.syscalls)?;
         Ok(())
     }
 }
@@ -0,0 +1,256 @@
+//! Attribution of steps to the guest regions
+//!
+//! * Guests mark regions of their code with `ziskos::zisk_region!("name", { ... })`, which emits
+//!   a begin and an end marker: `addi x0, reg, mark` hints, executed as nops, with the address of
+//!   the NUL-terminated region name in reg.  The address is the id of the region.
+//! * `RegionProfiler` attributes to every region the steps executed between its markers, as
+//!   counted by the step counter, without the overhead of the full statistics:
+//!   * `steps` includes the steps of the nested regions, counted only once for recursive ones.
+//!   * `self_steps` excludes them.
+//! * An end marker closes the inner regions still open, e.g. left through a panic, and an end
+//!   marker without begin is counted as unmatched.  The regions still open when the program ends
+//!   are closed at the last step.
+//! * The profiler is enabled by `EmuOptions::regions`.  `Emu::region_report()` returns its report,
+//!   sorted by steps, which `ziskemu` prints at the end of the emulation; the emulator itself
+//!   does not print it, since it also runs inside the prover.
+
+use std::{collections::HashMap, fmt};
+
+use zisk_core::{Mem, ZiskInst, SRC_IMM, SRC_MEM, SRC_REG};
+
+use crate::Emu;
+
+/// Immediate of the hint that marks the beginning of a region, as emitted by ziskos
+pub const REGION_BEGIN_MARK: u64 = 0x500;
+/// Immediate of the hint that marks the end of a region, as emitted by ziskos
+pub const REGION_END_MARK: u64 = 0x600;
+/// Maximum length of a region name
+const REGION_NAME_MAX_LEN: u64 = 256;
+
+/// Steps attributed to a region
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RegionStats {
+    pub name: String,
+    /// Number of times the region was entered
+    pub count: u64,
+    /// Steps inside the region, including the nested regions
+    pub steps: u64,
+    /// Steps inside the region, excluding the nested regions
+    pub self_steps: u64,
+}
+
+/// Region entered and not exited yet
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ActiveRegion {
+    index: usize,
+    start_step: u64,
+    /// Steps of the nested regions exited so far
+    nested_steps: u64,
+    /// True if the same region was already active, i.e. a recursive activation
+    recursive: bool,
+}
+
+/// Profiler of the guest regions, see the module documentation
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RegionProfiler {
+    regions: Vec<RegionStats>,
+    /// Index of every region in `regions`, by id
+    indexes: HashMap<u64, usize>,
+    active: Vec<ActiveRegion>,
+    unmatched: u64,
+}
+
+impl RegionProfiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns Some(true) for a begin marker and Some(false) for an end marker
+    #[inline(always)]
+    pub fn region_mark(instruction: &ZiskInst) -> Option<bool> {
+        if (instruction.op != 0)
+            || (instruction.b_src != SRC_IMM)
+            || ((instruction.a_src != SRC_REG) && (instruction.a_src != SRC_MEM))
+        {
+            return None;
+        }
+        match instruction.b_offset_imm0 {
+            REGION_BEGIN_MARK => Some(true),
+            REGION_END_MARK => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Enters the region, reading its name from memory the first time
+    pub fn begin(&mut self, id: u64, step: u64, mem: &Mem) {
+        let index = *self.indexes.entry(id).or_insert_with(|| {
+            self.regions.push(RegionStats { name: read_name(mem, id), ..Default::default() });
+            self.regions.len() - 1
+        });
+        let recursive = self.active.iter().any(|region| region.index == index);
+        self.active.push(ActiveRegion { index, start_step: step, nested_steps: 0, recursive });
+        self.regions[index].count += 1;
+    }
+
+    /// Exits the region, and the inner regions still open
+    pub fn end(&mut self, id: u64, step: u64) {
+        let Some(index) = self.indexes.get(&id) else {
+            self.unmatched += 1;
+            return;
+        };
+        let Some(depth) = self.active.iter().rposition(|region| region.index == *index) else {
+            self.unmatched += 1;
+            return;
+        };
+        while self.active.len() > depth {
+            self.exit(step);
+        }
+    }
+
+    /// Exits the innermost active region
+    fn exit(&mut self, step: u64) {
+        let region = self.active.pop().unwrap();
+        let steps = step - region.start_step;
+        let stats = &mut self.regions[region.index];
+        stats.self_steps += steps - region.nested_steps;
+        if !region.recursive {
+            stats.steps += steps;
+        }
+        if let Some(parent) = self.active.last_mut() {
+            parent.nested_steps += steps;
+        }
+    }
+
+    /// Closes the active regions at the provided step, and returns the report
+    pub fn finish(&mut self, step: u64) -> RegionReport {
+        while !self.active.is_empty() {
+            self.exit(step);
+        }
+        let mut regions = self.regions.clone();
+        regions.sort_by(|a, b| b.steps.cmp(&a.steps).then(a.name.cmp(&b.name)));
+        RegionReport { regions, total_steps: step, unmatched: self.unmatched }
+    }
+}
+
+/// Reads the NUL-terminated name of a region
+fn read_name(mem: &Mem, addr: u64) -> String {
+    let bytes: Vec<u8> = (addr..addr + REGION_NAME_MAX_LEN)
+        .map(|addr| mem.read(addr, 1) as u8)
+        .take_while(|byte| *byte != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Steps attributed to the regions of a run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegionReport {
+    /// Regions, sorted by steps
+    pub regions: Vec<RegionStats>,
+    /// Steps of the whole run
+    pub total_steps: u64,
+    /// Number of end markers without begin
+    pub unmatched: u64,
+}
+
+impl fmt::Display for RegionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "REGIONS (total steps {})", self.total_steps)?;
+        writeln!(f, "{:>14} {:>7} {:>14} {:>10}  NAME", "STEPS", "%", "SELF STEPS", "COUNT")?;
+        for region in &self.regions {
+            let percent = (region.steps as f64 * 100.0) / self.total_steps.max(1) as f64;
+            writeln!(
+                f,
+                "{:>14} {:>6.2}% {:>14} {:>10}  {}",
+                region.steps, percent, region.self_steps, region.count, region.name
+            )?;
+        }
+        if self.unmatched > 0 {
+            writeln!(f, "{} end markers without begin", self.unmatched)?;
+        }
+        Ok(())
+    }
+}
+
+impl Emu<'_> {
+    /// Enables the region profiler, clearing it
+    pub fn enable_region_profiler(&mut self) {
+        self.region_profiler = Some(RegionProfiler::new());
+        self.update_instrumented();
+    }
+
+    /// Closes the active regions at the current step, and returns the report, or None if the
+    /// region profiler is not enabled
+    pub fn region_report(&mut self) -> Option<RegionReport> {
+        let step = self.ctx.inst_ctx.step;
+        self.region_profiler.as_mut().map(|profiler| profiler.finish(step))
+    }
+
+    /// Updates the region profiler, if enabled, after executing the instruction, whose a
+    /// register contains the region id if it is a region marker
+    #[inline(always)]
+    pub(crate) fn track_regions(&mut self, instruction: &ZiskInst) {
+        if let Some(profiler) = &mut self.region_profiler {
+            let inst_ctx = &self.ctx.inst_ctx;
+            match RegionProfiler::region_mark(instruction) {
+                Some(true) => profiler.begin(inst_ctx.a, inst_ctx.step, &inst_ctx.mem),
+                Some(false) => profiler.end(inst_ctx.a, inst_ctx.step),
+                None => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zisk_core::{RAM_ADDR, ROM_ADDR};
+
+    #[test]
+    fn test_region_profiler() {
+        let mut mem = Mem::new();
+        mem.add_write_section(RAM_ADDR, 8);
+        mem.add_read_section(ROM_ADDR, b"outer\0inner\0");
+        let (outer, inner) = (ROM_ADDR, ROM_ADDR + 6);
+        let mut profiler = RegionProfiler::new();
+
+        // outer [0, 100) with inner [10, 30) twice, a recursive outer [40, 50), and an inner
+        // left open at [60, 100)
+        profiler.begin(outer, 0, &mem);
+        for (start, end) in [(10, 20), (25, 35)] {
+            profiler.begin(inner, start, &mem);
+            profiler.end(inner, end);
+        }
+        profiler.begin(outer, 40, &mem);
+        profiler.end(outer, 50);
+        profiler.begin(inner, 60, &mem);
+        profiler.end(outer, 100);
+        profiler.end(inner, 110);
+        profiler.begin(inner, 120, &mem);
+        let report = profiler.finish(130);
+
+        assert_eq!(report.unmatched, 1);
+        assert_eq!(
+            report.regions,
+            [
+                RegionStats { name: "outer".to_string(), count: 2, steps: 100, self_steps: 40 },
+                RegionStats { name: "inner".to_string(), count: 4, steps: 70, self_steps: 70 },
+            ]
+        );
+        let text = report.to_string();
+        let line = text.lines().find(|line| line.ends_with("outer")).unwrap();
+        assert_eq!(
+            line.split_whitespace().collect::<Vec<_>>(),
+            ["100", "76.92%", "40", "2", "outer"]
+        );
+        assert!(text.ends_with("1 end markers without begin\n"));
+
+        let hint = ZiskInst {
+            a_src: SRC_MEM,
+            b_src: SRC_IMM,
+            b_offset_imm0: REGION_END_MARK,
+            ..Default::default()
+        };
+        assert_eq!(RegionProfiler::region_mark(&hint), Some(false));
+        assert_eq!(RegionProfiler::region_mark(&ZiskInst { b_offset_imm0: 4, ..hint }), None);
+    }
+}
@@ -0,0 +1,389 @@
+//! Reversible execution, i.e. time-travel debugging
+//!
+//! * `ReversibleExecution` wraps an emulator and executes it step by step, recording for every
+//!   step the delta required to undo it: the previous values of the state registers, of the
+//!   modified registers, and of the modified memory addresses, as recorded by the memory write
+//!   journal.
+//! * The deltas are kept in a ring of limited capacity, so the execution can be moved backwards up
+//!   to `capacity` steps from the current one using `step_back()` or `seek()`, e.g. to inspect the
+//!   state that led to a failure that only manifests millions of steps in.
+//! * The instrumentation state is undone too: the shadow stack, whose removed frames are
+//!   journaled while recording, and the region profiler, saved by the region marker steps.  The
+//!   watchpoints only depend on the step and its previous registers, so `run_until_watch()`
+//!   triggers them again when an undone step is executed again.
+//! * Writes to MMIO regions are not undone, since their side effects belong to the device.
+
+use std::collections::VecDeque;
+
+use zisk_core::{FcallInstContext, MemWriteRecord, ZiskOperationType, REGS_IN_MAIN_TOTAL_NUMBER};
+
+use crate::{Emu, RegionProfiler, ShadowStackDelta, StopReason};
+
+/// State of the execution that is not stored in memory, before a step is executed
+struct StepState {
+    a: u64,
+    b: u64,
+    c: u64,
+    flag: bool,
+    sp: u64,
+    pc: u64,
+    step: u64,
+    end: bool,
+    error: bool,
+    free_input: u64,
+}
+
+/// Data required to undo one single step
+struct StepDelta {
+    state: StepState,
+    /// Registers modified by this step, with their previous values
+    regs: Vec<(usize, u64)>,
+    /// Memory writes done by this step, with the previous content of the written addresses
+    mem_writes: Vec<MemWriteRecord>,
+    /// Previous fcall context, only stored by the steps that modify it
+    fcall: Option<Box<FcallInstContext>>,
+    /// Changes done to the shadow stack, if enabled
+    shadow_stack: Option<ShadowStackDelta>,
+    /// Previous region profiler, only stored by the region marker steps
+    region_profiler: Option<Box<RegionProfiler>>,
+}
+
+/// Errors returned by `ReversibleExecution::seek()`
+#[derive(Debug, PartialEq, Eq)]
+pub enum SeekError {
+    /// The requested step is older than the oldest recorded step
+    StepNotRecorded { step: u64, oldest_step: u64 },
+    /// The program execution ended before reaching the requested step
+    EndReached { step: u64, last_step: u64 },
+}
+
+/// Emulator wrapper that records the execution deltas to be able to move it backwards
+pub struct ReversibleExecution<'a, 'b> {
+    emu: &'b mut Emu<'a>,
+    history: VecDeque<StepDelta>,
+    capacity: usize,
+}
+
+impl<'a, 'b> ReversibleExecution<'a, 'b> {
+    /// Creates a reversible execution that can undo up to `capacity` steps.
+    /// The emulator context must have been created before calling this function, e.g. using
+    /// `Emu::create_emu_context()`.
+    pub fn new(emu: &'b mut Emu<'a>, capacity: usize) -> Self {
+        emu.ctx.inst_ctx.mem.write_journal = Some(Vec::new());
+        if let Some(stack) = &mut emu.shadow_stack {
+            stack.removed_journal = Some(Vec::new());
+        }
+        Self { emu, history: VecDeque::with_capacity(capacity), capacity }
+    }
+
+    /// Returns the wrapped emulator, e.g. to inspect its registers or memory
+    pub fn emu(&self) -> &Emu<'a> {
+        self.emu
+    }
+
+    /// Returns the current execution step
+    pub fn current_step(&self) -> u64 {
+        self.emu.ctx.inst_ctx.step
+    }
+
+    /// Returns the oldest step the execution can be moved back to
+    pub fn oldest_step(&self) -> u64 {
+        self.history.front().map_or(self.current_step(), |delta| delta.state.step)
+    }
+
+    /// Executes one step, recording its delta; returns false if the program already ended
+    pub fn step(&mut self) -> bool {
+        if self.emu.ctx.inst_ctx.end {
+            return false;
+        }
+        let delta = self.execute();
+        self.record(delta);
+        true
+    }
+
+    /// Executes recorded steps until a watchpoint is triggered, the program ends or the step
+    /// `max_steps` is reached, like `Emu::run_until_watch()`, so that the steps that led to the
+    /// watchpoint can be undone
+    pub fn run_until_watch(&mut self, max_steps: u64) -> StopReason {
+        loop {
+            let inst_ctx = &self.emu.ctx.inst_ctx;
+            if inst_ctx.end {
+                return StopReason::End { error: inst_ctx.error };
+            }
+            if inst_ctx.step >= max_steps {
+                return StopReason::MaxSteps;
+            }
+            let (step, pc) = (inst_ctx.step, inst_ctx.pc);
+            let previous_regs = self.emu.get_regs_array();
+
+            let delta = self.execute();
+            let triggered = self.emu.triggered_watch(&previous_regs, &delta.mem_writes);
+            self.record(delta);
+            if let Some((id, watch)) = triggered {
+                return StopReason::Watchpoint { id, watch, step, pc };
+            }
+        }
+    }
+
+    /// Executes one step of a program that did not end, and returns its delta
+    fn execute(&mut self) -> StepDelta {
+        let inst_ctx = &self.emu.ctx.inst_ctx;
+
+        // Store the state before executing the step
+        let state = StepState {
+            a: inst_ctx.a,
+            b: inst_ctx.b,
+            c: inst_ctx.c,
+            flag: inst_ctx.flag,
+            sp: inst_ctx.sp,
+            pc: inst_ctx.pc,
+            step: inst_ctx.step,
+            end: inst_ctx.end,
+            error: inst_ctx.error,
+            free_input: inst_ctx.mem.free_input,
+        };
+        let previous_regs: [u64; REGS_IN_MAIN_TOTAL_NUMBER] = inst_ctx.regs;
+        let instruction = self.emu.rom.get_instruction(inst_ctx.pc);
+        let fcall = match instruction.op_type {
+            ZiskOperationType::FcallParam
+            | ZiskOperationType::Fcall
+            | ZiskOperationType::FcallGet => Some(Box::new(inst_ctx.fcall.clone())),
+            _ => None,
+        };
+        let mut shadow_stack = self.emu.shadow_stack.as_ref().map(|stack| stack.begin_delta());
+        let region_profiler = if RegionProfiler::region_mark(instruction).is_some() {
+            self.emu.region_profiler.clone().map(Box::new)
+        } else {
+            None
+        };
+
+        self.emu.step_fast();
+
+        if let (Some(stack), Some(delta)) = (&mut self.emu.shadow_stack, &mut shadow_stack) {
+            stack.end_delta(delta);
+        }
+
+        // Collect the modified registers and the journaled memory writes
+        let regs = previous_regs
+            .iter()
+            .enumerate()
+            .filter(|(i, value)| self.emu.ctx.inst_ctx.regs[*i] != **value)
+            .map(|(i, value)| (i, *value))
+            .collect();
+        let mem_writes = self
+            .emu
+            .ctx
+            .inst_ctx
+            .mem
+            .write_journal
+            .as_mut()
+            .map(std::mem::take)
+            .expect("ReversibleExecution::execute() memory write journal was disabled");
+
+        StepDelta { state, regs, mem_writes, fcall, shadow_stack, region_profiler }
+    }
+
+    /// Records the delta of the last executed step
+    fn record(&mut self, delta: StepDelta) {
+        // Drop the oldest delta if the ring is full
+        if self.history.len() == self.capacity {
+            self.history.pop_front();
+        }
+        if self.capacity > 0 {
+            self.history.push_back(delta);
+        }
+    }
+
+    /// Moves the execution up to `n` steps backwards; returns the number of undone steps, which
+    /// can be lower than `n` if the history does not contain enough steps
+    pub fn step_back(&mut self, n: u64) -> u64 {
+        let mut undone = 0;
+        while undone < n {
+            let Some(delta) = self.history.pop_back() else {
+                break;
+            };
+            self.undo(delta);
+            undone += 1;
+        }
+        undone
+    }
+
+    /// Moves the execution to the provided step, either backwards, using the recorded history, or
+    /// forwards, executing the program
+    pub fn seek(&mut self, step: u64) -> Result<(), SeekError> {
+        if step < self.current_step() {
+            let oldest_step = self.oldest_step();
+            if step < oldest_step {
+                return Err(SeekError::StepNotRecorded { step, oldest_step });
+            }
+            self.step_back(self.current_step() - step);
+        }
+        while self.current_step() < step {
+            if !self.step() {
+                return Err(SeekError::EndReached { step, last_step: self.current_step() });
+            }
+        }
+        Ok(())
+    }
+
+    /// Restores the state before the step recorded in the delta
+    fn undo(&mut self, delta: StepDelta) {
+        if let (Some(stack), Some(stack_delta)) = (&mut self.emu.shadow_stack, delta.shadow_stack) {
+            stack.undo(stack_delta);
+        }
+        if let Some(profiler) = delta.region_profiler {
+            self.emu.region_profiler = Some(*profiler);
+        }
+
+        let inst_ctx = &mut self.emu.ctx.inst_ctx;
+
+        // Undo memory writes in reverse order, without journaling them
+        let write_journal = inst_ctx.mem.write_journal.take();
+        for record in delta.mem_writes.iter().rev() {
+            inst_ctx.mem.write_silent(record.addr, record.previous_value, record.width);
+        }
+        inst_ctx.mem.write_journal = write_journal;
+
+        for (i, value) in delta.regs {
+            inst_ctx.regs[i] = value;
+        }
+        if let Some(fcall) = delta.fcall {
+            inst_ctx.fcall = *fcall;
+        }
+
+        let state = delta.state;
+        inst_ctx.a = state.a;
+        inst_ctx.b = state.b;
+        inst_ctx.c = state.c;
+        inst_ctx.flag = state.flag;
+        inst_ctx.sp = state.sp;
+        inst_ctx.pc = state.pc;
+        inst_ctx.step = state.step;
+        inst_ctx.end = state.end;
+        inst_ctx.error = state.error;
+        inst_ctx.mem.free_input = state.free_input;
+    }
+}
+
+impl Drop for ReversibleExecution<'_, '_> {
+    /// Disables the memory write and shadow stack journals, so that the emulator runs at full
+    /// speed again
+    fn drop(&mut self) {
+        self.emu.ctx.inst_ctx.mem.write_journal = None;
+        if let Some(stack) = &mut self.emu.shadow_stack {
+            stack.removed_journal = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zisk_core::{ZiskInst, ZiskInstBuilder, ZiskRom, RAM_ADDR, ROM_ADDR, ROM_ENTRY};
+
+    use super::*;
+    use crate::{ShadowStack, Watch, REGION_BEGIN_MARK, REGION_END_MARK};
+
+    /// Builds an instruction that continues with the next one, unless `build` changes it
+    fn inst(riscv: &str, op: &str, build: impl FnOnce(&mut ZiskInstBuilder)) -> ZiskInst {
+        let mut zib = ZiskInstBuilder::new_from_riscv(0, riscv.to_string());
+        zib.op(op).unwrap();
+        zib.src_a("imm", 0, false);
+        zib.src_b("imm", 0, false);
+        zib.j(4, 4);
+        build(&mut zib);
+        zib.i
+    }
+
+    /// State compared after undoing steps: pc, step, registers, the watched memory, the shadow
+    /// stack and the region profiler
+    type State = (u64, u64, [u64; 32], u64, Option<ShadowStack>, Option<RegionProfiler>);
+
+    fn state(emu: &Emu) -> State {
+        let inst_ctx = &emu.ctx.inst_ctx;
+        (
+            inst_ctx.pc,
+            inst_ctx.step,
+            emu.get_regs_array(),
+            inst_ctx.mem.read(RAM_ADDR, 8),
+            emu.shadow_stack.clone(),
+            emu.region_profiler.clone(),
+        )
+    }
+
+    #[test]
+    fn test_reversible_execution() {
+        // A region that calls a function, which writes the watched memory and returns
+        let name = RAM_ADDR + 0x100;
+        let function = ROM_ENTRY + 20;
+        let rom_entry_instructions = vec![
+            inst("li", "copyb", |zib| {
+                zib.src_b("imm", name, false);
+                zib.store("reg", 10, false, false);
+            }),
+            inst("addi", "flag", |zib| {
+                zib.src_a("reg", 10, false);
+                zib.src_b("imm", REGION_BEGIN_MARK, false);
+            }),
+            inst("jal", "copyb", |zib| {
+                zib.src_b("imm", function, false);
+                zib.set_pc();
+                zib.store_ra("reg", 1, false);
+                zib.j(0, 4);
+            }),
+            inst("addi", "flag", |zib| {
+                zib.src_a("reg", 10, false);
+                zib.src_b("imm", REGION_END_MARK, false);
+            }),
+            inst("end", "flag", |zib| zib.end()),
+            inst("sd", "copyb", |zib| {
+                zib.src_b("imm", 0x2a, false);
+                zib.store("mem", RAM_ADDR as i64, false, false);
+            }),
+            inst("ret", "copyb", |zib| {
+                zib.src_b("reg", 1, false);
+                zib.set_pc();
+                zib.j(0, 4);
+            }),
+        ];
+        let rom =
+            ZiskRom { rom_entry_instructions, min_program_pc: ROM_ADDR, ..Default::default() };
+        let mut emu = Emu::new(&rom);
+        emu.ctx = emu.create_emu_context(Vec::new());
+        emu.ctx.inst_ctx.mem.write(name, u64::from_le_bytes(*b"region\0\0"), 8);
+        emu.enable_shadow_stack(4);
+        emu.enable_region_profiler();
+        let id = emu.add_watchpoint(Watch::MemWrite(RAM_ADDR..RAM_ADDR + 8));
+        let mut execution = ReversibleExecution::new(&mut emu, 16);
+
+        // Run up to the end, recording the state after every step
+        let mut states = vec![state(execution.emu())];
+        while execution.step() {
+            states.push(state(execution.emu()));
+        }
+        assert_eq!(states.len(), 8);
+        assert_ne!(states[1].5, states[2].5);
+        assert_eq!(states[3].4.as_ref().unwrap().frames().count(), 1);
+        assert_eq!(states[5].4.as_ref().unwrap().frames().count(), 0);
+        assert_eq!(states[4].3, 0x2a);
+
+        // Every undone step restores the state before it, including the instrumentation
+        for n in (0..7).rev() {
+            assert_eq!(execution.step_back(1), 1);
+            assert_eq!(state(execution.emu()), states[n]);
+        }
+        assert_eq!(execution.step_back(1), 0);
+
+        // The watchpoint is triggered again when the undone write is executed again
+        let triggered = StopReason::Watchpoint {
+            id,
+            watch: Watch::MemWrite(RAM_ADDR..RAM_ADDR + 8),
+            step: 3,
+            pc: function,
+        };
+        assert_eq!(execution.run_until_watch(100), triggered);
+        assert_eq!(execution.step_back(1), 1);
+        assert_eq!(execution.run_until_watch(100), triggered);
+        assert_eq!(execution.seek(7), Ok(()));
+        assert_eq!(state(execution.emu()), states[7]);
+    }
+}
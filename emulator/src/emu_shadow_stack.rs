@@ -0,0 +1,266 @@
+//! Shadow call stack of the guest
+//!
+//! * Guests are usually built without frame pointers, so their call stack cannot be unwound from
+//!   memory when an execution fails.  `ShadowStack` reconstructs it online instead, from the jumps
+//!   executed by the emulator, using the calling convention heuristics:
+//!   * A jump that links its return address in ra, i.e. `jal ra`, `jalr ra` or `c.jalr`, is a
+//!     call, and pushes a frame.
+//!   * An indirect jump to the return address of a frame, e.g. `ret`, pops that frame and all the
+//!     frames above it, so that the frames skipped by tail calls or unwinds are not kept.
+//! * Only `max_depth` frames are kept; the oldest ones are dropped on deep recursions, and
+//!   counted, so the backtrace shows the innermost frames, which are the interesting ones.
+//! * `Emu::current_backtrace()` returns the backtrace at the current pc.  When the shadow stack is
+//!   requested in `EmuOptions`, `Emu::run()` prints it if the program ends with error, resolving
+//!   the addresses to the ELF functions if the symbols were read.
+
+use std::{collections::VecDeque, fmt};
+
+use zisk_core::{ZiskInst, STORE_REG};
+
+use crate::{Emu, SymbolInfo};
+
+/// Default maximum number of frames kept by the shadow stack
+pub const SHADOW_STACK_MAX_DEPTH: usize = 1024;
+
+/// RISC-V register that holds the return address
+const RA_REG: i64 = 1;
+
+/// Frame of the shadow stack, pushed by a call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShadowFrame {
+    /// pc of the RISC-V call instruction
+    pub call_pc: u64,
+    /// Address of the called function
+    pub function: u64,
+    /// Return address linked by the call
+    pub return_addr: u64,
+    /// Step of the call
+    pub step: u64,
+}
+
+/// Call stack reconstructed from the executed jumps, see the module documentation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShadowStack {
+    frames: VecDeque<ShadowFrame>,
+    max_depth: usize,
+    /// Number of oldest frames dropped because the maximum depth was reached
+    dropped: u64,
+    /// pc of the last executed RISC-V instruction, i.e. of the first Zisk instruction of it
+    riscv_pc: u64,
+    /// Frames removed by the steps, journaled only while a `ReversibleExecution` records them
+    pub(crate) removed_journal: Option<Vec<ShadowFrame>>,
+}
+
+/// Data required to undo the changes done by one step to the shadow stack
+#[derive(Debug)]
+pub(crate) struct ShadowStackDelta {
+    riscv_pc: u64,
+    dropped: u64,
+    len: usize,
+    /// Frames removed by the step: the oldest one, dropped by a call, or the ones popped by a
+    /// return
+    removed: Vec<ShadowFrame>,
+}
+
+impl ShadowStack {
+    pub fn new(max_depth: usize) -> Self {
+        Self {
+            frames: VecDeque::new(),
+            max_depth: max_depth.max(1),
+            dropped: 0,
+            riscv_pc: 0,
+            removed_journal: None,
+        }
+    }
+
+    /// Returns the frames, from the outermost one
+    pub fn frames(&self) -> impl DoubleEndedIterator<Item = &ShadowFrame> {
+        self.frames.iter()
+    }
+
+    /// Updates the stack after an instruction executed at `pc` has jumped to `next_pc`, given the
+    /// value of ra after it
+    #[inline(always)]
+    pub fn on_step(&mut self, instruction: &ZiskInst, pc: u64, next_pc: u64, ra: u64, step: u64) {
+        if instruction.riscv_inst.is_some() {
+            self.riscv_pc = pc;
+        }
+        if instruction.store_ra
+            && (instruction.store == STORE_REG)
+            && (instruction.store_offset == RA_REG)
+        {
+            if self.frames.len() == self.max_depth {
+                let frame = self.frames.pop_front().unwrap();
+                if let Some(journal) = &mut self.removed_journal {
+                    journal.push(frame);
+                }
+                self.dropped += 1;
+            }
+            self.frames.push_back(ShadowFrame {
+                call_pc: self.riscv_pc,
+                function: next_pc,
+                return_addr: ra,
+                step,
+            });
+        } else if instruction.set_pc {
+            if let Some(depth) = self.frames.iter().rposition(|f| f.return_addr == next_pc) {
+                if let Some(journal) = &mut self.removed_journal {
+                    journal.extend(self.frames.range(depth..));
+                }
+                self.frames.truncate(depth);
+            }
+        }
+    }
+
+    /// Returns the state required to undo the next step, to be completed with `end_delta()` after
+    /// executing it
+    pub(crate) fn begin_delta(&self) -> ShadowStackDelta {
+        ShadowStackDelta {
+            riscv_pc: self.riscv_pc,
+            dropped: self.dropped,
+            len: self.frames.len(),
+            removed: Vec::new(),
+        }
+    }
+
+    /// Completes the delta with the frames removed by the step, as journaled
+    pub(crate) fn end_delta(&mut self, delta: &mut ShadowStackDelta) {
+        if let Some(journal) = &mut self.removed_journal {
+            delta.removed = std::mem::take(journal);
+        }
+    }
+
+    /// Restores the stack before the step recorded in the delta
+    pub(crate) fn undo(&mut self, delta: ShadowStackDelta) {
+        // A call pushed one frame, dropping the oldest one if the stack was full, and a return
+        // popped the removed frames
+        let dropped = (self.dropped - delta.dropped) as usize;
+        if self.frames.len() + dropped > delta.len {
+            self.frames.pop_back();
+        }
+        if dropped > 0 {
+            self.frames.push_front(delta.removed[0]);
+        } else {
+            self.frames.extend(delta.removed);
+        }
+        self.dropped = delta.dropped;
+        self.riscv_pc = delta.riscv_pc;
+    }
+
+    /// Returns the backtrace at the provided pc
+    pub fn backtrace(&self, pc: u64) -> Backtrace {
+        Backtrace { pc, frames: self.frames.iter().rev().copied().collect(), dropped: self.dropped }
+    }
+}
+
+/// Backtrace of the guest, as reconstructed by the shadow stack
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backtrace {
+    /// Current pc
+    pub pc: u64,
+    /// Frames, from the innermost one
+    pub frames: Vec<ShadowFrame>,
+    /// Number of outermost frames not kept
+    pub dropped: u64,
+}
+
+impl Backtrace {
+    /// Returns the addresses of the backtrace, i.e. the current pc followed by the call pcs, from
+    /// the innermost frame
+    pub fn addresses(&self) -> impl Iterator<Item = u64> + '_ {
+        std::iter::once(self.pc).chain(self.frames.iter().map(|frame| frame.call_pc))
+    }
+
+    /// Formats the backtrace, resolving every address to the function that contains it
+    pub fn to_text_with_symbols<'a>(
+        &self,
+        functions: impl Iterator<Item = &'a SymbolInfo> + Clone,
+    ) -> String {
+        let mut text = "guest backtrace:".to_string();
+        for (i, addr) in self.addresses().enumerate() {
+            let function = functions
+                .clone()
+                .find(|f| (addr >= f.address) && (addr < f.address + f.size.max(1)))
+                .map(|f| format!("{}+{:#x}", f.name, addr - f.address))
+                .unwrap_or_else(|| "???".to_string());
+            text.push_str(&format!("\n  {i:>2}: {addr:#x} {function}"));
+        }
+        if self.dropped > 0 {
+            text.push_str(&format!("\n  ... {} older frames", self.dropped));
+        }
+        text
+    }
+}
+
+impl fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "guest backtrace:")?;
+        for (i, addr) in self.addresses().enumerate() {
+            write!(f, "\n  {i:>2}: {addr:#x}")?;
+        }
+        if self.dropped > 0 {
+            write!(f, "\n  ... {} older frames", self.dropped)?;
+        }
+        Ok(())
+    }
+}
+
+impl Emu<'_> {
+    /// Enables the shadow stack, keeping up to `max_depth` frames, and clears it
+    pub fn enable_shadow_stack(&mut self, max_depth: usize) {
+        self.shadow_stack = Some(ShadowStack::new(max_depth));
+        self.update_instrumented();
+    }
+
+    /// Returns the backtrace at the current pc, or None if the shadow stack is not enabled
+    pub fn current_backtrace(&self) -> Option<Backtrace> {
+        self.shadow_stack.as_ref().map(|stack| stack.backtrace(self.ctx.inst_ctx.pc))
+    }
+
+    /// Updates the shadow stack, if enabled, after executing the instruction at `pc`
+    #[inline(always)]
+    pub(crate) fn track_shadow_stack(&mut self, instruction: &ZiskInst, pc: u64) {
+        if let Some(stack) = &mut self.shadow_stack {
+            let inst_ctx = &self.ctx.inst_ctx;
+            stack.on_step(instruction, pc, inst_ctx.pc, inst_ctx.regs[1], inst_ctx.step);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shadow_stack() {
+        let riscv =
+            |inst: &str| ZiskInst { riscv_inst: Some(inst.to_string()), ..Default::default() };
+        let call =
+            ZiskInst { store_ra: true, store: STORE_REG, store_offset: RA_REG, ..riscv("jal") };
+        let ret = ZiskInst { set_pc: true, ..riscv("c.jr") };
+        let mut stack = ShadowStack::new(2);
+
+        // main calls f, which calls g, which tail calls h, which returns to f
+        stack.on_step(&call, 0x1000, 0x2000, 0x1004, 1);
+        stack.on_step(&call, 0x2010, 0x3000, 0x2014, 2);
+        stack.on_step(&ZiskInst { set_pc: true, ..riscv("jalr") }, 0x3008, 0x4000, 0x2014, 3);
+        let backtrace = stack.backtrace(0x4004);
+        assert_eq!(backtrace.addresses().collect::<Vec<_>>(), [0x4004, 0x2010, 0x1000]);
+        stack.on_step(&ret, 0x4010, 0x2014, 0x2014, 4);
+        assert_eq!(stack.backtrace(0x2014).addresses().collect::<Vec<_>>(), [0x2014, 0x1000]);
+
+        // Recursion deeper than the maximum depth
+        stack.on_step(&call, 0x2020, 0x2000, 0x2024, 5);
+        stack.on_step(&call, 0x2020, 0x2000, 0x2024, 6);
+        let backtrace = stack.backtrace(0x2000);
+        assert_eq!(backtrace.dropped, 1);
+        let functions = [SymbolInfo { name: "f".to_string(), address: 0x2000, size: 0x40 }];
+        assert_eq!(
+            backtrace.to_text_with_symbols(functions.iter()),
+            "guest backtrace:\n   0: 0x2000 f+0x0\n   1: 0x2020 f+0x20\n   2: 0x2020 f+0x20\n  \
+             ... 1 older frames"
+        );
+        stack.on_step(&ret, 0x2030, 0x2024, 0x2024, 7);
+        assert_eq!(stack.frames().map(|frame| frame.step).collect::<Vec<_>>(), [5]);
+    }
+}
@@ -0,0 +1,225 @@
+//! Host-side ECALL syscall handler
+//!
+//! * Zisk transpiles `ecall` into a jump to the trap handler stored in the MTVEC CSR, which ends
+//!   the program if a7 is the exit syscall, or returns to the caller otherwise.  Any other
+//!   syscall is silently ignored by the trap handler.
+//! * `EmuSyscalls` intercepts the execution when it reaches the trap handler and serves the rest
+//!   of the guest syscall surface on the host, so that guest binaries using it can be executed by
+//!   the emulator as a fast smoke test before proving:
+//!   - `read`: reads up to a2 bytes of the input data into the buffer at a1
+//!   - `write`: writes a2 bytes of the buffer at a1 to the output (fd 1) or to stderr (fd 2)
+//!   - `hint`: stores the a1 bytes of the buffer at a0 as a hint
+//!   - `exit`: left to the trap handler, which ends the program
+//! * The syscall numbers are the ones of the ziskos ecall ABI, see `ziskos::syscalls::ECALL_*`.
+//! * `ziskemu --syscalls` runs a program serving its syscalls, see `Emu::run_to_exit()`.
+//! * The syscall return value is stored in a0; unknown syscalls return -ENOSYS.
+//! * These syscalls are not part of the proven execution, so a program that needs them can be
+//!   emulated but not proven.
+//! * Every syscall is checked against `EmuSyscalls::policy` before being served, see
+//!   `SyscallPolicy`; the default policy allows them all.
+
+use std::io::Write;
+
+use zisk_core::MTVEC;
+use ziskos::syscalls::{ECALL_EXIT_ID, ECALL_HINT_ID, ECALL_READ_ID, ECALL_WRITE_ID};
+
+use crate::{Emu, SyscallDenyAction, SyscallPolicy, SyscallRecord, SYSCALL_ARGS};
+
+/// Syscall to read from the input data
+pub const SYSCALL_READ: u64 = ECALL_READ_ID;
+/// Syscall to write to the output or to stderr
+pub const SYSCALL_WRITE: u64 = ECALL_WRITE_ID;
+/// Syscall to end the program, handled by the Zisk trap handler
+pub const SYSCALL_EXIT: u64 = ECALL_EXIT_ID;
+/// Syscall to emit a hint
+pub const SYSCALL_HINT: u64 = ECALL_HINT_ID;
+
+/// Value returned in a0 by unknown syscalls, i.e. -ENOSYS
+const ENOSYS_RESULT: u64 = -38i64 as u64;
+/// Value returned in a0 by the syscalls denied by the policy, i.e. -EPERM
+const EPERM_RESULT: u64 = -1i64 as u64;
+
+/// Register indices of the RISC-V ABI arguments
+const REG_RA: usize = 1;
+const REG_A0: usize = 10;
+const REG_A1: usize = 11;
+const REG_A2: usize = 12;
+const REG_A7: usize = 17;
+
+/// Host-side state of the ECALL syscalls
+#[derive(Debug, Default)]
+pub struct EmuSyscalls {
+    /// Input data served by the read syscall
+    pub input: Vec<u8>,
+    /// Position of the next byte of input data to read
+    pub input_pos: usize,
+    /// Data written to fd 1
+    pub output: Vec<u8>,
+    /// Hints emitted by the program, in order
+    pub hints: Vec<Vec<u8>>,
+    /// Number of syscalls served
+    pub syscalls: u64,
+    /// Policy deciding which syscalls are served
+    pub policy: SyscallPolicy,
+    /// Syscalls requested by the program, in order, if the policy traces them
+    pub trace: Vec<SyscallRecord>,
+    /// Syscalls denied by the policy, in order
+    pub violations: Vec<SyscallRecord>,
+}
+
+impl EmuSyscalls {
+    /// Creates a syscall handler serving the provided input data
+    pub fn new(input: Vec<u8>) -> Self {
+        Self { input, ..Default::default() }
+    }
+
+    /// Sets the policy deciding which syscalls are served
+    pub fn with_policy(mut self, policy: SyscallPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Executes one step of the emulation, serving the syscall if the current pc is the trap
+    /// handler; returns false if the program already ended
+    pub fn step(&mut self, emu: &mut Emu) -> bool {
+        if emu.ctx.inst_ctx.end {
+            return false;
+        }
+        let inst_ctx = &emu.ctx.inst_ctx;
+        let trap_handler = inst_ctx.mem.read(MTVEC, 8);
+        if (inst_ctx.pc == trap_handler) && (inst_ctx.regs[REG_A7] != SYSCALL_EXIT) {
+            self.serve(emu);
+        } else {
+            emu.step_fast();
+        }
+        true
+    }
+
+    /// Runs the program up to its end, or up to `max_steps` steps, serving its syscalls
+    pub fn run(&mut self, emu: &mut Emu, max_steps: u64) {
+        while emu.ctx.inst_ctx.step < max_steps && self.step(emu) {}
+    }
+
+    /// Serves the syscall requested in a7, and returns to the instruction next to the ecall
+    fn serve(&mut self, emu: &mut Emu) {
+        let inst_ctx = &mut emu.ctx.inst_ctx;
+        let regs = inst_ctx.regs;
+
+        // Check the syscall against the policy; the ecall stored its next pc in ra
+        let mut args = [0u64; SYSCALL_ARGS];
+        args.copy_from_slice(&regs[REG_A0..REG_A0 + SYSCALL_ARGS]);
+        let record = SyscallRecord {
+            step: inst_ctx.step,
+            pc: regs[REG_RA].wrapping_sub(4),
+            number: regs[REG_A7],
+            args,
+            allowed: self.policy.is_allowed(regs[REG_A7], &args),
+        };
+        if self.policy.trace {
+            self.trace.push(record);
+        }
+        if !record.allowed {
+            eprintln!("EmuSyscalls: {record}");
+            self.violations.push(record);
+            if self.policy.deny_action == SyscallDenyAction::Trap {
+                inst_ctx.end = true;
+                inst_ctx.error = true;
+                return;
+            }
+        }
+
+        let result = match regs[REG_A7] {
+            _ if !record.allowed => EPERM_RESULT,
+            SYSCALL_READ => {
+                let len = (regs[REG_A2] as usize).min(self.input.len() - self.input_pos);
+                for i in 0..len {
+                    inst_ctx.mem.write(
+                        regs[REG_A1] + i as u64,
+                        self.input[self.input_pos + i] as u64,
+                        1,
+                    );
+                }
+                self.input_pos += len;
+                len as u64
+            }
+            SYSCALL_WRITE => {
+                let data = read_buffer(emu, regs[REG_A1], regs[REG_A2]);
+                match regs[REG_A0] {
+                    1 => self.output.extend_from_slice(&data),
+                    2 => {
+                        let _ = std::io::stderr().write_all(&data);
+                    }
+                    _ => {}
+                }
+                regs[REG_A2]
+            }
+            SYSCALL_HINT => {
+                self.hints.push(read_buffer(emu, regs[REG_A0], regs[REG_A1]));
+                0
+            }
+            _ => ENOSYS_RESULT,
+        };
+        self.syscalls += 1;
+
+        // Return to the caller, as the trap handler would do
+        let inst_ctx = &mut emu.ctx.inst_ctx;
+        inst_ctx.regs[REG_A0] = result;
+        inst_ctx.pc = inst_ctx.regs[REG_RA];
+        inst_ctx.step += 1;
+    }
+}
+
+/// Reads a buffer of guest memory
+fn read_buffer(emu: &Emu, addr: u64, len: u64) -> Vec<u8> {
+    (0..len).map(|i| emu.ctx.inst_ctx.mem.read(addr + i, 1) as u8).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zisk_core::{ZiskRom, RAM_ADDR};
+
+    /// Requests the syscall `number` from the instruction at 0x1000, and serves it
+    fn serve(syscalls: &mut EmuSyscalls, emu: &mut Emu, number: u64, args: [u64; 3]) -> u64 {
+        let regs = &mut emu.ctx.inst_ctx.regs;
+        regs[REG_A7] = number;
+        regs[REG_A0..=REG_A2].copy_from_slice(&args);
+        regs[REG_RA] = 0x1004;
+        syscalls.serve(emu);
+        assert_eq!(emu.ctx.inst_ctx.pc, 0x1004);
+        emu.ctx.inst_ctx.regs[REG_A0]
+    }
+
+    #[test]
+    fn test_syscalls() {
+        // The syscall table is the ziskos ecall ABI
+        assert_eq!([SYSCALL_READ, SYSCALL_WRITE, SYSCALL_EXIT, SYSCALL_HINT], [63, 64, 93, 0x850]);
+
+        let rom = ZiskRom::default();
+        let mut emu = Emu::new(&rom);
+        emu.ctx = emu.create_emu_context(Vec::new());
+        let mut syscalls = EmuSyscalls::new(vec![1, 2, 3, 4, 5]);
+        let buffer = RAM_ADDR + 0x1000;
+
+        // Reads are bounded by the remaining input data
+        assert_eq!(serve(&mut syscalls, &mut emu, SYSCALL_READ, [0, buffer, 3]), 3);
+        assert_eq!(read_buffer(&emu, buffer, 3), [1, 2, 3]);
+        assert_eq!(serve(&mut syscalls, &mut emu, SYSCALL_READ, [0, buffer, 8]), 2);
+        assert_eq!(read_buffer(&emu, buffer, 2), [4, 5]);
+        assert_eq!(serve(&mut syscalls, &mut emu, SYSCALL_READ, [0, buffer, 8]), 0);
+
+        // Writes to fd 1 go to the output, and hints are kept in order
+        assert_eq!(serve(&mut syscalls, &mut emu, SYSCALL_WRITE, [1, buffer, 2]), 2);
+        assert_eq!(syscalls.output, [4, 5]);
+        assert_eq!(serve(&mut syscalls, &mut emu, SYSCALL_HINT, [buffer, 1, 0]), 0);
+        assert_eq!(syscalls.hints, [vec![4]]);
+
+        // Unknown syscalls fail, and the denied ones too
+        assert_eq!(serve(&mut syscalls, &mut emu, 0x123, [0; 3]), ENOSYS_RESULT);
+        assert_eq!(syscalls.syscalls, 6);
+        syscalls.policy = SyscallPolicy::deny_all();
+        assert_eq!(serve(&mut syscalls, &mut emu, SYSCALL_HINT, [buffer, 1, 0]), EPERM_RESULT);
+        assert_eq!(syscalls.hints.len(), 1);
+        assert_eq!(syscalls.violations.len(), 1);
+    }
+}
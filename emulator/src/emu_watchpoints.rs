@@ -0,0 +1,175 @@
+//! Watchpoints on memory ranges and register values
+//!
+//! * `Emu::add_watchpoint()` registers a condition to be checked after every step executed by
+//!   `Emu::run_until_watch()`, which pauses the execution as soon as one of them is triggered and
+//!   returns the `StopReason`, so the exact step where a guest value goes wrong can be found
+//!   without dumping a full trace.
+//! * `Watch::MemWrite` is triggered by any write overlapping the address range, including the
+//!   writes of the precompiles, as recorded by the memory write journal; writes to MMIO regions
+//!   are not journaled, so they cannot be watched.
+//! * `Watch::RegEquals` is triggered when a RISC-V register takes the provided value, i.e. when a
+//!   step changes it from a different value, so it does not fire again while the value is kept.
+//! * The execution is paused after the triggering step, so the context already contains its
+//!   results, and it can be resumed calling `Emu::run_until_watch()` again.
+
+use std::{fmt, ops::Range};
+
+use zisk_core::MemWriteRecord;
+
+use crate::Emu;
+
+/// Condition that pauses the execution
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Watch {
+    /// Any write overlapping the address range
+    MemWrite(Range<u64>),
+    /// A RISC-V register, from 0 to 31, taking the value
+    RegEquals(usize, u64),
+}
+
+impl Watch {
+    /// Returns true if the memory write overlaps the watched range
+    pub fn matches_write(&self, addr: u64, width: u64) -> bool {
+        match self {
+            Watch::MemWrite(range) => (addr < range.end) && (range.start < addr + width),
+            Watch::RegEquals(..) => false,
+        }
+    }
+}
+
+impl fmt::Display for Watch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Watch::MemWrite(range) => write!(f, "write [{:#x}, {:#x})", range.start, range.end),
+            Watch::RegEquals(reg, value) => write!(f, "x{reg} == {value:#x}"),
+        }
+    }
+}
+
+/// Reason why `Emu::run_until_watch()` paused the execution
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    /// A watchpoint was triggered by the step executed at pc
+    Watchpoint { id: usize, watch: Watch, step: u64, pc: u64 },
+    /// The program execution ended, with or without error
+    End { error: bool },
+    /// The maximum number of steps was reached
+    MaxSteps,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::Watchpoint { id, watch, step, pc } => {
+                write!(f, "watchpoint {id} ({watch}) triggered at step={step} pc={pc:#x}")
+            }
+            StopReason::End { error } => write!(f, "program ended with error={error}"),
+            StopReason::MaxSteps => write!(f, "maximum number of steps reached"),
+        }
+    }
+}
+
+impl Emu<'_> {
+    /// Registers a watchpoint, returning its id
+    pub fn add_watchpoint(&mut self, watch: Watch) -> usize {
+        if let Watch::RegEquals(reg, _) = watch {
+            assert!(reg < 32, "Emu::add_watchpoint() invalid register x{reg}");
+        }
+        self.watchpoints.push(Some(watch));
+        self.update_instrumented();
+        self.watchpoints.len() - 1
+    }
+
+    /// Unregisters a watchpoint, returning it if it was registered
+    pub fn remove_watchpoint(&mut self, id: usize) -> Option<Watch> {
+        let watch = self.watchpoints.get_mut(id).and_then(Option::take);
+        self.update_instrumented();
+        watch
+    }
+
+    /// Executes steps until a watchpoint is triggered, the program ends or the step `max_steps` is
+    /// reached
+    pub fn run_until_watch(&mut self, max_steps: u64) -> StopReason {
+        // Use a journal of our own, keeping the previous one, if any, untouched
+        let previous_journal = self.ctx.inst_ctx.mem.write_journal.replace(Vec::new());
+        let reason = loop {
+            if self.ctx.inst_ctx.end {
+                break StopReason::End { error: self.ctx.inst_ctx.error };
+            }
+            if self.ctx.inst_ctx.step >= max_steps {
+                break StopReason::MaxSteps;
+            }
+            let (step, pc) = (self.ctx.inst_ctx.step, self.ctx.inst_ctx.pc);
+            let previous_regs = self.get_regs_array();
+
+            self.step_fast();
+
+            let writes = self.ctx.inst_ctx.mem.write_journal.as_mut().map(std::mem::take);
+            if let Some((id, watch)) =
+                self.triggered_watch(&previous_regs, &writes.unwrap_or_default())
+            {
+                break StopReason::Watchpoint { id, watch, step, pc };
+            }
+        };
+        self.ctx.inst_ctx.mem.write_journal = previous_journal;
+        reason
+    }
+
+    /// Returns the first watchpoint triggered by a step, given the registers before it and the
+    /// memory writes done by it
+    pub(crate) fn triggered_watch(
+        &self,
+        previous_regs: &[u64; 32],
+        writes: &[MemWriteRecord],
+    ) -> Option<(usize, Watch)> {
+        let triggered = |watch: &Watch| match *watch {
+            Watch::MemWrite(_) => writes.iter().any(|w| watch.matches_write(w.addr, w.width)),
+            Watch::RegEquals(reg, value) => {
+                (self.get_reg(reg) == value) && (previous_regs[reg] != value)
+            }
+        };
+        self.watchpoints.iter().enumerate().find_map(|(id, watch)| {
+            watch.as_ref().filter(|watch| triggered(watch)).map(|watch| (id, watch.clone()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watch_matches_write() {
+        let watch = Watch::MemWrite(0xa0001000..0xa0001010);
+        assert!(watch.matches_write(0xa0001000, 1) && watch.matches_write(0xa000100f, 1));
+        assert!(watch.matches_write(0xa0000ffc, 8) && watch.matches_write(0xa000100c, 8));
+        assert!(!watch.matches_write(0xa0000ff8, 8) && !watch.matches_write(0xa0001010, 8));
+        assert!(!Watch::RegEquals(10, 0xa0001000).matches_write(0xa0001000, 8));
+        assert_eq!(watch.to_string(), "write [0xa0001000, 0xa0001010)");
+
+        let reason = StopReason::Watchpoint {
+            id: 1,
+            watch: Watch::RegEquals(10, 0x2a),
+            step: 100,
+            pc: 0x80000010,
+        };
+        assert_eq!(
+            reason.to_string(),
+            "watchpoint 1 (x10 == 0x2a) triggered at step=100 pc=0x80000010"
+        );
+    }
+
+    #[test]
+    fn test_watchpoints_instrument_steps() {
+        // The steps only pay for the checks while something is enabled
+        let rom = zisk_core::ZiskRom::default();
+        let mut emu = Emu::new(&rom);
+        assert!(!emu.instrumented && !emu.ctx.inst_ctx.mem.defer_code_writes);
+        let id = emu.add_watchpoint(Watch::RegEquals(10, 0x2a));
+        assert!(emu.instrumented && emu.ctx.inst_ctx.mem.defer_code_writes);
+        assert_eq!(emu.remove_watchpoint(id), Some(Watch::RegEquals(10, 0x2a)));
+        assert!(!emu.instrumented);
+        emu.enable_shadow_stack(4);
+        assert!(emu.instrumented);
+    }
+}
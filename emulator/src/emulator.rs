@@ -15,18 +15,19 @@
 //!             Emu::run()
 //! ```
 
-use crate::{Emu, EmuOptions, ErrWrongArguments, ParEmuOptions, ZiskEmulatorErr};
+use crate::{run_gdb_server, Emu, EmuOptions, ErrWrongArguments, ParEmuOptions, ZiskEmulatorErr};
 
 use data_bus::DataBusTrait;
 use fields::PrimeField;
 use std::{
     fs,
+    io::{self, Write},
     path::{Path, PathBuf},
     time::Instant,
 };
 use sysinfo::System;
 use zisk_common::EmuTrace;
-use zisk_core::{Riscv2zisk, ZiskRom};
+use zisk_core::{force_backend, hash_backends, Riscv2zisk, ZiskRom};
 
 pub trait Emulator {
     fn emulate(
@@ -114,14 +115,42 @@ impl ZiskEmulator {
             println!("process_rom() rom size={} inputs size={}", rom.insts.len(), inputs.len());
         }
 
+        // Force the requested SHA-256 backend, if any, to reproduce the runs of other hosts
+        if options.hash_backend.is_some() {
+            force_backend(options.hash_backend).map_err(ZiskEmulatorErr::Unknown)?;
+        }
+
         // Create a emulator instance with the Zisk rom
         let mut emu = Emu::new(rom);
 
+        // Serve the emulation to gdb, if requested, instead of running it
+        if let Some(port) = options.gdb {
+            emu.ctx = emu.create_paged_emu_context(inputs.to_owned(), options.paged_input);
+            run_gdb_server(port, &mut emu).map_err(|e| ZiskEmulatorErr::Unknown(e.to_string()))?;
+
+            // The program can be killed from gdb before reaching its end
+            return Ok(if emu.terminated() { emu.get_output_8() } else { Vec::new() });
+        }
+
         // Get the current time, to be used to calculate the metrics
         let start = Instant::now();
 
-        // Run the emulation, using the input and the options
-        emu.run(inputs.to_owned(), options, callback);
+        // Run the emulation, using the input and the options, serving the guest syscalls on the
+        // host if requested
+        if options.syscalls {
+            let result = emu.run_to_exit(inputs.to_owned(), options.max_steps)?;
+            io::stdout()
+                .write_all(&result.stdout)
+                .map_err(|e| ZiskEmulatorErr::Unknown(e.to_string()))?;
+            if result.exit_code != 0 {
+                return Err(ZiskEmulatorErr::Unknown(format!(
+                    "program exited with code {}",
+                    result.exit_code
+                )));
+            }
+        } else {
+            emu.run(inputs.to_owned(), options, callback);
+        }
 
         // Check that the emulation completed, either successfully or not, but it must reach the end
         // of the program
@@ -132,6 +161,11 @@ impl ZiskEmulator {
         // Store the duration of the emulation process as a difference vs. the start time
         let duration = start.elapsed();
 
+        // Print the steps of the guest regions, if requested
+        if let Some(report) = emu.region_report() {
+            println!("{report}");
+        }
+
         // Log performance metrics
         if options.log_metrics {
             let secs = duration.as_secs_f64();
@@ -144,7 +178,8 @@ impl ZiskEmulator {
 
             let clocks_per_step = cpu_frequency / tp;
             println!(
-                "process_rom() steps={steps} duration={secs:.4} tp={tp:.4} Msteps/s freq={cpu_frequency:.4} {clocks_per_step:.4} clocks/step"
+                "process_rom() steps={steps} duration={secs:.4} tp={tp:.4} Msteps/s freq={cpu_frequency:.4} {clocks_per_step:.4} clocks/step hash backends: {}",
+                hash_backends()
             );
         }
 
@@ -0,0 +1,125 @@
+//! Structured result of a complete execution
+//!
+//! * `Emu::run_to_exit()` runs the program up to its end, serving its syscalls with
+//!   `EmuSyscalls`, and returns an `ExecutionResult`, so that testing frameworks can assert
+//!   directly on the guest outputs instead of scraping memory.
+//! * The exit code is the a0 argument of the exit syscall (93), as in the RISC-V Linux ABI, so a
+//!   guest can end early with an input-dependent code by issuing the syscall itself.  It is 0 if
+//!   the program ended without reaching the exit syscall.
+//! * The output bytes are the public outputs written by the guest, i.e. the output region as
+//!   returned by `Emu::get_output_8()`; the data written to fd 1 is returned apart in `stdout`.
+//! * The final state hash is the SHA-256 of the pc, the registers and the read-write memory, so
+//!   two executions of the same program and input can be checked to be identical.
+
+use sha2::{Digest, Sha256};
+use zisk_core::{MTVEC, REGS_IN_MAIN_TOTAL_NUMBER};
+
+use crate::{Emu, EmuSyscalls, ZiskEmulatorErr, SYSCALL_EXIT};
+
+/// Register indices of the exit syscall arguments
+const REG_A0: usize = 10;
+const REG_A7: usize = 17;
+
+/// Result of an execution that reached the end of the program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionResult {
+    /// Exit code passed in a0 to the exit syscall, or 0 if it was not reached
+    pub exit_code: i32,
+    /// Public outputs of the program
+    pub output_bytes: Vec<u8>,
+    /// Data written by the program to fd 1
+    pub stdout: Vec<u8>,
+    /// Number of executed steps
+    pub steps: u64,
+    /// SHA-256 of the final pc, registers and read-write memory
+    pub final_state_hash: [u8; 32],
+}
+
+/// Returns the SHA-256 of an execution state: the pc, the registers and the read-write memory
+/// starting at `rw_start`
+pub fn state_hash(
+    pc: u64,
+    regs: &[u64; REGS_IN_MAIN_TOTAL_NUMBER],
+    rw_start: u64,
+    rw_memory: &[u8],
+) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(pc.to_le_bytes());
+    for reg in regs {
+        hasher.update(reg.to_le_bytes());
+    }
+    hasher.update(rw_start.to_le_bytes());
+    hasher.update((rw_memory.len() as u64).to_le_bytes());
+    hasher.update(rw_memory);
+    hasher.finalize().into()
+}
+
+impl Emu<'_> {
+    /// Runs the program with the provided inputs up to its end, or up to `max_steps` steps,
+    /// serving its syscalls, and returns the structured result of the execution
+    pub fn run_to_exit(
+        &mut self,
+        inputs: Vec<u8>,
+        max_steps: u64,
+    ) -> Result<ExecutionResult, ZiskEmulatorErr> {
+        self.ctx = self.create_emu_context(inputs.clone());
+        let mut syscalls = EmuSyscalls::new(inputs);
+
+        // Record the exit code when the exit syscall reaches the trap handler, since the handler
+        // is free to overwrite a0 before ending the program
+        let mut exit_code = 0;
+        while !self.ctx.inst_ctx.end && (self.ctx.inst_ctx.step < max_steps) {
+            let inst_ctx = &self.ctx.inst_ctx;
+            if (inst_ctx.pc == inst_ctx.mem.read(MTVEC, 8))
+                && (inst_ctx.regs[REG_A7] == SYSCALL_EXIT)
+            {
+                exit_code = inst_ctx.regs[REG_A0] as i32;
+            }
+            syscalls.step(self);
+        }
+
+        let inst_ctx = &self.ctx.inst_ctx;
+        if !inst_ctx.end {
+            return Err(ZiskEmulatorErr::EmulationNoCompleted);
+        }
+        if inst_ctx.error {
+            return Err(ZiskEmulatorErr::Unknown(format!(
+                "execution ended with error at step={} pc=0x{:x}",
+                inst_ctx.step, inst_ctx.pc
+            )));
+        }
+
+        let rw_section = &inst_ctx.mem.write_section;
+        Ok(ExecutionResult {
+            exit_code,
+            output_bytes: self.get_output_8(),
+            stdout: syscalls.output,
+            steps: inst_ctx.step,
+            final_state_hash: state_hash(
+                inst_ctx.pc,
+                &inst_ctx.regs,
+                rw_section.start,
+                &rw_section.buffer,
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_hash() {
+        let mut regs = [0u64; REGS_IN_MAIN_TOTAL_NUMBER];
+        let memory = [1u8, 2, 3, 4];
+        let hash = state_hash(0x1000, &regs, 0xa0000000, &memory);
+        assert_eq!(hash, state_hash(0x1000, &regs, 0xa0000000, &memory));
+
+        // Any difference in the pc, the registers or the memory changes the hash
+        assert_ne!(hash, state_hash(0x1004, &regs, 0xa0000000, &memory));
+        assert_ne!(hash, state_hash(0x1000, &regs, 0xa0000000, &memory[..3]));
+        regs[REG_A0] = 1;
+        assert_ne!(hash, state_hash(0x1000, &regs, 0xa0000000, &memory));
+    }
+}
@@ -0,0 +1,519 @@
+//! GDB remote serial protocol (RSP) server
+//!
+//! Exposes an emulator instance through a minimal implementation of the GDB remote serial
+//! protocol, so that guest programs can be debugged with gdb under the exact transpilation and
+//! execution semantics of the Zisk emulator, instead of under qemu, whose behavior differs in some
+//! corner cases.
+//!
+//! ```text
+//! $ ziskemu -e program.elf -i input.bin --gdb 9000
+//! $ riscv64-unknown-elf-gdb program.elf -ex "target remote :9000"
+//! ```
+//!
+//! * Every RISC-V instruction is transpiled into one or more Zisk instructions, so execution is
+//!   only stopped at RISC-V instruction boundaries, i.e. at Zisk instructions that were generated
+//!   as the first one of a RISC-V instruction.
+//! * The registers exposed to gdb are the 32 RISC-V integer registers plus the pc, as described by
+//!   the target description returned through `qXfer:features:read`.
+//! * Supported requests: `?`, `g`, `G`, `p`, `P`, `m`, `M`, `c`, `s`, `Z0`/`z0`, `Z1`/`z1`, `D`,
+//!   `k` and the `q` queries required to attach.  Execution can be interrupted with Ctrl-C.
+
+use std::{
+    collections::HashSet,
+    io::{self, ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+};
+
+use riscv::RiscVRegisters;
+use zisk_core::{Mem, REG_FIRST};
+
+use crate::Emu;
+
+/// Maximum packet size accepted by this server, reported to gdb in the `qSupported` response
+const GDB_PACKET_SIZE: usize = 0x4000;
+/// Number of steps executed between two checks of the Ctrl-C interruption request
+const GDB_INTERRUPT_CHECK_STEPS: u64 = 0x10000;
+/// Register number of the pc in the target description
+const GDB_PC_REGNUM: usize = 32;
+/// Number of bytes of the register file mapped at REG_FIRST
+const GDB_REGS_SIZE: u64 = 32 * 8;
+
+/// Reason why the execution was stopped, as reported to gdb
+enum StopReason {
+    /// Stopped by a breakpoint, a single step or an interruption
+    Trap,
+    /// The program execution ended, with or without error
+    Exited(bool),
+}
+
+/// GDB server state, including the debugged emulator and the active breakpoints
+struct GdbServer<'a, 'b> {
+    emu: &'b mut Emu<'a>,
+    breakpoints: HashSet<u64>,
+}
+
+/// Accepts one gdb connection on the provided TCP port and serves it until gdb detaches, kills the
+/// program or closes the connection.
+/// The emulator context must have been created before calling this function, e.g. using
+/// `Emu::create_emu_context()`.  Execution is advanced up to the first RISC-V instruction before
+/// accepting the connection.
+pub fn run_gdb_server(port: u16, emu: &mut Emu) -> io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    println!("run_gdb_server() waiting for gdb connection on port {port}");
+    let (mut stream, address) = listener.accept()?;
+    println!("run_gdb_server() gdb connected from {address}");
+    stream.set_nodelay(true)?;
+
+    let mut server = GdbServer { emu, breakpoints: HashSet::new() };
+
+    // Skip the BIOS setup instructions, which have no RISC-V counterpart
+    if !server.emu.is_riscv_boundary() {
+        server.step();
+    }
+
+    while let Some(packet) = read_packet(&mut stream)? {
+        let (response, keep_serving) = server.handle_packet(&packet, &mut stream)?;
+        if let Some(response) = response {
+            write_packet(&mut stream, &response)?;
+        }
+        if !keep_serving {
+            break;
+        }
+    }
+
+    println!("run_gdb_server() gdb disconnected");
+    Ok(())
+}
+
+impl GdbServer<'_, '_> {
+    /// Handles a gdb request and returns the response to send, if any, and whether the server must
+    /// keep serving requests
+    fn handle_packet(
+        &mut self,
+        packet: &str,
+        stream: &mut TcpStream,
+    ) -> io::Result<(Option<String>, bool)> {
+        let (command, args) = packet.split_at(packet.len().min(1));
+        let response = match command {
+            "?" => "S05".to_string(),
+            "g" => self.read_registers(),
+            "G" => self.write_registers(args),
+            "p" => self.read_register(args),
+            "P" => self.write_register(args),
+            "m" => self.read_memory(args),
+            "M" => self.write_memory(args),
+            "c" => stop_reply(self.resume(stream)?),
+            "s" => stop_reply(self.step()),
+            "Z" => self.set_breakpoint(args, true),
+            "z" => self.set_breakpoint(args, false),
+            "H" => "OK".to_string(),
+            "q" => self.query(args),
+            "D" => return Ok((Some("OK".to_string()), false)),
+            "k" => return Ok((None, false)),
+            // Empty response means that the request is not supported
+            _ => String::new(),
+        };
+        Ok((Some(response), true))
+    }
+
+    /// Executes Zisk instructions up to the next RISC-V instruction boundary, or the end
+    fn step(&mut self) -> StopReason {
+        self.emu.step_riscv_instruction();
+        if self.emu.ctx.inst_ctx.end {
+            StopReason::Exited(self.emu.ctx.inst_ctx.error)
+        } else {
+            StopReason::Trap
+        }
+    }
+
+    /// Executes RISC-V instructions until a breakpoint is hit, the program ends or gdb requests an
+    /// interruption
+    fn resume(&mut self, stream: &mut TcpStream) -> io::Result<StopReason> {
+        let mut steps: u64 = 0;
+        loop {
+            if let StopReason::Exited(error) = self.step() {
+                return Ok(StopReason::Exited(error));
+            }
+            if self.breakpoints.contains(&self.emu.ctx.inst_ctx.pc) {
+                return Ok(StopReason::Trap);
+            }
+            steps += 1;
+            if steps.is_multiple_of(GDB_INTERRUPT_CHECK_STEPS) && interrupt_requested(stream)? {
+                return Ok(StopReason::Trap);
+            }
+        }
+    }
+
+    /// Returns the value of a register, using the gdb register numbering
+    fn get_register(&self, regnum: usize) -> Option<u64> {
+        match regnum {
+            0..=31 => Some(self.emu.get_reg(regnum)),
+            GDB_PC_REGNUM => Some(self.emu.ctx.inst_ctx.pc),
+            _ => None,
+        }
+    }
+
+    /// Sets the value of a register, using the gdb register numbering; x0 is hardwired to zero
+    fn set_register(&mut self, regnum: usize, value: u64) -> bool {
+        match regnum {
+            0 => true,
+            1..=31 => {
+                self.emu.set_reg(regnum, value);
+                true
+            }
+            GDB_PC_REGNUM => {
+                self.emu.ctx.inst_ctx.pc = value;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn read_registers(&self) -> String {
+        (0..=GDB_PC_REGNUM).map(|regnum| encode_u64(self.get_register(regnum).unwrap())).collect()
+    }
+
+    fn write_registers(&mut self, args: &str) -> String {
+        let Some(bytes) = decode_hex(args) else {
+            return "E01".to_string();
+        };
+        for (regnum, chunk) in bytes.chunks_exact(8).take(GDB_PC_REGNUM + 1).enumerate() {
+            self.set_register(regnum, u64::from_le_bytes(chunk.try_into().unwrap()));
+        }
+        "OK".to_string()
+    }
+
+    fn read_register(&self, args: &str) -> String {
+        usize::from_str_radix(args, 16)
+            .ok()
+            .and_then(|regnum| self.get_register(regnum))
+            .map_or_else(|| "E01".to_string(), encode_u64)
+    }
+
+    fn write_register(&mut self, args: &str) -> String {
+        let Some((regnum, value)) = args.split_once('=') else {
+            return "E01".to_string();
+        };
+        let regnum = usize::from_str_radix(regnum, 16).ok();
+        let value = decode_hex(value).and_then(|bytes| <[u8; 8]>::try_from(bytes).ok());
+        match (regnum, value) {
+            (Some(regnum), Some(value)) if self.set_register(regnum, u64::from_le_bytes(value)) => {
+                "OK".to_string()
+            }
+            _ => "E01".to_string(),
+        }
+    }
+
+    /// Reads one byte of memory, returning None if the address is not mapped.  The register file
+    /// is served from the context registers, since they are not kept in memory.
+    fn read_byte(&self, addr: u64) -> Option<u8> {
+        if (REG_FIRST..REG_FIRST + GDB_REGS_SIZE).contains(&addr) {
+            let reg = self.emu.get_reg(((addr - REG_FIRST) >> 3) as usize);
+            return Some(reg.to_le_bytes()[(addr & 0x7) as usize]);
+        }
+        let mem = &self.emu.ctx.inst_ctx.mem;
+        if is_mapped(mem, addr) {
+            Some(mem.read(addr, 1) as u8)
+        } else {
+            None
+        }
+    }
+
+    /// Writes one byte of memory, returning false if the address is not writable, i.e. if it is out
+    /// of the RAM write section or in an MMIO region
+    fn write_byte(&mut self, addr: u64, value: u8) -> bool {
+        if (REG_FIRST..REG_FIRST + GDB_REGS_SIZE).contains(&addr) {
+            let index = ((addr - REG_FIRST) >> 3) as usize;
+            let mut bytes = self.emu.get_reg(index).to_le_bytes();
+            bytes[(addr & 0x7) as usize] = value;
+            return self.set_register(index, u64::from_le_bytes(bytes));
+        }
+        let mem = &mut self.emu.ctx.inst_ctx.mem;
+        if is_writable(mem, addr) {
+            mem.write_silent(addr, value as u64, 1);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn read_memory(&self, args: &str) -> String {
+        let Some((addr, len)) = parse_addr_len(args) else {
+            return "E01".to_string();
+        };
+        let len = len.min(GDB_PACKET_SIZE as u64 / 2);
+        let mut bytes = Vec::with_capacity(len as usize);
+        for offset in 0..len {
+            match self.read_byte(addr.wrapping_add(offset)) {
+                Some(byte) => bytes.push(byte),
+                None if bytes.is_empty() => return "E14".to_string(),
+                None => break,
+            }
+        }
+        encode_hex(&bytes)
+    }
+
+    fn write_memory(&mut self, args: &str) -> String {
+        let Some((addr_len, data)) = args.split_once(':') else {
+            return "E01".to_string();
+        };
+        let (Some((addr, len)), Some(bytes)) = (parse_addr_len(addr_len), decode_hex(data)) else {
+            return "E01".to_string();
+        };
+        if bytes.len() as u64 != len {
+            return "E01".to_string();
+        }
+        for (offset, byte) in bytes.iter().enumerate() {
+            if !self.write_byte(addr.wrapping_add(offset as u64), *byte) {
+                return "E14".to_string();
+            }
+        }
+        "OK".to_string()
+    }
+
+    /// Inserts or removes a software (Z0) or hardware (Z1) breakpoint; both are implemented as a
+    /// check of the pc, so no instruction is modified
+    fn set_breakpoint(&mut self, args: &str, insert: bool) -> String {
+        let mut fields = args.split(',');
+        let (Some(kind), Some(addr)) = (fields.next(), fields.next()) else {
+            return "E01".to_string();
+        };
+        if kind != "0" && kind != "1" {
+            return String::new();
+        }
+        let Ok(addr) = u64::from_str_radix(addr, 16) else {
+            return "E01".to_string();
+        };
+        if insert {
+            self.breakpoints.insert(addr);
+        } else {
+            self.breakpoints.remove(&addr);
+        }
+        "OK".to_string()
+    }
+
+    fn query(&self, args: &str) -> String {
+        if args.starts_with("Supported") {
+            format!("PacketSize={GDB_PACKET_SIZE:x};qXfer:features:read+;swbreak+;hwbreak+")
+        } else if let Some(range) = args.strip_prefix("Xfer:features:read:target.xml:") {
+            match parse_addr_len(range) {
+                Some((offset, len)) => xfer_chunk(&target_xml(), offset, len),
+                None => "E01".to_string(),
+            }
+        } else if args == "Attached" {
+            "1".to_string()
+        } else if args == "C" {
+            "QC1".to_string()
+        } else if args == "fThreadInfo" {
+            "m1".to_string()
+        } else if args == "sThreadInfo" {
+            "l".to_string()
+        } else {
+            String::new()
+        }
+    }
+}
+
+/// Builds the stop reply packet corresponding to the stop reason
+fn stop_reply(reason: StopReason) -> String {
+    match reason {
+        StopReason::Trap => "S05".to_string(),
+        StopReason::Exited(error) => format!("W{:02x}", error as u8),
+    }
+}
+
+/// Returns true if the address belongs to one of the memory sections
+fn is_mapped(mem: &Mem, addr: u64) -> bool {
+    let in_write_section = (addr >= mem.write_section.start) && (addr < mem.write_section.end);
+    in_write_section
+        || mem.read_sections.iter().any(|section| (addr >= section.start) && (addr < section.end))
+}
+
+/// Returns true if the address belongs to the RAM write section and not to an MMIO region, since
+/// the read sections, including the code and the input, are read-only
+fn is_writable(mem: &Mem, addr: u64) -> bool {
+    (addr >= mem.write_section.start)
+        && (addr < mem.write_section.end)
+        && !mem.mmio_regions.iter().any(|region| region.overlaps(addr, 1))
+}
+
+/// Builds the gdb target description, declaring a RV64 cpu with 32 integer registers and pc
+fn target_xml() -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\"?><!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+         <target version=\"1.0\"><architecture>riscv:rv64</architecture>\
+         <feature name=\"org.gnu.gdb.riscv.cpu\">",
+    );
+    for regnum in 0..32 {
+        let name = RiscVRegisters::name_from_usize(regnum).unwrap();
+        let reg_type = if name == "sp" { "data_ptr" } else { "int" };
+        xml += &format!(
+            "<reg name=\"{name}\" bitsize=\"64\" type=\"{reg_type}\" regnum=\"{regnum}\"/>"
+        );
+    }
+    xml += &format!(
+        "<reg name=\"pc\" bitsize=\"64\" type=\"code_ptr\" regnum=\"{GDB_PC_REGNUM}\"/>\
+         </feature></target>"
+    );
+    xml
+}
+
+/// Returns the requested chunk of a qXfer object, prefixed with 'm' if there is more data to read,
+/// or with 'l' if this is the last chunk
+fn xfer_chunk(data: &str, offset: u64, len: u64) -> String {
+    let start = (offset as usize).min(data.len());
+    let end = (start + len as usize).min(data.len());
+    let prefix = if end == data.len() { 'l' } else { 'm' };
+    format!("{prefix}{}", &data[start..end])
+}
+
+/// Checks, without blocking, if gdb sent an interruption request (Ctrl-C, i.e. 0x03)
+fn interrupt_requested(stream: &mut TcpStream) -> io::Result<bool> {
+    stream.set_nonblocking(true)?;
+    let mut byte = [0u8; 1];
+    let result = stream.read(&mut byte);
+    stream.set_nonblocking(false)?;
+    match result {
+        Ok(1) => Ok(byte[0] == 0x03),
+        Ok(_) => Err(ErrorKind::UnexpectedEof.into()),
+        Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Reads a `$<data>#<checksum>` packet, acknowledging it; returns None if the connection was
+/// closed
+fn read_packet(stream: &mut TcpStream) -> io::Result<Option<String>> {
+    let mut byte = [0u8; 1];
+    loop {
+        // Skip everything until the packet start, including acks and interruption requests
+        loop {
+            if stream.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            if byte[0] == b'$' {
+                break;
+            }
+        }
+
+        // Read the packet data, up to the checksum mark
+        let mut data = Vec::new();
+        loop {
+            if stream.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            if byte[0] == b'#' {
+                break;
+            }
+            data.push(byte[0]);
+        }
+
+        // Read and verify the checksum, requesting a retransmission if it does not match
+        let mut checksum = [0u8; 2];
+        stream.read_exact(&mut checksum)?;
+        let expected =
+            std::str::from_utf8(&checksum).ok().and_then(|s| u8::from_str_radix(s, 16).ok());
+        if expected == Some(compute_checksum(&data)) {
+            stream.write_all(b"+")?;
+            return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+        }
+        stream.write_all(b"-")?;
+    }
+}
+
+/// Writes a `$<data>#<checksum>` packet, escaping the reserved characters
+fn write_packet(stream: &mut TcpStream, data: &str) -> io::Result<()> {
+    let mut escaped = Vec::with_capacity(data.len());
+    for byte in data.bytes() {
+        if matches!(byte, b'$' | b'#' | b'}' | b'*') {
+            escaped.push(b'}');
+            escaped.push(byte ^ 0x20);
+        } else {
+            escaped.push(byte);
+        }
+    }
+    let mut packet = Vec::with_capacity(escaped.len() + 4);
+    packet.push(b'$');
+    packet.extend_from_slice(&escaped);
+    packet.extend_from_slice(format!("#{:02x}", compute_checksum(&escaped)).as_bytes());
+    stream.write_all(&packet)?;
+    stream.flush()
+}
+
+fn compute_checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |checksum, byte| checksum.wrapping_add(*byte))
+}
+
+/// Parses an `<addr>,<len>` pair of hexadecimal numbers
+fn parse_addr_len(args: &str) -> Option<(u64, u64)> {
+    let (addr, len) = args.split_once(',')?;
+    Some((u64::from_str_radix(addr, 16).ok()?, u64::from_str_radix(len, 16).ok()?))
+}
+
+fn encode_u64(value: u64) -> String {
+    encode_hex(&value.to_le_bytes())
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zisk_core::{ZiskRom, INPUT_ADDR, RAM_ADDR};
+
+    #[test]
+    fn test_gdb_server_requests() {
+        let rom = ZiskRom::default();
+        let mut emu = Emu::new(&rom);
+        emu.ctx = emu.create_emu_context(vec![1, 2, 3]);
+        let mut server = GdbServer { emu: &mut emu, breakpoints: HashSet::new() };
+
+        // Packet parsing helpers
+        assert_eq!(parse_addr_len("a0001000,4"), Some((0xa0001000, 4)));
+        assert_eq!(parse_addr_len("a0001000"), None);
+        assert_eq!(decode_hex("0aff"), Some(vec![0x0a, 0xff]));
+        assert_eq!(decode_hex("abc"), None);
+        assert_eq!(compute_checksum(b"OK"), 0x9a);
+
+        // Registers, with x0 hardwired to zero
+        assert_eq!(server.write_register("a=2a00000000000000"), "OK");
+        assert_eq!(server.read_register("a"), encode_u64(0x2a));
+        assert_eq!(server.write_register("0=2a00000000000000"), "OK");
+        assert_eq!(server.read_register("0"), encode_u64(0));
+        assert_eq!(server.write_register("a=2a"), "E01");
+        assert_eq!(server.read_register("40"), "E01");
+
+        // RAM is readable and writable
+        let ram = RAM_ADDR + 0x1000;
+        assert_eq!(server.write_memory(&format!("{ram:x},4:deadbeef")), "OK");
+        assert_eq!(server.read_memory(&format!("{ram:x},4")), "deadbeef");
+        assert_eq!(server.write_memory(&format!("{ram:x},4:dead")), "E01");
+
+        // The input section is read-only, and unmapped memory is not accessible
+        let input = INPUT_ADDR + 16;
+        assert_eq!(server.read_memory(&format!("{input:x},3")), "010203");
+        assert_eq!(server.write_memory(&format!("{input:x},1:ff")), "E14");
+        assert_eq!(server.read_memory(&format!("{input:x},3")), "010203");
+        assert_eq!(server.read_memory("10,4"), "E14");
+        assert_eq!(server.write_memory("10,1:ff"), "E14");
+
+        // Breakpoints and queries
+        assert_eq!(server.set_breakpoint("0,80000000,4", true), "OK");
+        assert!(server.breakpoints.contains(&0x80000000));
+        assert_eq!(server.set_breakpoint("0,80000000,4", false), "OK");
+        assert!(server.breakpoints.is_empty());
+        assert_eq!(server.set_breakpoint("2,80000000,4", true), "");
+        assert!(server.query("Supported:swbreak+").starts_with("PacketSize=4000;"));
+        assert_eq!(server.query("Attached"), "1");
+        assert_eq!(stop_reply(StopReason::Exited(true)), "W01");
+    }
+}
@@ -0,0 +1,178 @@
+//! Guest log capture
+//!
+//! * The guest writes its log records to `LOG_ADDR` (see `ziskos::log`), separately from the
+//!   standard output written to `UART_ADDR`.
+//! * `GuestLog` is an MMIO device that decodes the written words into `GuestLogRecord`s, keeping
+//!   only the ones that pass the configured level and target filters, so that the host can
+//!   retrieve them as structured records once the emulation completes.
+
+use std::sync::{Arc, Mutex};
+
+use zisk_core::{MmioDevice, LOG_ADDR};
+use ziskos::log::{log_header, LogLevel};
+
+use crate::Emu;
+
+/// Log record written by the guest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuestLogRecord {
+    /// Level, from 1 (error) to 5 (trace)
+    pub level: u8,
+    /// Target, i.e. the module path of the guest code that wrote the record
+    pub target: String,
+    /// Message
+    pub message: String,
+}
+
+impl GuestLogRecord {
+    /// Returns the name of the record level, or UNKNOWN if it is not a `LogLevel`
+    pub fn level_name(&self) -> &'static str {
+        LogLevel::try_from(self.level).map_or("UNKNOWN", |level| level.as_str())
+    }
+}
+
+/// Filter applied to the guest log records before storing them
+#[derive(Debug, Clone)]
+pub struct GuestLogFilter {
+    /// Most verbose level to keep, e.g. 3 keeps error, warn and info records
+    pub max_level: u8,
+    /// Target prefixes to keep; if empty, all targets are kept
+    pub targets: Vec<String>,
+}
+
+impl Default for GuestLogFilter {
+    fn default() -> Self {
+        Self { max_level: 5, targets: Vec::new() }
+    }
+}
+
+impl GuestLogFilter {
+    /// Returns true if the record must be kept
+    pub fn matches(&self, record: &GuestLogRecord) -> bool {
+        (record.level <= self.max_level)
+            && (self.targets.is_empty()
+                || self.targets.iter().any(|target| record.target.starts_with(target.as_str())))
+    }
+}
+
+/// Record being received: (level, target length, message length) and the bytes received so far
+struct PendingRecord {
+    level: u8,
+    target_len: usize,
+    message_len: usize,
+    bytes: Vec<u8>,
+}
+
+/// MMIO device decoding the guest log records written to `LOG_ADDR`
+pub struct GuestLog {
+    filter: GuestLogFilter,
+    pending: Option<PendingRecord>,
+    records: Arc<Mutex<Vec<GuestLogRecord>>>,
+}
+
+impl GuestLog {
+    /// Creates a guest log device, returning it together with the shared list of records
+    pub fn new(filter: GuestLogFilter) -> (Self, Arc<Mutex<Vec<GuestLogRecord>>>) {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        (Self { filter, pending: None, records: records.clone() }, records)
+    }
+
+    /// Attaches a guest log device to the emulator, returning the shared list of records.
+    /// It must be called before the emulator context is created.
+    pub fn attach(emu: &mut Emu, filter: GuestLogFilter) -> Arc<Mutex<Vec<GuestLogRecord>>> {
+        let (device, records) = Self::new(filter);
+        emu.add_mmio_region(LOG_ADDR, 8, Box::new(device));
+        records
+    }
+}
+
+impl MmioDevice for GuestLog {
+    fn read(&self, _addr: u64, _width: u64) -> u64 {
+        0
+    }
+
+    fn write(&mut self, _addr: u64, val: u64, width: u64) {
+        if width != 8 {
+            panic!("GuestLog::write() invalid width={width}, guest log words must be 8 bytes");
+        }
+
+        // A word received with no pending record is a header
+        match &mut self.pending {
+            None => {
+                self.pending = Some(PendingRecord {
+                    level: (val >> 56) as u8,
+                    target_len: ((val >> 32) & 0xffff) as usize,
+                    message_len: (val & 0xffffffff) as usize,
+                    bytes: Vec::new(),
+                })
+            }
+            Some(pending) => pending.bytes.extend_from_slice(&val.to_le_bytes()),
+        }
+
+        // The record is complete once all its bytes have been received; a record with empty
+        // target and message is completed by its header
+        let pending = self.pending.as_ref().unwrap();
+        if pending.bytes.len() >= pending.target_len + pending.message_len {
+            self.complete();
+        }
+    }
+}
+
+impl GuestLog {
+    /// Builds the pending record and stores it, if it passes the filter
+    fn complete(&mut self) {
+        let pending = self.pending.take().unwrap();
+        let (target, message) = pending.bytes.split_at(pending.target_len.min(pending.bytes.len()));
+        let record = GuestLogRecord {
+            level: pending.level,
+            target: String::from_utf8_lossy(target).into_owned(),
+            message: String::from_utf8_lossy(&message[..pending.message_len.min(message.len())])
+                .into_owned(),
+        };
+        if self.filter.matches(&record) {
+            self.records.lock().unwrap().push(record);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encodes a record as the guest does
+    fn encode(level: LogLevel, target: &str, message: &str) -> Vec<u64> {
+        let mut words = vec![log_header(level, target.len(), message.len())];
+        let bytes: Vec<u8> = target.bytes().chain(message.bytes()).collect();
+        for chunk in bytes.chunks(8) {
+            let mut word = [0u8; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            words.push(u64::from_le_bytes(word));
+        }
+        words
+    }
+
+    #[test]
+    fn test_guest_log_records() {
+        let filter = GuestLogFilter { max_level: 3, targets: vec!["guest::".to_string()] };
+        let (mut device, records) = GuestLog::new(filter);
+        for (level, target, message) in [
+            (LogLevel::Info, "guest::main", "starting the block execution"),
+            (LogLevel::Debug, "guest::main", "filtered by level"),
+            (LogLevel::Error, "other", "filtered by target"),
+            (LogLevel::Error, "guest::db", ""),
+        ] {
+            for word in encode(level, target, message) {
+                device.write(LOG_ADDR, word, 8);
+            }
+        }
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].message, "starting the block execution");
+        assert_eq!(records[0].level_name(), "INFO");
+        assert_eq!(records[1].target, "guest::db");
+        assert_eq!(records[1].message, "");
+        assert_eq!(records[1].level_name(), "ERROR");
+        let record = GuestLogRecord { level: 0, ..records[1].clone() };
+        assert_eq!(record.level_name(), "UNKNOWN");
+    }
+}
@@ -0,0 +1,117 @@
+//! Guest panic decoding
+//!
+//! * Guests built with the ziskos `panic-info` feature report their panics as a guest log error
+//!   record with target `GUEST_PANIC_TARGET`, whose message contains the location, a shallow
+//!   backtrace of return addresses and the panic message (see `ziskos::panic_info`).
+//! * `GuestPanicInfo` decodes that record, and formats it resolving the return addresses to the
+//!   ELF functions that contain them, so that a failed execution is self-explanatory in the logs.
+
+use std::fmt;
+
+use crate::{GuestLogRecord, SymbolInfo};
+
+/// Target of the guest log records reporting a panic, as written by ziskos
+pub const GUEST_PANIC_TARGET: &str = "ziskos::panic";
+
+/// Panic reported by the guest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuestPanicInfo {
+    /// Source file, line and column of the panic, if known
+    pub location: Option<(String, u32, u32)>,
+    /// Return addresses of the calling frames, from the innermost one
+    pub backtrace: Vec<u64>,
+    /// Panic message
+    pub message: String,
+}
+
+impl GuestPanicInfo {
+    /// Decodes a guest log record, returning None if it does not report a panic
+    pub fn from_record(record: &GuestLogRecord) -> Option<Self> {
+        if record.target != GUEST_PANIC_TARGET {
+            return None;
+        }
+        let mut lines = record.message.splitn(3, '\n');
+        let location = lines.next()?;
+        let backtrace = lines.next()?;
+        let message = lines.next().unwrap_or_default().to_string();
+
+        // The file name can contain colons, so the line and column are taken from the end
+        let location = {
+            let mut parts = location.rsplitn(3, ':');
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(column), Some(line), Some(file)) => {
+                    Some((file.to_string(), line.parse().ok()?, column.parse().ok()?))
+                }
+                _ => None,
+            }
+        };
+        let backtrace = backtrace
+            .split_whitespace()
+            .map(|ra| u64::from_str_radix(ra, 16).ok())
+            .collect::<Option<Vec<u64>>>()?;
+
+        Some(Self { location, backtrace, message })
+    }
+
+    /// Returns the first panic reported in the guest log records, if any
+    pub fn find(records: &[GuestLogRecord]) -> Option<Self> {
+        records.iter().find_map(Self::from_record)
+    }
+
+    /// Formats the panic, resolving every return address to the function that contains it
+    pub fn to_text_with_symbols<'a>(
+        &self,
+        functions: impl Iterator<Item = &'a SymbolInfo> + Clone,
+    ) -> String {
+        let mut text = format!("{self}");
+        for (i, ra) in self.backtrace.iter().enumerate() {
+            let function = functions
+                .clone()
+                .find(|f| (*ra >= f.address) && (*ra < f.address + f.size.max(1)))
+                .map(|f| format!("{}+{:#x}", f.name, ra - f.address))
+                .unwrap_or_else(|| "???".to_string());
+            text.push_str(&format!("\n  {i:>2}: {ra:#x} {function}"));
+        }
+        text
+    }
+}
+
+impl fmt::Display for GuestPanicInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.location {
+            Some((file, line, column)) => {
+                write!(f, "guest panicked at {file}:{line}:{column}: {}", self.message)
+            }
+            None => write!(f, "guest panicked: {}", self.message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guest_panic_info() {
+        let record = GuestLogRecord {
+            level: 1,
+            target: GUEST_PANIC_TARGET.to_string(),
+            message: "src/main.rs:12:5\n80001010 80000200\nindex out of bounds\nlen is 3"
+                .to_string(),
+        };
+        let info = GuestPanicInfo::from_record(&record).unwrap();
+        assert_eq!(info.location, Some(("src/main.rs".to_string(), 12, 5)));
+        assert_eq!(info.backtrace, vec![0x80001010, 0x80000200]);
+        assert_eq!(info.message, "index out of bounds\nlen is 3");
+
+        let functions =
+            [SymbolInfo { name: "guest::main".to_string(), address: 0x80001000, size: 0x40 }];
+        let text = info.to_text_with_symbols(functions.iter());
+        assert!(text.starts_with("guest panicked at src/main.rs:12:5: index out of bounds"));
+        assert!(text.contains("0x80001010 guest::main+0x10"));
+        assert!(text.contains("0x80000200 ???"));
+
+        let record = GuestLogRecord { target: "guest".to_string(), ..record };
+        assert_eq!(GuestPanicInfo::find(&[record]), None);
+    }
+}
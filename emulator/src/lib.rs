@@ -12,40 +12,80 @@
 //! User configuration -------> EmuOptions /
 //! ```
 
+mod compliance;
+mod conformance;
+mod coverage_map;
 mod elf_symbol_reader;
 mod emu;
+mod emu_atomics;
 mod emu_context;
 pub mod emu_costs;
+mod emu_ebreak;
 mod emu_full_trace;
+mod emu_misaligned;
 pub mod emu_options;
 mod emu_par_options;
 mod emu_reg_trace;
+mod emu_regions;
+mod emu_reversible;
 mod emu_segment;
+mod emu_shadow_stack;
+mod emu_syscalls;
+mod emu_watchpoints;
 mod emulator;
 mod emulator_errors;
+mod execution_result;
+mod gdb_server;
+mod guest_log;
+mod guest_panic;
 pub mod mem_operations_stats;
+mod pc_heatmap;
 mod regions_of_interest;
+mod segment_advisor;
+mod snapshot_fuzz;
 pub mod stats;
 mod stats_cost_mark;
 mod stats_costs;
 pub mod stats_coverage_report;
 pub mod stats_report;
+mod syscall_policy;
+mod trace_export;
 
+pub use compliance::*;
+pub use conformance::*;
+pub use coverage_map::*;
 pub use elf_symbol_reader::*;
 pub use emu::*;
+pub use emu_atomics::*;
 pub use emu_context::*;
 pub use emu_costs::*;
+pub use emu_ebreak::*;
 pub use emu_full_trace::*;
+pub use emu_misaligned::*;
 pub use emu_options::*;
 pub use emu_par_options::*;
 pub use emu_reg_trace::*;
+pub use emu_regions::*;
+pub use emu_reversible::*;
 pub use emu_segment::*;
+pub use emu_shadow_stack::*;
+pub use emu_syscalls::*;
+pub use emu_watchpoints::*;
 pub use emulator::*;
 pub use emulator_errors::*;
+pub use execution_result::*;
+pub use gdb_server::*;
+pub use guest_log::*;
+pub use guest_panic::*;
 pub use mem_operations_stats::*;
+pub use pc_heatmap::*;
 pub use regions_of_interest::*;
+pub use segment_advisor::*;
+pub use snapshot_fuzz::*;
 pub use stats::*;
 pub use stats_cost_mark::*;
 pub use stats_costs::*;
 pub use stats_coverage_report::*;
 pub use stats_report::*;
+pub use syscall_policy::*;
+pub use trace_export::*;
@@ -0,0 +1,167 @@
+//! Program counter heatmap
+//!
+//! * `PcHeatmap` counts how many times every program counter was executed, i.e. the ROM
+//!   histogram, and is the basis to find where a guest program spends its steps.
+//! * It can be built from any execution source implementing `PcTrace`: a list of executed pcs
+//!   (e.g. the one recorded by the reversible execution), or the pc histogram collected by the
+//!   emulator statistics.
+//! * When a DWARF line table is provided, e.g. the rows decoded from `.debug_line`, the counts are
+//!   also aggregated per source line.
+//! * The result is exported as JSON.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+
+/// Source of executed program counters
+pub trait PcTrace {
+    /// Calls `f(pc, count)` for every executed pc, where count is the number of executions
+    fn visit_pcs(&self, f: &mut dyn FnMut(u64, u64));
+}
+
+impl PcTrace for [u64] {
+    fn visit_pcs(&self, f: &mut dyn FnMut(u64, u64)) {
+        for pc in self {
+            f(*pc, 1);
+        }
+    }
+}
+
+impl PcTrace for HashMap<u64, u64> {
+    fn visit_pcs(&self, f: &mut dyn FnMut(u64, u64)) {
+        for (pc, count) in self {
+            f(*pc, *count);
+        }
+    }
+}
+
+/// Row of a DWARF line table: the instructions from `address` up to the address of the next row
+/// belong to `file`:`line`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineTableRow {
+    pub address: u64,
+    pub file: String,
+    pub line: u32,
+}
+
+/// Execution count of a source line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SourceLineCount {
+    pub file: String,
+    pub line: u32,
+    pub count: u64,
+}
+
+/// Execution count of a program counter
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PcCount {
+    pub pc: String,
+    pub count: u64,
+}
+
+/// JSON document exported by `PcHeatmap::to_json()`
+#[derive(Serialize)]
+struct PcHeatmapJson {
+    total: u64,
+    pcs: Vec<PcCount>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lines: Option<Vec<SourceLineCount>>,
+}
+
+/// Number of executions of every program counter
+#[derive(Debug, Clone, Default)]
+pub struct PcHeatmap {
+    /// Execution count of every executed pc, sorted by pc
+    pub counts: BTreeMap<u64, u64>,
+    /// Total number of executions
+    pub total: u64,
+}
+
+impl PcHeatmap {
+    /// Builds the heatmap of the provided execution source
+    pub fn from_trace<T: PcTrace + ?Sized>(trace: &T) -> Self {
+        let mut heatmap = Self::default();
+        trace.visit_pcs(&mut |pc, count| {
+            *heatmap.counts.entry(pc).or_insert(0) += count;
+            heatmap.total += count;
+        });
+        heatmap
+    }
+
+    /// Returns the `n` most executed pcs, with their counts, from most to least executed
+    pub fn top(&self, n: usize) -> Vec<(u64, u64)> {
+        let mut counts: Vec<(u64, u64)> = self.counts.iter().map(|(pc, c)| (*pc, *c)).collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        counts.truncate(n);
+        counts
+    }
+
+    /// Aggregates the counts per source line, using the provided line table, and returns them from
+    /// most to least executed.  Pcs below the first row of the table are not attributed.
+    pub fn line_counts(&self, line_table: &[LineTableRow]) -> Vec<SourceLineCount> {
+        let mut rows: Vec<&LineTableRow> = line_table.iter().collect();
+        rows.sort_by_key(|row| row.address);
+
+        let mut counts: HashMap<(&str, u32), u64> = HashMap::new();
+        for (pc, count) in &self.counts {
+            let index = rows.partition_point(|row| row.address <= *pc);
+            if index == 0 {
+                continue;
+            }
+            let row = rows[index - 1];
+            *counts.entry((row.file.as_str(), row.line)).or_insert(0) += count;
+        }
+
+        let mut line_counts: Vec<SourceLineCount> = counts
+            .into_iter()
+            .map(|((file, line), count)| SourceLineCount { file: file.to_string(), line, count })
+            .collect();
+        line_counts.sort_by(|a, b| {
+            b.count.cmp(&a.count).then(a.file.cmp(&b.file)).then(a.line.cmp(&b.line))
+        });
+        line_counts
+    }
+
+    /// Exports the heatmap as JSON, including the per source line counts if a line table is
+    /// provided
+    pub fn to_json(&self, line_table: Option<&[LineTableRow]>) -> String {
+        let json = PcHeatmapJson {
+            total: self.total,
+            pcs: self
+                .counts
+                .iter()
+                .map(|(pc, count)| PcCount { pc: format!("0x{pc:x}"), count: *count })
+                .collect(),
+            lines: line_table.map(|line_table| self.line_counts(line_table)),
+        };
+        serde_json::to_string_pretty(&json).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pc_heatmap() {
+        let trace: Vec<u64> = vec![0x1000, 0x1004, 0x1008, 0x1004, 0x1008, 0x100c];
+        let heatmap = PcHeatmap::from_trace(trace.as_slice());
+        assert_eq!(heatmap.total, 6);
+        assert_eq!(heatmap.top(2), vec![(0x1004, 2), (0x1008, 2)]);
+
+        let line_table = vec![
+            LineTableRow { address: 0x1000, file: "main.rs".to_string(), line: 3 },
+            LineTableRow { address: 0x1004, file: "main.rs".to_string(), line: 4 },
+            LineTableRow { address: 0x100c, file: "main.rs".to_string(), line: 6 },
+        ];
+        let lines = heatmap.line_counts(&line_table);
+        assert_eq!(lines[0], SourceLineCount { file: "main.rs".to_string(), line: 4, count: 4 });
+        assert_eq!(lines.len(), 3);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&heatmap.to_json(Some(&line_table))).unwrap();
+        assert_eq!(json["total"], 6);
+        assert_eq!(json["pcs"][0]["pc"], "0x1000");
+        assert_eq!(json["lines"][0]["count"], 4);
+    }
+}
@@ -0,0 +1,206 @@
+//! Segment advisor
+//!
+//! * Analyzes a trace of executed pcs to find the hot loops, i.e. the loops whose back edge (a jump
+//!   to a lower or equal pc) is taken many consecutive times, and suggests the steps where the
+//!   execution should be split into proof segments.
+//! * Splitting a tight loop across two segments inflates the continuation overhead, so the cut
+//!   points are moved before the beginning of the hot loop that contains them, as long as the
+//!   segment does not become smaller than half the target size.
+//! * When the loop is too long to be avoided, the cut is placed at the beginning of one of its
+//!   iterations, i.e. right after a back edge, so that every segment contains whole iterations.
+
+use std::{collections::HashMap, fmt};
+
+/// Minimum number of consecutive iterations for a loop to be considered hot
+const HOT_LOOP_MIN_ITERATIONS: usize = 8;
+
+/// Reason why a cut point was placed at its step
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CutRationale {
+    /// The ideal cut point does not split any hot loop
+    NoLoop,
+    /// The ideal cut point was inside a hot loop, so the cut was moved before the loop start
+    BeforeLoop { head: u64, tail: u64, moved_steps: u64 },
+    /// The hot loop is too long to be avoided, so the cut was moved to an iteration boundary
+    IterationBoundary { head: u64, tail: u64, moved_steps: u64 },
+    /// The ideal cut point splits a hot loop, and no better cut point was found
+    SplitsLoop { head: u64, tail: u64 },
+}
+
+/// Suggested segment boundary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentCut {
+    /// Step where the new segment starts
+    pub step: u64,
+    /// Pc of the first instruction of the new segment
+    pub pc: u64,
+    /// Why the cut was placed here
+    pub rationale: CutRationale,
+}
+
+impl fmt::Display for SegmentCut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "step={} pc=0x{:x} ", self.step, self.pc)?;
+        match &self.rationale {
+            CutRationale::NoLoop => write!(f, "no hot loop at the ideal cut point"),
+            CutRationale::BeforeLoop { head, tail, moved_steps } => write!(
+                f,
+                "moved {moved_steps} steps back to avoid splitting hot loop 0x{head:x}-0x{tail:x}"
+            ),
+            CutRationale::IterationBoundary { head, tail, moved_steps } => write!(
+                f,
+                "moved {moved_steps} steps back to an iteration boundary of hot loop \
+                 0x{head:x}-0x{tail:x}"
+            ),
+            CutRationale::SplitsLoop { head, tail } => {
+                write!(f, "splits hot loop 0x{head:x}-0x{tail:x}")
+            }
+        }
+    }
+}
+
+/// Hot loop execution, covering the steps in the range [start, end)
+#[derive(Debug, Clone)]
+struct HotLoopRun {
+    head: u64,
+    tail: u64,
+    start: usize,
+    end: usize,
+    /// Steps where an iteration starts, i.e. the steps right after each back edge
+    iterations: Vec<usize>,
+}
+
+/// Finds the runs of consecutive iterations of every loop, and keeps the hot ones
+fn find_hot_loop_runs(trace: &[u64]) -> Vec<HotLoopRun> {
+    // Back edges, by loop, in step order
+    let mut back_edges: HashMap<(u64, u64), Vec<usize>> = HashMap::new();
+    for step in 0..trace.len().saturating_sub(1) {
+        if trace[step + 1] <= trace[step] {
+            back_edges.entry((trace[step + 1], trace[step])).or_default().push(step + 1);
+        }
+    }
+
+    let mut runs = Vec::new();
+    for ((head, tail), steps) in back_edges {
+        // Consecutive iterations are taken as part of the same run while the distance between
+        // back edges does not grow over twice the previous one
+        let mut first = 0;
+        for i in 1..=steps.len() {
+            let continues = (i < steps.len())
+                && ((i == first + 1)
+                    || (steps[i] - steps[i - 1] <= 2 * (steps[i - 1] - steps[i - 2])));
+            if continues {
+                continue;
+            }
+            if i - first >= HOT_LOOP_MIN_ITERATIONS {
+                // The first iteration started one iteration length before the first back edge, and
+                // the last one ends one iteration length after the last back edge
+                let first_length = steps[first + 1] - steps[first];
+                let last_length = steps[i - 1] - steps[i - 2];
+                runs.push(HotLoopRun {
+                    head,
+                    tail,
+                    start: steps[first].saturating_sub(first_length),
+                    end: (steps[i - 1] + last_length).min(trace.len()),
+                    iterations: steps[first..i].to_vec(),
+                });
+            }
+            first = i;
+        }
+    }
+    runs.sort_by_key(|run| (run.start, run.end));
+    runs
+}
+
+/// Suggests the steps where a trace of pcs should be split into segments of about
+/// `target_steps_per_segment` steps, avoiding splitting hot loops.  No segment is longer than the
+/// target size.
+pub fn suggest_segments(trace: &[u64], target_steps_per_segment: u64) -> Vec<SegmentCut> {
+    assert!(target_steps_per_segment > 1, "suggest_segments() target must be greater than one");
+    let target = target_steps_per_segment as usize;
+    let runs = find_hot_loop_runs(trace);
+
+    let mut cuts = Vec::new();
+    let mut last_cut = 0;
+    while last_cut + target < trace.len() {
+        let ideal = last_cut + target;
+        let min_cut = last_cut + target / 2;
+
+        // The outermost (i.e. the earliest starting) hot loop run containing the ideal cut point
+        let Some(run) = runs.iter().find(|run| (run.start < ideal) && (ideal < run.end)) else {
+            cuts.push(SegmentCut {
+                step: ideal as u64,
+                pc: trace[ideal],
+                rationale: CutRationale::NoLoop,
+            });
+            last_cut = ideal;
+            continue;
+        };
+
+        let (step, rationale) = if run.start >= min_cut {
+            let moved_steps = (ideal - run.start) as u64;
+            (run.start, CutRationale::BeforeLoop { head: run.head, tail: run.tail, moved_steps })
+        } else {
+            // Iteration boundary of the innermost hot loop containing the ideal cut point
+            let boundary = runs
+                .iter()
+                .filter(|run| (run.start < ideal) && (ideal < run.end))
+                .filter_map(|run| {
+                    let i = run.iterations.partition_point(|step| *step <= ideal);
+                    (i > 0 && run.iterations[i - 1] > min_cut).then(|| (run, run.iterations[i - 1]))
+                })
+                .max_by_key(|(_, step)| *step);
+            match boundary {
+                Some((run, step)) => (
+                    step,
+                    CutRationale::IterationBoundary {
+                        head: run.head,
+                        tail: run.tail,
+                        moved_steps: (ideal - step) as u64,
+                    },
+                ),
+                None => (ideal, CutRationale::SplitsLoop { head: run.head, tail: run.tail }),
+            }
+        };
+        cuts.push(SegmentCut { step: step as u64, pc: trace[step], rationale });
+        last_cut = step;
+    }
+    cuts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a trace with a straight-line prologue, a hot loop and a straight-line epilogue
+    fn build_trace(prologue: u64, iterations: u64, epilogue: u64) -> Vec<u64> {
+        let mut trace: Vec<u64> = (0..prologue).map(|i| 0x1000 + i * 4).collect();
+        for _ in 0..iterations {
+            trace.extend((0..4).map(|i| 0x2000 + i * 4));
+        }
+        trace.extend((0..epilogue).map(|i| 0x3000 + i * 4));
+        trace
+    }
+
+    #[test]
+    fn test_suggest_segments_before_loop() {
+        // The ideal cut at step 100 falls inside the loop, which starts at step 80
+        let trace = build_trace(80, 10, 200);
+        let cuts = suggest_segments(&trace, 100);
+        assert_eq!(cuts[0].step, 80);
+        assert_eq!(
+            cuts[0].rationale,
+            CutRationale::BeforeLoop { head: 0x2000, tail: 0x200c, moved_steps: 20 }
+        );
+        assert!(cuts.windows(2).all(|w| w[1].step - w[0].step <= 100));
+    }
+
+    #[test]
+    fn test_suggest_segments_iteration_boundary() {
+        // The loop is longer than a segment, so the cuts fall at iteration boundaries
+        let trace = build_trace(10, 100, 10);
+        let cuts = suggest_segments(&trace, 50);
+        assert!(matches!(cuts[0].rationale, CutRationale::IterationBoundary { .. }));
+        assert!(cuts.iter().filter(|cut| cut.step < 410).all(|cut| cut.pc == 0x2000));
+    }
+}
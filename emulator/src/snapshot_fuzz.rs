@@ -0,0 +1,266 @@
+//! Snapshot fuzzing
+//!
+//! * `EmuSnapshot` captures the state of the emulator at a given step, e.g. once the program has
+//!   been initialized, and records every memory write done afterwards in the memory write
+//!   journal, so that `restore()` can bring the emulator back to the captured state by undoing
+//!   them, without re-running the initialization.
+//! * `snapshot_fuzz()` runs every input of a corpus from the same snapshot, up to a budget of
+//!   steps, recording the edge coverage of every run in a `CoverageMap` and reporting the inputs
+//!   that crash the emulation (a panic or an error state) or that exceed the budget.
+//! * The input data is replaced before every run, so the state captured in the snapshot must not
+//!   depend on it.  Writes to MMIO regions are not undone, since their side effects belong to the
+//!   device.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use zisk_core::{FcallInstContext, INPUT_ADDR, REGS_IN_MAIN_TOTAL_NUMBER};
+
+use crate::{CoverageMap, Emu};
+
+/// Captured state of the emulator
+pub struct EmuSnapshot {
+    a: u64,
+    b: u64,
+    c: u64,
+    flag: bool,
+    sp: u64,
+    pc: u64,
+    step: u64,
+    end: bool,
+    error: bool,
+    free_input: u64,
+    regs: [u64; REGS_IN_MAIN_TOTAL_NUMBER],
+    fcall: FcallInstContext,
+}
+
+impl EmuSnapshot {
+    /// Captures the current state of the emulator, and starts journaling its memory writes.
+    /// The emulator context must have been created before calling this function, e.g. using
+    /// `Emu::create_emu_context()`.
+    pub fn take(emu: &mut Emu) -> Self {
+        let inst_ctx = &mut emu.ctx.inst_ctx;
+        inst_ctx.mem.write_journal = Some(Vec::new());
+        Self {
+            a: inst_ctx.a,
+            b: inst_ctx.b,
+            c: inst_ctx.c,
+            flag: inst_ctx.flag,
+            sp: inst_ctx.sp,
+            pc: inst_ctx.pc,
+            step: inst_ctx.step,
+            end: inst_ctx.end,
+            error: inst_ctx.error,
+            free_input: inst_ctx.mem.free_input,
+            regs: inst_ctx.regs,
+            fcall: inst_ctx.fcall.clone(),
+        }
+    }
+
+    /// Restores the captured state, undoing the memory writes done since it was captured
+    pub fn restore(&self, emu: &mut Emu) {
+        let inst_ctx = &mut emu.ctx.inst_ctx;
+
+        // Undo memory writes in reverse order, without journaling them
+        let write_journal = inst_ctx
+            .mem
+            .write_journal
+            .take()
+            .expect("EmuSnapshot::restore() memory write journal was disabled");
+        for record in write_journal.iter().rev() {
+            inst_ctx.mem.write_silent(record.addr, record.previous_value, record.width);
+        }
+        inst_ctx.mem.write_journal = Some(Vec::new());
+        inst_ctx.mem.code_write = None;
+
+        inst_ctx.a = self.a;
+        inst_ctx.b = self.b;
+        inst_ctx.c = self.c;
+        inst_ctx.flag = self.flag;
+        inst_ctx.sp = self.sp;
+        inst_ctx.pc = self.pc;
+        inst_ctx.step = self.step;
+        inst_ctx.end = self.end;
+        inst_ctx.error = self.error;
+        inst_ctx.mem.free_input = self.free_input;
+        inst_ctx.regs = self.regs;
+        inst_ctx.fcall = self.fcall.clone();
+    }
+}
+
+/// Input that crashed the emulation
+#[derive(Debug, Clone)]
+pub struct FuzzCrash {
+    /// Index of the input in the corpus
+    pub input: usize,
+    /// Step at which the emulation crashed
+    pub step: u64,
+    /// Pc at which the emulation crashed
+    pub pc: u64,
+    /// Panic message, or the error state description
+    pub reason: String,
+}
+
+/// Aggregated result of a snapshot fuzzing campaign
+#[derive(Debug, Default)]
+pub struct FuzzReport {
+    /// Number of executed inputs
+    pub runs: usize,
+    /// Total number of executed steps
+    pub steps: u64,
+    /// Merged edge coverage of all the runs
+    pub coverage: CoverageMap,
+    /// Indices of the inputs that covered new edges, in execution order
+    pub new_coverage: Vec<usize>,
+    /// Inputs that crashed the emulation
+    pub crashes: Vec<FuzzCrash>,
+    /// Indices of the inputs that exceeded the budget
+    pub timeouts: Vec<usize>,
+}
+
+/// Runs every input of the corpus from a snapshot of the current state of the emulator, up to
+/// `budget` steps each, and returns the aggregated coverage and crashes.  The emulator is left in
+/// the state it had when this function was called.
+pub fn snapshot_fuzz(emu: &mut Emu, corpus: &[Vec<u8>], budget: u64) -> FuzzReport {
+    let snapshot = EmuSnapshot::take(emu);
+    let mem = &emu.ctx.inst_ctx.mem;
+    let original_input: Vec<u8> =
+        (0..mem.read(INPUT_ADDR + 8, 8)).map(|i| mem.read(INPUT_ADDR + 16 + i, 1) as u8).collect();
+    let mut report = FuzzReport::default();
+    let mut coverage = CoverageMap::default();
+
+    for (index, input) in corpus.iter().enumerate() {
+        emu.ctx.inst_ctx.mem.replace_input(input);
+        coverage.reset();
+
+        let max_steps = snapshot.step + budget;
+        let result = catch_unwind(AssertUnwindSafe(|| coverage.run(emu, max_steps)));
+
+        let inst_ctx = &emu.ctx.inst_ctx;
+        let reason = match result {
+            Err(panic) => Some(
+                panic
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .or_else(|| panic.downcast_ref::<&str>().map(|s| s.to_string()))
+                    .unwrap_or_else(|| "unknown panic".to_string()),
+            ),
+            Ok(()) if inst_ctx.error => Some("emulation ended with error".to_string()),
+            Ok(()) => None,
+        };
+        if let Some(reason) = reason {
+            report.crashes.push(FuzzCrash {
+                input: index,
+                step: inst_ctx.step,
+                pc: inst_ctx.pc,
+                reason,
+            });
+        } else if !inst_ctx.end {
+            report.timeouts.push(index);
+        }
+
+        if coverage.has_new_coverage(&report.coverage) {
+            report.new_coverage.push(index);
+        }
+        report.coverage.merge(&coverage);
+        report.runs += 1;
+        report.steps += inst_ctx.step - snapshot.step;
+
+        snapshot.restore(emu);
+    }
+
+    emu.ctx.inst_ctx.mem.replace_input(&original_input);
+    emu.ctx.inst_ctx.mem.write_journal = None;
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use zisk_core::{ZiskInstBuilder, ZiskRom, RAM_ADDR, ROM_ADDR, ROM_ENTRY};
+
+    use super::*;
+
+    /// Returns pc, step, registers and the memory written by the program
+    fn state(emu: &Emu) -> (u64, u64, [u64; 32], u64) {
+        let inst_ctx = &emu.ctx.inst_ctx;
+        (inst_ctx.pc, inst_ctx.step, emu.get_regs_array(), inst_ctx.mem.read(RAM_ADDR, 8))
+    }
+
+    #[test]
+    fn test_snapshot_fuzz() {
+        // Writes the memory only if the first input word is zero, and reads out of the input if
+        // it is shorter than a word
+        let mut branch = ZiskInstBuilder::new(ROM_ENTRY);
+        branch.op("eq").unwrap();
+        branch.src_a("imm", 0, false);
+        branch.src_b("mem", INPUT_ADDR + 16, false);
+        branch.j(4, 8);
+        let mut write = ZiskInstBuilder::new(ROM_ENTRY + 4);
+        write.op("copyb").unwrap();
+        write.src_a("imm", 0, false);
+        write.src_b("imm", 0x2a, false);
+        write.store("mem", RAM_ADDR as i64, false, false);
+        write.j(4, 4);
+        let mut end = ZiskInstBuilder::new(ROM_ENTRY + 8);
+        end.op("flag").unwrap();
+        end.end();
+        let rom = ZiskRom {
+            rom_entry_instructions: vec![branch.i, write.i, end.i],
+            min_program_pc: ROM_ADDR,
+            ..Default::default()
+        };
+        let mut emu = Emu::new(&rom);
+        emu.ctx = emu.create_emu_context(vec![1; 8]);
+        let initial = state(&emu);
+
+        // A snapshot restores the state captured before the run
+        let snapshot = EmuSnapshot::take(&mut emu);
+        emu.ctx.inst_ctx.mem.replace_input(&[0; 8]);
+        while !emu.ctx.inst_ctx.end {
+            emu.step_fast();
+        }
+        assert_eq!(state(&emu), (ROM_ENTRY + 8, 3, initial.2, 0x2a));
+        snapshot.restore(&mut emu);
+        assert_eq!(state(&emu), initial);
+        emu.ctx.inst_ctx.mem.write_journal = None;
+
+        // A corpus generated from a fixed seed gives the same report on every campaign, and
+        // leaves the emulator as it was
+        let mut seed = 0x5eed_u64;
+        let corpus: Vec<Vec<u8>> = (0..32)
+            .map(|_| {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                match seed >> 62 {
+                    0 => vec![0; 8],
+                    1 => vec![(seed >> 8) as u8; 4],
+                    _ => (seed | 1).to_le_bytes().to_vec(),
+                }
+            })
+            .collect();
+        let summary = |report: &FuzzReport| {
+            let crashes: Vec<_> = report.crashes.iter().map(|crash| crash.input).collect();
+            (
+                report.runs,
+                report.steps,
+                report.new_coverage.clone(),
+                crashes,
+                report.timeouts.clone(),
+            )
+        };
+        let report = snapshot_fuzz(&mut emu, &corpus, 100);
+        assert_eq!(state(&emu), initial);
+        assert_eq!(summary(&snapshot_fuzz(&mut emu, &corpus, 100)), summary(&report));
+        assert_eq!(state(&emu), initial);
+
+        // Every input ran from the snapshot: the short ones crash reading the input, and the
+        // rest take the write or the skip path
+        let short = corpus.iter().filter(|input| input.len() < 8).count();
+        let zero = corpus.iter().filter(|input| **input == [0; 8]).count();
+        assert!((short > 0) && (zero > 0) && (short + zero < corpus.len()));
+        assert_eq!(report.runs, corpus.len());
+        assert_eq!(report.crashes.len(), short);
+        assert_eq!(report.steps as usize, 3 * zero + 2 * (corpus.len() - short - zero));
+        assert!(report.timeouts.is_empty());
+        assert_eq!(report.new_coverage, [0, 4]);
+        assert_eq!(report.coverage.covered_edges(), 4);
+    }
+}
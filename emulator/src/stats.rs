@@ -874,6 +874,10 @@ impl Stats {
     pub fn set_main_name(&mut self, value: String) {
         self.main_name = value;
     }
+    /// Returns the PC histogram, i.e. number of times each PC was executed
+    pub fn pc_histogram(&self) -> &HashMap<u64, u64> {
+        &self.pc_histogram
+    }
     #[cfg(feature = "debug_stats_trace")]
     pub fn debug_stats_trace(&mut self, pc: u64) {
         if self.costs.steps == 1 || self.previous_roi != self.current_roi {
@@ -0,0 +1,184 @@
+//! Syscall filtering policy
+//!
+//! * `EmuSyscalls` serves the guest syscalls on the host, so a third-party guest can read the
+//!   inputs, write to stderr or emit hints at will.  `SyscallPolicy` restricts that surface, as a
+//!   seccomp filter does: every syscall is checked against an ordered list of rules before being
+//!   served.
+//! * A rule matches a syscall number and, optionally, ranges of its a0 - a5 arguments, e.g. to
+//!   allow hints only with lengths in a given range.  The first matching rule allows or denies the
+//!   syscall; if no rule matches, the default action applies.
+//! * A denied syscall is not served: it returns -EPERM to the guest with `SyscallDenyAction::Error`
+//!   (the default), or ends the emulation with error at the ecall with `SyscallDenyAction::Trap`.
+//!   Every violation is logged with the pc of the ecall and kept in `EmuSyscalls::violations`.
+//! * With `trace`, every syscall, allowed or not, is recorded in `EmuSyscalls::trace`.
+//! * The exit syscall is left to the trap handler, so it is never filtered.
+
+use std::{fmt, ops::Range};
+
+/// Number of syscall arguments, passed in a0 - a5
+pub const SYSCALL_ARGS: usize = 6;
+
+/// What `EmuSyscalls` does with a syscall denied by the policy
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SyscallDenyAction {
+    /// Return -EPERM to the guest without serving it
+    #[default]
+    Error,
+    /// End the emulation with error at the trap handler
+    Trap,
+}
+
+/// Rule of a `SyscallPolicy`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyscallRule {
+    /// Syscall number, i.e. a7
+    pub number: u64,
+    /// Ranges the arguments must be in for the rule to match, as (argument index, range)
+    pub arg_ranges: Vec<(usize, Range<u64>)>,
+    /// True if the matching syscalls are allowed, false if they are denied
+    pub allow: bool,
+}
+
+impl SyscallRule {
+    /// Returns true if the rule applies to the syscall
+    pub fn matches(&self, number: u64, args: &[u64; SYSCALL_ARGS]) -> bool {
+        (self.number == number)
+            && self.arg_ranges.iter().all(|(arg, range)| range.contains(&args[*arg]))
+    }
+}
+
+/// Ordered list of rules deciding which syscalls are served
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyscallPolicy {
+    /// Rules, checked in order
+    pub rules: Vec<SyscallRule>,
+    /// True if the syscalls not matched by any rule are allowed
+    pub default_allow: bool,
+    /// What is done with the denied syscalls
+    pub deny_action: SyscallDenyAction,
+    /// True to record every syscall
+    pub trace: bool,
+}
+
+impl Default for SyscallPolicy {
+    fn default() -> Self {
+        Self::allow_all()
+    }
+}
+
+impl SyscallPolicy {
+    /// Creates a policy allowing all the syscalls not denied by a rule
+    pub fn allow_all() -> Self {
+        Self {
+            rules: Vec::new(),
+            default_allow: true,
+            deny_action: SyscallDenyAction::default(),
+            trace: false,
+        }
+    }
+
+    /// Creates a policy denying all the syscalls not allowed by a rule
+    pub fn deny_all() -> Self {
+        Self { default_allow: false, ..Self::allow_all() }
+    }
+
+    /// Adds a rule allowing the syscall
+    pub fn allow(self, number: u64) -> Self {
+        self.rule(number, Vec::new(), true)
+    }
+
+    /// Adds a rule denying the syscall
+    pub fn deny(self, number: u64) -> Self {
+        self.rule(number, Vec::new(), false)
+    }
+
+    /// Adds a rule allowing the syscall if argument `arg` (0 for a0) is in `range`
+    pub fn allow_arg(self, number: u64, arg: usize, range: Range<u64>) -> Self {
+        assert!(arg < SYSCALL_ARGS, "SyscallPolicy::allow_arg() invalid argument index {arg}");
+        self.rule(number, vec![(arg, range)], true)
+    }
+
+    /// Adds a rule denying the syscall if argument `arg` (0 for a0) is in `range`
+    pub fn deny_arg(self, number: u64, arg: usize, range: Range<u64>) -> Self {
+        assert!(arg < SYSCALL_ARGS, "SyscallPolicy::deny_arg() invalid argument index {arg}");
+        self.rule(number, vec![(arg, range)], false)
+    }
+
+    /// Sets what is done with the denied syscalls
+    pub fn on_deny(mut self, action: SyscallDenyAction) -> Self {
+        self.deny_action = action;
+        self
+    }
+
+    /// Enables the recording of every syscall
+    pub fn traced(mut self) -> Self {
+        self.trace = true;
+        self
+    }
+
+    /// Returns true if the syscall is allowed
+    pub fn is_allowed(&self, number: u64, args: &[u64; SYSCALL_ARGS]) -> bool {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(number, args))
+            .map_or(self.default_allow, |rule| rule.allow)
+    }
+
+    fn rule(mut self, number: u64, arg_ranges: Vec<(usize, Range<u64>)>, allow: bool) -> Self {
+        self.rules.push(SyscallRule { number, arg_ranges, allow });
+        self
+    }
+}
+
+/// Syscall requested by the guest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyscallRecord {
+    /// Step at which the trap handler was reached
+    pub step: u64,
+    /// Address of the ecall instruction
+    pub pc: u64,
+    /// Syscall number
+    pub number: u64,
+    /// Arguments a0 - a5
+    pub args: [u64; SYSCALL_ARGS],
+    /// True if the policy allowed it
+    pub allowed: bool,
+}
+
+impl fmt::Display for SyscallRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "syscall {} {} at pc=0x{:x} step={} args={:x?}",
+            self.number,
+            if self.allowed { "allowed" } else { "denied" },
+            self.pc,
+            self.step,
+            self.args
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_syscall_policy() {
+        let args = |a0: u64| [a0, 0, 0, 0, 0, 0];
+        assert!(SyscallPolicy::default().is_allowed(0x850, &args(0)));
+
+        // Only hints with a0 in [0x100, 0x200)
+        let policy = SyscallPolicy::deny_all().allow_arg(0x850, 0, 0x100..0x200);
+        assert!(policy.is_allowed(0x850, &args(0x100)));
+        assert!(!policy.is_allowed(0x850, &args(0x200)));
+        assert!(!policy.is_allowed(63, &args(0)));
+
+        // The first matching rule wins
+        let policy = SyscallPolicy::allow_all().deny_arg(64, 0, 2..3).allow(64);
+        assert!(policy.is_allowed(64, &args(1)));
+        assert!(!policy.is_allowed(64, &args(2)));
+        let policy = SyscallPolicy::allow_all().allow(64).deny_arg(64, 0, 2..3);
+        assert!(policy.is_allowed(64, &args(2)));
+    }
+}
@@ -0,0 +1,204 @@
+//! Golden trace export
+//!
+//! * The prover consumes the execution as a list of minimal trace chunks (`EmuTrace`): the state
+//!   at the beginning of every chunk, i.e. pc, sp, c, step and registers, plus the list of memory
+//!   reads done during the chunk, which is all it needs to re-execute the chunk.
+//! * `TraceFormat` serializes and deserializes a list of chunks, so that the interpreter runs can
+//!   be stored as golden traces and fed to the proving pipeline, e.g. in a distributed setup.
+//! * `MinimalTraceFormat` uses the layout of the assembly emulator minimal trace output, i.e. a
+//!   header followed by every chunk and its memory reads, all of them little-endian u64 words, so
+//!   both emulators produce byte-identical traces for the same program and chunk size.
+
+use std::io::{self, Read, Write};
+
+use zisk_common::{EmuTrace, EmuTraceStart};
+use zisk_core::{ZiskRom, REGS_IN_MAIN_FROM, REGS_IN_MAIN_TOTAL_NUMBER};
+
+use crate::{Emu, EmuOptions, ParEmuOptions};
+
+/// Serialization of the minimal trace chunks of an execution
+pub trait TraceFormat {
+    /// Writes the chunks of an execution that ended with `exit_code`
+    fn write(&self, chunks: &[EmuTrace], exit_code: u64, writer: &mut dyn Write) -> io::Result<()>;
+
+    /// Reads the chunks of an execution, and its exit code
+    fn read(&self, reader: &mut dyn Read) -> io::Result<(Vec<EmuTrace>, u64)>;
+}
+
+/// Layout of the assembly emulator minimal trace output
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MinimalTraceFormat;
+
+impl MinimalTraceFormat {
+    /// Version of the layout
+    pub const VERSION: u64 = 1;
+    /// Number of words of the header: version, exit code, allocated size, used size and number
+    /// of chunks
+    const HEADER_WORDS: usize = 5;
+    /// Number of registers of every chunk, as stored by the assembly emulator
+    const CHUNK_REGS: usize = 33;
+    /// Number of words of every chunk, before its memory reads: pc, sp, c, step, registers,
+    /// last c, end, steps and number of memory reads
+    const CHUNK_WORDS: usize = 4 + Self::CHUNK_REGS + 4;
+}
+
+impl TraceFormat for MinimalTraceFormat {
+    fn write(&self, chunks: &[EmuTrace], exit_code: u64, writer: &mut dyn Write) -> io::Result<()> {
+        let words = Self::HEADER_WORDS
+            + chunks.iter().map(|chunk| Self::CHUNK_WORDS + chunk.mem_reads.len()).sum::<usize>();
+        let size = (words * 8) as u64;
+
+        let mut data: Vec<u64> = Vec::with_capacity(words);
+        data.extend_from_slice(&[Self::VERSION, exit_code, size, size, chunks.len() as u64]);
+        for chunk in chunks {
+            let start = &chunk.start_state;
+            data.extend_from_slice(&[start.pc, start.sp, start.c, start.step]);
+
+            // The registers are stored from x1 on, since x0 is always zero
+            let mut regs = [0u64; Self::CHUNK_REGS];
+            let regs_len = REGS_IN_MAIN_TOTAL_NUMBER - REGS_IN_MAIN_FROM;
+            regs[..regs_len].copy_from_slice(&start.regs[REGS_IN_MAIN_FROM..]);
+            data.extend_from_slice(&regs);
+
+            data.extend_from_slice(&[
+                chunk.last_c,
+                chunk.end as u64,
+                chunk.steps,
+                chunk.mem_reads.len() as u64,
+            ]);
+            data.extend_from_slice(&chunk.mem_reads);
+        }
+
+        let bytes: Vec<u8> = data.iter().flat_map(|word| word.to_le_bytes()).collect();
+        writer.write_all(&bytes)
+    }
+
+    fn read(&self, reader: &mut dyn Read) -> io::Result<(Vec<EmuTrace>, u64)> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        if bytes.len() % 8 != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "trace size is not a multiple of 8",
+            ));
+        }
+        let data: Vec<u64> = bytes
+            .chunks_exact(8)
+            .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+
+        let truncated = || io::Error::new(io::ErrorKind::UnexpectedEof, "truncated trace");
+        let header = data.get(..Self::HEADER_WORDS).ok_or_else(truncated)?;
+        if header[0] != Self::VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported trace version {}", header[0]),
+            ));
+        }
+        let exit_code = header[1];
+        let num_chunks = header[4] as usize;
+
+        let mut pos = Self::HEADER_WORDS;
+        let mut chunks = Vec::with_capacity(num_chunks);
+        for _ in 0..num_chunks {
+            let chunk = data.get(pos..pos + Self::CHUNK_WORDS).ok_or_else(truncated)?;
+            let (start, rest) = chunk.split_at(4);
+            let (chunk_regs, rest) = rest.split_at(Self::CHUNK_REGS);
+
+            let mut regs = [0u64; REGS_IN_MAIN_TOTAL_NUMBER];
+            let regs_len = REGS_IN_MAIN_TOTAL_NUMBER - REGS_IN_MAIN_FROM;
+            regs[REGS_IN_MAIN_FROM..].copy_from_slice(&chunk_regs[..regs_len]);
+
+            pos += Self::CHUNK_WORDS;
+            let mem_reads_len = rest[3] as usize;
+            let mem_reads = data.get(pos..pos + mem_reads_len).ok_or_else(truncated)?.to_vec();
+            pos += mem_reads_len;
+
+            chunks.push(EmuTrace {
+                start_state: EmuTraceStart {
+                    pc: start[0],
+                    sp: start[1],
+                    c: start[2],
+                    step: start[3],
+                    regs,
+                },
+                last_c: rest[0],
+                end: rest[1] == 1,
+                steps: rest[2],
+                mem_reads,
+            });
+        }
+
+        Ok((chunks, exit_code))
+    }
+}
+
+/// Runs the program with the interpreter, splitting the execution in chunks of
+/// `options.chunk_size` steps, and writes its minimal trace using the provided format; the exit
+/// code is 1 if the program ended with error, or 0 otherwise
+pub fn export_trace(
+    rom: &ZiskRom,
+    inputs: &[u8],
+    options: &EmuOptions,
+    format: &dyn TraceFormat,
+    writer: &mut dyn Write,
+) -> io::Result<()> {
+    let chunk_size = options.chunk_size.expect("export_trace() chunk_size is required") as usize;
+    let par_options = ParEmuOptions::new(1, 0, chunk_size);
+
+    let mut emu = Emu::new(rom);
+    let chunks = emu.par_run(inputs.to_owned(), options, &par_options);
+    if !emu.terminated() {
+        return Err(io::Error::other("export_trace() emulation did not complete"));
+    }
+
+    let exit_code = if emu.ctx.inst_ctx.error { 1 } else { 0 };
+    format.write(&chunks, exit_code, writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minimal_trace_format() {
+        let mut regs = [0u64; REGS_IN_MAIN_TOTAL_NUMBER];
+        regs.iter_mut().enumerate().skip(1).for_each(|(i, reg)| *reg = 100 + i as u64);
+        let chunks = vec![
+            EmuTrace {
+                start_state: EmuTraceStart { pc: 0x1000, sp: 0, c: 3, step: 0, regs },
+                last_c: 7,
+                steps: 4,
+                mem_reads: vec![11, 12, 13],
+                end: false,
+            },
+            EmuTrace {
+                start_state: EmuTraceStart { pc: 0x1010, sp: 0, c: 7, step: 4, regs },
+                last_c: 0,
+                steps: 2,
+                mem_reads: Vec::new(),
+                end: true,
+            },
+        ];
+
+        let mut bytes = Vec::new();
+        MinimalTraceFormat.write(&chunks, 0, &mut bytes).unwrap();
+        assert_eq!(bytes.len(), 8 * (5 + 2 * 41 + 3));
+
+        // x1 is stored right after pc, sp, c and step
+        let word = |i: usize| u64::from_le_bytes(bytes[8 * i..8 * i + 8].try_into().unwrap());
+        assert_eq!((word(4), word(5), word(9)), (2, 0x1000, 101));
+
+        let (read, exit_code) = MinimalTraceFormat.read(&mut bytes.as_slice()).unwrap();
+        assert_eq!(exit_code, 0);
+        assert_eq!(read.len(), 2);
+        for (a, b) in read.iter().zip(chunks.iter()) {
+            assert_eq!(a.start_state.pc, b.start_state.pc);
+            assert_eq!(a.start_state.regs, b.start_state.regs);
+            assert_eq!((a.last_c, a.steps, a.end), (b.last_c, b.steps, b.end));
+            assert_eq!(a.mem_reads, b.mem_reads);
+        }
+
+        assert!(MinimalTraceFormat.read(&mut &bytes[..bytes.len() - 8]).is_err());
+    }
+}
@@ -4,6 +4,7 @@
 #include "../bls12_381/bls12_381_fe.hpp"
 #include <stdint.h>
 #include <assert.h>
+#include <string.h>
 
 int Fcall (
     struct FcallContext * ctx  // fcall context
@@ -98,6 +99,11 @@ int Fcall (
             iresult = BinDecompCtx(ctx);
             break;
         }
+        case FCALL_INPUT_PAGE_ID:
+        {
+            iresult = InputPageCtx(ctx);
+            break;
+        }
         default:
         {
             printf("Fcall() found unsupported function_id=%lu\n", ctx->function_id);
@@ -964,4 +970,51 @@ int BinDecompCtx (
     ctx->result_size++;
     
     return 0;
-}
\ No newline at end of file
+}
+
+/**************/
+/* INPUT PAGE */
+/**************/
+
+static const uint8_t * fcall_input_section = NULL;
+
+void FcallSetInputSection (
+    const uint8_t * input_section // input section address, or NULL if there is no input
+)
+{
+    fcall_input_section = input_section;
+}
+
+int InputPageCtx (
+    struct FcallContext * ctx  // fcall context
+)
+{
+    // Parse input parameter
+    uint64_t page = ctx->params[0];
+
+    // Get the input length and data from the input section, if any
+    uint64_t input_len = 0;
+    const uint8_t * input = NULL;
+    if (fcall_input_section != NULL)
+    {
+        input_len = ((const uint64_t *)fcall_input_section)[1];
+        input = fcall_input_section + 16;
+    }
+
+    // Copy the page bytes, padding with zeros past the end of the input
+    ctx->result[0] = input_len;
+    memset(&ctx->result[1], 0, FCALL_INPUT_PAGE_SIZE);
+    if (page < input_len / FCALL_INPUT_PAGE_SIZE + 1)
+    {
+        uint64_t start = page * FCALL_INPUT_PAGE_SIZE;
+        uint64_t end = start + FCALL_INPUT_PAGE_SIZE;
+        if (end > input_len) end = input_len;
+        if (start < end)
+        {
+            memcpy(&ctx->result[1], input + start, end - start);
+        }
+    }
+
+    ctx->result_size = 1 + FCALL_INPUT_PAGE_WORDS;
+    return ctx->result_size;
+}
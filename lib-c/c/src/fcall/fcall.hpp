@@ -25,6 +25,10 @@ extern "C" {
 #define FCALL_BIGINT256_DIV_ID 16
 #define FCALL_BIG_INT_DIV_ID 17
 #define FCALL_BIN_DECOMP_ID 18
+#define FCALL_INPUT_PAGE_ID 19
+
+#define FCALL_INPUT_PAGE_SIZE 4096
+#define FCALL_INPUT_PAGE_WORDS (FCALL_INPUT_PAGE_SIZE / 8)
 
 #define FCALL_PARAMS_MAX_SIZE 386
 #define FCALL_RESULT_MAX_SIZE 8193
@@ -98,6 +102,15 @@ int BigIntDivCtx (
 int BinDecompCtx (
     struct FcallContext * ctx  // fcall context
 );
+int InputPageCtx (
+    struct FcallContext * ctx  // fcall context
+);
+
+// Sets the input section served by the input page fcall; the section starts with the free input
+// and input length u64 words, followed by the input data
+void FcallSetInputSection (
+    const uint8_t * input_section // input section address, or NULL if there is no input
+);
 
 // Functions supported by fcall, in u64 array format
 int InverseFpEc (
@@ -4,6 +4,7 @@
 
 use std::{collections::VecDeque, ops::Add};
 
+use precompiles_common::{AddrOverflow, MemBusHelpers};
 use zisk_common::MemCollectorInfo;
 use zisk_common::{
     BusDevice, BusDeviceMode, BusId, Counter, Metrics, A, B, OP, OPERATION_BUS_ID, OP_TYPE,
@@ -72,8 +73,12 @@ impl ArithEqCounterInputGen {
         (op_type == ZiskOperationType::ArithEq).then_some(self.counter.inst_count)
     }
 
-    fn skip_data(&self, data: &[u64], mem_collectors_info: &[MemCollectorInfo]) -> bool {
-        let addr_main = data[B] as u32;
+    fn skip_data(
+        &self,
+        data: &[u64],
+        mem_collectors_info: &[MemCollectorInfo],
+    ) -> Result<bool, AddrOverflow> {
+        let addr_main = MemBusHelpers::try_narrow_addr(data[B])?;
 
         match data[OP] as u8 {
             ARITH256_OP => skip_arith256_mem_inputs(addr_main, data, mem_collectors_info),
@@ -100,83 +105,24 @@ impl ArithEqCounterInputGen {
             }
         }
     }
-}
-
-impl Metrics for ArithEqCounterInputGen {
-    /// Tracks activity on the connected bus and updates counters for recognized operations.
-    ///
-    /// # Arguments
-    /// * `_bus_id` - The ID of the bus (unused in this implementation).
-    /// * `_data` - The data received from the bus.
-    ///
-    /// # Returns
-    /// An empty vector, as this implementation does not produce any derived inputs for the bus.
-    #[inline(always)]
-    fn measure(&mut self, _data: &[u64]) {
-        self.counter.update(1);
-    }
-
-    /// Provides a dynamic reference for downcasting purposes.
-    ///
-    /// # Returns
-    /// A reference to `self` as `dyn std::any::Any`.
-    fn as_any(&self) -> &dyn std::any::Any {
-        self
-    }
-}
-
-impl Add for ArithEqCounterInputGen {
-    type Output = ArithEqCounterInputGen;
-
-    /// Combines two `Arith256Counter` instances by summing their counters.
-    ///
-    /// # Arguments
-    /// * `self` - The first `Arith256Counter` instance.
-    /// * `other` - The second `Arith256Counter` instance.
-    ///
-    /// # Returns
-    /// A new `Arith256Counter` with combined counters.
-    fn add(self, other: Self) -> ArithEqCounterInputGen {
-        ArithEqCounterInputGen { counter: &self.counter + &other.counter, mode: self.mode }
-    }
-}
 
-impl BusDevice<u64> for ArithEqCounterInputGen {
-    /// Processes data received on the bus, updating counters and generating inputs when applicable.
-    ///
-    /// # Arguments
-    /// * `bus_id` - The ID of the bus sending the data.
-    /// * `data` - The data received from the bus.
-    /// * `pending` – A queue of pending bus operations used to send derived inputs.
-    ///
-    /// # Returns
-    /// A boolean indicating whether the program should continue execution or terminate.
-    /// Returns `true` to continue execution, `false` to stop.
-    #[inline(always)]
-    fn process_data(
+    /// Generates the memory inputs of an operation, unless the memory collectors skip all of them,
+    /// failing if any of its memory addresses overflows the 32-bit address space
+    fn process_operation(
         &mut self,
-        bus_id: &BusId,
         data: &[u64],
         pending: &mut VecDeque<(BusId, Vec<u64>)>,
         mem_collector_info: Option<&[MemCollectorInfo]>,
-    ) -> bool {
-        debug_assert!(*bus_id == OPERATION_BUS_ID);
-
-        const ARITH_EQ: u64 = ZiskOperationType::ArithEq as u64;
-
-        if data[OP_TYPE] != ARITH_EQ {
-            return true;
-        }
-
+    ) -> Result<(), AddrOverflow> {
         if let Some(mem_collectors_info) = mem_collector_info {
-            if self.skip_data(data, mem_collectors_info) {
-                return true;
+            if self.skip_data(data, mem_collectors_info)? {
+                return Ok(());
             }
         }
 
         let op = data[OP] as u8;
         let step_main = data[A];
-        let addr_main = data[B] as u32;
+        let addr_main = MemBusHelpers::try_narrow_addr(data[B])?;
 
         let only_counters = self.mode == BusDeviceMode::Counter;
         if only_counters {
@@ -185,7 +131,7 @@ impl BusDevice<u64> for ArithEqCounterInputGen {
 
         match op {
             ARITH256_OP => {
-                generate_arith256_mem_inputs(addr_main, step_main, data, only_counters, pending);
+                generate_arith256_mem_inputs(addr_main, step_main, data, only_counters, pending)?;
             }
             ARITH256_MOD_OP => {
                 generate_arith256_mod_mem_inputs(
@@ -194,7 +140,7 @@ impl BusDevice<u64> for ArithEqCounterInputGen {
                     data,
                     only_counters,
                     pending,
-                );
+                )?;
             }
             SECP256K1_ADD_OP => {
                 generate_secp256k1_add_mem_inputs(
@@ -203,7 +149,7 @@ impl BusDevice<u64> for ArithEqCounterInputGen {
                     data,
                     only_counters,
                     pending,
-                );
+                )?;
             }
             SECP256K1_DBL_OP => {
                 generate_secp256k1_dbl_mem_inputs(
@@ -212,7 +158,7 @@ impl BusDevice<u64> for ArithEqCounterInputGen {
                     data,
                     only_counters,
                     pending,
-                );
+                )?;
             }
             BN254_CURVE_ADD_OP => {
                 generate_bn254_curve_add_mem_inputs(
@@ -221,7 +167,7 @@ impl BusDevice<u64> for ArithEqCounterInputGen {
                     data,
                     only_counters,
                     pending,
-                );
+                )?;
             }
             BN254_CURVE_DBL_OP => {
                 generate_bn254_curve_dbl_mem_inputs(
@@ -230,7 +176,7 @@ impl BusDevice<u64> for ArithEqCounterInputGen {
                     data,
                     only_counters,
                     pending,
-                );
+                )?;
             }
             BN254_COMPLEX_ADD_OP => {
                 generate_bn254_complex_add_mem_inputs(
@@ -239,7 +185,7 @@ impl BusDevice<u64> for ArithEqCounterInputGen {
                     data,
                     only_counters,
                     pending,
-                );
+                )?;
             }
             BN254_COMPLEX_SUB_OP => {
                 generate_bn254_complex_sub_mem_inputs(
@@ -248,7 +194,7 @@ impl BusDevice<u64> for ArithEqCounterInputGen {
                     data,
                     only_counters,
                     pending,
-                );
+                )?;
             }
             BN254_COMPLEX_MUL_OP => {
                 generate_bn254_complex_mul_mem_inputs(
@@ -257,7 +203,7 @@ impl BusDevice<u64> for ArithEqCounterInputGen {
                     data,
                     only_counters,
                     pending,
-                );
+                )?;
             }
 
             _ => {
@@ -265,6 +211,82 @@ impl BusDevice<u64> for ArithEqCounterInputGen {
             }
         }
 
+        Ok(())
+    }
+}
+
+impl Metrics for ArithEqCounterInputGen {
+    /// Tracks activity on the connected bus and updates counters for recognized operations.
+    ///
+    /// # Arguments
+    /// * `_bus_id` - The ID of the bus (unused in this implementation).
+    /// * `_data` - The data received from the bus.
+    ///
+    /// # Returns
+    /// An empty vector, as this implementation does not produce any derived inputs for the bus.
+    #[inline(always)]
+    fn measure(&mut self, _data: &[u64]) {
+        self.counter.update(1);
+    }
+
+    /// Provides a dynamic reference for downcasting purposes.
+    ///
+    /// # Returns
+    /// A reference to `self` as `dyn std::any::Any`.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+impl Add for ArithEqCounterInputGen {
+    type Output = ArithEqCounterInputGen;
+
+    /// Combines two `Arith256Counter` instances by summing their counters.
+    ///
+    /// # Arguments
+    /// * `self` - The first `Arith256Counter` instance.
+    /// * `other` - The second `Arith256Counter` instance.
+    ///
+    /// # Returns
+    /// A new `Arith256Counter` with combined counters.
+    fn add(self, other: Self) -> ArithEqCounterInputGen {
+        ArithEqCounterInputGen { counter: &self.counter + &other.counter, mode: self.mode }
+    }
+}
+
+impl BusDevice<u64> for ArithEqCounterInputGen {
+    /// Processes data received on the bus, updating counters and generating inputs when applicable.
+    ///
+    /// # Arguments
+    /// * `bus_id` - The ID of the bus sending the data.
+    /// * `data` - The data received from the bus.
+    /// * `pending` – A queue of pending bus operations used to send derived inputs.
+    ///
+    /// # Returns
+    /// A boolean indicating whether the program should continue execution or terminate.
+    /// Returns `true` to continue execution, `false` to stop.
+    #[inline(always)]
+    fn process_data(
+        &mut self,
+        bus_id: &BusId,
+        data: &[u64],
+        pending: &mut VecDeque<(BusId, Vec<u64>)>,
+        mem_collector_info: Option<&[MemCollectorInfo]>,
+    ) -> bool {
+        debug_assert!(*bus_id == OPERATION_BUS_ID);
+
+        const ARITH_EQ: u64 = ZiskOperationType::ArithEq as u64;
+
+        if data[OP_TYPE] != ARITH_EQ {
+            return true;
+        }
+
+        // The bus has no error channel, so an operation whose memory addresses overflow the 32-bit
+        // address space cannot be processed
+        if let Err(e) = self.process_operation(data, pending, mem_collector_info) {
+            panic!("ArithEqCounterInputGen::process_data() {e}");
+        }
+
         true
     }
 
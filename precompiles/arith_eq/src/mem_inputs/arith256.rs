@@ -1,5 +1,6 @@
 use super::ArithEqMemInputConfig;
 use crate::executors::Arith256;
+use precompiles_common::AddrOverflow;
 use std::collections::VecDeque;
 use zisk_common::BusId;
 use zisk_common::MemCollectorInfo;
@@ -18,7 +19,7 @@ pub fn generate_arith256_mem_inputs(
     data: &[u64],
     only_counters: bool,
     pending: &mut VecDeque<(BusId, Vec<u64>)>,
-) {
+) -> Result<(), AddrOverflow> {
     // op,op_type,a,b,addr[5],...
     let a: &[u64; 4] = &data[9..13].try_into().unwrap();
     let b: &[u64; 4] = &data[13..17].try_into().unwrap();
@@ -40,13 +41,13 @@ pub fn generate_arith256_mem_inputs(
         only_counters,
         pending,
         &ARITH_256_MEM_CONFIG,
-    );
+    )
 }
 
 pub fn skip_arith256_mem_inputs(
     addr_main: u32,
     data: &[u64],
     mem_collectors_info: &[MemCollectorInfo],
-) -> bool {
+) -> Result<bool, AddrOverflow> {
     super::skip_mem_inputs(addr_main, data, &ARITH_256_MEM_CONFIG, mem_collectors_info)
 }
@@ -1,5 +1,6 @@
 use super::ArithEqMemInputConfig;
 use crate::executors::Bn254Complex;
+use precompiles_common::AddrOverflow;
 use std::collections::VecDeque;
 use zisk_common::BusId;
 use zisk_common::MemCollectorInfo;
@@ -18,7 +19,7 @@ pub fn generate_bn254_complex_mul_mem_inputs(
     data: &[u64],
     only_counters: bool,
     pending: &mut VecDeque<(BusId, Vec<u64>)>,
-) {
+) -> Result<(), AddrOverflow> {
     // op,op_type,a,b,addr[2],...
     let f1: &[u64; 8] = &data[6..14].try_into().unwrap();
     let f2: &[u64; 8] = &data[14..22].try_into().unwrap();
@@ -33,13 +34,13 @@ pub fn generate_bn254_complex_mul_mem_inputs(
         only_counters,
         pending,
         &BN254_COMPLEX_MUL_MEM_CONFIG,
-    );
+    )
 }
 
 pub fn skip_bn254_complex_mul_mem_inputs(
     addr_main: u32,
     data: &[u64],
     mem_collectors_info: &[MemCollectorInfo],
-) -> bool {
+) -> Result<bool, AddrOverflow> {
     super::skip_mem_inputs(addr_main, data, &BN254_COMPLEX_MUL_MEM_CONFIG, mem_collectors_info)
 }
@@ -1,4 +1,4 @@
-use precompiles_common::MemBusHelpers;
+use precompiles_common::{AddrOverflow, MemBusHelpers};
 use std::collections::VecDeque;
 use zisk_common::MemCollectorInfo;
 use zisk_common::{BusId, OPERATION_BUS_DATA_SIZE};
@@ -19,13 +19,13 @@ pub fn generate_mem_inputs(
     only_counters: bool,
     pending: &mut VecDeque<(BusId, Vec<u64>)>,
     config: &ArithEqMemInputConfig,
-) {
+) -> Result<(), AddrOverflow> {
     let params_count = config.read_params + config.write_params;
     let params_offset = OPERATION_BUS_DATA_SIZE + config.indirect_params;
 
     for iparam in 0..config.indirect_params {
         MemBusHelpers::mem_aligned_load(
-            addr_main + iparam as u32 * 8,
+            MemBusHelpers::try_chunk_addr(addr_main, iparam)?,
             step_main,
             data[OPERATION_BUS_DATA_SIZE + iparam],
             pending,
@@ -39,9 +39,9 @@ pub fn generate_mem_inputs(
         };
         let param_addr = if config.indirect_params > 0 {
             // read indirect parameters, means stored the address of parameter
-            data[OPERATION_BUS_DATA_SIZE + param_index] as u32
+            MemBusHelpers::try_narrow_addr(data[OPERATION_BUS_DATA_SIZE + param_index])?
         } else {
-            addr_main + (param_index * 8 * config.chunks_per_param) as u32
+            MemBusHelpers::try_chunk_addr(addr_main, param_index * config.chunks_per_param)?
         };
 
         // read/write all chunks of the iparam parameter
@@ -62,7 +62,7 @@ pub fn generate_mem_inputs(
                 data[current_param_offset + ichunk]
             };
             MemBusHelpers::mem_aligned_op(
-                param_addr + ichunk as u32 * 8,
+                MemBusHelpers::try_chunk_addr(param_addr, ichunk)?,
                 step_main,
                 chunk_data,
                 is_write,
@@ -70,6 +70,8 @@ pub fn generate_mem_inputs(
             )
         }
     }
+
+    Ok(())
 }
 
 pub fn skip_mem_inputs(
@@ -77,15 +79,15 @@ pub fn skip_mem_inputs(
     data: &[u64],
     config: &ArithEqMemInputConfig,
     mem_collectors_info: &[MemCollectorInfo],
-) -> bool {
+) -> Result<bool, AddrOverflow> {
     let params_count = config.read_params + config.write_params;
 
     // Check indirect loads
     for iparam in 0..config.indirect_params {
-        let addr = addr_main + iparam as u32 * 8;
+        let addr = MemBusHelpers::try_chunk_addr(addr_main, iparam)?;
         for mem_collector in mem_collectors_info {
             if !mem_collector.skip_addr(addr) {
-                return false;
+                return Ok(false);
             }
         }
     }
@@ -97,18 +99,18 @@ pub fn skip_mem_inputs(
             iparam
         };
         let param_addr = if config.indirect_params > 0 {
-            data[OPERATION_BUS_DATA_SIZE + param_index] as u32
+            MemBusHelpers::try_narrow_addr(data[OPERATION_BUS_DATA_SIZE + param_index])?
         } else {
-            addr_main + (param_index * 8 * config.chunks_per_param) as u32
+            MemBusHelpers::try_chunk_addr(addr_main, param_index * config.chunks_per_param)?
         };
         for ichunk in 0..config.chunks_per_param {
-            let addr = param_addr + ichunk as u32 * 8;
+            let addr = MemBusHelpers::try_chunk_addr(param_addr, ichunk)?;
             for mem_collector in mem_collectors_info {
                 if !mem_collector.skip_addr(addr) {
-                    return false;
+                    return Ok(false);
                 }
             }
         }
     }
-    true
+    Ok(true)
 }
@@ -1,5 +1,6 @@
 use super::ArithEqMemInputConfig;
 use crate::executors::Secp256k1;
+use precompiles_common::AddrOverflow;
 use std::collections::VecDeque;
 use zisk_common::BusId;
 use zisk_common::MemCollectorInfo;
@@ -18,7 +19,7 @@ pub fn generate_secp256k1_dbl_mem_inputs(
     data: &[u64],
     only_counters: bool,
     pending: &mut VecDeque<(BusId, Vec<u64>)>,
-) {
+) -> Result<(), AddrOverflow> {
     // op,op_type,a,b,...
     let p1: &[u64; 8] = &data[4..12].try_into().unwrap();
     let mut p3 = [0u64; 8];
@@ -32,13 +33,13 @@ pub fn generate_secp256k1_dbl_mem_inputs(
         only_counters,
         pending,
         &SECP256K1_DBL_MEM_CONFIG,
-    );
+    )
 }
 
 pub fn skip_secp256k1_dbl_mem_inputs(
     addr_main: u32,
     data: &[u64],
     mem_collectors_info: &[MemCollectorInfo],
-) -> bool {
+) -> Result<bool, AddrOverflow> {
     super::skip_mem_inputs(addr_main, data, &SECP256K1_DBL_MEM_CONFIG, mem_collectors_info)
 }
@@ -4,6 +4,7 @@
 
 use std::{collections::VecDeque, ops::Add};
 
+use precompiles_common::{AddrOverflow, MemBusHelpers};
 use zisk_common::{
     BusDevice, BusDeviceMode, BusId, Counter, MemCollectorInfo, Metrics, A, B, OP,
     OPERATION_BUS_ID, OP_TYPE,
@@ -63,8 +64,12 @@ impl ArithEq384CounterInputGen {
         (op_type == ZiskOperationType::ArithEq384).then_some(self.counter.inst_count)
     }
 
-    fn skip_data(&self, data: &[u64], mem_collectors_info: &[MemCollectorInfo]) -> bool {
-        let addr_main = data[B] as u32;
+    fn skip_data(
+        &self,
+        data: &[u64],
+        mem_collectors_info: &[MemCollectorInfo],
+    ) -> Result<bool, AddrOverflow> {
+        let addr_main = MemBusHelpers::try_narrow_addr(data[B])?;
 
         match data[OP] as u8 {
             ARITH384_MOD_OP => skip_arith384_mod_mem_inputs(addr_main, data, mem_collectors_info),
@@ -88,83 +93,24 @@ impl ArithEq384CounterInputGen {
             }
         }
     }
-}
-
-impl Metrics for ArithEq384CounterInputGen {
-    /// Tracks activity on the connected bus and updates counters for recognized operations.
-    ///
-    /// # Arguments
-    /// * `_bus_id` - The ID of the bus (unused in this implementation).
-    /// * `_data` - The data received from the bus.
-    ///
-    /// # Returns
-    /// An empty vector, as this implementation does not produce any derived inputs for the bus.
-    #[inline(always)]
-    fn measure(&mut self, _data: &[u64]) {
-        self.counter.update(1);
-    }
-
-    /// Provides a dynamic reference for downcasting purposes.
-    ///
-    /// # Returns
-    /// A reference to `self` as `dyn std::any::Any`.
-    fn as_any(&self) -> &dyn std::any::Any {
-        self
-    }
-}
-
-impl Add for ArithEq384CounterInputGen {
-    type Output = ArithEq384CounterInputGen;
-
-    /// Combines two `Arith384Counter` instances by summing their counters.
-    ///
-    /// # Arguments
-    /// * `self` - The first `Arith384Counter` instance.
-    /// * `other` - The second `Arith384Counter` instance.
-    ///
-    /// # Returns
-    /// A new `Arith384Counter` with combined counters.
-    fn add(self, other: Self) -> ArithEq384CounterInputGen {
-        ArithEq384CounterInputGen { counter: &self.counter + &other.counter, mode: self.mode }
-    }
-}
 
-impl BusDevice<u64> for ArithEq384CounterInputGen {
-    /// Processes data received on the bus, updating counters and generating inputs when applicable.
-    ///
-    /// # Arguments
-    /// * `bus_id` - The ID of the bus sending the data.
-    /// * `data` - The data received from the bus.
-    /// * `pending` – A queue of pending bus operations used to send derived inputs.
-    ///
-    /// # Returns
-    /// A boolean indicating whether the program should continue execution or terminate.
-    /// Returns `true` to continue execution, `false` to stop.
-    #[inline(always)]
-    fn process_data(
+    /// Generates the memory inputs of an operation, unless the memory collectors skip all of them,
+    /// failing if any of its memory addresses overflows the 32-bit address space
+    fn process_operation(
         &mut self,
-        bus_id: &BusId,
         data: &[u64],
         pending: &mut VecDeque<(BusId, Vec<u64>)>,
         mem_collector_info: Option<&[MemCollectorInfo]>,
-    ) -> bool {
-        debug_assert!(*bus_id == OPERATION_BUS_ID);
-
-        const ARITH_EQ_384: u64 = ZiskOperationType::ArithEq384 as u64;
-
-        if data[OP_TYPE] != ARITH_EQ_384 {
-            return true;
-        }
-
+    ) -> Result<(), AddrOverflow> {
         if let Some(mem_collectors_info) = mem_collector_info {
-            if self.skip_data(data, mem_collectors_info) {
-                return true;
+            if self.skip_data(data, mem_collectors_info)? {
+                return Ok(());
             }
         }
 
         let op = data[OP] as u8;
         let step_main = data[A];
-        let addr_main = data[B] as u32;
+        let addr_main = MemBusHelpers::try_narrow_addr(data[B])?;
 
         let only_counters = self.mode == BusDeviceMode::Counter;
         if only_counters {
@@ -179,7 +125,7 @@ impl BusDevice<u64> for ArithEq384CounterInputGen {
                     data,
                     only_counters,
                     pending,
-                );
+                )?;
             }
             BLS12_381_CURVE_ADD_OP => {
                 generate_bls12_381_curve_add_mem_inputs(
@@ -188,7 +134,7 @@ impl BusDevice<u64> for ArithEq384CounterInputGen {
                     data,
                     only_counters,
                     pending,
-                );
+                )?;
             }
             BLS12_381_CURVE_DBL_OP => {
                 generate_bls12_381_curve_dbl_mem_inputs(
@@ -197,7 +143,7 @@ impl BusDevice<u64> for ArithEq384CounterInputGen {
                     data,
                     only_counters,
                     pending,
-                );
+                )?;
             }
             BLS12_381_COMPLEX_ADD_OP => {
                 generate_bls12_381_complex_add_mem_inputs(
@@ -206,7 +152,7 @@ impl BusDevice<u64> for ArithEq384CounterInputGen {
                     data,
                     only_counters,
                     pending,
-                );
+                )?;
             }
             BLS12_381_COMPLEX_SUB_OP => {
                 generate_bls12_381_complex_sub_mem_inputs(
@@ -215,7 +161,7 @@ impl BusDevice<u64> for ArithEq384CounterInputGen {
                     data,
                     only_counters,
                     pending,
-                );
+                )?;
             }
             BLS12_381_COMPLEX_MUL_OP => {
                 generate_bls12_381_complex_mul_mem_inputs(
@@ -224,13 +170,89 @@ impl BusDevice<u64> for ArithEq384CounterInputGen {
                     data,
                     only_counters,
                     pending,
-                );
+                )?;
             }
             _ => {
                 panic!("ArithEq384CounterInputGen: Unsupported data length {}", data.len());
             }
         }
 
+        Ok(())
+    }
+}
+
+impl Metrics for ArithEq384CounterInputGen {
+    /// Tracks activity on the connected bus and updates counters for recognized operations.
+    ///
+    /// # Arguments
+    /// * `_bus_id` - The ID of the bus (unused in this implementation).
+    /// * `_data` - The data received from the bus.
+    ///
+    /// # Returns
+    /// An empty vector, as this implementation does not produce any derived inputs for the bus.
+    #[inline(always)]
+    fn measure(&mut self, _data: &[u64]) {
+        self.counter.update(1);
+    }
+
+    /// Provides a dynamic reference for downcasting purposes.
+    ///
+    /// # Returns
+    /// A reference to `self` as `dyn std::any::Any`.
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+impl Add for ArithEq384CounterInputGen {
+    type Output = ArithEq384CounterInputGen;
+
+    /// Combines two `Arith384Counter` instances by summing their counters.
+    ///
+    /// # Arguments
+    /// * `self` - The first `Arith384Counter` instance.
+    /// * `other` - The second `Arith384Counter` instance.
+    ///
+    /// # Returns
+    /// A new `Arith384Counter` with combined counters.
+    fn add(self, other: Self) -> ArithEq384CounterInputGen {
+        ArithEq384CounterInputGen { counter: &self.counter + &other.counter, mode: self.mode }
+    }
+}
+
+impl BusDevice<u64> for ArithEq384CounterInputGen {
+    /// Processes data received on the bus, updating counters and generating inputs when applicable.
+    ///
+    /// # Arguments
+    /// * `bus_id` - The ID of the bus sending the data.
+    /// * `data` - The data received from the bus.
+    /// * `pending` – A queue of pending bus operations used to send derived inputs.
+    ///
+    /// # Returns
+    /// A boolean indicating whether the program should continue execution or terminate.
+    /// Returns `true` to continue execution, `false` to stop.
+    #[inline(always)]
+    fn process_data(
+        &mut self,
+        bus_id: &BusId,
+        data: &[u64],
+        pending: &mut VecDeque<(BusId, Vec<u64>)>,
+        mem_collector_info: Option<&[MemCollectorInfo]>,
+    ) -> bool {
+        debug_assert!(*bus_id == OPERATION_BUS_ID);
+
+        const ARITH_EQ_384: u64 = ZiskOperationType::ArithEq384 as u64;
+
+        if data[OP_TYPE] != ARITH_EQ_384 {
+            return true;
+        }
+
+        // The bus has no error channel, so an operation whose memory addresses overflow the 32-bit
+        // address space cannot be processed
+        if let Err(e) = self.process_operation(data, pending, mem_collector_info) {
+            panic!("ArithEq384CounterInputGen::process_data() {e}");
+        }
+
         true
     }
 
@@ -1,3 +1,4 @@
+use precompiles_common::AddrOverflow;
 use std::collections::VecDeque;
 use zisk_common::{BusId, MemCollectorInfo};
 
@@ -18,7 +19,7 @@ pub fn generate_bls12_381_complex_mul_mem_inputs(
     data: &[u64],
     only_counters: bool,
     pending: &mut VecDeque<(BusId, Vec<u64>)>,
-) {
+) -> Result<(), AddrOverflow> {
     let mut pos_offset: usize = 6; // op,op_type,a,b,addr[2],...
     let f1: &[u64; ARITH_EQ_384_U64S_DOUBLE] =
         &data[pos_offset..(pos_offset + ARITH_EQ_384_U64S_DOUBLE)].try_into().unwrap();
@@ -36,13 +37,13 @@ pub fn generate_bls12_381_complex_mul_mem_inputs(
         only_counters,
         pending,
         &BLS12_381_COMPLEX_MUL_MEM_CONFIG,
-    );
+    )
 }
 
 pub fn skip_bls12_381_complex_mul_mem_inputs(
     addr_main: u32,
     data: &[u64],
     mem_collectors_info: &[MemCollectorInfo],
-) -> bool {
+) -> Result<bool, AddrOverflow> {
     super::skip_mem_inputs(addr_main, data, &BLS12_381_COMPLEX_MUL_MEM_CONFIG, mem_collectors_info)
 }
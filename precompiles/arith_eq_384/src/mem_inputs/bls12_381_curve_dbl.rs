@@ -1,3 +1,4 @@
+use precompiles_common::AddrOverflow;
 use std::collections::VecDeque;
 use zisk_common::{BusId, MemCollectorInfo};
 
@@ -18,7 +19,7 @@ pub fn generate_bls12_381_curve_dbl_mem_inputs(
     data: &[u64],
     only_counters: bool,
     pending: &mut VecDeque<(BusId, Vec<u64>)>,
-) {
+) -> Result<(), AddrOverflow> {
     let pos_offset: usize = 4; // op,op_type,a,b,...
     let p1: &[u64; ARITH_EQ_384_U64S_DOUBLE] =
         &data[pos_offset..(pos_offset + ARITH_EQ_384_U64S_DOUBLE)].try_into().unwrap();
@@ -33,13 +34,13 @@ pub fn generate_bls12_381_curve_dbl_mem_inputs(
         only_counters,
         pending,
         &BLS12_381_CURVE_DBL_MEM_CONFIG,
-    );
+    )
 }
 
 pub fn skip_bls12_381_curve_dbl_mem_inputs(
     addr_main: u32,
     data: &[u64],
     mem_collectors_info: &[MemCollectorInfo],
-) -> bool {
+) -> Result<bool, AddrOverflow> {
     super::skip_mem_inputs(addr_main, data, &BLS12_381_CURVE_DBL_MEM_CONFIG, mem_collectors_info)
 }
@@ -6,6 +6,7 @@ use rayon::prelude::*;
 use pil_std_lib::Std;
 use proofman_common::{AirInstance, FromTrace, ProofmanResult};
 use proofman_util::{timer_start_trace, timer_stop_and_log_trace};
+use zisk_common::trace_batch_size;
 
 #[cfg(not(feature = "packed"))]
 use zisk_pil::{Add256Trace, Add256TraceRow};
@@ -141,8 +142,7 @@ impl<F: PrimeField64> Add256SM<F> {
         let trace_rows = trace.buffer.as_mut_slice();
 
         // Determinar tamaño óptimo de chunks
-        let num_threads = rayon::current_num_threads();
-        let chunk_size = std::cmp::max(1, flat_inputs.len() / num_threads);
+        let chunk_size = trace_batch_size(flat_inputs.len());
 
         // Procesar en chunks para compartir arrays locales de multiplicities
         let local_multiplicities_vec: Vec<Vec<u32>> = flat_inputs
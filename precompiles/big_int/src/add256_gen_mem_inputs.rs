@@ -1,7 +1,7 @@
 use lib_c::add256;
 
 use crate::add256_constants::*;
-use precompiles_common::MemBusHelpers;
+use precompiles_common::{AddrOverflow, MemBusHelpers};
 use std::collections::VecDeque;
 use zisk_common::MemCollectorInfo;
 use zisk_common::{BusId, OPERATION_BUS_DATA_SIZE};
@@ -21,11 +21,11 @@ pub fn generate_add256_mem_inputs(
     data: &[u64],
     only_counters: bool,
     pending: &mut VecDeque<(BusId, Vec<u64>)>,
-) {
+) -> Result<(), AddrOverflow> {
     // Start by generating the params (indirection read, direct, indirection write)
     for iparam in 0..PARAMS {
         MemBusHelpers::mem_aligned_load(
-            addr_main + iparam as u32 * 8,
+            MemBusHelpers::try_chunk_addr(addr_main, iparam)?,
             step_main,
             data[OPERATION_BUS_DATA_SIZE + iparam],
             pending,
@@ -34,10 +34,10 @@ pub fn generate_add256_mem_inputs(
 
     // generate load params
     for iparam in 0..READ_PARAMS {
-        let param_addr = data[OPERATION_BUS_DATA_SIZE + iparam] as u32;
+        let param_addr = MemBusHelpers::try_narrow_addr(data[OPERATION_BUS_DATA_SIZE + iparam])?;
         for ichunk in 0..PARAM_CHUNKS {
             MemBusHelpers::mem_aligned_load(
-                param_addr + ichunk as u32 * 8,
+                MemBusHelpers::try_chunk_addr(param_addr, ichunk)?,
                 step_main,
                 data[START_READ_PARAMS + iparam * PARAM_CHUNKS + ichunk],
                 pending,
@@ -57,11 +57,14 @@ pub fn generate_add256_mem_inputs(
     }
 
     // verify write param
-    let write_addr = data[OPERATION_BUS_DATA_SIZE + WRITE_ADDR_PARAM] as u32;
+    let write_addr =
+        MemBusHelpers::try_narrow_addr(data[OPERATION_BUS_DATA_SIZE + WRITE_ADDR_PARAM])?;
     for (ichunk, write_data) in write_data.iter().enumerate().take(PARAM_CHUNKS) {
-        let param_addr = write_addr + ichunk as u32 * 8;
+        let param_addr = MemBusHelpers::try_chunk_addr(write_addr, ichunk)?;
         MemBusHelpers::mem_aligned_write(param_addr, step_main, *write_data, pending);
     }
+
+    Ok(())
 }
 
 // op_a = step
@@ -72,40 +75,41 @@ pub fn skip_add256_mem_inputs(
     addr_main: u32,
     data: &[u64],
     mem_collectors_info: &[MemCollectorInfo],
-) -> bool {
+) -> Result<bool, AddrOverflow> {
     // verify main params "struct" of indirections
     for iparam in 0..PARAMS {
-        let addr = addr_main + iparam as u32 * 8;
+        let addr = MemBusHelpers::try_chunk_addr(addr_main, iparam)?;
         for mem_collector in mem_collectors_info {
             if !mem_collector.skip_addr(addr) {
-                return false;
+                return Ok(false);
             }
         }
     }
 
     // verify read params
     for iparam in 0..READ_PARAMS {
-        let param_addr = data[OPERATION_BUS_DATA_SIZE + iparam] as u32;
+        let param_addr = MemBusHelpers::try_narrow_addr(data[OPERATION_BUS_DATA_SIZE + iparam])?;
         for ichunk in 0..PARAM_CHUNKS {
-            let addr = param_addr + ichunk as u32 * 8;
+            let addr = MemBusHelpers::try_chunk_addr(param_addr, ichunk)?;
             for mem_collector in mem_collectors_info {
                 if !mem_collector.skip_addr(addr) {
-                    return false;
+                    return Ok(false);
                 }
             }
         }
     }
 
     // verify write param
-    let write_addr = data[OPERATION_BUS_DATA_SIZE + WRITE_ADDR_PARAM] as u32;
+    let write_addr =
+        MemBusHelpers::try_narrow_addr(data[OPERATION_BUS_DATA_SIZE + WRITE_ADDR_PARAM])?;
     for ichunk in 0..PARAM_CHUNKS {
-        let addr = write_addr + ichunk as u32 * 8;
+        let addr = MemBusHelpers::try_chunk_addr(write_addr, ichunk)?;
         for mem_collector in mem_collectors_info {
             if !mem_collector.skip_addr(addr) {
-                return false;
+                return Ok(false);
             }
         }
     }
 
-    true
+    Ok(true)
 }
@@ -0,0 +1,76 @@
+//! Bit manipulation helpers shared by the precompile crates
+
+/// Returns the floor of the base 2 logarithm of `n`, which must not be zero
+pub const fn log2_floor(n: usize) -> usize {
+    assert!(n != 0, "log2_floor() of zero");
+    (usize::BITS - 1 - n.leading_zeros()) as usize
+}
+
+/// Returns the ceiling of the base 2 logarithm of `n`, which must not be zero
+pub const fn log2_ceil(n: usize) -> usize {
+    assert!(n != 0, "log2_ceil() of zero");
+    if n == 1 {
+        0
+    } else {
+        log2_floor(n - 1) + 1
+    }
+}
+
+/// Returns the base 2 logarithm of `n` if it is a power of two, or None otherwise
+pub const fn log2_exact(n: usize) -> Option<usize> {
+    if n.is_power_of_two() {
+        Some(n.trailing_zeros() as usize)
+    } else {
+        None
+    }
+}
+
+/// Returns true if `n` is a power of two; zero is not
+pub const fn is_power_of_two(n: u64) -> bool {
+    n.is_power_of_two()
+}
+
+/// Returns the smallest power of two greater than or equal to `n`, i.e. 1 for zero; it panics if
+/// the result does not fit in a u64
+pub const fn next_power_of_two_u64(n: u64) -> u64 {
+    match n.checked_next_power_of_two() {
+        Some(power) => power,
+        None => panic!("next_power_of_two_u64() overflow"),
+    }
+}
+
+/// Reverses the `bits` least significant bits of `index`, e.g. to reorder the NTT evaluations;
+/// `index` must be lower than 2^bits
+pub const fn bit_reverse(index: usize, bits: usize) -> usize {
+    assert!(bits <= usize::BITS as usize);
+    if bits == 0 {
+        return 0;
+    }
+    assert!((bits == usize::BITS as usize) || (index >> bits) == 0);
+    index.reverse_bits() >> (usize::BITS as usize - bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bits() {
+        assert_eq!(log2_floor(1), 0);
+        assert_eq!(log2_floor(9), 3);
+        assert_eq!(log2_ceil(1), 0);
+        assert_eq!(log2_ceil(8), 3);
+        assert_eq!(log2_ceil(9), 4);
+        assert_eq!(log2_exact(8), Some(3));
+        assert_eq!(log2_exact(9), None);
+        assert!(is_power_of_two(1 << 40) && !is_power_of_two(0));
+        assert_eq!(next_power_of_two_u64(0), 1);
+        assert_eq!(next_power_of_two_u64(5), 8);
+        assert_eq!(bit_reverse(0b0011, 4), 0b1100);
+        assert_eq!(bit_reverse(1, 1), 1);
+        assert_eq!(bit_reverse(0, 0), 0);
+
+        // The existing log2() is the floor, with log2(0) = 0
+        assert!((1..=1024).all(|n| crate::log2(n) == log2_floor(n)));
+    }
+}
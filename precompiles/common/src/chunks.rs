@@ -0,0 +1,125 @@
+//! Splitting of big precompile operations into continuation chunks
+//!
+//! * A precompile operation over a big input, e.g. hashing a long message, may need more trace
+//!   rows than the ones available in a single AIR instance.
+//! * `split_into_chunks()` deterministically partitions the input of an operation into chunks of
+//!   whole blocks, the unit the precompile processes at once, so that every chunk fits in
+//!   `max_rows` rows and can be scheduled in a different instance.
+//! * Consecutive chunks are linked through chaining values: the `chain_out()` of a chunk is the
+//!   `chain_in()` of the next one, so that the bus operations of the chunks can be matched to
+//!   rebuild the whole operation.
+
+/// Precompile whose operations can be split into continuation chunks
+pub trait ChunkedPrecompile {
+    /// Number of input words processed at once, i.e. the unit that cannot be split
+    const BLOCK_WORDS: usize;
+    /// Number of trace rows used to process one block
+    const ROWS_PER_BLOCK: usize;
+}
+
+/// Continuation chunk of a precompile operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrecompileChunk<'a> {
+    /// Position of the chunk in the operation
+    pub seq: usize,
+    /// Input words of the chunk; the last chunk can contain a partial block
+    pub input: &'a [u64],
+    /// Number of trace rows used by the chunk
+    pub rows: usize,
+    /// True for the first chunk of the operation
+    pub is_first: bool,
+    /// True for the last chunk of the operation
+    pub is_last: bool,
+}
+
+impl PrecompileChunk<'_> {
+    /// Returns the chaining value linking this chunk with the previous one, or 0 for the first
+    /// chunk of the operation
+    pub fn chain_in(&self, op_id: u64) -> u64 {
+        if self.is_first {
+            0
+        } else {
+            Self::chain_value(op_id, self.seq)
+        }
+    }
+
+    /// Returns the chaining value linking this chunk with the next one, or 0 for the last chunk
+    /// of the operation
+    pub fn chain_out(&self, op_id: u64) -> u64 {
+        if self.is_last {
+            0
+        } else {
+            Self::chain_value(op_id, self.seq + 1)
+        }
+    }
+
+    /// Returns the chaining value between the chunks `seq - 1` and `seq` of an operation
+    fn chain_value(op_id: u64, seq: usize) -> u64 {
+        (op_id << 24) | seq as u64
+    }
+}
+
+/// Splits the input of an operation into the minimum number of chunks of whole blocks that fit
+/// in `max_rows` rows each; an empty input results in one single empty chunk
+pub fn split_into_chunks<P: ChunkedPrecompile>(
+    input: &[u64],
+    max_rows: usize,
+) -> Vec<PrecompileChunk<'_>> {
+    assert!(P::BLOCK_WORDS > 0);
+    let blocks_per_chunk = max_rows / P::ROWS_PER_BLOCK;
+    if blocks_per_chunk == 0 {
+        panic!(
+            "split_into_chunks() max_rows={} is lower than the rows of one block={}",
+            max_rows,
+            P::ROWS_PER_BLOCK
+        );
+    }
+
+    let chunk_words = blocks_per_chunk * P::BLOCK_WORDS;
+    let chunks = input.len().div_ceil(chunk_words).max(1);
+    (0..chunks)
+        .map(|seq| {
+            let start = seq * chunk_words;
+            let input = &input[start..(start + chunk_words).min(input.len())];
+            PrecompileChunk {
+                seq,
+                input,
+                rows: input.len().div_ceil(P::BLOCK_WORDS) * P::ROWS_PER_BLOCK,
+                is_first: seq == 0,
+                is_last: seq == chunks - 1,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Test;
+
+    impl ChunkedPrecompile for Test {
+        const BLOCK_WORDS: usize = 4;
+        const ROWS_PER_BLOCK: usize = 10;
+    }
+
+    #[test]
+    fn test_split_into_chunks() {
+        let input: Vec<u64> = (0..18).collect();
+        let chunks = split_into_chunks::<Test>(&input, 25);
+        // 2 blocks (8 words, 20 rows) per chunk, and a last chunk with a partial block
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].input, &input[0..8]);
+        assert_eq!(chunks[2].input, &input[16..18]);
+        assert_eq!(chunks.iter().map(|c| c.rows).collect::<Vec<_>>(), vec![20, 20, 10]);
+        assert!(chunks[0].is_first && chunks[2].is_last);
+        assert_eq!(chunks[0].chain_in(7), 0);
+        assert_eq!(chunks[0].chain_out(7), chunks[1].chain_in(7));
+        assert_ne!(chunks[0].chain_out(7), chunks[1].chain_out(7));
+        assert_eq!(chunks[2].chain_out(7), 0);
+
+        let chunks = split_into_chunks::<Test>(&[], 25);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].rows, 0);
+    }
+}
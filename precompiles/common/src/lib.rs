@@ -1,10 +1,21 @@
+mod bits;
+mod chunks;
 mod goldilocks_constants;
+mod seeded_rng;
+mod self_test;
 
+pub use bits::*;
+pub use chunks::*;
 pub use goldilocks_constants::{get_ks, GOLDILOCKS_GEN, GOLDILOCKS_K};
+pub use seeded_rng::*;
+pub use self_test::*;
 
-use std::collections::VecDeque;
-use zisk_common::{BusId, MEM_BUS_ID};
-use zisk_core::InstContext;
+use std::{collections::VecDeque, fmt};
+use zisk_common::{
+    memmap, BusId, BusPayload, MemBusPayload, MEM_BUS_ID, MEM_BUS_LOAD_OP,
+    MEM_BUS_OPS_BY_MAIN_STEP, MEM_BUS_STEP_BASE, MEM_BUS_STORE_OP,
+};
+use zisk_core::{precompile_name, InstContext};
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 pub struct PrecompileCode(u16);
@@ -19,6 +30,15 @@ impl PrecompileCode {
     }
 }
 
+impl fmt::Display for PrecompileCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match precompile_name(self.0) {
+            Some(name) => write!(f, "{name}(0x{:x})", self.0),
+            None => write!(f, "unknown(0x{:x})", self.0),
+        }
+    }
+}
+
 impl From<u16> for PrecompileCode {
     fn from(value: u16) -> Self {
         PrecompileCode::new(value)
@@ -31,40 +51,166 @@ impl From<PrecompileCode> for u16 {
     }
 }
 
-pub struct PrecompileContext {}
+/// Data shared by all the precompile calls, besides the instruction context
+pub struct PrecompileContext<'a> {
+    /// Main step of the precompile call
+    pub step: u64,
+    /// Base address of the precompile parameters in memory, i.e. the value written to the CSR
+    pub scratch_addr: u64,
+    /// Pending bus data, where the precompile pushes its memory operations
+    pub pending: &'a mut VecDeque<(BusId, Vec<u64>)>,
+    /// Deterministic seed, for the precompiles that need pseudo-random values
+    pub seed: u64,
+}
+
+impl<'a> PrecompileContext<'a> {
+    /// Creates a precompile context, deriving its seed from the step so that the executions are
+    /// reproducible
+    pub fn new(step: u64, scratch_addr: u64, pending: &'a mut VecDeque<(BusId, Vec<u64>)>) -> Self {
+        Self { step, scratch_addr, pending, seed: Self::derive_seed(step) }
+    }
+
+    /// Mixes the step into a seed, using the splitmix64 finalizer
+    fn derive_seed(step: u64) -> u64 {
+        SeededRng::new(step).next_u64()
+    }
+
+    /// Returns a generator seeded with `seed`, for the pseudo-random values of the call
+    pub fn rng(&self) -> SeededRng {
+        SeededRng::new(self.seed)
+    }
+}
+
+/// Result of a successful precompile call, written back to the instruction context
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct PrecompileOutput {
+    /// Value of the `c` register
+    pub c: u64,
+    /// Value of the flag
+    pub flag: bool,
+}
+
+impl PrecompileOutput {
+    pub fn new(c: u64, flag: bool) -> Self {
+        Self { c, flag }
+    }
+}
+
+/// Reasons why a precompile call could not be executed
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum PrecompileError {
+    /// The parameters read from memory do not have the size expected by the precompile
+    BadInputLength { opcode: PrecompileCode, expected: usize, actual: usize },
+    /// A parameter or result address is not aligned, or is outside of the mapped memory
+    AddressOutOfRange { opcode: PrecompileCode, addr: u64 },
+    /// The precompile does not implement the opcode
+    UnsupportedOpcode(PrecompileCode),
+    /// The precompile computed a result that does not satisfy its own constraints
+    InternalInconsistency { opcode: PrecompileCode, reason: String },
+}
+
+impl PrecompileError {
+    /// Returns the error of an address overflow in the memory operations of `opcode`
+    pub fn from_addr_overflow(opcode: PrecompileCode, overflow: AddrOverflow) -> Self {
+        PrecompileError::AddressOutOfRange {
+            opcode,
+            addr: overflow.base.saturating_add(overflow.offset),
+        }
+    }
+}
+
+impl fmt::Display for PrecompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrecompileError::BadInputLength { opcode, expected, actual } => {
+                write!(f, "{opcode} bad input length {actual}, expected {expected}")
+            }
+            PrecompileError::AddressOutOfRange { opcode, addr } => {
+                write!(f, "{opcode} address out of range {addr:#x}")
+            }
+            PrecompileError::UnsupportedOpcode(opcode) => write!(f, "{opcode} unsupported opcode"),
+            PrecompileError::InternalInconsistency { opcode, reason } => {
+                write!(f, "{opcode} internal inconsistency: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PrecompileError {}
 
 pub trait PrecompileCall: Send + Sync {
-    fn execute(&self, opcode: PrecompileCode, ctx: &mut InstContext) -> Option<(u64, bool)>;
+    fn execute(
+        &self,
+        opcode: PrecompileCode,
+        inst_ctx: &mut InstContext,
+        ctx: &mut PrecompileContext,
+    ) -> Result<PrecompileOutput, PrecompileError>;
 }
 
-pub struct MemBusHelpers {}
+/// Address of a memory bus message outside of the 32-bit address space, i.e. `base + offset`
+/// does not fit in a `u32`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct AddrOverflow {
+    pub base: u64,
+    pub offset: u64,
+}
+
+impl fmt::Display for AddrOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "address {:#x} + {:#x} overflows the 32-bit address space",
+            self.base, self.offset
+        )
+    }
+}
 
-const MEMORY_LOAD_OP: u64 = 1;
-const MEMORY_STORE_OP: u64 = 2;
+impl std::error::Error for AddrOverflow {}
 
-const MEM_STEP_BASE: u64 = 1;
-const MAX_MEM_OPS_BY_MAIN_STEP: u64 = 4;
+pub struct MemBusHelpers {}
 
 impl MemBusHelpers {
+    /// Returns the 32-bit memory address of a 64-bit value read from the bus data, e.g. an
+    /// indirect parameter
+    pub fn try_narrow_addr(addr: u64) -> Result<u32, AddrOverflow> {
+        u32::try_from(addr).map_err(|_| AddrOverflow { base: addr, offset: 0 })
+    }
+    /// Returns the address of the 8-bytes chunk `index` of the memory starting at `base`
+    pub fn try_chunk_addr(base: u32, index: usize) -> Result<u32, AddrOverflow> {
+        let offset = (index as u64).saturating_mul(8);
+        (base as u64)
+            .checked_add(offset)
+            .and_then(|addr| u32::try_from(addr).ok())
+            .ok_or(AddrOverflow { base: base as u64, offset })
+    }
+    /// Same as `try_narrow_addr()`, panicking on overflow instead of producing a wrong address
+    pub fn narrow_addr(addr: u64) -> u32 {
+        Self::try_narrow_addr(addr).unwrap_or_else(|e| panic!("MemBusHelpers::narrow_addr() {e}"))
+    }
+    /// Same as `try_chunk_addr()`, panicking on overflow instead of producing a wrong address
+    pub fn chunk_addr(base: u32, index: usize) -> u32 {
+        Self::try_chunk_addr(base, index)
+            .unwrap_or_else(|e| panic!("MemBusHelpers::chunk_addr() {e}"))
+    }
     pub fn mem_aligned_load(
         addr: u32,
         step: u64,
         mem_value: u64,
         pending: &mut VecDeque<(BusId, Vec<u64>)>,
     ) {
-        assert!(addr % 8 == 0);
-        pending.push_back((
-            MEM_BUS_ID,
-            vec![
-                MEMORY_LOAD_OP,
-                addr as u64,
-                MEM_STEP_BASE + MAX_MEM_OPS_BY_MAIN_STEP * step + 2,
-                8,
-                mem_value,
-                0,
-                0,
-            ],
-        ));
+        debug_assert!(
+            addr.is_multiple_of(8) && memmap::is_mapped(addr as u64),
+            "MemBusHelpers::mem_aligned_load() invalid address {addr:#x}"
+        );
+        let payload = MemBusPayload {
+            op: MEM_BUS_LOAD_OP,
+            addr: addr as u64,
+            step: MEM_BUS_STEP_BASE + MEM_BUS_OPS_BY_MAIN_STEP * step + 2,
+            bytes: 8,
+            mem_value_0: mem_value,
+            ..Default::default()
+        };
+        pending.push_back((MEM_BUS_ID, payload.to_payload()));
     }
     pub fn mem_aligned_write(
         addr: u32,
@@ -72,19 +218,19 @@ impl MemBusHelpers {
         value: u64,
         pending: &mut VecDeque<(BusId, Vec<u64>)>,
     ) {
-        assert!(addr % 8 == 0);
-        pending.push_back((
-            MEM_BUS_ID,
-            vec![
-                MEMORY_STORE_OP,
-                addr as u64,
-                MEM_STEP_BASE + MAX_MEM_OPS_BY_MAIN_STEP * step + 3,
-                8,
-                0,
-                0,
-                value,
-            ],
-        ));
+        debug_assert!(
+            addr.is_multiple_of(8) && memmap::is_writable(addr as u64),
+            "MemBusHelpers::mem_aligned_write() invalid address {addr:#x}"
+        );
+        let payload = MemBusPayload {
+            op: MEM_BUS_STORE_OP,
+            addr: addr as u64,
+            step: MEM_BUS_STEP_BASE + MEM_BUS_OPS_BY_MAIN_STEP * step + 3,
+            bytes: 8,
+            value,
+            ..Default::default()
+        };
+        pending.push_back((MEM_BUS_ID, payload.to_payload()));
     }
     pub fn mem_aligned_op(
         addr: u32,
@@ -93,18 +239,27 @@ impl MemBusHelpers {
         is_write: bool,
         pending: &mut VecDeque<(BusId, Vec<u64>)>,
     ) {
-        pending.push_back((
-            MEM_BUS_ID,
-            vec![
-                if is_write { MEMORY_STORE_OP } else { MEMORY_LOAD_OP },
-                addr as u64,
-                MEM_STEP_BASE + MAX_MEM_OPS_BY_MAIN_STEP * step + if is_write { 3 } else { 2 },
-                8,
-                if is_write { 0 } else { value },
-                0,
-                if is_write { value } else { 0 },
-            ],
-        ));
+        debug_assert!(
+            addr.is_multiple_of(8)
+                && if is_write {
+                    memmap::is_writable(addr as u64)
+                } else {
+                    memmap::is_mapped(addr as u64)
+                },
+            "MemBusHelpers::mem_aligned_op() invalid address {addr:#x}"
+        );
+        let payload = MemBusPayload {
+            op: if is_write { MEM_BUS_STORE_OP } else { MEM_BUS_LOAD_OP },
+            addr: addr as u64,
+            step: MEM_BUS_STEP_BASE
+                + MEM_BUS_OPS_BY_MAIN_STEP * step
+                + if is_write { 3 } else { 2 },
+            bytes: 8,
+            mem_value_0: if is_write { 0 } else { value },
+            mem_value_1: 0,
+            value: if is_write { value } else { 0 },
+        };
+        pending.push_back((MEM_BUS_ID, payload.to_payload()));
     }
 }
 
@@ -117,3 +272,76 @@ pub fn log2(n: usize) -> usize {
     }
     res
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checked_addr() {
+        assert_eq!(MemBusHelpers::try_narrow_addr(0xa000_0000), Ok(0xa000_0000));
+        assert_eq!(
+            MemBusHelpers::try_narrow_addr(0x1_0000_0000),
+            Err(AddrOverflow { base: 0x1_0000_0000, offset: 0 })
+        );
+        assert_eq!(MemBusHelpers::chunk_addr(0xa000_0000, 3), 0xa000_0018);
+        assert_eq!(MemBusHelpers::chunk_addr(0xffff_fff0, 1), 0xffff_fff8);
+        let err = MemBusHelpers::try_chunk_addr(0xffff_fff0, 2).unwrap_err();
+        assert_eq!(err, AddrOverflow { base: 0xffff_fff0, offset: 0x10 });
+        assert_eq!(err.to_string(), "address 0xfffffff0 + 0x10 overflows the 32-bit address space");
+        assert!(MemBusHelpers::try_chunk_addr(0, usize::MAX).is_err());
+        assert!(std::panic::catch_unwind(|| MemBusHelpers::chunk_addr(0xffff_fff8, 1)).is_err());
+    }
+
+    #[test]
+    fn test_precompile_context() {
+        // The seed depends only on the step, so re-executing a call gives the same values
+        let mut pending = VecDeque::new();
+        let ctx = PrecompileContext::new(1000, 0xa000_0000, &mut pending);
+        let seed = ctx.seed;
+        assert_eq!(ctx.rng().next_u64(), SeededRng::new(seed).next_u64());
+        assert_eq!(PrecompileContext::new(1000, 0, &mut VecDeque::new()).seed, seed);
+        assert_ne!(PrecompileContext::new(1001, 0xa000_0000, &mut VecDeque::new()).seed, seed);
+
+        // The precompile memory operations are pushed to the pending bus data
+        let mut ctx = PrecompileContext::new(1000, 0xa000_0000, &mut pending);
+        MemBusHelpers::mem_aligned_load(ctx.scratch_addr as u32, ctx.step, 7, &mut *ctx.pending);
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].0, MEM_BUS_ID);
+    }
+
+    /// Precompile reading one parameter chunk after the scratch address, as a call site would
+    struct ChunkLoad;
+
+    impl PrecompileCall for ChunkLoad {
+        fn execute(
+            &self,
+            opcode: PrecompileCode,
+            _inst_ctx: &mut InstContext,
+            ctx: &mut PrecompileContext,
+        ) -> Result<PrecompileOutput, PrecompileError> {
+            let addr = MemBusHelpers::try_chunk_addr(ctx.scratch_addr as u32, 1)
+                .map_err(|e| PrecompileError::from_addr_overflow(opcode, e))?;
+            MemBusHelpers::mem_aligned_load(addr, ctx.step, 0, ctx.pending);
+            Ok(PrecompileOutput::new(0, false))
+        }
+    }
+
+    #[test]
+    fn test_precompile_error() {
+        let opcode = PrecompileCode::new(0xffff);
+        let mut inst_ctx = InstContext::default();
+        let mut pending = VecDeque::new();
+
+        let mut ctx = PrecompileContext::new(1, 0xa000_0000, &mut pending);
+        let output = ChunkLoad.execute(opcode, &mut inst_ctx, &mut ctx);
+        assert_eq!(output, Ok(PrecompileOutput { c: 0, flag: false }));
+
+        // The address overflow is returned to the caller instead of panicking
+        let mut ctx = PrecompileContext::new(1, 0xffff_fff8, &mut pending);
+        let err = ChunkLoad.execute(opcode, &mut inst_ctx, &mut ctx).unwrap_err();
+        assert_eq!(err, PrecompileError::AddressOutOfRange { opcode, addr: 0x1_0000_0000 });
+        assert_eq!(err.to_string(), "unknown(0xffff) address out of range 0x100000000");
+        assert_eq!(pending.len(), 1);
+    }
+}
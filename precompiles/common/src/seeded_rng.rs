@@ -0,0 +1,87 @@
+//! Deterministic randomness of the host precompile code
+//!
+//! * The host code that needs randomness, e.g. the randomized checks of `self_test()`, the bases
+//!   of a Miller-Rabin test or the randomizers of a batch verification, must take it from a
+//!   `SeededRng`, never from the OS or from a thread RNG, so that a run can be replayed with the
+//!   same values.
+//! * The seed is chosen by the caller and reported with the results, e.g. `SelfTestReport::seed`.
+//! * `SeededRng` is splitmix64: fast and with a 64-bits state, but not cryptographically secure.
+//! * `fork()` derives independent generators from one, e.g. one per check or per batch element.
+
+/// Increment of the splitmix64 state, i.e. the 64-bits golden ratio
+const GOLDEN_GAMMA: u64 = 0x9e3779b97f4a7c15;
+
+/// Deterministic pseudo-random generator, see the module documentation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Returns the next pseudo-random value
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(GOLDEN_GAMMA);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a pseudo-random value uniformly distributed in [0, bound)
+    pub fn below(&mut self, bound: u64) -> u64 {
+        assert!(bound > 0, "SeededRng::below() invalid bound 0");
+        // Reject the values of the last incomplete range, so that there is no modulo bias
+        let limit = u64::MAX - (u64::MAX % bound + 1) % bound;
+        loop {
+            let value = self.next_u64();
+            if value <= limit {
+                return value % bound;
+            }
+        }
+    }
+
+    /// Fills the buffer with pseudo-random bytes
+    pub fn fill_bytes(&mut self, buffer: &mut [u8]) {
+        for chunk in buffer.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    /// Returns an independent generator for the stream `stream`, without advancing this one
+    pub fn fork(&self, stream: u64) -> SeededRng {
+        SeededRng::new(SeededRng::new(self.state ^ stream.wrapping_mul(GOLDEN_GAMMA)).next_u64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_rng() {
+        // Reference splitmix64 outputs for seed 0
+        let mut rng = SeededRng::new(0);
+        assert_eq!(rng.next_u64(), 0xe220a8397b1dcdaf);
+        assert_eq!(rng.next_u64(), 0x6e789e6aa1b965f4);
+
+        let values: Vec<u64> = (0..1000).map(|_| rng.below(10)).collect();
+        assert!(values.iter().all(|value| *value < 10));
+        assert!((0..10).all(|digit| values.contains(&digit)));
+        assert_eq!(SeededRng::new(7).below(1), 0);
+
+        let mut bytes = [0u8; 12];
+        SeededRng::new(0).fill_bytes(&mut bytes);
+        assert_eq!(bytes[..8], 0xe220a8397b1dcdaf_u64.to_le_bytes());
+        assert_eq!(bytes[8..], 0x6e789e6aa1b965f4_u64.to_le_bytes()[..4]);
+
+        let rng = SeededRng::new(42);
+        assert_eq!(rng.fork(1), rng.fork(1));
+        assert_ne!(rng.fork(1).next_u64(), rng.fork(2).next_u64());
+        assert_eq!(rng, SeededRng::new(42));
+    }
+}
@@ -0,0 +1,329 @@
+//! Host crypto self-test
+//!
+//! * The emulator computes the precompile results on the host with native code: Keccak-f from
+//!   `tiny-keccak`, the SHA-256 compression from `sha2`, with the implementation it selects for
+//!   the CPU, and the curve and 256-bit arithmetic from `precompiles-helpers`, which uses the
+//!   `lib-c` assembly on x86_64 Linux.  A miscompiled build, or one running on a CPU variant the
+//!   code was not built for, produces wrong witnesses that are only detected when the proof
+//!   fails, after a long run.
+//! * `self_test()` runs a known-answer test of every host crypto routine and returns a
+//!   `SelfTestReport`.  `cargo-zisk check-setup --self-test` runs it, and fails if any routine
+//!   failed, so that a build can be checked once on every machine without delaying the prover
+//!   startup.
+//! * The known-answer tests only cover fixed inputs, so the self-test also checks algebraic
+//!   identities on pseudo-random points and values taken from a `SeededRng`.  `self_test()` uses
+//!   `SELF_TEST_SEED`, and `self_test_with_seed()` replays a run with the seed of its report.
+//! * A routine that panics is reported as failed instead of aborting the self-test.
+//! * The report includes the hashing backends in use, see `hash_backends()`, so that a failure
+//!   can be traced to the backend that produced it.
+
+use std::{fmt, panic};
+
+use precompiles_helpers::{
+    arith256_mod, bn254_curve_add, bn254_curve_dbl, secp256k1_add, secp256k1_dbl,
+};
+use tiny_keccak::keccakf;
+use zisk_core::{hash_backends, sha256f, HashBackends};
+
+use crate::SeededRng;
+
+/// Seed of the randomized checks of `self_test()`
+pub const SELF_TEST_SEED: u64 = 0x5a15_5e1f_7e57_0001;
+
+/// secp256k1 generator G, as x and y in little-endian 64-bit limbs
+const SECP256K1_G: [u64; 8] = [
+    0x59f2815b16f81798,
+    0x029bfcdb2dce28d9,
+    0x55a06295ce870b07,
+    0x79be667ef9dcbbac,
+    0x9c47d08ffb10d4b8,
+    0xfd17b448a6855419,
+    0x5da4fbfc0e1108a8,
+    0x483ada7726a3c465,
+];
+/// secp256k1 2G
+const SECP256K1_2G: [u64; 8] = [
+    0xabac09b95c709ee5,
+    0x5c778e4b8cef3ca7,
+    0x3045406e95c07cd8,
+    0xc6047f9441ed7d6d,
+    0x236431a950cfe52a,
+    0xf7f632653266d0e1,
+    0xa3c58419466ceaee,
+    0x1ae168fea63dc339,
+];
+/// secp256k1 3G
+const SECP256K1_3G: [u64; 8] = [
+    0x8601f113bce036f9,
+    0xb531c845836f99b0,
+    0x49344f85f89d5229,
+    0xf9308a019258c310,
+    0x6cb9fd7584b8e672,
+    0x6500a99934c2231b,
+    0x0fe337e62a37f356,
+    0x388f7b0f632de814,
+];
+
+/// secp256k1 group order n
+const SECP256K1_N: [u64; 4] =
+    [0xbfd25e8cd0364141, 0xbaaedce6af48a03b, 0xfffffffffffffffe, 0xffffffffffffffff];
+
+/// BN254 G1 generator (1, 2)
+const BN254_G: [u64; 8] = [1, 0, 0, 0, 2, 0, 0, 0];
+/// BN254 G1 2G
+const BN254_2G: [u64; 8] = [
+    0xd3c208c16d87cfd3,
+    0xd97816a916871ca8,
+    0x9b85045b68181585,
+    0x030644e72e131a02,
+    0xff3ebf7a5a18a2c4,
+    0x68a6a449e3538fc7,
+    0xe7845f96b2ae9c0a,
+    0x15ed738c0e0a7c92,
+];
+/// BN254 G1 3G
+const BN254_3G: [u64; 8] = [
+    0xf2d355961915abf0,
+    0x9315d84715b8e679,
+    0xf40232bcb1b6bd15,
+    0x0769bf9ac56bea3f,
+    0xcdf1ff3dd9fe2261,
+    0x319e63b40b9c5b57,
+    0x554fdb7c8d086475,
+    0x2ab799bee0489429,
+];
+
+/// Known-answer test of a host crypto routine
+struct KnownAnswerTest {
+    name: &'static str,
+    run: fn() -> bool,
+}
+
+const KNOWN_ANSWER_TESTS: [KnownAnswerTest; 7] = [
+    KnownAnswerTest { name: "keccakf", run: keccakf_kat },
+    KnownAnswerTest { name: "sha256f", run: sha256f_kat },
+    KnownAnswerTest { name: "secp256k1_add", run: secp256k1_add_kat },
+    KnownAnswerTest { name: "secp256k1_dbl", run: secp256k1_dbl_kat },
+    KnownAnswerTest { name: "bn254_curve_add", run: bn254_curve_add_kat },
+    KnownAnswerTest { name: "bn254_curve_dbl", run: bn254_curve_dbl_kat },
+    KnownAnswerTest { name: "modexp", run: modexp_kat },
+];
+
+/// Randomized check of a host crypto routine
+struct RandomizedTest {
+    name: &'static str,
+    run: fn(&mut SeededRng) -> bool,
+}
+
+const RANDOMIZED_TESTS: [RandomizedTest; 3] = [
+    RandomizedTest { name: "secp256k1_random", run: secp256k1_random_check },
+    RandomizedTest { name: "bn254_random", run: bn254_random_check },
+    RandomizedTest { name: "modexp_random", run: modexp_random_check },
+];
+
+/// Result of the known-answer test or randomized check of one routine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfTestResult {
+    /// Name of the routine
+    pub name: &'static str,
+    /// True if the routine returned the expected answer, or satisfied the checked identity
+    pub passed: bool,
+}
+
+/// Results of the host crypto self-test
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfTestReport {
+    /// Results of every routine, in test order
+    pub results: Vec<SelfTestResult>,
+    /// Backends of the hashing routines tested
+    pub hash_backends: HashBackends,
+    /// Seed of the randomized checks
+    pub seed: u64,
+}
+
+impl SelfTestReport {
+    /// Returns true if all the routines passed
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(|result| result.passed)
+    }
+
+    /// Returns the names of the routines that failed
+    pub fn failures(&self) -> Vec<&'static str> {
+        self.results.iter().filter(|result| !result.passed).map(|result| result.name).collect()
+    }
+}
+
+impl fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<16} {}", "hash backends", self.hash_backends)?;
+        writeln!(f, "{:<16} {:#018x}", "seed", self.seed)?;
+        for result in &self.results {
+            writeln!(f, "{:<16} {}", result.name, if result.passed { "ok" } else { "FAILED" })?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs the known-answer tests and the randomized checks of all the host crypto routines
+pub fn self_test() -> SelfTestReport {
+    self_test_with_seed(SELF_TEST_SEED)
+}
+
+/// Runs the self-test with the provided seed for the randomized checks
+pub fn self_test_with_seed(seed: u64) -> SelfTestReport {
+    let known_answers = KNOWN_ANSWER_TESTS.iter().map(|test| SelfTestResult {
+        name: test.name,
+        passed: panic::catch_unwind(test.run).unwrap_or(false),
+    });
+    // Every check takes its own stream, so its values do not depend on the other checks
+    let rng = SeededRng::new(seed);
+    let randomized = RANDOMIZED_TESTS.iter().enumerate().map(|(index, test)| {
+        let mut check_rng = rng.fork(index as u64);
+        SelfTestResult {
+            name: test.name,
+            passed: panic::catch_unwind(move || (test.run)(&mut check_rng)).unwrap_or(false),
+        }
+    });
+    let results = known_answers.chain(randomized).collect();
+    SelfTestReport { results, hash_backends: hash_backends(), seed }
+}
+
+/// Keccak-f[1600] of the all-zeros state
+fn keccakf_kat() -> bool {
+    let mut state = [0u64; 25];
+    keccakf(&mut state);
+    (state[0] == 0xf1258f7940e1dde7) && (state[24] == 0xeaf1ff7b5ceca249)
+}
+
+/// SHA-256 compression of the padded "abc" block from the initial hash value
+fn sha256f_kat() -> bool {
+    let mut state =
+        [0x6a09e667bb67ae85, 0x3c6ef372a54ff53a, 0x510e527f9b05688c, 0x1f83d9ab5be0cd19];
+    let input = [0x6162638000000000, 0, 0, 0, 0, 0, 0, 0x18];
+    sha256f(&mut state, &input);
+    state == [0xba7816bf8f01cfea, 0x414140de5dae2223, 0xb00361a396177a9c, 0xb410ff61f20015ad]
+}
+
+/// secp256k1 G + 2G = 3G
+fn secp256k1_add_kat() -> bool {
+    let mut p = [0u64; 8];
+    secp256k1_add(&SECP256K1_G, &SECP256K1_2G, &mut p);
+    p == SECP256K1_3G
+}
+
+/// secp256k1 2 * G = 2G
+fn secp256k1_dbl_kat() -> bool {
+    let mut p = [0u64; 8];
+    secp256k1_dbl(&SECP256K1_G, &mut p);
+    p == SECP256K1_2G
+}
+
+/// BN254 G + 2G = 3G
+fn bn254_curve_add_kat() -> bool {
+    let mut p = [0u64; 8];
+    bn254_curve_add(&BN254_G, &BN254_2G, &mut p);
+    p == BN254_3G
+}
+
+/// BN254 2 * G = 2G
+fn bn254_curve_dbl_kat() -> bool {
+    let mut p = [0u64; 8];
+    bn254_curve_dbl(&BN254_G, &mut p);
+    p == BN254_2G
+}
+
+/// base^65537 mod n, with n the secp256k1 group order, computed by square-and-multiply with the
+/// modular multiplication used by the modexp guests
+fn modexp_kat() -> bool {
+    let base = [0x0123456789abcdef; 4];
+    let mut result = base;
+    for _ in 0..16 {
+        let square = result;
+        arith256_mod(&square, &square, &[0; 4], &SECP256K1_N, &mut result);
+    }
+    let power = result;
+    arith256_mod(&power, &base, &[0; 4], &SECP256K1_N, &mut result);
+    result == [0x1e11bac61748363f, 0x0ec3def67e262980, 0x7d64c060c9b6c5bf, 0x229f235619ea0bd1]
+}
+
+/// Returns k * G for a pseudo-random k in [4, 2^64), computed by double-and-add, so that the
+/// additions never take equal or opposite points
+fn random_multiple(
+    rng: &mut SeededRng,
+    g: &[u64; 8],
+    add: fn(&[u64; 8], &[u64; 8], &mut [u64; 8]),
+    dbl: fn(&[u64; 8], &mut [u64; 8]),
+) -> [u64; 8] {
+    let k = 4 + rng.below(u64::MAX - 3);
+    let mut p = *g;
+    for bit in (0..(63 - k.leading_zeros())).rev() {
+        let q = p;
+        dbl(&q, &mut p);
+        if (k >> bit) & 1 == 1 {
+            let q = p;
+            add(&q, g, &mut p);
+        }
+    }
+    p
+}
+
+/// (P + G) + 2G = P + 3G, for a pseudo-random secp256k1 point P
+fn secp256k1_random_check(rng: &mut SeededRng) -> bool {
+    let p = random_multiple(rng, &SECP256K1_G, secp256k1_add, secp256k1_dbl);
+    let (mut p_g, mut lhs, mut rhs) = ([0u64; 8], [0u64; 8], [0u64; 8]);
+    secp256k1_add(&p, &SECP256K1_G, &mut p_g);
+    secp256k1_add(&p_g, &SECP256K1_2G, &mut lhs);
+    secp256k1_add(&p, &SECP256K1_3G, &mut rhs);
+    lhs == rhs
+}
+
+/// (P + G) + 2G = P + 3G, for a pseudo-random BN254 point P
+fn bn254_random_check(rng: &mut SeededRng) -> bool {
+    let p = random_multiple(rng, &BN254_G, bn254_curve_add, bn254_curve_dbl);
+    let (mut p_g, mut lhs, mut rhs) = ([0u64; 8], [0u64; 8], [0u64; 8]);
+    bn254_curve_add(&p, &BN254_G, &mut p_g);
+    bn254_curve_add(&p_g, &BN254_2G, &mut lhs);
+    bn254_curve_add(&p, &BN254_3G, &mut rhs);
+    lhs == rhs
+}
+
+/// Fermat's little theorem base^(n-1) = 1 mod n, for a pseudo-random base and the secp256k1
+/// group order n, which is prime
+fn modexp_random_check(rng: &mut SeededRng) -> bool {
+    // The top limb below the one of n keeps the base in [1, n)
+    let base = [rng.next_u64() | 1, rng.next_u64(), rng.next_u64(), rng.below(u64::MAX)];
+    let exponent = [SECP256K1_N[0] - 1, SECP256K1_N[1], SECP256K1_N[2], SECP256K1_N[3]];
+    let mut result = [1, 0, 0, 0];
+    for bit in (0..256).rev() {
+        let square = result;
+        arith256_mod(&square, &square, &[0; 4], &SECP256K1_N, &mut result);
+        if (exponent[bit / 64] >> (bit % 64)) & 1 == 1 {
+            let power = result;
+            arith256_mod(&power, &base, &[0; 4], &SECP256K1_N, &mut result);
+        }
+    }
+    result == [1, 0, 0, 0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_self_test() {
+        let report = self_test();
+        assert_eq!(report.results.len(), KNOWN_ANSWER_TESTS.len() + RANDOMIZED_TESTS.len());
+        assert!(report.passed(), "host crypto self-test failed:\n{report}");
+        assert!(report.failures().is_empty());
+        assert_eq!(report.seed, SELF_TEST_SEED);
+
+        // Two runs with the same seed check the same points, and another seed other ones
+        let point = |seed| {
+            random_multiple(&mut SeededRng::new(seed), &SECP256K1_G, secp256k1_add, secp256k1_dbl)
+        };
+        assert_eq!(point(7), point(7));
+        assert_ne!(point(7), point(8));
+        assert_eq!(self_test_with_seed(7), self_test_with_seed(7));
+        assert!(self_test_with_seed(7).passed());
+    }
+}
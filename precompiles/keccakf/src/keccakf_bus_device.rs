@@ -4,6 +4,7 @@
 
 use std::{collections::VecDeque, ops::Add};
 
+use precompiles_common::{AddrOverflow, MemBusHelpers};
 use zisk_common::MemCollectorInfo;
 use zisk_common::{
     BusDevice, BusDeviceMode, BusId, Counter, Metrics, A, B, OPERATION_BUS_ID, OP_TYPE,
@@ -48,6 +49,36 @@ impl KeccakfCounterInputGen {
     pub fn inst_count(&self, op_type: ZiskOperationType) -> Option<u64> {
         (op_type == ZiskOperationType::Keccak).then_some(self.counter.inst_count)
     }
+
+    /// Generates the memory inputs of an operation, unless the memory collectors skip all of them,
+    /// failing if any of its memory addresses overflows the 32-bit address space
+    fn process_operation(
+        &mut self,
+        data: &[u64],
+        pending: &mut VecDeque<(BusId, Vec<u64>)>,
+        mem_collector_info: Option<&[MemCollectorInfo]>,
+    ) -> Result<(), AddrOverflow> {
+        if let Some(mem_collectors_info) = mem_collector_info {
+            if skip_keccakf_mem_inputs(
+                MemBusHelpers::try_narrow_addr(data[B])?,
+                mem_collectors_info,
+            )? {
+                return Ok(());
+            }
+        }
+
+        let step_main = data[A];
+        let addr_main = MemBusHelpers::try_narrow_addr(data[B])?;
+
+        let only_counters = self.mode == BusDeviceMode::Counter;
+        if only_counters {
+            self.measure(data);
+        }
+
+        generate_keccakf_mem_inputs(addr_main, step_main, data, only_counters, pending)?;
+
+        Ok(())
+    }
 }
 
 impl Metrics for KeccakfCounterInputGen {
@@ -114,22 +145,12 @@ impl BusDevice<u64> for KeccakfCounterInputGen {
             return true;
         }
 
-        if let Some(mem_collectors_info) = mem_collector_info {
-            if skip_keccakf_mem_inputs(data[B] as u32, mem_collectors_info) {
-                return true;
-            }
-        }
-
-        let step_main = data[A];
-        let addr_main = data[B] as u32;
-
-        let only_counters = self.mode == BusDeviceMode::Counter;
-        if only_counters {
-            self.measure(data);
+        // The bus has no error channel, so an operation whose memory addresses overflow the 32-bit
+        // address space cannot be processed
+        if let Err(e) = self.process_operation(data, pending, mem_collector_info) {
+            panic!("KeccakfCounterInputGen::process_data() {e}");
         }
 
-        generate_keccakf_mem_inputs(addr_main, step_main, data, only_counters, pending);
-
         true
     }
 
@@ -1,7 +1,7 @@
 use std::collections::VecDeque;
 use tiny_keccak::keccakf;
 
-use precompiles_common::MemBusHelpers;
+use precompiles_common::{AddrOverflow, MemBusHelpers};
 
 use zisk_common::MemCollectorInfo;
 use zisk_common::{BusId, OPERATION_BUS_DATA_SIZE};
@@ -20,7 +20,7 @@ pub fn generate_keccakf_mem_inputs(
     data: &[u64],
     only_counters: bool,
     pending: &mut VecDeque<(BusId, Vec<u64>)>,
-) {
+) -> Result<(), AddrOverflow> {
     // Get the basic data from the input
     // op,op_type,a,b,...
     let state: &mut [u64; 25] = &mut data[4..29].try_into().unwrap();
@@ -37,7 +37,7 @@ pub fn generate_keccakf_mem_inputs(
     for iparam in 0..params_count {
         let is_write = iparam >= read_params;
         let param_index = if is_write { iparam - read_params } else { iparam };
-        let param_addr = addr_main + (param_index * 8 * chunks_per_param) as u32;
+        let param_addr = MemBusHelpers::try_chunk_addr(addr_main, param_index * chunks_per_param)?;
 
         // read/write all chunks of the iparam parameter
         let current_param_offset = if is_write {
@@ -55,7 +55,7 @@ pub fn generate_keccakf_mem_inputs(
                 data[current_param_offset + ichunk]
             };
             MemBusHelpers::mem_aligned_op(
-                param_addr + ichunk as u32 * 8,
+                MemBusHelpers::try_chunk_addr(param_addr, ichunk)?,
                 step_main,
                 chunk_data,
                 is_write,
@@ -63,21 +63,26 @@ pub fn generate_keccakf_mem_inputs(
             );
         }
     }
+
+    Ok(())
 }
 
-pub fn skip_keccakf_mem_inputs(addr_main: u32, mem_collectors_info: &[MemCollectorInfo]) -> bool {
+pub fn skip_keccakf_mem_inputs(
+    addr_main: u32,
+    mem_collectors_info: &[MemCollectorInfo],
+) -> Result<bool, AddrOverflow> {
     let write_params = 1;
     let chunks_per_param = 25;
     for param_index in 0..write_params {
-        let param_addr = addr_main + (param_index * 8 * chunks_per_param) as u32;
+        let param_addr = MemBusHelpers::try_chunk_addr(addr_main, param_index * chunks_per_param)?;
         for ichunk in 0..chunks_per_param {
-            let addr = param_addr + ichunk as u32 * 8;
+            let addr = MemBusHelpers::try_chunk_addr(param_addr, ichunk)?;
             for mem_collector in mem_collectors_info {
                 if !mem_collector.skip_addr(addr) {
-                    return false;
+                    return Ok(false);
                 }
             }
         }
     }
-    true
+    Ok(true)
 }
@@ -1,10 +1,8 @@
-use sha2::compress256;
-
-use precompiles_common::MemBusHelpers;
+use precompiles_common::{AddrOverflow, MemBusHelpers};
 use std::collections::VecDeque;
 use zisk_common::MemCollectorInfo;
 use zisk_common::{BusId, OPERATION_BUS_DATA_SIZE};
-use zisk_core::{convert_u32_to_u64, convert_u64_to_generic_array_bytes, convert_u64_to_u32};
+use zisk_core::sha256f;
 
 #[derive(Debug)]
 pub struct Sha256MemInputConfig {
@@ -21,18 +19,14 @@ pub fn generate_sha256f_mem_inputs(
     data: &[u64],
     only_counters: bool,
     pending: &mut VecDeque<(BusId, Vec<u64>)>,
-) {
+) -> Result<(), AddrOverflow> {
     // Get the basic data from the input
     // op,op_type,a,b,addr[2],...
     let state: &mut [u64; 4] = &mut data[6..10].try_into().unwrap();
     let input: &[u64; 8] = &data[10..18].try_into().unwrap();
 
     // Apply the sha256f function and get the output
-    let mut state_u32: [u32; 8] = convert_u64_to_u32(state).try_into().unwrap();
-    let block = convert_u64_to_generic_array_bytes(input);
-    compress256(&mut state_u32, &[block]);
-
-    *state = convert_u32_to_u64(&state_u32);
+    sha256f(state, input);
 
     // Generate the memory reads/writes
     let indirect_params = 2;
@@ -40,7 +34,7 @@ pub fn generate_sha256f_mem_inputs(
     // Start by generating the indirection reads
     for iparam in 0..indirect_params {
         MemBusHelpers::mem_aligned_load(
-            addr_main + iparam as u32 * 8,
+            MemBusHelpers::try_chunk_addr(addr_main, iparam)?,
             step_main,
             data[OPERATION_BUS_DATA_SIZE + iparam],
             pending,
@@ -57,7 +51,8 @@ pub fn generate_sha256f_mem_inputs(
     for (iparam, &chunks) in chunks_per_param.iter().enumerate().take(params_count) {
         let is_write = iparam >= read_params;
         let param_index = if is_write { iparam - read_params } else { iparam };
-        let param_addr = data[OPERATION_BUS_DATA_SIZE + param_index] as u32;
+        let param_addr =
+            MemBusHelpers::try_narrow_addr(data[OPERATION_BUS_DATA_SIZE + param_index])?;
         // read/write all chunks of the iparam parameter
         let current_param_offset = if is_write {
             // if write calculate index over write_data
@@ -77,7 +72,7 @@ pub fn generate_sha256f_mem_inputs(
                 data[current_param_offset + ichunk]
             };
             MemBusHelpers::mem_aligned_op(
-                param_addr + ichunk as u32 * 8,
+                MemBusHelpers::try_chunk_addr(param_addr, ichunk)?,
                 step_main,
                 chunk_data,
                 is_write,
@@ -85,23 +80,25 @@ pub fn generate_sha256f_mem_inputs(
             );
         }
     }
+
+    Ok(())
 }
 
 pub fn skip_sha256f_mem_inputs(
     addr_main: u32,
     data: &[u64],
     mem_collectors_info: &[MemCollectorInfo],
-) -> bool {
+) -> Result<bool, AddrOverflow> {
     let indirect_params = 2;
     let read_params = 2;
     let write_params = 1;
     let chunks_per_param = [4usize, 8, 4];
 
     for iparam in 0..indirect_params {
-        let addr = addr_main + iparam as u32 * 8;
+        let addr = MemBusHelpers::try_chunk_addr(addr_main, iparam)?;
         for mem_collector in mem_collectors_info {
             if !mem_collector.skip_addr(addr) {
-                return false;
+                return Ok(false);
             }
         }
     }
@@ -109,16 +106,17 @@ pub fn skip_sha256f_mem_inputs(
     for (iparam, &chunks) in chunks_per_param.iter().enumerate().take(read_params + write_params) {
         let is_write = iparam >= read_params;
         let param_index = if is_write { iparam - read_params } else { iparam };
-        let param_addr = data[OPERATION_BUS_DATA_SIZE + param_index] as u32;
+        let param_addr =
+            MemBusHelpers::try_narrow_addr(data[OPERATION_BUS_DATA_SIZE + param_index])?;
 
         for ichunk in 0..chunks {
-            let addr = param_addr + ichunk as u32 * 8;
+            let addr = MemBusHelpers::try_chunk_addr(param_addr, ichunk)?;
             for mem_collector in mem_collectors_info {
                 if !mem_collector.skip_addr(addr) {
-                    return false;
+                    return Ok(false);
                 }
             }
         }
     }
-    true
+    Ok(true)
 }
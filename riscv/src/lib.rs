@@ -2,12 +2,28 @@
 //! The riscv_interpreter function accepts a buffer of bytes (a slice of u8), parses it according to
 //! the RISC-V spec, and generates a vector of RiscvInstruction's
 
+pub mod riscv_decode_outcome;
+pub mod riscv_decoded_item;
 pub mod riscv_inst;
 pub mod riscv_interpreter;
+pub mod riscv_json;
+pub mod riscv_materialize;
+pub mod riscv_mnemonic;
+#[cfg(test)]
+mod riscv_opcodes_conformance;
 pub mod riscv_registers;
 pub mod riscv_rvd;
+pub mod riscv_semantics;
+pub mod riscv_table;
 
+pub use riscv_decode_outcome::*;
+pub use riscv_decoded_item::*;
 pub use riscv_inst::*;
 pub use riscv_interpreter::*;
+pub use riscv_json::*;
+pub use riscv_materialize::*;
+pub use riscv_mnemonic::*;
 pub use riscv_registers::*;
 pub use riscv_rvd::*;
+pub use riscv_semantics::*;
+pub use riscv_table::*;
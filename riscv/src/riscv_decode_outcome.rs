@@ -0,0 +1,185 @@
+//! Decode outcome classification
+//!
+//! The decoder maps every encoding it does not execute to `reserved` or `c.reserved`, which is
+//! not enough for a test oracle, e.g. a fuzzer comparing Zisk against a reference model.
+//! `decode_outcome_16()` and `decode_outcome_32()` classify an instruction word following the
+//! opcode map of the RISC-V unprivileged specification:
+//! * `Valid`: a valid RV64IMAFDC encoding, including the hints, which Zisk executes
+//! * `Reserved`: an encoding reserved for future standard extensions, or a reserved combination
+//!   of fields of a valid instruction (e.g. `c.lwsp` with `rd = 0`)
+//! * `Illegal`: an encoding defined as illegal, i.e. all zeros or all ones
+//! * `UnsupportedByTarget`: a valid encoding of a standard extension, or a custom opcode, that
+//!   Zisk does not implement (B, Zcb, Zfh, Q, V, Zicbo*, atomics other than A, privileged
+//!   instructions)
+
+use crate::decoder_table_lookup;
+
+/// Classification of an instruction encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DecodeOutcome {
+    /// Valid instruction supported by Zisk
+    Valid,
+    /// Encoding reserved for future extensions, or reserved combination of fields
+    Reserved,
+    /// Architecturally illegal encoding
+    Illegal,
+    /// Valid encoding of an extension not supported by Zisk
+    UnsupportedByTarget,
+}
+
+impl DecodeOutcome {
+    /// Returns true if the instruction is valid and supported by Zisk
+    pub fn is_valid(&self) -> bool {
+        *self == DecodeOutcome::Valid
+    }
+}
+
+/// Classifies an instruction word, compressed if its 2 least significant bits are not `11`, in
+/// which case only the 16 least significant bits are used
+pub fn decode_outcome(inst: u32) -> DecodeOutcome {
+    if (inst & 3) == 3 {
+        decode_outcome_32(inst)
+    } else {
+        decode_outcome_16(inst as u16)
+    }
+}
+
+/// Classifies a compressed (16-bit) instruction
+pub fn decode_outcome_16(inst: u16) -> DecodeOutcome {
+    use DecodeOutcome::*;
+
+    assert!(
+        (inst & 3) != 3,
+        "decode_outcome_16() inst=0x{inst:04x} is not a compressed instruction"
+    );
+    if inst == 0 {
+        return Illegal;
+    }
+    let funct3 = (inst >> 13) & 7;
+    let bit12 = (inst >> 12) & 1;
+    let rd = (inst >> 7) & 0x1f;
+    let rs2 = (inst >> 2) & 0x1f;
+    let ci_imm = (bit12 << 5) | rs2;
+    let reserved_if = |reserved: bool| if reserved { Reserved } else { Valid };
+
+    match (inst & 3, funct3) {
+        // c.addi4spn with nzuimm = 0
+        (0, 0) => reserved_if(((inst >> 5) & 0xff) == 0),
+        // Zcb loads and stores
+        (0, 4) => UnsupportedByTarget,
+        // c.addiw with rd = 0
+        (1, 1) => reserved_if(rd == 0),
+        // c.addi16sp and c.lui with a zero immediate
+        (1, 3) => reserved_if(ci_imm == 0),
+        // c.mul and the Zcb unary operations
+        (1, 4) if ((inst >> 10) & 3) == 3 && bit12 == 1 && ((inst >> 5) & 3) >= 2 => {
+            UnsupportedByTarget
+        }
+        // c.lwsp and c.ldsp with rd = 0
+        (2, 2) | (2, 3) => reserved_if(rd == 0),
+        // c.jr with rs1 = 0
+        (2, 4) => reserved_if(bit12 == 0 && rs2 == 0 && rd == 0),
+        _ => Valid,
+    }
+}
+
+/// Returns true if the OP or OP-32 funct7 belongs to the bit manipulation extensions
+fn is_bitmanip_funct7(funct7: u32) -> bool {
+    matches!(funct7, 0x04 | 0x05 | 0x10 | 0x14 | 0x20 | 0x24 | 0x30 | 0x34)
+}
+
+/// Classifies a standard (32-bit) instruction
+pub fn decode_outcome_32(inst: u32) -> DecodeOutcome {
+    use DecodeOutcome::*;
+
+    if inst == 0 || inst == u32::MAX {
+        return Illegal;
+    }
+    assert!((inst & 3) == 3, "decode_outcome_32() inst=0x{inst:08x} is a compressed instruction");
+    let opcode = (inst >> 2) & 0x1f;
+    let funct3 = (inst >> 12) & 7;
+    let funct7 = inst >> 25;
+
+    // Longer instruction encodings: 48, 64 and 80+ bits
+    if (opcode & 7) == 7 {
+        return Reserved;
+    }
+
+    // The decoder table does not check every field of the system and fence instructions
+    if opcode == 0x1c && funct3 == 0 {
+        return match inst {
+            0x00000073 | 0x00100073 => Valid,
+            // sret, mret, wfi, dret
+            0x10200073 | 0x30200073 | 0x10500073 | 0x7b200073 => UnsupportedByTarget,
+            // sfence.vma, hfence.*, sinval.vma
+            _ if (inst & 0x00007f80) == 0 && matches!(funct7, 0x09 | 0x0b | 0x11 | 0x31) => {
+                UnsupportedByTarget
+            }
+            _ => Reserved,
+        };
+    }
+    if inst == 0x8330000f {
+        // fence.tso
+        return UnsupportedByTarget;
+    }
+    if opcode == 0x03 && funct3 <= 1 {
+        let reserved_bits = if funct3 == 0 { 0xf00f8f80 } else { 0xffff8f80 };
+        return if (inst & reserved_bits) == 0 { Valid } else { Reserved };
+    }
+    if decoder_table_lookup(inst).is_some() {
+        return Valid;
+    }
+
+    match opcode {
+        // LOAD-FP and STORE-FP: half, quad and vector loads and stores
+        0x01 | 0x09 => UnsupportedByTarget,
+        // custom-0, custom-1, custom-2 and custom-3
+        0x02 | 0x0a | 0x16 | 0x1e => UnsupportedByTarget,
+        // MISC-MEM: cache block operations
+        0x03 if funct3 == 2 => UnsupportedByTarget,
+        // OP-IMM and OP-IMM-32 shifts space: bit manipulation immediates
+        0x04 | 0x06 if funct3 == 1 || funct3 == 5 => UnsupportedByTarget,
+        // OP and OP-32: bit manipulation
+        0x0c | 0x0e if is_bitmanip_funct7(funct7) => UnsupportedByTarget,
+        // AMO: byte, halfword and quadword atomics, and compare and swap
+        0x0b if matches!(funct3, 0 | 1 | 4) || (inst >> 27) == 0x05 => UnsupportedByTarget,
+        // MADD, MSUB, NMSUB, NMADD and OP-FP: half and quad precision
+        0x10..=0x14 if (funct7 & 3) >= 2 => UnsupportedByTarget,
+        // OP-V
+        0x15 => UnsupportedByTarget,
+        // SYSTEM: hypervisor loads and stores
+        0x1c if funct3 == 4 => UnsupportedByTarget,
+        _ => Reserved,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DecodeOutcome::*;
+
+    #[test]
+    fn test_decode_outcome() {
+        let cases: [(u32, DecodeOutcome); 16] = [
+            (0x0000, Illegal),
+            (0xffffffff, Illegal),
+            (0x0001, Valid),                   // c.nop
+            (0x4002, Reserved),                // c.lwsp x0
+            (0x8002, Reserved),                // c.jr x0
+            (0x6101, Reserved),                // c.addi16sp 0
+            (0x8000, UnsupportedByTarget),     // c.lbu
+            (0x00b50533, Valid),               // add
+            (0x02b57553, Valid),               // fadd.d
+            (0x30200073, UnsupportedByTarget), // mret
+            (0x40b56533, UnsupportedByTarget), // orn
+            (0x04b57553, UnsupportedByTarget), // fadd.h
+            (0x00000057, UnsupportedByTarget), // OP-V
+            (0x0000000b, UnsupportedByTarget), // custom-0
+            (0x00002063, Reserved),            // branch funct3 = 2
+            (0x0000001f, Reserved),            // 48-bit instruction
+        ];
+        for (inst, outcome) in cases {
+            assert_eq!(decode_outcome(inst), outcome, "inst=0x{inst:08x}");
+        }
+    }
+}
@@ -0,0 +1,329 @@
+//! Decoding of code regions containing data
+//!
+//! Compilers can place data in the executable sections, e.g. jump tables or constant pools, and
+//! mark it with the `$d` mapping symbols.  Decoding it as code produces bogus instructions, and if
+//! the data size is not a multiple of 4, the bogus last instruction can swallow the first half of
+//! the next valid one.  `decode_bytes()` decodes a code region skipping the provided data ranges,
+//! which are returned as data items, and restarts the decoding at the end of every data range.
+//!
+//! With `DecodeErrorPolicy::Recover`, `decode_bytes_with_policy()` does not rely on the decoder to
+//! accept every word: each undecodable word, i.e. not a valid encoding according to
+//! `decode_outcome()` or a 32-bit instruction cut by the end of the code, is recorded as a
+//! `DecodeErrorRecord` and replaced by an `Illegal` item, and the decoding continues at the next
+//! 2-byte alignment point.  This allows to analyze partially corrupt dumps and stripped binaries
+//! whose embedded data is not marked.  The all-zeros halfwords are not errors, since the decoder
+//! maps them to the padding nop and to the halt instruction.
+//!
+//! `chunk_offsets()` finds where a code region can be cut into chunks that decode to the same
+//! items as the whole region, so that large regions can be decoded in parallel.
+
+use crate::{decode_outcome, riscv_interpreter, DecodeOutcome, RiscvInstruction};
+
+/// Item of a decoded code region
+#[derive(Debug)]
+pub enum DecodedItem {
+    /// Decoded instruction
+    Instruction(RiscvInstruction),
+    /// Data embedded in the code region, starting at `addr`
+    Data { addr: u64, bytes: Vec<u8> },
+    /// Undecodable word starting at `addr`, replaced by this placeholder of 2 bytes; only
+    /// returned with `DecodeErrorPolicy::Recover`
+    Illegal { addr: u64, raw: u32 },
+}
+
+/// What `decode_bytes_with_policy()` does with the words that cannot be decoded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DecodeErrorPolicy {
+    /// Decode them as the decoder does, i.e. as `reserved` instructions, panicking on a 32-bit
+    /// instruction cut by the end of the code
+    #[default]
+    Strict,
+    /// Record them, replace them by `Illegal` items and continue at the next alignment point
+    Recover,
+}
+
+/// Reason why a word could not be decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// Encoding that is not valid or not supported by Zisk
+    Invalid(DecodeOutcome),
+    /// 32-bit instruction cut by the end of the code, or by the start of a data range
+    Truncated,
+}
+
+/// Undecodable word found by `decode_bytes_with_policy()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeErrorRecord {
+    /// Address of the word
+    pub pc: u64,
+    /// Word, 16 or 32 bits long, or just its first halfword if it was truncated
+    pub raw: u32,
+    /// Reason why it could not be decoded
+    pub error: DecodeError,
+}
+
+/// Decoded code region, with the errors found when decoding it
+#[derive(Debug, Default)]
+pub struct DecodedRegion {
+    /// Items of the region, in address order
+    pub items: Vec<DecodedItem>,
+    /// Undecodable words, in address order; always empty with `DecodeErrorPolicy::Strict`
+    pub errors: Vec<DecodeErrorRecord>,
+}
+
+impl DecodedItem {
+    /// Returns the address of the item
+    pub fn addr(&self) -> u64 {
+        match self {
+            DecodedItem::Instruction(i) => i.rom_address,
+            DecodedItem::Data { addr, .. } | DecodedItem::Illegal { addr, .. } => *addr,
+        }
+    }
+
+    /// Returns the instruction, or None if the item is data
+    pub fn instruction(&self) -> Option<&RiscvInstruction> {
+        match self {
+            DecodedItem::Instruction(i) => Some(i),
+            DecodedItem::Data { .. } | DecodedItem::Illegal { .. } => None,
+        }
+    }
+}
+
+/// Decodes the code region of `bytes` starting at `rom_address`, returning the `[start, end)`
+/// address ranges of `data_ranges` inside it as data items.  The data ranges are widened to
+/// 2-byte boundaries, since instructions are 2-byte aligned.
+pub fn decode_bytes(
+    rom_address: u64,
+    bytes: &[u8],
+    data_ranges: &[(u64, u64)],
+) -> Vec<DecodedItem> {
+    decode_bytes_with_policy(rom_address, bytes, data_ranges, DecodeErrorPolicy::Strict).items
+}
+
+/// Decodes the code region as `decode_bytes()`, applying `policy` to the undecodable words
+pub fn decode_bytes_with_policy(
+    rom_address: u64,
+    bytes: &[u8],
+    data_ranges: &[(u64, u64)],
+    policy: DecodeErrorPolicy,
+) -> DecodedRegion {
+    let code_len = bytes.len() & !1;
+    let ranges = clip_data_ranges(rom_address, code_len, data_ranges);
+
+    let mut region = DecodedRegion::default();
+    let mut offset = 0;
+    let decode_code = |region: &mut DecodedRegion, from: usize, to: usize| match policy {
+        DecodeErrorPolicy::Strict => {
+            if from < to {
+                let code: Vec<u16> = bytes[from..to]
+                    .chunks_exact(2)
+                    .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]))
+                    .collect();
+                region.items.extend(
+                    riscv_interpreter(rom_address + from as u64, &code)
+                        .into_iter()
+                        .map(DecodedItem::Instruction),
+                );
+            }
+        }
+        DecodeErrorPolicy::Recover => decode_code_recovering(rom_address, bytes, from, to, region),
+    };
+    for (start, end) in ranges {
+        let start = start.max(offset);
+        if start >= end {
+            continue;
+        }
+        decode_code(&mut region, offset, start);
+        region.items.push(DecodedItem::Data {
+            addr: rom_address + start as u64,
+            bytes: bytes[start..end].to_vec(),
+        });
+        offset = end;
+    }
+    decode_code(&mut region, offset, code_len);
+    region
+}
+
+/// Returns the offsets at which the code region of `bytes` starting at `rom_address` can be cut
+/// into chunks of at least `chunk_size` bytes, to be decoded apart with `decode_bytes()` producing
+/// the same items as the whole region.  The offsets start with 0 and end with the code length, and
+/// the cuts are at instruction boundaries outside of `data_ranges`.
+pub fn chunk_offsets(
+    rom_address: u64,
+    bytes: &[u8],
+    data_ranges: &[(u64, u64)],
+    chunk_size: usize,
+) -> Vec<usize> {
+    let code_len = bytes.len() & !1;
+    let mut ranges = clip_data_ranges(rom_address, code_len, data_ranges).into_iter().peekable();
+    let halfword = |offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+
+    // Scan the instruction lengths as the decoder does, restarting at the end of every data range
+    let mut offsets = vec![0];
+    let mut offset = 0;
+    while offset < code_len {
+        if let Some(&(start, end)) = ranges.peek() {
+            if start <= offset {
+                offset = offset.max(end);
+                ranges.next();
+                continue;
+            }
+        }
+        let low = halfword(offset);
+        let zero_pair = (low == 0) && ((offset + 4) <= code_len) && (halfword(offset + 2) == 0);
+        offset += if ((low & 3) == 3) || zero_pair { 4 } else { 2 };
+        if (offset < code_len) && ((offset - offsets[offsets.len() - 1]) >= chunk_size) {
+            offsets.push(offset);
+        }
+    }
+    if code_len > 0 {
+        offsets.push(code_len);
+    }
+    offsets
+}
+
+/// Clips the `[start, end)` address ranges of `data_ranges` to the code region of `code_len`
+/// bytes starting at `rom_address`, returning them as sorted offsets widened to 2-byte boundaries
+fn clip_data_ranges(
+    rom_address: u64,
+    code_len: usize,
+    data_ranges: &[(u64, u64)],
+) -> Vec<(usize, usize)> {
+    let region_end = rom_address + code_len as u64;
+    let mut ranges: Vec<(usize, usize)> = data_ranges
+        .iter()
+        .filter(|(start, end)| (start < end) && (*start < region_end) && (*end > rom_address))
+        .map(|(start, end)| {
+            let start = (start.max(&rom_address) - rom_address) as usize & !1;
+            let end = ((end.min(&region_end) - rom_address) as usize).next_multiple_of(2);
+            (start, end)
+        })
+        .collect();
+    ranges.sort_unstable();
+    ranges
+}
+
+/// Decodes the code of `bytes[from..to]` one word at a time, recording the undecodable words and
+/// replacing them by 2-byte `Illegal` items
+fn decode_code_recovering(
+    rom_address: u64,
+    bytes: &[u8],
+    from: usize,
+    to: usize,
+    region: &mut DecodedRegion,
+) {
+    let halfword = |offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+    let mut offset = from;
+    while offset < to {
+        let pc = rom_address + offset as u64;
+        let low = halfword(offset);
+
+        // A zero halfword followed by another one is the 32-bit padding nop
+        let has_high = (offset + 4) <= to;
+        let zero_pair = (low == 0) && has_high && (halfword(offset + 2) == 0);
+        let code = if ((low & 3) != 3) && !zero_pair {
+            vec![low]
+        } else if has_high {
+            vec![low, halfword(offset + 2)]
+        } else {
+            region.errors.push(DecodeErrorRecord {
+                pc,
+                raw: low as u32,
+                error: DecodeError::Truncated,
+            });
+            region.items.push(DecodedItem::Illegal { addr: pc, raw: low as u32 });
+            offset += 2;
+            continue;
+        };
+
+        let raw = code.iter().rev().fold(0u32, |raw, half| (raw << 16) | *half as u32);
+        let outcome = if raw == 0 { DecodeOutcome::Valid } else { decode_outcome(raw) };
+        if outcome.is_valid() {
+            region
+                .items
+                .extend(riscv_interpreter(pc, &code).into_iter().map(DecodedItem::Instruction));
+            offset += code.len() * 2;
+        } else {
+            region.errors.push(DecodeErrorRecord { pc, raw, error: DecodeError::Invalid(outcome) });
+            region.items.push(DecodedItem::Illegal { addr: pc, raw });
+            offset += 2;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_bytes() {
+        // c.nop; 6 bytes of jump table; addi a0, a0, 1
+        let mut bytes = vec![0x01, 0x00];
+        bytes.extend([0x13, 0x05, 0x15, 0x00, 0xff, 0xff]);
+        bytes.extend(0x00150513u32.to_le_bytes());
+
+        // Without the data range the table is decoded as code, misaligning the last instruction
+        let items = decode_bytes(0x1000, &bytes, &[]);
+        assert!(items.iter().all(|item| item.instruction().is_some()));
+        assert!(!items.iter().any(|item| item.addr() == 0x1008));
+
+        let items = decode_bytes(0x1000, &bytes, &[(0x1002, 0x1007), (0x2000, 0x2004)]);
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].instruction().unwrap().inst, "c.nop");
+        assert!(matches!(&items[1], DecodedItem::Data { addr: 0x1002, bytes } if bytes.len() == 6));
+        assert_eq!(items[2].addr(), 0x1008);
+        assert_eq!(items[2].instruction().unwrap().inst, "addi");
+    }
+
+    #[test]
+    fn test_chunk_offsets() {
+        // addi a0, a0, 1; c.nop; 32-bit padding nop; 6 bytes of jump table; c.nop; addi a0, a0, 1
+        let mut bytes = 0x00150513u32.to_le_bytes().to_vec();
+        bytes.extend([0x01, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        bytes.extend([0x13, 0x05, 0x15, 0x00, 0xff, 0xff, 0x01, 0x00]);
+        bytes.extend(0x00150513u32.to_le_bytes());
+        let ranges = [(0x100a, 0x100f)];
+
+        // The cuts are never inside an instruction or the data
+        let offsets = chunk_offsets(0x1000, &bytes, &ranges, 1);
+        assert_eq!(offsets, vec![0, 4, 6, 10, 18, 22]);
+        assert_eq!(chunk_offsets(0x1000, &bytes, &ranges, 8), vec![0, 10, 18, 22]);
+        assert_eq!(chunk_offsets(0x1000, &bytes, &ranges, 1 << 20), vec![0, 22]);
+        assert_eq!(chunk_offsets(0x1000, &[], &ranges, 1), vec![0]);
+
+        // Decoding the chunks apart produces the same items
+        let whole: Vec<u64> =
+            decode_bytes(0x1000, &bytes, &ranges).iter().map(|item| item.addr()).collect();
+        let chunked: Vec<u64> = offsets
+            .windows(2)
+            .flat_map(|w| decode_bytes(0x1000 + w[0] as u64, &bytes[w[0]..w[1]], &ranges))
+            .map(|item| item.addr())
+            .collect();
+        assert_eq!(whole, chunked);
+    }
+
+    #[test]
+    fn test_decode_bytes_recover() {
+        // addi a0, a0, 1; an all-ones word; c.nop; the first half of addi a0, a0, 1
+        let mut bytes = 0x00150513u32.to_le_bytes().to_vec();
+        bytes.extend([0xff, 0xff, 0xff, 0xff, 0x01, 0x00, 0x13, 0x05]);
+
+        let region = decode_bytes_with_policy(0x1000, &bytes, &[], DecodeErrorPolicy::Recover);
+        let addrs: Vec<u64> = region.items.iter().map(|item| item.addr()).collect();
+        assert_eq!(addrs, vec![0x1000, 0x1004, 0x1006, 0x1008, 0x100a]);
+        assert_eq!(region.items[3].instruction().unwrap().inst, "c.nop");
+
+        // The all-ones word is illegal, and its second half starts a reserved longer encoding
+        let errors: Vec<(u64, DecodeError)> =
+            region.errors.iter().map(|error| (error.pc, error.error)).collect();
+        assert_eq!(
+            errors,
+            vec![
+                (0x1004, DecodeError::Invalid(DecodeOutcome::Illegal)),
+                (0x1006, DecodeError::Invalid(DecodeOutcome::Reserved)),
+                (0x100a, DecodeError::Truncated),
+            ]
+        );
+        assert!(matches!(region.items[4], DecodedItem::Illegal { addr: 0x100a, raw: 0x0513 }));
+    }
+}
@@ -32,6 +32,8 @@
 //!
 //! See <https://devopedia.org/risc-v-instruction-sets>
 
+use crate::Mnemonic;
+
 /// RISC-V instruction data
 #[derive(Default, Debug)]
 pub struct RiscvInstruction {
@@ -70,7 +72,7 @@ impl RiscvInstruction {
             rvinst,
             rom_address,
             t: "I".to_string(),
-            inst: "addi".to_string(),
+            inst: Mnemonic::Addi.to_string(),
             rd: 0,
             rs1: 0,
             rs2: 0,
@@ -85,7 +87,7 @@ impl RiscvInstruction {
             rvinst,
             rom_address,
             t: "CINVALID".to_string(),
-            inst: "c.halt".to_string(),
+            inst: Mnemonic::CHalt.to_string(),
             rd: 0,
             rs1: 0,
             rs2: 0,
@@ -94,6 +96,26 @@ impl RiscvInstruction {
         }
     }
 
+    /// Returns the instruction mnemonic
+    pub fn mnemonic(&self) -> Mnemonic {
+        self.inst.parse().unwrap_or_else(|e| panic!("RiscvInstruction::mnemonic() {e}"))
+    }
+
+    /// Returns true if the instruction was decoded from a 16-bits compressed instruction
+    pub fn is_compressed(&self) -> bool {
+        self.inst.starts_with("c.")
+    }
+
+    /// Returns the length of the original instruction in bytes, i.e. 2 for compressed
+    /// instructions and 4 otherwise, so that the next pc is `rom_address + len_bytes()`
+    pub fn len_bytes(&self) -> u64 {
+        if self.is_compressed() {
+            2
+        } else {
+            4
+        }
+    }
+
     /// Creates a human-readable string containing RISCV data fields that are non-zero
     pub fn to_text(&self) -> String {
         let mut s = String::new();
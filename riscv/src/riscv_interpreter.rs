@@ -1,6 +1,6 @@
 //! Parses a 32-bits RISC-V instruction
 
-use crate::{RiscvInstruction, Rvd};
+use crate::{Mnemonic, RiscvInstruction, Rvd};
 
 /// Convert 32-bits data chunk that contains a signed integer of a specified size in bits to a
 /// signed integer of 32 bits
@@ -14,6 +14,11 @@ fn signext(v: u32, size: u32) -> i32 {
     }
 }
 
+/// Returns the total size in bytes of the code the provided instructions were decoded from
+pub fn total_code_size(insts: &[RiscvInstruction]) -> u64 {
+    insts.iter().map(|i| i.len_bytes()).sum()
+}
+
 /// Interprets a buffer of 32-bits RICSV instructions into a vector of decoded RISCV instructions
 /// split by field
 pub fn riscv_interpreter(rom_address: u64, code: &[u16]) -> Vec<RiscvInstruction> {
@@ -232,16 +237,16 @@ fn riscv_get_instruction_32(inst: u32, root_address: u64, code_index: usize) ->
         i.rs2 = (inst & 0x1F00000) >> 20;
         i.funct5 = (inst & 0xF8000000) >> 27;
         i.aq = (inst & 0x4000000) >> 26;
-        i.rl = (inst & 0x2000000) >> 24;
+        i.rl = (inst & 0x2000000) >> 25;
     } else if i.t == *"C" {
         i.funct3 = (inst & 0x7000) >> 12;
         if i.funct3 == 0 {
             if inst == 0x00000073 {
-                i.inst = "ecall".to_string();
+                i.inst = Mnemonic::Ecall.to_string();
             } else if inst == 0x00100073 {
-                i.inst = "ebreak".to_string();
+                i.inst = Mnemonic::Ebreak.to_string();
             } else {
-                i.inst = "ecall".to_string();
+                i.inst = Mnemonic::Ecall.to_string();
                 // TODO check what means this extra bits in ECALL
                 // throw new Error(`Invalid opcode: ${opcode} at index=${code_index}`);
             }
@@ -260,22 +265,22 @@ fn riscv_get_instruction_32(inst: u32, root_address: u64, code_index: usize) ->
         if i.funct3 == 0 {
             if (inst & 0xF00F8F80) != 0 {
                 //panic!("Invalid F funct3=0 inst=0x{inst:x} at index={code_index} addr=0x{rom_address:x}");
-                i.inst = "reserved".to_string();
+                i.inst = Mnemonic::Reserved.to_string();
             } else {
                 i.pred = (inst & 0x0F000000) >> 24;
                 i.succ = (inst & 0x00F00000) >> 20;
-                i.inst = "fence".to_string();
+                i.inst = Mnemonic::Fence.to_string();
             }
         } else if i.funct3 == 1 {
             if (inst & 0xFFFF8F80) != 0 {
                 //panic!("Invalid F funct3=1 inst=0x{inst:x} at index={code_index} addr=0x{rom_address:x}");
-                i.inst = "reserved".to_string();
+                i.inst = Mnemonic::Reserved.to_string();
             } else {
-                i.inst = "fence.i".to_string();
+                i.inst = Mnemonic::FenceI.to_string();
             }
         } else {
             //panic!("Invalid F funct3={:?} inst=0x{inst:x} at index={code_index} addr=0x{rom_address:x}", i.funct3);
-            i.inst = "reserved".to_string();
+            i.inst = Mnemonic::Reserved.to_string();
         }
     } else if i.t == *"INVALID" {
     } else {
@@ -287,6 +292,7 @@ fn riscv_get_instruction_32(inst: u32, root_address: u64, code_index: usize) ->
 fn riscv_get_instruction_16(inst: u16, root_address: u64, code_index: usize) -> RiscvInstruction {
     // This is a 16-bit instruction, so we need to decode it accordingly
     let (inst_type, inst_name) = Rvd::get_type_and_name_16_bits(inst);
+    let mnemonic: Mnemonic = inst_name.parse().unwrap();
 
     // Create a RISCV instruction instance to be filled with data from the instruction and from
     // the RVD info data
@@ -309,20 +315,20 @@ fn riscv_get_instruction_16(inst: u16, root_address: u64, code_index: usize) ->
         i.rs1 = ((inst >> 7) & 0x1F) as u32;
         i.rs2 = ((inst >> 2) & 0x1F) as u32;
 
-        if inst_name == "c.jr" {
+        if mnemonic == Mnemonic::CJr {
             i.rd = 0;
             if i.rs2 != 0 {
                 //panic!("Invalid use of rs2!=0 in c.jr at index={code_index} addr=0x{rom_address:x}");
-                i.inst = "c.reserved".to_string();
+                i.inst = Mnemonic::CReserved.to_string();
             }
-        } else if inst_name == "c.jalr" {
+        } else if mnemonic == Mnemonic::CJalr {
             i.rd = 1;
-        } else if inst_name == "c.mv" {
+        } else if mnemonic == Mnemonic::CMv {
             i.rd = i.rs1;
             i.rs1 = 0;
             if i.rd == 0 {
                 // This is a hint and must not be executed
-                i.inst = "c.nop".to_string(); // Change to c.nop
+                i.inst = Mnemonic::CNop.to_string(); // Change to c.nop
             }
         } else {
             i.rd = i.rs1;
@@ -332,7 +338,7 @@ fn riscv_get_instruction_16(inst: u16, root_address: u64, code_index: usize) ->
         // CI     Immediate            |funct3   |imm |rd/rs1      |imm       |op |
         i.rd = ((inst >> 7) & 0x1F) as u32;
         i.rs1 = i.rd;
-        if inst_name == "c.addi16sp" {
+        if mnemonic == Mnemonic::CAddi16sp {
             let imm9 = ((inst >> 12) & 0x1) as u32;
             let imm4 = ((inst >> 6) & 0x1) as u32;
             let imm6 = ((inst >> 5) & 0x1) as u32;
@@ -340,19 +346,19 @@ fn riscv_get_instruction_16(inst: u16, root_address: u64, code_index: usize) ->
             let imm5 = ((inst >> 2) & 0x1) as u32;
             let imm = (imm9 << 9) | (imm8_7 << 7) | (imm6 << 6) | (imm5 << 5) | (imm4 << 4);
             i.imm = signext(imm, 10);
-        } else if (inst_name == "c.addi") || (inst_name == "c.addiw") {
+        } else if (mnemonic == Mnemonic::CAddi) || (mnemonic == Mnemonic::CAddiw) {
             let imm5 = ((inst >> 12) & 0x1) as u32;
             let imm4_0 = ((inst >> 2) & 0x1F) as u32;
             let imm = (imm5 << 5) | imm4_0;
             i.imm = signext(imm, 6);
             if i.rd == 0 {
                 // This is a hint and must not be executed
-                i.inst = "c.nop".to_string(); // Change to c.nop
+                i.inst = Mnemonic::CNop.to_string(); // Change to c.nop
             }
-        } else if inst_name == "c.li" {
+        } else if mnemonic == Mnemonic::CLi {
             if i.rd == 0 {
                 // This is a hint and must not be executed
-                i.inst = "c.nop".to_string(); // Change to c.nop
+                i.inst = Mnemonic::CNop.to_string(); // Change to c.nop
             } else {
                 let imm5 = ((inst >> 12) & 0x1) as u32;
                 let imm4_0 = ((inst >> 2) & 0x1F) as u32;
@@ -360,22 +366,22 @@ fn riscv_get_instruction_16(inst: u16, root_address: u64, code_index: usize) ->
                 i.imm = signext(imm, 6);
                 i.rs1 = 0;
             }
-        } else if inst_name == "c.lui" {
+        } else if mnemonic == Mnemonic::CLui {
             let imm17 = ((inst >> 12) & 0x1) as u32;
             let imm16_12 = ((inst >> 2) & 0x1F) as u32;
             i.imm = signext((imm17 << 17) | (imm16_12 << 12), 18);
             if i.rd == 0 {
                 // This is a hint and must not be executed
-                i.inst = "c.nop".to_string(); // Change to c.nop
+                i.inst = Mnemonic::CNop.to_string(); // Change to c.nop
             }
             if i.rd == 2 {
                 // panic!(
                 //     "Invalid use of rd=2 in c.lui at index={} inst=0x{:x} addr=0x{:x}",
                 //     code_index, inst, rom_address
                 // );
-                i.inst = "c.reserved".to_string();
+                i.inst = Mnemonic::CReserved.to_string();
             }
-        } else if inst_name == "c.ldsp" {
+        } else if mnemonic == Mnemonic::CLdsp {
             let imm5 = ((inst >> 12) & 0x1) as u32;
             let imm4_3 = ((inst >> 5) & 0x3) as u32;
             let imm8_6 = ((inst >> 2) & 0x7) as u32;
@@ -384,10 +390,10 @@ fn riscv_get_instruction_16(inst: u16, root_address: u64, code_index: usize) ->
                 // panic!(
                 //     "Invalid use of rd=0 in c.ldsp at index={code_index} addr=0x{rom_address:x}"
                 // );
-                i.inst = "c.reserved".to_string();
+                i.inst = Mnemonic::CReserved.to_string();
             }
             i.rs1 = 2; // x2 is always the base pointer for LDSP instructions
-        } else if inst_name == "c.lwsp" {
+        } else if mnemonic == Mnemonic::CLwsp {
             let imm5 = ((inst >> 12) & 0x1) as u32;
             let imm4_2 = ((inst >> 4) & 0x7) as u32;
             let imm7_6 = ((inst >> 2) & 0x3) as u32;
@@ -396,7 +402,7 @@ fn riscv_get_instruction_16(inst: u16, root_address: u64, code_index: usize) ->
                 // panic!(
                 //     "Invalid use of rd=0 in c.lwsp at index={code_index} addr=0x{rom_address:x}"
                 // );
-                i.inst = "c.reserved".to_string();
+                i.inst = Mnemonic::CReserved.to_string();
             }
             i.rs1 = 2; // x2 is always the base pointer for LWSP instructions
         } else {
@@ -433,7 +439,7 @@ fn riscv_get_instruction_16(inst: u16, root_address: u64, code_index: usize) ->
                 // (inst >> 13) & 0x7,
                 // code_index,
                 // rom_address),
-                i.inst = "c.reserved".to_string();
+                i.inst = Mnemonic::CReserved.to_string();
             }
         }
 
@@ -453,7 +459,7 @@ fn riscv_get_instruction_16(inst: u16, root_address: u64, code_index: usize) ->
     } else if i.t == "CL" {
         // Format Meaning              |15 14 13 |12  11 10 |9 8 7 |6 5 |4 3 2 |1 0|
         // CL     Load                 |funct3   |imm       |rs1′  |imm |rd′   |op |
-        if inst_name == "c.lw" {
+        if mnemonic == Mnemonic::CLw {
             // Immediate is in format imm[5:3], imm[2|6]
             let imm5_3 = ((inst >> 10) & 0x7) as u32;
             let imm2 = ((inst >> 6) & 0x1) as u32;
@@ -470,7 +476,7 @@ fn riscv_get_instruction_16(inst: u16, root_address: u64, code_index: usize) ->
     } else if i.t == "CS" {
         // Format Meaning              |15 14 13 |12  11 10 |9 8 7 |6 5 |4 3 2 |1 0|
         // CS     Store                |funct3   |imm       |rs1′  |imm |rs2′  |op |
-        if inst_name == "c.sw" {
+        if mnemonic == Mnemonic::CSw {
             // Immediate is in format imm[5:3], imm[2|6]
             let imm5_3 = ((inst >> 10) & 0x7) as u32;
             let imm2 = ((inst >> 6) & 0x1) as u32;
@@ -494,7 +500,7 @@ fn riscv_get_instruction_16(inst: u16, root_address: u64, code_index: usize) ->
         // Format Meaning              |15 14 13 |12  11 10 |9 8 7 |6 5 4 3 2 |1 0|
         // CB     Branch               |funct3   |offset    |rs1′  |offset    |op |
         // Offset is in format offset[8|4:3] and offset[7:6|2:1|5]
-        if inst_name == "c.andi" {
+        if mnemonic == Mnemonic::CAndi {
             let imm5 = ((inst >> 12) & 0x1) as u32;
             let imm4_0 = ((inst >> 2) & 0x1F) as u32;
             i.imm = signext((imm5 << 5) | imm4_0, 6);
@@ -504,9 +510,9 @@ fn riscv_get_instruction_16(inst: u16, root_address: u64, code_index: usize) ->
                 // panic!(
                 //     "Invalid use of rd=0 in c.andi at index={code_index} addr=0x{rom_address:x}"
                 // );
-                i.inst = "c.reserved".to_string();
+                i.inst = Mnemonic::CReserved.to_string();
             }
-        } else if inst_name == "c.srli" {
+        } else if mnemonic == Mnemonic::CSrli {
             let imm5 = ((inst >> 12) & 0x1) as u32;
             let imm4_0 = ((inst >> 2) & 0x1F) as u32;
             i.imm = ((imm5 << 5) | imm4_0) as i32;
@@ -514,7 +520,7 @@ fn riscv_get_instruction_16(inst: u16, root_address: u64, code_index: usize) ->
             i.rs1 = i.rd;
             if i.rd == 0 {
                 // This is a hint and must not be executed
-                i.inst = "c.nop".to_string(); // Change to c.nop
+                i.inst = Mnemonic::CNop.to_string(); // Change to c.nop
             }
         } else {
             let offset8 = ((inst >> 12) & 0x1) as u32;
@@ -0,0 +1,159 @@
+//! Machine-readable JSON output of decoded programs
+//!
+//! * `to_json()` returns the decoded instructions of a program as a JSON document, so that
+//!   analytics and web tooling can consume them without Rust bindings.  `JsonProgramWriter` writes
+//!   the same document incrementally to any `io::Write`, for programs too large to hold it in
+//!   memory.
+//! * The schema is identified by `JSON_SCHEMA` and `JSON_SCHEMA_VERSION`:
+//! ```text
+//! {"schema":"zisk-decoded-program","version":1,"instructions":[
+//! {"pc":"0x80000000","mnemonic":"addi","operands":{"rd":10,"rs1":10,"imm":1},"raw":"0x00150513","length":4,"compressed":false},
+//! {"pc":"0x80000004","mnemonic":"c.nop","operands":{},"raw":"0x0001","length":2,"compressed":true}
+//! ]}
+//! ```
+//!   * `pc` and `raw` are hexadecimal strings, since JSON numbers lose precision above 2^53.
+//!   * `operands` contains the fields of the instruction format, i.e. of the `t` type: `rd`,
+//!     `rs1`, `rs2`, `rs3`, `imm` and `imme`, `csr`, `aq` and `rl`, `pred` and `succ`.  For the
+//!     compressed and invalid formats, it contains the non-zero registers and immediates of the
+//!     equivalent expanded instruction.
+//!   * `length` is the encoding length in bytes, and `compressed` is true for 16-bit encodings.
+//! * Every instruction is written in a line of its own, with its fields in this order, so that
+//!   line-oriented tools can process the document too.
+//! * Fields can be added to the schema without changing its version; removing a field or changing
+//!   its meaning requires a new version.
+
+use std::io::{self, Write};
+
+use crate::RiscvInstruction;
+
+/// Name of the schema of the JSON documents
+pub const JSON_SCHEMA: &str = "zisk-decoded-program";
+/// Version of the schema of the JSON documents
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+/// Returns the JSON document of the decoded instructions, provided as (pc, instruction, true if
+/// it was decoded from a compressed encoding)
+pub fn to_json(instructions: &[(u64, RiscvInstruction, bool)]) -> String {
+    let mut writer = JsonProgramWriter::new(Vec::new()).unwrap();
+    for (pc, instruction, compressed) in instructions {
+        writer.write(*pc, instruction, *compressed).unwrap();
+    }
+    String::from_utf8(writer.finish().unwrap()).unwrap()
+}
+
+/// Incremental writer of the JSON document of decoded instructions
+pub struct JsonProgramWriter<W: Write> {
+    out: W,
+    count: u64,
+}
+
+impl<W: Write> JsonProgramWriter<W> {
+    /// Creates a writer, writing the start of the document
+    pub fn new(mut out: W) -> io::Result<Self> {
+        write!(
+            out,
+            "{{\"schema\":\"{JSON_SCHEMA}\",\"version\":{JSON_SCHEMA_VERSION},\"instructions\":["
+        )?;
+        Ok(Self { out, count: 0 })
+    }
+
+    /// Writes the next instruction of the document
+    pub fn write(
+        &mut self,
+        pc: u64,
+        instruction: &RiscvInstruction,
+        compressed: bool,
+    ) -> io::Result<()> {
+        let separator = if self.count == 0 { "\n" } else { ",\n" };
+        let length = if compressed { 2 } else { 4 };
+        let raw_digits = length * 2;
+        let operands: Vec<String> = operands(instruction)
+            .into_iter()
+            .map(|(name, value)| format!("\"{name}\":{value}"))
+            .collect();
+        write!(
+            self.out,
+            "{separator}{{\"pc\":\"0x{pc:x}\",\"mnemonic\":\"{}\",\"operands\":{{{}}},\"raw\":\"0x{:0raw_digits$x}\",\"length\":{length},\"compressed\":{compressed}}}",
+            escape(&instruction.inst),
+            operands.join(","),
+            instruction.rvinst,
+        )?;
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Writes the end of the document and returns the output
+    pub fn finish(mut self) -> io::Result<W> {
+        writeln!(self.out, "\n]}}")?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Returns the operands of the instruction format, as (name, value)
+fn operands(i: &RiscvInstruction) -> Vec<(&'static str, i64)> {
+    let (rd, rs1, rs2, rs3, imm) =
+        (i.rd as i64, i.rs1 as i64, i.rs2 as i64, i.rs3 as i64, i.imm as i64);
+    match i.t.as_str() {
+        "I" => vec![("rd", rd), ("rs1", rs1), ("imm", imm)],
+        "R" => vec![("rd", rd), ("rs1", rs1), ("rs2", rs2)],
+        "R4" => vec![("rd", rd), ("rs1", rs1), ("rs2", rs2), ("rs3", rs3)],
+        "S" | "B" => vec![("rs1", rs1), ("rs2", rs2), ("imm", imm)],
+        "U" | "J" => vec![("rd", rd), ("imm", imm)],
+        "A" => {
+            vec![("rd", rd), ("rs1", rs1), ("rs2", rs2), ("aq", i.aq as i64), ("rl", i.rl as i64)]
+        }
+        "C" if i.funct3 == 0 => vec![],
+        "C" if (i.funct3 & 0x4) != 0 => {
+            vec![("rd", rd), ("imme", i.imme as i64), ("csr", i.csr as i64)]
+        }
+        "C" => vec![("rd", rd), ("rs1", rs1), ("csr", i.csr as i64)],
+        "F" => vec![("pred", i.pred as i64), ("succ", i.succ as i64)],
+        _ => [("rd", rd), ("rs1", rs1), ("rs2", rs2), ("imm", imm)]
+            .into_iter()
+            .filter(|(_, value)| *value != 0)
+            .collect(),
+    }
+}
+
+/// Escapes a string to be written inside a JSON string
+fn escape(s: &str) -> String {
+    s.chars()
+        .flat_map(|c| match c {
+            '"' | '\\' => vec!['\\', c],
+            c if (c as u32) < 0x20 => format!("\\u{:04x}", c as u32).chars().collect(),
+            c => vec![c],
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::riscv_interpreter;
+
+    #[test]
+    fn test_to_json() {
+        // addi a0, a0, 1; c.nop
+        let instructions: Vec<(u64, RiscvInstruction, bool)> =
+            riscv_interpreter(0x80000000, &[0x0513, 0x0015, 0x0001])
+                .into_iter()
+                .map(|i| {
+                    let compressed = i.is_compressed();
+                    (i.rom_address, i, compressed)
+                })
+                .collect();
+        assert_eq!(
+            to_json(&instructions),
+            "{\"schema\":\"zisk-decoded-program\",\"version\":1,\"instructions\":[\n\
+             {\"pc\":\"0x80000000\",\"mnemonic\":\"addi\",\"operands\":{\"rd\":10,\"rs1\":10,\"imm\":1},\"raw\":\"0x00150513\",\"length\":4,\"compressed\":false},\n\
+             {\"pc\":\"0x80000004\",\"mnemonic\":\"c.nop\",\"operands\":{},\"raw\":\"0x0001\",\"length\":2,\"compressed\":true}\n\
+             ]}\n"
+        );
+        assert_eq!(
+            to_json(&[]),
+            "{\"schema\":\"zisk-decoded-program\",\"version\":1,\"instructions\":[\n]}\n"
+        );
+        assert_eq!(escape("a\"b\\c\n"), "a\\\"b\\\\c\\u000a");
+    }
+}
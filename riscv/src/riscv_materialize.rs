@@ -0,0 +1,269 @@
+//! Materialization of 64-bit constants in a register
+//!
+//! * `materialize_imm()` returns the instructions that load an arbitrary 64-bit constant into a
+//!   register, e.g. for the patcher trampolines or for the programs built by the tests.
+//! * The base sequence is the one used by compilers: the constant is split into a sign-extended
+//!   12-bits low part and a high part, the high part is materialized recursively and shifted left
+//!   with `slli`, and the low part is added with `addi`.  A constant that fits in 32 bits is
+//!   loaded with `lui` and `addiw`, or with a single `addi` from x0.
+//! * Two alternatives are also tried, and the shortest sequence is returned:
+//!   * Constants with trailing zeros are materialized without them, and shifted left at the end.
+//!   * Positive constants with leading zeros are materialized shifted left, with their low bits
+//!     filled with zeros or ones, and shifted right at the end with `srli`.
+//! * With `MaterializeTarget::Rv64ic`, every instruction that has a compressed form with the same
+//!   result is emitted compressed, and ties between sequences of the same length are broken by
+//!   their size in bytes.
+
+use std::cmp::Ordering;
+
+/// Instruction set the materialization sequences are generated for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaterializeTarget {
+    /// RV64I, i.e. only 32-bit instructions
+    Rv64i,
+    /// RV64IC, i.e. compressed instructions where possible
+    Rv64ic,
+}
+
+/// Encoded instruction of a materialization sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodedInstruction {
+    /// 32-bit instruction
+    Full(u32),
+    /// 16-bit compressed instruction
+    Compressed(u16),
+}
+
+impl EncodedInstruction {
+    /// Returns the size of the instruction in bytes
+    pub fn size(&self) -> usize {
+        match self {
+            EncodedInstruction::Full(_) => 4,
+            EncodedInstruction::Compressed(_) => 2,
+        }
+    }
+
+    /// Returns the instruction as little-endian 16-bit parcels, as expected by
+    /// `riscv_interpreter()`
+    pub fn to_parcels(&self) -> Vec<u16> {
+        match self {
+            EncodedInstruction::Full(inst) => vec![*inst as u16, (*inst >> 16) as u16],
+            EncodedInstruction::Compressed(inst) => vec![*inst],
+        }
+    }
+
+    /// Returns the instruction as little-endian bytes
+    pub fn to_le_bytes(&self) -> Vec<u8> {
+        self.to_parcels().iter().flat_map(|parcel| parcel.to_le_bytes()).collect()
+    }
+}
+
+/// Operation of a materialization sequence, applied to the destination register
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ImmOp {
+    /// `addi rd, x0, imm`
+    Li(i64),
+    /// `lui rd, imm20`
+    Lui(i64),
+    /// `addiw rd, rd, imm`
+    Addiw(i64),
+    /// `addi rd, rd, imm`
+    Addi(i64),
+    /// `slli rd, rd, shamt`
+    Slli(u32),
+    /// `srli rd, rd, shamt`
+    Srli(u32),
+}
+
+/// Returns the instructions that load `value` into register `rd`, which is empty for x0
+pub fn materialize_imm(rd: u32, value: i64, target: MaterializeTarget) -> Vec<EncodedInstruction> {
+    assert!(rd < 32, "materialize_imm() invalid register {rd}");
+    if rd == 0 {
+        return Vec::new();
+    }
+
+    let mut candidates = vec![base_sequence(value)];
+    if value != value as i32 as i64 {
+        let trailing_zeros = value.trailing_zeros();
+        if trailing_zeros > 0 {
+            let mut ops = base_sequence(value >> trailing_zeros);
+            ops.push(ImmOp::Slli(trailing_zeros));
+            candidates.push(ops);
+        }
+        let leading_zeros = value.leading_zeros();
+        if (value > 0) && (leading_zeros > 0) {
+            let shifted = value << leading_zeros;
+            for fill in [0, (1 << leading_zeros) - 1] {
+                let mut ops = base_sequence(shifted | fill);
+                ops.push(ImmOp::Srli(leading_zeros));
+                candidates.push(ops);
+            }
+        }
+    }
+
+    candidates
+        .into_iter()
+        .map(|ops| ops.into_iter().map(|op| encode(rd, op, target)).collect::<Vec<_>>())
+        .min_by(|a, b| compare_sequences(a, b))
+        .unwrap()
+}
+
+/// Orders the sequences by number of instructions, and then by size in bytes
+fn compare_sequences(a: &[EncodedInstruction], b: &[EncodedInstruction]) -> Ordering {
+    let size = |s: &[EncodedInstruction]| s.iter().map(|i| i.size()).sum::<usize>();
+    a.len().cmp(&b.len()).then(size(a).cmp(&size(b)))
+}
+
+/// Returns the sign-extended low 12 bits of the value
+fn low12(value: i64) -> i64 {
+    (value << 52) >> 52
+}
+
+/// Returns the compiler sequence that materializes the value
+fn base_sequence(value: i64) -> Vec<ImmOp> {
+    if value == value as i32 as i64 {
+        let lo = low12(value);
+        let hi20 = (value.wrapping_add(0x800) >> 12) & 0xfffff;
+        return match (hi20, lo) {
+            (0, _) => vec![ImmOp::Li(lo)],
+            (_, 0) => vec![ImmOp::Lui(hi20)],
+            _ => vec![ImmOp::Lui(hi20), ImmOp::Addiw(lo)],
+        };
+    }
+
+    // The high part is not zero, since the value does not fit in 32 bits
+    let lo = low12(value);
+    let hi = value.wrapping_sub(lo) >> 12;
+    let trailing_zeros = hi.trailing_zeros();
+    let mut ops = base_sequence(hi >> trailing_zeros);
+    ops.push(ImmOp::Slli(12 + trailing_zeros));
+    if lo != 0 {
+        ops.push(ImmOp::Addi(lo));
+    }
+    ops
+}
+
+/// Returns true if the immediate fits in the 6-bits signed field of the compressed instructions
+fn fits_imm6(imm: i64) -> bool {
+    (-32..32).contains(&imm)
+}
+
+/// Encodes a CI-format compressed instruction
+fn encode_ci(funct3: u32, rd: u32, imm6: i64, op: u32) -> EncodedInstruction {
+    let imm = imm6 as u32;
+    EncodedInstruction::Compressed(
+        ((funct3 << 13) | (((imm >> 5) & 0x1) << 12) | (rd << 7) | ((imm & 0x1f) << 2) | op) as u16,
+    )
+}
+
+/// Encodes an I-format instruction
+fn encode_i(imm12: i64, rs1: u32, funct3: u32, rd: u32, opcode: u32) -> EncodedInstruction {
+    EncodedInstruction::Full(
+        (((imm12 as u32) & 0xfff) << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode,
+    )
+}
+
+/// Encodes an operation on `rd`, compressed if the target allows it
+fn encode(rd: u32, op: ImmOp, target: MaterializeTarget) -> EncodedInstruction {
+    let c = target == MaterializeTarget::Rv64ic;
+    match op {
+        ImmOp::Li(imm) if c && fits_imm6(imm) => encode_ci(0b010, rd, imm, 0b01),
+        ImmOp::Li(imm) => encode_i(imm, 0, 0b000, rd, 0x13),
+        ImmOp::Lui(hi20) => {
+            let imm = (hi20 << 44) >> 44;
+            if c && (rd != 2) && fits_imm6(imm) {
+                encode_ci(0b011, rd, imm, 0b01)
+            } else {
+                EncodedInstruction::Full(((hi20 as u32) << 12) | (rd << 7) | 0x37)
+            }
+        }
+        ImmOp::Addiw(imm) if c && fits_imm6(imm) => encode_ci(0b001, rd, imm, 0b01),
+        ImmOp::Addiw(imm) => encode_i(imm, rd, 0b000, rd, 0x1b),
+        ImmOp::Addi(imm) if c && fits_imm6(imm) => encode_ci(0b000, rd, imm, 0b01),
+        ImmOp::Addi(imm) => encode_i(imm, rd, 0b000, rd, 0x13),
+        ImmOp::Slli(shamt) if c => encode_ci(0b000, rd, shamt as i64, 0b10),
+        ImmOp::Slli(shamt) => encode_i(shamt as i64, rd, 0b001, rd, 0x13),
+        ImmOp::Srli(shamt) if c && (8..16).contains(&rd) => {
+            // CB format, i.e. CI format with rd' = rd - 8 and funct2 = 00
+            encode_ci(0b100, rd - 8, shamt as i64, 0b01)
+        }
+        ImmOp::Srli(shamt) => encode_i(shamt as i64, rd, 0b101, rd, 0x13),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::riscv_interpreter;
+
+    /// Executes the sequence with the decoder as oracle, returning the value of `rd`
+    fn execute(rd: u32, sequence: &[EncodedInstruction]) -> i64 {
+        let parcels: Vec<u16> = sequence.iter().flat_map(|i| i.to_parcels()).collect();
+        let mut x = [0x5a5a_5a5a_5a5a_5a5a_i64; 32];
+        x[0] = 0;
+        for i in riscv_interpreter(0x1000, &parcels) {
+            let (rs1, imm) = (x[i.rs1 as usize], i.imm as i64);
+            let result = match i.inst.as_str() {
+                "lui" | "c.lui" => imm,
+                "addi" | "c.li" | "c.addi" => rs1.wrapping_add(imm),
+                "addiw" | "c.addiw" => rs1.wrapping_add(imm) as i32 as i64,
+                "slli" | "c.slli" => rs1 << imm,
+                "srli" | "c.srli" => ((rs1 as u64) >> imm) as i64,
+                name => panic!("unexpected instruction {name}"),
+            };
+            assert_eq!(i.rd, rd, "{} writes x{} instead of x{rd}", i.inst, i.rd);
+            x[i.rd as usize] = result;
+        }
+        x[rd as usize]
+    }
+
+    #[test]
+    fn test_materialize_imm() {
+        // Small values, bit ranges of ones and their complements, 32-bit boundaries and random
+        let mut values: Vec<i64> = (-5000..5000).collect();
+        for start in 0..64 {
+            for end in start..64 {
+                let ones = ((u64::MAX >> (63 - end)) >> start) << start;
+                values.extend([ones as i64, !ones as i64]);
+            }
+        }
+        for boundary in [i32::MIN as i64, i32::MAX as i64, u32::MAX as i64, i64::MIN, i64::MAX] {
+            values.extend((-2050..2050).map(|delta: i64| boundary.wrapping_add(delta)));
+        }
+        let mut state = 0x2545f4914f6cdd1du64;
+        for _ in 0..20000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            values.push(state as i64);
+        }
+
+        for target in [MaterializeTarget::Rv64i, MaterializeTarget::Rv64ic] {
+            for rd in [1, 2, 10, 31] {
+                for &value in &values {
+                    let sequence = materialize_imm(rd, value, target);
+                    assert_eq!(execute(rd, &sequence), value, "value=0x{value:x} rd=x{rd}");
+                    assert!(sequence.len() <= 8, "value=0x{value:x} {sequence:x?}");
+                    if value == value as i32 as i64 {
+                        assert!(sequence.len() <= 2, "value=0x{value:x} {sequence:x?}");
+                    }
+                    if target == MaterializeTarget::Rv64i {
+                        assert!(sequence.iter().all(|i| i.size() == 4));
+                    }
+                }
+            }
+        }
+
+        assert!(materialize_imm(0, 1, MaterializeTarget::Rv64i).is_empty());
+        // li a0, -1; slli a0, a0, 63
+        assert_eq!(
+            materialize_imm(10, i64::MIN, MaterializeTarget::Rv64ic),
+            [EncodedInstruction::Compressed(0x557d), EncodedInstruction::Compressed(0x157e)]
+        );
+        // lui a0, 0x12345; addiw a0, a0, 0x678
+        assert_eq!(
+            materialize_imm(10, 0x12345678, MaterializeTarget::Rv64i),
+            [EncodedInstruction::Full(0x12345537), EncodedInstruction::Full(0x6785051b)]
+        );
+    }
+}
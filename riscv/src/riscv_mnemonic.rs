@@ -0,0 +1,298 @@
+//! RISC-V instruction mnemonics
+//!
+//! `Mnemonic` lists every instruction name produced by the decoder, including the pseudo names
+//! used for the decoded hints and invalid encodings (`c.nop`, `c.reserved`, `reserved`) and the
+//! Zisk halt instruction (`c.halt`).  Comparing against the enum instead of against string
+//! literals turns a misspelled name into a compilation error.
+//!
+//! `RiscvInstruction::inst` keeps the name as a string, so `RiscvInstruction::mnemonic()` parses
+//! it; every name set by the decoder is guaranteed to parse.
+
+use std::{fmt, str::FromStr};
+
+/// Defines the `Mnemonic` enum, mapping every variant to its assembly name
+macro_rules! mnemonics {
+    ($($variant:ident => $name:literal,)+) => {
+        /// RISC-V instruction mnemonic, as decoded into `RiscvInstruction::inst`
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub enum Mnemonic {
+            $($variant,)+
+        }
+
+        impl Mnemonic {
+            /// All the mnemonics, in alphabetical order of their names
+            pub const ALL: &'static [Mnemonic] = &[$(Mnemonic::$variant,)+];
+
+            /// Returns the assembly name, e.g. `fcvt.d.lu`
+            pub const fn as_str(&self) -> &'static str {
+                match self {
+                    $(Mnemonic::$variant => $name,)+
+                }
+            }
+        }
+
+        impl FromStr for Mnemonic {
+            type Err = UnknownMnemonic;
+
+            fn from_str(name: &str) -> Result<Self, Self::Err> {
+                match name {
+                    $($name => Ok(Mnemonic::$variant),)+
+                    _ => Err(UnknownMnemonic(name.to_string())),
+                }
+            }
+        }
+    };
+}
+
+mnemonics! {
+    Add => "add",
+    Addi => "addi",
+    Addiw => "addiw",
+    Addw => "addw",
+    AmoaddD => "amoadd.d",
+    AmoaddW => "amoadd.w",
+    AmoandD => "amoand.d",
+    AmoandW => "amoand.w",
+    AmomaxD => "amomax.d",
+    AmomaxW => "amomax.w",
+    AmomaxuD => "amomaxu.d",
+    AmomaxuW => "amomaxu.w",
+    AmominD => "amomin.d",
+    AmominW => "amomin.w",
+    AmominuD => "amominu.d",
+    AmominuW => "amominu.w",
+    AmoorD => "amoor.d",
+    AmoorW => "amoor.w",
+    AmoswapD => "amoswap.d",
+    AmoswapW => "amoswap.w",
+    AmoxorD => "amoxor.d",
+    AmoxorW => "amoxor.w",
+    And => "and",
+    Andi => "andi",
+    Auipc => "auipc",
+    Beq => "beq",
+    Bge => "bge",
+    Bgeu => "bgeu",
+    Blt => "blt",
+    Bltu => "bltu",
+    Bne => "bne",
+    CAdd => "c.add",
+    CAddi => "c.addi",
+    CAddi16sp => "c.addi16sp",
+    CAddi4spn => "c.addi4spn",
+    CAddiw => "c.addiw",
+    CAddw => "c.addw",
+    CAnd => "c.and",
+    CAndi => "c.andi",
+    CBeqz => "c.beqz",
+    CBnez => "c.bnez",
+    CEbreak => "c.ebreak",
+    CFld => "c.fld",
+    CFldsp => "c.fldsp",
+    CFsd => "c.fsd",
+    CFsdsp => "c.fsdsp",
+    CHalt => "c.halt",
+    CJ => "c.j",
+    CJalr => "c.jalr",
+    CJr => "c.jr",
+    CLd => "c.ld",
+    CLdsp => "c.ldsp",
+    CLi => "c.li",
+    CLui => "c.lui",
+    CLw => "c.lw",
+    CLwsp => "c.lwsp",
+    CMv => "c.mv",
+    CNop => "c.nop",
+    COr => "c.or",
+    CReserved => "c.reserved",
+    CSd => "c.sd",
+    CSdsp => "c.sdsp",
+    CSlli => "c.slli",
+    CSrai => "c.srai",
+    CSrli => "c.srli",
+    CSub => "c.sub",
+    CSubw => "c.subw",
+    CSw => "c.sw",
+    CSwsp => "c.swsp",
+    CXor => "c.xor",
+    Csrrc => "csrrc",
+    Csrrci => "csrrci",
+    Csrrs => "csrrs",
+    Csrrsi => "csrrsi",
+    Csrrw => "csrrw",
+    Csrrwi => "csrrwi",
+    Div => "div",
+    Divu => "divu",
+    Divuw => "divuw",
+    Divw => "divw",
+    Ebreak => "ebreak",
+    Ecall => "ecall",
+    FaddD => "fadd.d",
+    FaddS => "fadd.s",
+    FclassD => "fclass.d",
+    FclassS => "fclass.s",
+    FcvtDL => "fcvt.d.l",
+    FcvtDLu => "fcvt.d.lu",
+    FcvtDS => "fcvt.d.s",
+    FcvtDW => "fcvt.d.w",
+    FcvtDWu => "fcvt.d.wu",
+    FcvtLD => "fcvt.l.d",
+    FcvtLS => "fcvt.l.s",
+    FcvtLuD => "fcvt.lu.d",
+    FcvtLuS => "fcvt.lu.s",
+    FcvtSD => "fcvt.s.d",
+    FcvtSL => "fcvt.s.l",
+    FcvtSLu => "fcvt.s.lu",
+    FcvtSW => "fcvt.s.w",
+    FcvtSWu => "fcvt.s.wu",
+    FcvtWD => "fcvt.w.d",
+    FcvtWS => "fcvt.w.s",
+    FcvtWuD => "fcvt.wu.d",
+    FcvtWuS => "fcvt.wu.s",
+    FdivD => "fdiv.d",
+    FdivS => "fdiv.s",
+    Fence => "fence",
+    FenceI => "fence.i",
+    FeqD => "feq.d",
+    FeqS => "feq.s",
+    Fld => "fld",
+    FleD => "fle.d",
+    FleS => "fle.s",
+    FltD => "flt.d",
+    FltS => "flt.s",
+    Flw => "flw",
+    FmaddD => "fmadd.d",
+    FmaddS => "fmadd.s",
+    FmaxD => "fmax.d",
+    FmaxS => "fmax.s",
+    FminD => "fmin.d",
+    FminS => "fmin.s",
+    FmsubD => "fmsub.d",
+    FmsubS => "fmsub.s",
+    FmulD => "fmul.d",
+    FmulS => "fmul.s",
+    FmvDX => "fmv.d.x",
+    FmvWX => "fmv.w.x",
+    FmvXD => "fmv.x.d",
+    FmvXW => "fmv.x.w",
+    FnmaddD => "fnmadd.d",
+    FnmaddS => "fnmadd.s",
+    FnmsubD => "fnmsub.d",
+    FnmsubS => "fnmsub.s",
+    Fsd => "fsd",
+    FsgnjD => "fsgnj.d",
+    FsgnjS => "fsgnj.s",
+    FsgnjnD => "fsgnjn.d",
+    FsgnjnS => "fsgnjn.s",
+    FsgnjxD => "fsgnjx.d",
+    FsgnjxS => "fsgnjx.s",
+    FsqrtD => "fsqrt.d",
+    FsqrtS => "fsqrt.s",
+    FsubD => "fsub.d",
+    FsubS => "fsub.s",
+    Fsw => "fsw",
+    Jal => "jal",
+    Jalr => "jalr",
+    Lb => "lb",
+    Lbu => "lbu",
+    Ld => "ld",
+    Lh => "lh",
+    Lhu => "lhu",
+    LrD => "lr.d",
+    LrW => "lr.w",
+    Lui => "lui",
+    Lw => "lw",
+    Lwu => "lwu",
+    Mul => "mul",
+    Mulh => "mulh",
+    Mulhsu => "mulhsu",
+    Mulhu => "mulhu",
+    Mulw => "mulw",
+    Or => "or",
+    Ori => "ori",
+    Rem => "rem",
+    Remu => "remu",
+    Remuw => "remuw",
+    Remw => "remw",
+    Reserved => "reserved",
+    Sb => "sb",
+    ScD => "sc.d",
+    ScW => "sc.w",
+    Sd => "sd",
+    Sh => "sh",
+    Sll => "sll",
+    Slli => "slli",
+    Slliw => "slliw",
+    Sllw => "sllw",
+    Slt => "slt",
+    Slti => "slti",
+    Sltiu => "sltiu",
+    Sltu => "sltu",
+    Sra => "sra",
+    Srai => "srai",
+    Sraiw => "sraiw",
+    Sraw => "sraw",
+    Srl => "srl",
+    Srli => "srli",
+    Srliw => "srliw",
+    Srlw => "srlw",
+    Sub => "sub",
+    Subw => "subw",
+    Sw => "sw",
+    Xor => "xor",
+    Xori => "xori",
+}
+
+impl Mnemonic {
+    /// Returns true if the instruction is a compressed (16-bit) one
+    pub fn is_compressed(&self) -> bool {
+        self.as_str().starts_with("c.")
+    }
+}
+
+impl fmt::Display for Mnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Error returned when parsing a name that is not a known mnemonic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownMnemonic(pub String);
+
+impl fmt::Display for UnknownMnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown RISC-V mnemonic '{}'", self.0)
+    }
+}
+
+impl std::error::Error for UnknownMnemonic {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decoder_table, riscv_interpreter, Rvd};
+
+    #[test]
+    fn test_mnemonic_coverage() {
+        // Every mnemonic round-trips through its name, and the names are unique
+        for mnemonic in Mnemonic::ALL {
+            assert_eq!(mnemonic.as_str().parse::<Mnemonic>(), Ok(*mnemonic));
+        }
+        assert!(Mnemonic::ALL.windows(2).all(|w| w[0].as_str() < w[1].as_str()));
+        assert_eq!("c_unimp".parse::<Mnemonic>(), Err(UnknownMnemonic("c_unimp".to_string())));
+
+        // Every name produced by the decoder is a mnemonic
+        for spec in decoder_table() {
+            let (_, name, _) = Rvd::get_type_and_name_32_bits(spec.matches);
+            assert_eq!(name.parse::<Mnemonic>().map(|m| m.as_str()), Ok(spec.name));
+        }
+        for inst in (0..=u16::MAX).filter(|inst| (inst & 3) != 3) {
+            let (_, name) = Rvd::get_type_and_name_16_bits(inst);
+            assert!(name.parse::<Mnemonic>().is_ok(), "{name}");
+            for i in riscv_interpreter(0x80000000, &[inst]) {
+                assert!(i.inst.parse::<Mnemonic>().is_ok(), "{}", i.inst);
+            }
+        }
+    }
+}
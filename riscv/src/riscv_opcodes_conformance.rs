@@ -0,0 +1,306 @@
+//! Round-trip conformance tests generated from the riscv-opcodes definitions
+//!
+//! * The instruction definitions of the extensions supported by Zisk are vendored in
+//!   `riscv/riscv-opcodes`, in the format of <https://github.com/riscv/riscv-opcodes>: every line
+//!   is an instruction name, its operand fields, and its fixed bit ranges as `msb..lsb=value` or
+//!   `bit=value`.
+//! * For every instruction, `ENCODINGS_PER_INSTRUCTION` encodings are generated with random
+//!   operands, respecting the operand constraints of the spec (e.g. `rd_n0` is not x0), and
+//!   decoded with `riscv_interpreter()`, checking the mnemonic and, for the 32-bit encodings,
+//!   the decoded operands against the values that were encoded.  They are also classified with
+//!   `decode_outcome()`, which must report them as valid.
+//! * `ZISK_FIXED_OPERANDS` lists the operands that the Zisk decoder only accepts with a fixed
+//!   value, e.g. `fence` with a non-zero `fm` is decoded as reserved; they are encoded with that
+//!   value instead of a random one.
+//! * `SPEC_HINTS` lists the operands that make an encoding a HINT in the spec when they are zero,
+//!   e.g. `c.li` with rd=x0; the Zisk decoder decodes them as `c.nop`, which is also accepted.
+
+use crate::{decode_outcome, riscv_interpreter, RiscvInstruction};
+
+/// Vendored riscv-opcodes definitions of the supported extensions
+const OPCODES: [(&str, &str); 15] = [
+    ("rv_i", include_str!("../riscv-opcodes/rv_i")),
+    ("rv64_i", include_str!("../riscv-opcodes/rv64_i")),
+    ("rv_m", include_str!("../riscv-opcodes/rv_m")),
+    ("rv64_m", include_str!("../riscv-opcodes/rv64_m")),
+    ("rv_a", include_str!("../riscv-opcodes/rv_a")),
+    ("rv64_a", include_str!("../riscv-opcodes/rv64_a")),
+    ("rv_zicsr", include_str!("../riscv-opcodes/rv_zicsr")),
+    ("rv_zifencei", include_str!("../riscv-opcodes/rv_zifencei")),
+    ("rv_f", include_str!("../riscv-opcodes/rv_f")),
+    ("rv64_f", include_str!("../riscv-opcodes/rv64_f")),
+    ("rv_d", include_str!("../riscv-opcodes/rv_d")),
+    ("rv64_d", include_str!("../riscv-opcodes/rv64_d")),
+    ("rv_c", include_str!("../riscv-opcodes/rv_c")),
+    ("rv64_c", include_str!("../riscv-opcodes/rv64_c")),
+    ("rv_c_d", include_str!("../riscv-opcodes/rv_c_d")),
+];
+
+/// Number of random encodings tested per instruction
+const ENCODINGS_PER_INSTRUCTION: usize = 64;
+
+/// Operands only accepted by the Zisk decoder with a fixed value, as (instruction, operand, value)
+const ZISK_FIXED_OPERANDS: [(&str, &str, u32); 6] = [
+    ("fence", "fm", 0),
+    ("fence", "rs1", 0),
+    ("fence", "rd", 0),
+    ("fence.i", "rd", 0),
+    ("fence.i", "rs1", 0),
+    ("fence.i", "imm12", 0),
+];
+
+/// Operands whose zero value makes the encoding a HINT, decoded as `c.nop`, as (instruction,
+/// operand)
+const SPEC_HINTS: [(&str, &str); 2] = [("c.li", "rd"), ("c.mv", "rd")];
+
+/// Value constraint of an operand field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Constraint {
+    Any,
+    /// Not zero
+    NonZero,
+    /// Neither zero nor 2, i.e. not x0 nor sp
+    NotZeroNorTwo,
+    /// Not zero, together with the other `JointNonZero` operands of the instruction
+    JointNonZero,
+}
+
+/// Returns the bit range and constraint of an operand field
+fn operand_field(name: &str) -> (u32, u32, Constraint) {
+    use Constraint::*;
+    match name {
+        "rd" | "imm12lo" | "bimm12lo" => (11, 7, Any),
+        "rs1" | "zimm" => (19, 15, Any),
+        "rs2" | "shamtw" => (24, 20, Any),
+        "rs3" => (31, 27, Any),
+        "rm" => (14, 12, Any),
+        "imm12" | "csr" => (31, 20, Any),
+        "imm12hi" | "bimm12hi" => (31, 25, Any),
+        "imm20" | "jimm20" => (31, 12, Any),
+        "shamtd" => (25, 20, Any),
+        "aq" => (26, 26, Any),
+        "rl" => (25, 25, Any),
+        "fm" => (31, 28, Any),
+        "pred" => (27, 24, Any),
+        "succ" => (23, 20, Any),
+        "rd_p" | "rs2_p" => (4, 2, Any),
+        "rs1_p" | "rd_rs1_p" => (9, 7, Any),
+        "rd_rs1" | "rd_rs1_n0" | "rd_n0" | "rs1_n0" | "c_rs1_n0" => {
+            (11, 7, if name.ends_with("_n0") { NonZero } else { Any })
+        }
+        "rd_n2" => (11, 7, NotZeroNorTwo),
+        "c_rs2" => (6, 2, Any),
+        "c_rs2_n0" => (6, 2, NonZero),
+        "c_nzuimm10" => (12, 5, NonZero),
+        "c_uimm7lo" | "c_uimm8lo" => (6, 5, Any),
+        "c_uimm7hi" | "c_uimm8hi" | "c_bimm9hi" => (12, 10, Any),
+        "c_imm6hi" | "c_uimm8sphi" | "c_uimm9sphi" => (12, 12, Any),
+        "c_imm6lo" | "c_uimm8splo" | "c_uimm9splo" | "c_bimm9lo" => (6, 2, Any),
+        "c_nzimm6hi" | "c_nzimm10hi" | "c_nzimm18hi" | "c_nzuimm6hi" => (12, 12, JointNonZero),
+        "c_nzimm6lo" | "c_nzimm10lo" | "c_nzimm18lo" | "c_nzuimm6lo" => (6, 2, JointNonZero),
+        "c_imm12" => (12, 2, Any),
+        "c_uimm8sp_s" | "c_uimm9sp_s" => (12, 7, Any),
+        _ => panic!("operand_field() unknown operand {name}"),
+    }
+}
+
+/// Instruction definition
+struct Definition {
+    name: String,
+    operands: Vec<String>,
+    /// Mask and value of the fixed bits
+    mask: u32,
+    bits: u32,
+    /// True for the 16-bit encodings
+    compressed: bool,
+}
+
+/// Parses a riscv-opcodes definition line
+fn parse_definition(line: &str) -> Definition {
+    let mut tokens = line.split_whitespace();
+    let name = tokens.next().unwrap().to_string();
+    let (mut operands, mut mask, mut bits) = (Vec::new(), 0u32, 0u32);
+    for token in tokens {
+        let Some((range, value)) = token.split_once('=') else {
+            operands.push(token.to_string());
+            continue;
+        };
+        let (msb, lsb) = match range.split_once("..") {
+            Some((msb, lsb)) => (msb.parse::<u32>().unwrap(), lsb.parse::<u32>().unwrap()),
+            None => (range.parse().unwrap(), range.parse().unwrap()),
+        };
+        let value = match value.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16).unwrap(),
+            None => value.parse().unwrap(),
+        };
+        let field_mask = (((1u64 << (msb - lsb + 1)) - 1) as u32) << lsb;
+        assert_eq!(mask & field_mask, 0, "{name} overlapping fixed bits {token}");
+        mask |= field_mask;
+        bits |= (value << lsb) & field_mask;
+    }
+    let compressed = (bits & 3) != 3;
+    Definition { name, operands, mask, bits, compressed }
+}
+
+/// Returns the sign extension of the `width` lower bits of `value`
+fn signext(value: u32, width: u32) -> i32 {
+    ((value << (32 - width)) as i32) >> (32 - width)
+}
+
+/// Returns the operands the decoder must report for a 32-bit encoding, as (field, expected,
+/// decoded)
+fn decoded_operands(
+    operands: &[(String, u32)],
+    i: &RiscvInstruction,
+) -> Vec<(&'static str, i64, i64)> {
+    let value = |name: &str| operands.iter().find(|(n, _)| n == name).map(|(_, v)| *v);
+    let mut checks = Vec::new();
+    for (name, v) in operands {
+        let check = match name.as_str() {
+            "rd" => ("rd", *v as i64, i.rd as i64),
+            "rs1" => ("rs1", *v as i64, i.rs1 as i64),
+            "rs2" => ("rs2", *v as i64, i.rs2 as i64),
+            "rs3" => ("rs3", *v as i64, i.rs3 as i64),
+            "rm" => ("rm", *v as i64, i.funct3 as i64),
+            "imm12" => ("imm", signext(*v, 12) as i64, i.imm as i64),
+            "imm12hi" => {
+                let imm = (v << 5) | value("imm12lo").unwrap();
+                ("imm", signext(imm, 12) as i64, i.imm as i64)
+            }
+            "bimm12hi" => {
+                let lo = value("bimm12lo").unwrap();
+                let imm = ((v >> 6) << 12) | ((lo & 1) << 11) | ((v & 0x3f) << 5) | (lo & 0x1e);
+                ("imm", signext(imm, 13) as i64, i.imm as i64)
+            }
+            "imm20" => ("imm", (v << 12) as i32 as i64, i.imm as i64),
+            "jimm20" => {
+                let imm = ((v >> 19) << 20)
+                    | ((v & 0xff) << 12)
+                    | (((v >> 8) & 1) << 11)
+                    | (((v >> 9) & 0x3ff) << 1);
+                ("imm", signext(imm, 21) as i64, i.imm as i64)
+            }
+            "shamtd" | "shamtw" => ("shamt", *v as i64, i.imm as i64),
+            "aq" => ("aq", *v as i64, i.aq as i64),
+            "rl" => ("rl", *v as i64, i.rl as i64),
+            "csr" => ("csr", *v as i64, i.csr as i64),
+            "zimm" => ("zimm", *v as i64, i.imme as i64),
+            "pred" => ("pred", *v as i64, i.pred as i64),
+            "succ" => ("succ", *v as i64, i.succ as i64),
+            _ => continue,
+        };
+        checks.push(check);
+    }
+    checks
+}
+
+/// xorshift64 generator, so that the encodings are reproducible
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 32) as u32
+    }
+}
+
+/// Generates a random encoding of the instruction, returning it and its operand values
+fn encode(definition: &Definition, rng: &mut Rng) -> (u32, Vec<(String, u32)>) {
+    loop {
+        let mut inst = definition.bits;
+        let mut operands = Vec::new();
+        let mut joint_non_zero = None;
+        let mut valid = true;
+        for name in &definition.operands {
+            let (msb, lsb, constraint) = operand_field(name);
+            let width = msb - lsb + 1;
+            let fixed = ZISK_FIXED_OPERANDS
+                .iter()
+                .find(|(inst, operand, _)| (*inst == definition.name) && (operand == name));
+            let value = match fixed {
+                Some((_, _, value)) => *value,
+                None => rng.next() & (((1u64 << width) - 1) as u32),
+            };
+            valid &= match constraint {
+                Constraint::Any => true,
+                Constraint::NonZero => value != 0,
+                Constraint::NotZeroNorTwo => (value != 0) && (value != 2),
+                Constraint::JointNonZero => {
+                    joint_non_zero = Some(joint_non_zero.unwrap_or(false) | (value != 0));
+                    true
+                }
+            };
+            inst |= value << lsb;
+            operands.push((name.clone(), value));
+        }
+        if valid && joint_non_zero.unwrap_or(true) {
+            assert_eq!(
+                inst & definition.mask,
+                definition.bits,
+                "{} operand overlap",
+                definition.name
+            );
+            return (inst, operands);
+        }
+    }
+}
+
+#[test]
+fn test_riscv_opcodes_round_trip() {
+    let mut rng = Rng(0x5a15_c0de_2024_0001);
+    let mut failures = Vec::new();
+    let mut tested = 0;
+    for (file, definitions) in OPCODES {
+        for line in definitions.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let definition = parse_definition(line);
+            for _ in 0..ENCODINGS_PER_INSTRUCTION {
+                let (inst, operands) = encode(&definition, &mut rng);
+                let code: Vec<u16> = if definition.compressed {
+                    vec![inst as u16]
+                } else {
+                    vec![inst as u16, (inst >> 16) as u16]
+                };
+                let decoded = riscv_interpreter(0x1000, &code);
+                let i = &decoded[0];
+                let outcome = decode_outcome(inst);
+                if !outcome.is_valid() {
+                    failures.push(format!(
+                        "{file}: {} 0x{inst:08x} classified as {outcome:?}",
+                        definition.name
+                    ));
+                }
+                let hint = SPEC_HINTS.iter().any(|(inst, operand)| {
+                    (*inst == definition.name) && operands.contains(&(operand.to_string(), 0))
+                });
+                if hint && (i.inst == "c.nop") {
+                    continue;
+                }
+                if (decoded.len() != 1) || (i.inst != definition.name) {
+                    failures.push(format!(
+                        "{file}: {} 0x{inst:08x} decoded as {}",
+                        definition.name, i.inst
+                    ));
+                    break;
+                }
+                if !definition.compressed {
+                    for (field, expected, got) in decoded_operands(&operands, i) {
+                        if expected != got {
+                            failures.push(format!(
+                                "{file}: {} 0x{inst:08x} {field}={got}, expected {expected}",
+                                definition.name
+                            ));
+                        }
+                    }
+                }
+            }
+            tested += 1;
+        }
+    }
+    assert!(tested > 150, "only {tested} instructions tested");
+    assert!(failures.is_empty(), "{} failures:\n{}", failures.len(), failures.join("\n"));
+}
@@ -0,0 +1,468 @@
+//! RISC-V instruction semantics
+//!
+//! Describes what a decoded instruction does in terms of registers read and written, memory
+//! accesses and control flow, so that generic analyses (liveness, reordering, caching) can be
+//! written once instead of matching instruction names in every consumer.
+//!
+//! Compressed instructions are described as their 32-bit equivalents, using the register fields
+//! filled by the decoder.  The registers used by `ecall` depend on the execution environment, so
+//! they are not described.
+
+use crate::RiscvInstruction;
+
+/// Set of integer (x1-x31) and floating point (f0-f31) registers; x0 is never included, since it
+/// is hardwired to zero
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RegSet {
+    /// Bits 0-31 are the integer registers, and bits 32-63 are the floating point registers
+    pub bits: u64,
+}
+
+impl RegSet {
+    /// Adds the integer register `reg`, unless it is x0
+    pub fn insert_x(&mut self, reg: u32) {
+        assert!(reg < 32, "RegSet::insert_x() invalid register {}", reg);
+        if reg != 0 {
+            self.bits |= 1 << reg;
+        }
+    }
+
+    /// Adds the floating point register `reg`
+    pub fn insert_f(&mut self, reg: u32) {
+        assert!(reg < 32, "RegSet::insert_f() invalid register {}", reg);
+        self.bits |= 1 << (32 + reg);
+    }
+
+    /// Returns true if the set contains the integer register `reg`
+    pub fn contains_x(&self, reg: u32) -> bool {
+        (reg < 32) && ((self.bits >> reg) & 1) != 0
+    }
+
+    /// Returns true if the set contains the floating point register `reg`
+    pub fn contains_f(&self, reg: u32) -> bool {
+        (reg < 32) && ((self.bits >> (32 + reg)) & 1) != 0
+    }
+
+    /// Returns true if the set contains no register
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    /// Returns true if both sets contain a common register
+    pub fn intersects(&self, other: &RegSet) -> bool {
+        (self.bits & other.bits) != 0
+    }
+}
+
+/// Kind of memory access
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemAccessKind {
+    Load,
+    Store,
+    /// Atomic memory operation, which loads and stores the same address
+    ReadModifyWrite,
+}
+
+/// Memory access done by an instruction, at address `rs1 + imm`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemAccess {
+    pub kind: MemAccessKind,
+    /// Access width in bytes
+    pub width: u64,
+    /// True if the loaded value is sign-extended
+    pub signed: bool,
+}
+
+/// Semantics of an instruction
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct InstSemantics {
+    /// Registers read
+    pub reads: RegSet,
+    /// Registers written
+    pub writes: RegSet,
+    /// Memory access, if any
+    pub mem: Option<MemAccess>,
+    /// True if the instruction can set the pc to a value other than the next instruction
+    pub modifies_pc: bool,
+    /// True if executing the instruction twice in a row leaves the same state as executing it
+    /// once, i.e. it does not write what it reads, it does not modify the pc, and it has no side
+    /// effects outside registers and memory (traps, CSRs, reservations)
+    pub idempotent: bool,
+}
+
+/// Operand classes of an instruction
+enum Operands {
+    None,
+    /// rd = f(rs1, rs2)
+    Xxx,
+    /// rd = f(rs1)
+    Xx,
+    /// rd = f(imm)
+    X,
+    /// f(rs1, rs2)
+    Branch,
+    /// fd = f(fs1, fs2), or fd = f(fs1, fs2, fs3) for R4
+    Fff,
+    /// fd = f(fs1)
+    Ff,
+    /// rd = f(fs1, fs2)
+    Xff,
+    /// rd = f(fs1)
+    Xf,
+    /// fd = f(rs1)
+    Fx,
+}
+
+impl RiscvInstruction {
+    /// Returns the name of the equivalent 32-bit instruction, or `nop` for the hints
+    fn base_name(&self) -> &str {
+        match self.inst.as_str() {
+            "c.nop" => "nop",
+            "c.mv" | "c.add" => "add",
+            "c.li" | "c.addi4spn" | "c.addi16sp" | "c.addi" => "addi",
+            "c.j" => "jal",
+            "c.jr" | "c.jalr" => "jalr",
+            "c.beqz" => "beq",
+            "c.bnez" => "bne",
+            "c.lwsp" => "lw",
+            "c.ldsp" => "ld",
+            "c.swsp" => "sw",
+            "c.sdsp" => "sd",
+            "c.fldsp" => "fld",
+            "c.fsdsp" => "fsd",
+            name => name.strip_prefix("c.").unwrap_or(name),
+        }
+    }
+
+    /// Returns the registers read and written, memory accesses and control flow of the instruction
+    pub fn semantics(&self) -> InstSemantics {
+        use MemAccessKind::*;
+        let mut s = InstSemantics::default();
+        let mut side_effects = false;
+        let mem = |kind, width, signed| Some(MemAccess { kind, width, signed });
+
+        let name = self.base_name();
+        let operands = match name {
+            "add" | "sub" | "sll" | "slt" | "sltu" | "xor" | "srl" | "sra" | "or" | "and"
+            | "addw" | "subw" | "sllw" | "srlw" | "sraw" | "mul" | "mulh" | "mulhsu" | "mulhu"
+            | "mulw" | "div" | "divu" | "divw" | "divuw" | "rem" | "remu" | "remw" | "remuw" => {
+                Operands::Xxx
+            }
+            "addi" | "slti" | "sltiu" | "xori" | "ori" | "andi" | "slli" | "srli" | "srai"
+            | "addiw" | "slliw" | "srliw" | "sraiw" => Operands::Xx,
+            "lui" | "auipc" => Operands::X,
+            "jal" => {
+                s.modifies_pc = true;
+                Operands::X
+            }
+            "jalr" => {
+                s.modifies_pc = true;
+                Operands::Xx
+            }
+            "beq" | "bne" | "blt" | "bge" | "bltu" | "bgeu" => {
+                s.modifies_pc = true;
+                Operands::Branch
+            }
+            "lb" | "lh" | "lw" | "ld" | "lbu" | "lhu" | "lwu" => {
+                let width = match name {
+                    "lb" | "lbu" => 1,
+                    "lh" | "lhu" => 2,
+                    "lw" | "lwu" => 4,
+                    _ => 8,
+                };
+                s.mem = mem(Load, width, matches!(name, "lb" | "lh" | "lw"));
+                Operands::Xx
+            }
+            "sb" | "sh" | "sw" | "sd" => {
+                let width = match name {
+                    "sb" => 1,
+                    "sh" => 2,
+                    "sw" => 4,
+                    _ => 8,
+                };
+                s.mem = mem(Store, width, false);
+                Operands::Branch
+            }
+            "flw" | "fld" => {
+                s.mem = mem(Load, if name == "flw" { 4 } else { 8 }, false);
+                Operands::Fx
+            }
+            "fsw" | "fsd" => {
+                s.mem = mem(Store, if name == "fsw" { 4 } else { 8 }, false);
+                s.reads.insert_x(self.rs1);
+                s.reads.insert_f(self.rs2);
+                Operands::None
+            }
+            "lr.w" | "lr.d" => {
+                side_effects = true;
+                s.mem = mem(Load, if name == "lr.w" { 4 } else { 8 }, name == "lr.w");
+                Operands::Xx
+            }
+            "sc.w" | "sc.d" => {
+                side_effects = true;
+                s.mem = mem(Store, if name == "sc.w" { 4 } else { 8 }, false);
+                Operands::Xxx
+            }
+            _ if name.starts_with("amo") => {
+                let is_word = name.ends_with(".w");
+                s.mem = mem(ReadModifyWrite, if is_word { 4 } else { 8 }, is_word);
+                Operands::Xxx
+            }
+            "fence" | "nop" => Operands::None,
+            "ecall" | "ebreak" | "fence.i" => {
+                side_effects = true;
+                Operands::None
+            }
+            "csrrw" | "csrrs" | "csrrc" => {
+                side_effects = true;
+                Operands::Xx
+            }
+            "csrrwi" | "csrrsi" | "csrrci" => {
+                side_effects = true;
+                Operands::X
+            }
+            "feq.s" | "flt.s" | "fle.s" | "feq.d" | "flt.d" | "fle.d" => Operands::Xff,
+            "fclass.s" | "fclass.d" | "fmv.x.w" | "fmv.x.d" => Operands::Xf,
+            "fmv.w.x" | "fmv.d.x" => Operands::Fx,
+            "fsqrt.s" | "fsqrt.d" | "fcvt.s.d" | "fcvt.d.s" => Operands::Ff,
+            _ if name.starts_with("fcvt.w") || name.starts_with("fcvt.l") => Operands::Xf,
+            _ if name.starts_with("fcvt.") => Operands::Fx,
+            _ if name.starts_with('f') => Operands::Fff,
+            _ => {
+                // Reserved and halt instructions end the execution
+                side_effects = true;
+                s.modifies_pc = true;
+                Operands::None
+            }
+        };
+
+        match operands {
+            Operands::None => {}
+            Operands::Xxx => {
+                s.reads.insert_x(self.rs1);
+                s.reads.insert_x(self.rs2);
+                s.writes.insert_x(self.rd);
+            }
+            Operands::Xx => {
+                s.reads.insert_x(self.rs1);
+                s.writes.insert_x(self.rd);
+            }
+            Operands::X => s.writes.insert_x(self.rd),
+            Operands::Branch => {
+                s.reads.insert_x(self.rs1);
+                s.reads.insert_x(self.rs2);
+            }
+            Operands::Fff => {
+                s.reads.insert_f(self.rs1);
+                s.reads.insert_f(self.rs2);
+                if self.t == "R4" {
+                    s.reads.insert_f(self.rs3);
+                }
+                s.writes.insert_f(self.rd);
+            }
+            Operands::Ff => {
+                s.reads.insert_f(self.rs1);
+                s.writes.insert_f(self.rd);
+            }
+            Operands::Xff => {
+                s.reads.insert_f(self.rs1);
+                s.reads.insert_f(self.rs2);
+                s.writes.insert_x(self.rd);
+            }
+            Operands::Xf => {
+                s.reads.insert_f(self.rs1);
+                s.writes.insert_x(self.rd);
+            }
+            Operands::Fx => {
+                s.reads.insert_x(self.rs1);
+                s.writes.insert_f(self.rd);
+            }
+        }
+
+        s.idempotent = !side_effects
+            && !s.modifies_pc
+            && !s.reads.intersects(&s.writes)
+            && !matches!(s.mem, Some(MemAccess { kind: ReadModifyWrite, .. }));
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::riscv_interpreter;
+    use MemAccessKind::*;
+
+    /// Returns the set of the integer registers `x` and the floating point registers `f`
+    fn regs(x: &[u32], f: &[u32]) -> RegSet {
+        let mut set = RegSet::default();
+        x.iter().for_each(|reg| set.insert_x(*reg));
+        f.iter().for_each(|reg| set.insert_f(*reg));
+        set
+    }
+
+    /// Decodes a 16-bit or 32-bit instruction and returns its semantics
+    fn semantics(inst: u32) -> InstSemantics {
+        let code = if (inst & 3) == 3 {
+            vec![inst as u16, (inst >> 16) as u16]
+        } else {
+            vec![inst as u16]
+        };
+        let decoded = riscv_interpreter(0x80000000, &code);
+        assert_eq!(decoded.len(), 1, "{inst:08x}");
+        decoded[0].semantics()
+    }
+
+    #[test]
+    fn test_semantics() {
+        let load = |width, signed| Some(MemAccess { kind: Load, width, signed });
+        let store = |width| Some(MemAccess { kind: Store, width, signed: false });
+        let rmw = |width, signed| Some(MemAccess { kind: ReadModifyWrite, width, signed });
+        let none = regs(&[], &[]);
+
+        // Instruction, reads, writes, memory access, modifies pc and idempotent
+        let cases = [
+            (
+                0x00c58533,
+                "add a0, a1, a2",
+                regs(&[11, 12], &[]),
+                regs(&[10], &[]),
+                None,
+                false,
+                true,
+            ),
+            (
+                0x00b50533,
+                "add a0, a0, a1",
+                regs(&[10, 11], &[]),
+                regs(&[10], &[]),
+                None,
+                false,
+                false,
+            ),
+            (0x00500513, "addi a0, zero, 5", none, regs(&[10], &[]), None, false, true),
+            (0x12345537, "lui a0, 0x12345", none, regs(&[10], &[]), None, false, true),
+            (0x010000ef, "jal ra, 16", none, regs(&[1], &[]), None, true, false),
+            (0x00008067, "jalr zero, 0(ra)", regs(&[1], &[]), none, None, true, false),
+            (0x00b50463, "beq a0, a1, 8", regs(&[10, 11], &[]), none, None, true, false),
+            (
+                0x0085a503,
+                "lw a0, 8(a1)",
+                regs(&[11], &[]),
+                regs(&[10], &[]),
+                load(4, true),
+                false,
+                true,
+            ),
+            (
+                0x0005c503,
+                "lbu a0, 0(a1)",
+                regs(&[11], &[]),
+                regs(&[10], &[]),
+                load(1, false),
+                false,
+                true,
+            ),
+            (0x00c5b023, "sd a2, 0(a1)", regs(&[11, 12], &[]), none, store(8), false, true),
+            (
+                0x1005a52f,
+                "lr.w a0, (a1)",
+                regs(&[11], &[]),
+                regs(&[10], &[]),
+                load(4, true),
+                false,
+                false,
+            ),
+            (
+                0x18c5b52f,
+                "sc.d a0, a2, (a1)",
+                regs(&[11, 12], &[]),
+                regs(&[10], &[]),
+                store(8),
+                false,
+                false,
+            ),
+            (
+                0x00c5a52f,
+                "amoadd.w a0, a2, (a1)",
+                regs(&[11, 12], &[]),
+                regs(&[10], &[]),
+                rmw(4, true),
+                false,
+                false,
+            ),
+            (0x00000073, "ecall", none, none, None, false, false),
+            (0x0ff0000f, "fence", none, none, None, false, true),
+            (
+                0x30059573,
+                "csrrw a0, mstatus, a1",
+                regs(&[11], &[]),
+                regs(&[10], &[]),
+                None,
+                false,
+                false,
+            ),
+            (
+                0x02c5f553,
+                "fadd.d fa0, fa1, fa2",
+                regs(&[], &[11, 12]),
+                regs(&[], &[10]),
+                None,
+                false,
+                true,
+            ),
+            (
+                0x6ac5f543,
+                "fmadd.d fa0, fa1, fa2, fa3",
+                regs(&[], &[11, 12, 13]),
+                regs(&[], &[10]),
+                None,
+                false,
+                true,
+            ),
+            (
+                0xa2c5a553,
+                "feq.d a0, fa1, fa2",
+                regs(&[], &[11, 12]),
+                regs(&[10], &[]),
+                None,
+                false,
+                true,
+            ),
+            (0xc225f553, "fcvt.l.d a0, fa1", regs(&[], &[11]), regs(&[10], &[]), None, false, true),
+            (0xd225f553, "fcvt.d.l fa0, a1", regs(&[11], &[]), regs(&[], &[10]), None, false, true),
+            (
+                0x0005b507,
+                "fld fa0, 0(a1)",
+                regs(&[11], &[]),
+                regs(&[], &[10]),
+                load(8, false),
+                false,
+                true,
+            ),
+            (0x00c5b027, "fsd fa2, 0(a1)", regs(&[11], &[12]), none, store(8), false, true),
+            (0x0000200f, "reserved", none, none, None, true, false),
+            (0x852e, "c.mv a0, a1", regs(&[11], &[]), regs(&[10], &[]), None, false, true),
+            (
+                0x4502,
+                "c.lwsp a0, 0(sp)",
+                regs(&[2], &[]),
+                regs(&[10], &[]),
+                load(4, true),
+                false,
+                true,
+            ),
+            (0xa001, "c.j 0", none, none, None, true, false),
+            (0x0001, "c.nop", none, none, None, false, true),
+        ];
+        for (inst, asm, reads, writes, mem, modifies_pc, idempotent) in cases {
+            let expected = InstSemantics { reads, writes, mem, modifies_pc, idempotent };
+            assert_eq!(semantics(inst), expected, "{asm}");
+        }
+
+        // x0 is never part of a set
+        assert_eq!(regs(&[0], &[]), none);
+        assert!(regs(&[10], &[]).contains_x(10) && !regs(&[10], &[]).contains_f(10));
+        assert!(!regs(&[10], &[]).intersects(&regs(&[], &[10])));
+    }
+}
@@ -0,0 +1,329 @@
+//! RISC-V decoder table
+//!
+//! Machine-readable description of the 32-bit instructions accepted by the decoder, so that other
+//! components (e.g. the PIL/AIR generators or the documentation tooling) can consume the same
+//! instruction definitions instead of maintaining their own lists.
+//!
+//! An instruction word `inst` encodes the instruction described by an `InstSpec` if
+//! `inst & spec.mask == spec.matches`.  The masks only contain the fields checked by
+//! `Rvd::get_type_and_name_32_bits()`, so both always agree on the decoded name and type.
+//! Compressed instructions are not listed, since they are expanded to their 32-bit equivalents.
+
+use InstOperand::*;
+
+/// Operand of an instruction, in assembly order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstOperand {
+    /// Destination register, bits 11:7
+    Rd,
+    /// First source register, bits 19:15
+    Rs1,
+    /// Second source register, bits 24:20
+    Rs2,
+    /// Third source register, bits 31:27
+    Rs3,
+    /// Immediate, whose bits depend on the instruction type
+    Imm,
+    /// Shift amount, bits 25:20 (or 24:20 for the 32-bit word variants)
+    Shamt,
+    /// Unsigned immediate of the CSR instructions, bits 19:15
+    Uimm,
+    /// CSR address, bits 31:20
+    Csr,
+    /// Floating point rounding mode, bits 14:12
+    Rm,
+    /// Atomic ordering bits aq and rl, bits 26:25
+    Aqrl,
+    /// Fence predecessor set, bits 27:24
+    Pred,
+    /// Fence successor set, bits 23:20
+    Succ,
+}
+
+/// Description of a 32-bit instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstSpec {
+    /// Instruction name, as in `RiscvInstruction::inst`
+    pub name: &'static str,
+    /// Instruction type, as in `RiscvInstruction::t`
+    pub inst_type: &'static str,
+    /// Bits of the instruction word that identify the instruction
+    pub mask: u32,
+    /// Value of the masked bits
+    pub matches: u32,
+    /// Operands of the instruction
+    pub operands: &'static [InstOperand],
+}
+
+impl InstSpec {
+    /// Returns true if the instruction word encodes this instruction
+    pub fn is_match(&self, inst: u32) -> bool {
+        (inst & self.mask) == self.matches
+    }
+}
+
+/// Builds an `InstSpec`, keeping the table below compact
+const fn spec(
+    name: &'static str,
+    inst_type: &'static str,
+    mask: u32,
+    matches: u32,
+    operands: &'static [InstOperand],
+) -> InstSpec {
+    InstSpec { name, inst_type, mask, matches, operands }
+}
+
+const DECODER_TABLE: &[InstSpec] = &[
+    spec("lb", "I", 0x0000707f, 0x00000003, &[Rd, Rs1, Imm]),
+    spec("lh", "I", 0x0000707f, 0x00001003, &[Rd, Rs1, Imm]),
+    spec("lw", "I", 0x0000707f, 0x00002003, &[Rd, Rs1, Imm]),
+    spec("ld", "I", 0x0000707f, 0x00003003, &[Rd, Rs1, Imm]),
+    spec("lbu", "I", 0x0000707f, 0x00004003, &[Rd, Rs1, Imm]),
+    spec("lhu", "I", 0x0000707f, 0x00005003, &[Rd, Rs1, Imm]),
+    spec("lwu", "I", 0x0000707f, 0x00006003, &[Rd, Rs1, Imm]),
+    spec("flw", "I", 0x0000707f, 0x00002007, &[Rd, Rs1, Imm]),
+    spec("fld", "I", 0x0000707f, 0x00003007, &[Rd, Rs1, Imm]),
+    spec("fence", "F", 0x0000707f, 0x0000000f, &[Pred, Succ]),
+    spec("fence.i", "F", 0x0000707f, 0x0000100f, &[]),
+    spec("addi", "I", 0x0000707f, 0x00000013, &[Rd, Rs1, Imm]),
+    spec("slti", "I", 0x0000707f, 0x00002013, &[Rd, Rs1, Imm]),
+    spec("sltiu", "I", 0x0000707f, 0x00003013, &[Rd, Rs1, Imm]),
+    spec("xori", "I", 0x0000707f, 0x00004013, &[Rd, Rs1, Imm]),
+    spec("ori", "I", 0x0000707f, 0x00006013, &[Rd, Rs1, Imm]),
+    spec("andi", "I", 0x0000707f, 0x00007013, &[Rd, Rs1, Imm]),
+    spec("slli", "I", 0xfc00707f, 0x00001013, &[Rd, Rs1, Shamt]),
+    spec("srli", "I", 0xfc00707f, 0x00005013, &[Rd, Rs1, Shamt]),
+    spec("srai", "I", 0xfc00707f, 0x40005013, &[Rd, Rs1, Shamt]),
+    spec("auipc", "U", 0x0000007f, 0x00000017, &[Rd, Imm]),
+    spec("addiw", "I", 0x0000707f, 0x0000001b, &[Rd, Rs1, Imm]),
+    spec("slliw", "I", 0xfe00707f, 0x0000101b, &[Rd, Rs1, Shamt]),
+    spec("srliw", "I", 0xfe00707f, 0x0000501b, &[Rd, Rs1, Shamt]),
+    spec("sraiw", "I", 0xfe00707f, 0x4000501b, &[Rd, Rs1, Shamt]),
+    spec("sb", "S", 0x0000707f, 0x00000023, &[Rs1, Rs2, Imm]),
+    spec("sh", "S", 0x0000707f, 0x00001023, &[Rs1, Rs2, Imm]),
+    spec("sw", "S", 0x0000707f, 0x00002023, &[Rs1, Rs2, Imm]),
+    spec("sd", "S", 0x0000707f, 0x00003023, &[Rs1, Rs2, Imm]),
+    spec("fsw", "S", 0x0000707f, 0x00002027, &[Rs1, Rs2, Imm]),
+    spec("fsd", "S", 0x0000707f, 0x00003027, &[Rs1, Rs2, Imm]),
+    spec("lr.w", "A", 0xf800707f, 0x1000202f, &[Rd, Rs1, Aqrl]),
+    spec("sc.w", "A", 0xf800707f, 0x1800202f, &[Rd, Rs1, Rs2, Aqrl]),
+    spec("amoswap.w", "A", 0xf800707f, 0x0800202f, &[Rd, Rs1, Rs2, Aqrl]),
+    spec("amoadd.w", "A", 0xf800707f, 0x0000202f, &[Rd, Rs1, Rs2, Aqrl]),
+    spec("amoxor.w", "A", 0xf800707f, 0x2000202f, &[Rd, Rs1, Rs2, Aqrl]),
+    spec("amoand.w", "A", 0xf800707f, 0x6000202f, &[Rd, Rs1, Rs2, Aqrl]),
+    spec("amoor.w", "A", 0xf800707f, 0x4000202f, &[Rd, Rs1, Rs2, Aqrl]),
+    spec("amomin.w", "A", 0xf800707f, 0x8000202f, &[Rd, Rs1, Rs2, Aqrl]),
+    spec("amomax.w", "A", 0xf800707f, 0xa000202f, &[Rd, Rs1, Rs2, Aqrl]),
+    spec("amominu.w", "A", 0xf800707f, 0xc000202f, &[Rd, Rs1, Rs2, Aqrl]),
+    spec("amomaxu.w", "A", 0xf800707f, 0xe000202f, &[Rd, Rs1, Rs2, Aqrl]),
+    spec("lr.d", "A", 0xf800707f, 0x1000302f, &[Rd, Rs1, Aqrl]),
+    spec("sc.d", "A", 0xf800707f, 0x1800302f, &[Rd, Rs1, Rs2, Aqrl]),
+    spec("amoswap.d", "A", 0xf800707f, 0x0800302f, &[Rd, Rs1, Rs2, Aqrl]),
+    spec("amoadd.d", "A", 0xf800707f, 0x0000302f, &[Rd, Rs1, Rs2, Aqrl]),
+    spec("amoxor.d", "A", 0xf800707f, 0x2000302f, &[Rd, Rs1, Rs2, Aqrl]),
+    spec("amoand.d", "A", 0xf800707f, 0x6000302f, &[Rd, Rs1, Rs2, Aqrl]),
+    spec("amoor.d", "A", 0xf800707f, 0x4000302f, &[Rd, Rs1, Rs2, Aqrl]),
+    spec("amomin.d", "A", 0xf800707f, 0x8000302f, &[Rd, Rs1, Rs2, Aqrl]),
+    spec("amomax.d", "A", 0xf800707f, 0xa000302f, &[Rd, Rs1, Rs2, Aqrl]),
+    spec("amominu.d", "A", 0xf800707f, 0xc000302f, &[Rd, Rs1, Rs2, Aqrl]),
+    spec("amomaxu.d", "A", 0xf800707f, 0xe000302f, &[Rd, Rs1, Rs2, Aqrl]),
+    spec("add", "R", 0xfe00707f, 0x00000033, &[Rd, Rs1, Rs2]),
+    spec("mul", "R", 0xfe00707f, 0x02000033, &[Rd, Rs1, Rs2]),
+    spec("sub", "R", 0xfe00707f, 0x40000033, &[Rd, Rs1, Rs2]),
+    spec("sll", "R", 0xfe00707f, 0x00001033, &[Rd, Rs1, Rs2]),
+    spec("mulh", "R", 0xfe00707f, 0x02001033, &[Rd, Rs1, Rs2]),
+    spec("slt", "R", 0xfe00707f, 0x00002033, &[Rd, Rs1, Rs2]),
+    spec("mulhsu", "R", 0xfe00707f, 0x02002033, &[Rd, Rs1, Rs2]),
+    spec("sltu", "R", 0xfe00707f, 0x00003033, &[Rd, Rs1, Rs2]),
+    spec("mulhu", "R", 0xfe00707f, 0x02003033, &[Rd, Rs1, Rs2]),
+    spec("xor", "R", 0xfe00707f, 0x00004033, &[Rd, Rs1, Rs2]),
+    spec("div", "R", 0xfe00707f, 0x02004033, &[Rd, Rs1, Rs2]),
+    spec("srl", "R", 0xfe00707f, 0x00005033, &[Rd, Rs1, Rs2]),
+    spec("divu", "R", 0xfe00707f, 0x02005033, &[Rd, Rs1, Rs2]),
+    spec("sra", "R", 0xfe00707f, 0x40005033, &[Rd, Rs1, Rs2]),
+    spec("or", "R", 0xfe00707f, 0x00006033, &[Rd, Rs1, Rs2]),
+    spec("rem", "R", 0xfe00707f, 0x02006033, &[Rd, Rs1, Rs2]),
+    spec("and", "R", 0xfe00707f, 0x00007033, &[Rd, Rs1, Rs2]),
+    spec("remu", "R", 0xfe00707f, 0x02007033, &[Rd, Rs1, Rs2]),
+    spec("lui", "U", 0x0000007f, 0x00000037, &[Rd, Imm]),
+    spec("addw", "R", 0xfe00707f, 0x0000003b, &[Rd, Rs1, Rs2]),
+    spec("mulw", "R", 0xfe00707f, 0x0200003b, &[Rd, Rs1, Rs2]),
+    spec("subw", "R", 0xfe00707f, 0x4000003b, &[Rd, Rs1, Rs2]),
+    spec("sllw", "R", 0xfe00707f, 0x0000103b, &[Rd, Rs1, Rs2]),
+    spec("divw", "R", 0xfe00707f, 0x0200403b, &[Rd, Rs1, Rs2]),
+    spec("srlw", "R", 0xfe00707f, 0x0000503b, &[Rd, Rs1, Rs2]),
+    spec("divuw", "R", 0xfe00707f, 0x0200503b, &[Rd, Rs1, Rs2]),
+    spec("sraw", "R", 0xfe00707f, 0x4000503b, &[Rd, Rs1, Rs2]),
+    spec("remw", "R", 0xfe00707f, 0x0200603b, &[Rd, Rs1, Rs2]),
+    spec("remuw", "R", 0xfe00707f, 0x0200703b, &[Rd, Rs1, Rs2]),
+    spec("fmadd.s", "R4", 0x0600007f, 0x00000043, &[Rd, Rs1, Rs2, Rs3, Rm]),
+    spec("fmadd.d", "R4", 0x0600007f, 0x02000043, &[Rd, Rs1, Rs2, Rs3, Rm]),
+    spec("fmsub.s", "R4", 0x0600007f, 0x00000047, &[Rd, Rs1, Rs2, Rs3, Rm]),
+    spec("fmsub.d", "R4", 0x0600007f, 0x02000047, &[Rd, Rs1, Rs2, Rs3, Rm]),
+    spec("fnmsub.s", "R4", 0x0600007f, 0x0000004b, &[Rd, Rs1, Rs2, Rs3, Rm]),
+    spec("fnmsub.d", "R4", 0x0600007f, 0x0200004b, &[Rd, Rs1, Rs2, Rs3, Rm]),
+    spec("fnmadd.s", "R4", 0x0600007f, 0x0000004f, &[Rd, Rs1, Rs2, Rs3, Rm]),
+    spec("fnmadd.d", "R4", 0x0600007f, 0x0200004f, &[Rd, Rs1, Rs2, Rs3, Rm]),
+    spec("fadd.s", "R", 0xfe00007f, 0x00000053, &[Rd, Rs1, Rs2, Rm]),
+    spec("fadd.d", "R", 0xfe00007f, 0x02000053, &[Rd, Rs1, Rs2, Rm]),
+    spec("fsub.s", "R", 0xfe00007f, 0x08000053, &[Rd, Rs1, Rs2, Rm]),
+    spec("fsub.d", "R", 0xfe00007f, 0x0a000053, &[Rd, Rs1, Rs2, Rm]),
+    spec("fmul.s", "R", 0xfe00007f, 0x10000053, &[Rd, Rs1, Rs2, Rm]),
+    spec("fmul.d", "R", 0xfe00007f, 0x12000053, &[Rd, Rs1, Rs2, Rm]),
+    spec("fdiv.s", "R", 0xfe00007f, 0x18000053, &[Rd, Rs1, Rs2, Rm]),
+    spec("fdiv.d", "R", 0xfe00007f, 0x1a000053, &[Rd, Rs1, Rs2, Rm]),
+    spec("fsgnj.s", "R", 0xfe00707f, 0x20000053, &[Rd, Rs1, Rs2]),
+    spec("fsgnjn.s", "R", 0xfe00707f, 0x20001053, &[Rd, Rs1, Rs2]),
+    spec("fsgnjx.s", "R", 0xfe00707f, 0x20002053, &[Rd, Rs1, Rs2]),
+    spec("fsgnj.d", "R", 0xfe00707f, 0x22000053, &[Rd, Rs1, Rs2]),
+    spec("fsgnjn.d", "R", 0xfe00707f, 0x22001053, &[Rd, Rs1, Rs2]),
+    spec("fsgnjx.d", "R", 0xfe00707f, 0x22002053, &[Rd, Rs1, Rs2]),
+    spec("fmin.s", "R", 0xfe00707f, 0x28000053, &[Rd, Rs1, Rs2]),
+    spec("fmax.s", "R", 0xfe00707f, 0x28001053, &[Rd, Rs1, Rs2]),
+    spec("fmin.d", "R", 0xfe00707f, 0x2a000053, &[Rd, Rs1, Rs2]),
+    spec("fmax.d", "R", 0xfe00707f, 0x2a001053, &[Rd, Rs1, Rs2]),
+    spec("fcvt.s.d", "R", 0xfff0007f, 0x40100053, &[Rd, Rs1, Rm]),
+    spec("fcvt.d.s", "R", 0xfff0007f, 0x42000053, &[Rd, Rs1, Rm]),
+    spec("fsqrt.s", "R", 0xfff0007f, 0x58000053, &[Rd, Rs1, Rm]),
+    spec("fsqrt.d", "R", 0xfff0007f, 0x5a000053, &[Rd, Rs1, Rm]),
+    spec("feq.s", "R", 0xfe00707f, 0xa0002053, &[Rd, Rs1, Rs2]),
+    spec("flt.s", "R", 0xfe00707f, 0xa0001053, &[Rd, Rs1, Rs2]),
+    spec("fle.s", "R", 0xfe00707f, 0xa0000053, &[Rd, Rs1, Rs2]),
+    spec("feq.d", "R", 0xfe00707f, 0xa2002053, &[Rd, Rs1, Rs2]),
+    spec("flt.d", "R", 0xfe00707f, 0xa2001053, &[Rd, Rs1, Rs2]),
+    spec("fle.d", "R", 0xfe00707f, 0xa2000053, &[Rd, Rs1, Rs2]),
+    spec("fcvt.w.s", "R", 0xfff0007f, 0xc0000053, &[Rd, Rs1, Rm]),
+    spec("fcvt.wu.s", "R", 0xfff0007f, 0xc0100053, &[Rd, Rs1, Rm]),
+    spec("fcvt.l.s", "R", 0xfff0007f, 0xc0200053, &[Rd, Rs1, Rm]),
+    spec("fcvt.lu.s", "R", 0xfff0007f, 0xc0300053, &[Rd, Rs1, Rm]),
+    spec("fcvt.w.d", "R", 0xfff0007f, 0xc2000053, &[Rd, Rs1, Rm]),
+    spec("fcvt.wu.d", "R", 0xfff0007f, 0xc2100053, &[Rd, Rs1, Rm]),
+    spec("fcvt.l.d", "R", 0xfff0007f, 0xc2200053, &[Rd, Rs1, Rm]),
+    spec("fcvt.lu.d", "R", 0xfff0007f, 0xc2300053, &[Rd, Rs1, Rm]),
+    spec("fcvt.s.w", "R", 0xfff0007f, 0xd0000053, &[Rd, Rs1, Rm]),
+    spec("fcvt.s.wu", "R", 0xfff0007f, 0xd0100053, &[Rd, Rs1, Rm]),
+    spec("fcvt.s.l", "R", 0xfff0007f, 0xd0200053, &[Rd, Rs1, Rm]),
+    spec("fcvt.s.lu", "R", 0xfff0007f, 0xd0300053, &[Rd, Rs1, Rm]),
+    spec("fcvt.d.w", "R", 0xfff0007f, 0xd2000053, &[Rd, Rs1, Rm]),
+    spec("fcvt.d.wu", "R", 0xfff0007f, 0xd2100053, &[Rd, Rs1, Rm]),
+    spec("fcvt.d.l", "R", 0xfff0007f, 0xd2200053, &[Rd, Rs1, Rm]),
+    spec("fcvt.d.lu", "R", 0xfff0007f, 0xd2300053, &[Rd, Rs1, Rm]),
+    spec("fmv.x.w", "R", 0xfff0707f, 0xe0000053, &[Rd, Rs1]),
+    spec("fclass.s", "R", 0xfff0707f, 0xe0001053, &[Rd, Rs1]),
+    spec("fmv.x.d", "R", 0xfff0707f, 0xe2000053, &[Rd, Rs1]),
+    spec("fclass.d", "R", 0xfff0707f, 0xe2001053, &[Rd, Rs1]),
+    spec("fmv.w.x", "I", 0xfff0707f, 0xf0000053, &[Rd, Rs1]),
+    spec("fmv.d.x", "I", 0xfff0707f, 0xf2000053, &[Rd, Rs1]),
+    spec("beq", "B", 0x0000707f, 0x00000063, &[Rs1, Rs2, Imm]),
+    spec("bne", "B", 0x0000707f, 0x00001063, &[Rs1, Rs2, Imm]),
+    spec("blt", "B", 0x0000707f, 0x00004063, &[Rs1, Rs2, Imm]),
+    spec("bge", "B", 0x0000707f, 0x00005063, &[Rs1, Rs2, Imm]),
+    spec("bltu", "B", 0x0000707f, 0x00006063, &[Rs1, Rs2, Imm]),
+    spec("bgeu", "B", 0x0000707f, 0x00007063, &[Rs1, Rs2, Imm]),
+    spec("jalr", "I", 0x0000007f, 0x00000067, &[Rd, Rs1, Imm]),
+    spec("jal", "J", 0x0000007f, 0x0000006f, &[Rd, Imm]),
+    spec("ecall", "C", 0xfff0707f, 0x00000073, &[]),
+    spec("ebreak", "C", 0xfff0707f, 0x00100073, &[]),
+    spec("csrrw", "C", 0x0000707f, 0x00001073, &[Rd, Rs1, Csr]),
+    spec("csrrs", "C", 0x0000707f, 0x00002073, &[Rd, Rs1, Csr]),
+    spec("csrrc", "C", 0x0000707f, 0x00003073, &[Rd, Rs1, Csr]),
+    spec("csrrwi", "C", 0x0000707f, 0x00005073, &[Rd, Uimm, Csr]),
+    spec("csrrsi", "C", 0x0000707f, 0x00006073, &[Rd, Uimm, Csr]),
+    spec("csrrci", "C", 0x0000707f, 0x00007073, &[Rd, Uimm, Csr]),
+];
+
+/// Returns the description of all the 32-bit instructions accepted by the decoder
+pub fn decoder_table() -> &'static [InstSpec] {
+    DECODER_TABLE
+}
+
+/// Returns the major opcode of a 32-bit instruction, i.e. bits 6:2
+const fn major_opcode(inst: u32) -> usize {
+    ((inst >> 2) & 0x1f) as usize
+}
+
+/// Builds the index of the decoder table by major opcode, i.e. the [start, end) range of the
+/// entries of every opcode; it fails to compile if the entries of an opcode are not contiguous
+const fn build_opcode_index() -> [(usize, usize); 32] {
+    let mut index = [(0, 0); 32];
+    let mut i = 0;
+    while i < DECODER_TABLE.len() {
+        let opcode = major_opcode(DECODER_TABLE[i].matches);
+        assert!(index[opcode].1 == 0, "decoder table entries must be grouped by opcode");
+        let start = i;
+        while (i < DECODER_TABLE.len()) && (major_opcode(DECODER_TABLE[i].matches) == opcode) {
+            i += 1;
+        }
+        index[opcode] = (start, i);
+    }
+    index
+}
+
+/// Range of the decoder table entries of every major opcode
+const OPCODE_INDEX: [(usize, usize); 32] = build_opcode_index();
+
+/// Returns the description of all the 32-bit instructions with the given major opcode, i.e. bits
+/// 6:2 of the instruction word
+pub fn decoder_table_opcode(opcode: u32) -> &'static [InstSpec] {
+    let (start, end) = OPCODE_INDEX[(opcode & 0x1f) as usize];
+    &DECODER_TABLE[start..end]
+}
+
+/// Returns the description of the 32-bit instruction encoded by `inst`, if any
+///
+/// Only the entries of the instruction major opcode are checked, through a constant index, so the
+/// lookup does not allocate nor scan the whole table.
+pub fn decoder_table_lookup(inst: u32) -> Option<&'static InstSpec> {
+    if (inst & 3) != 3 {
+        return None;
+    }
+    decoder_table_opcode(major_opcode(inst) as u32).iter().find(|spec| spec.is_match(inst))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::riscv_interpreter;
+
+    #[test]
+    fn test_decoder_table() {
+        // Every entry is decoded by the interpreter to its name, type and register operands,
+        // whatever the value of the bits outside of its mask, and is found by the lookup
+        for spec in decoder_table() {
+            // The table does not check the unused fields of the fence and environment
+            // instructions, which must be zero, as `decode_outcome_32()` reports
+            let free_mask = match spec.name {
+                "fence" => !spec.mask & !0xf00f8f80,
+                "fence.i" | "ecall" | "ebreak" => 0,
+                _ => !spec.mask,
+            };
+            for free_bits in [0, 0x5555_5555, 0xaaaa_aaaa, u32::MAX] {
+                let inst = spec.matches | (free_bits & free_mask);
+                let decoded = riscv_interpreter(0x80000000, &[inst as u16, (inst >> 16) as u16]);
+                assert_eq!(decoded.len(), 1, "{} {inst:08x}", spec.name);
+                let i = &decoded[0];
+                assert_eq!(
+                    (i.inst.as_str(), i.t.as_str()),
+                    (spec.name, spec.inst_type),
+                    "{inst:08x}"
+                );
+                for (operand, value, shift) in [(Rd, i.rd, 7), (Rs1, i.rs1, 15), (Rs2, i.rs2, 20)] {
+                    if spec.operands.contains(&operand) {
+                        assert_eq!(value, (inst >> shift) & 0x1f, "{} {operand:?}", spec.name);
+                    }
+                }
+                assert_eq!(decoder_table_lookup(inst), Some(spec));
+            }
+        }
+
+        // The names are unique, the opcode index covers the whole table, and the compressed
+        // instructions are not listed
+        let mut names: Vec<_> = decoder_table().iter().map(|spec| spec.name).collect();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), decoder_table().len());
+        let indexed: usize = (0..32).map(|opcode| decoder_table_opcode(opcode).len()).sum();
+        assert_eq!(indexed, decoder_table().len());
+        assert_eq!(decoder_table_lookup(0x0001), None);
+    }
+}
@@ -16,7 +16,7 @@ use pil_std_lib::Std;
 use proofman_common::{AirInstance, FromTrace, ProofmanResult};
 use rayon::prelude::*;
 use sm_binary::{GT_OP, LTU_OP, LT_ABS_NP_OP, LT_ABS_PN_OP};
-use zisk_common::{BusId, ExtOperationData, OperationBusData, OperationData};
+use zisk_common::{trace_batch_size, BusId, ExtOperationData, OperationBusData, OperationData};
 use zisk_core::{zisk_ops::ZiskOp, ZiskOperationType};
 #[cfg(not(feature = "packed"))]
 use zisk_pil::{ArithTrace, ArithTraceRow};
@@ -111,7 +111,7 @@ impl<F: PrimeField64> ArithFullSM<F> {
         // Split the arith_trace.buffer into slices matching each inner vector’s length.
         let flat_inputs: Vec<_> = inputs.iter().flatten().collect(); // Vec<&OperationData<u64>>
         let flat_buffer = arith_trace.buffer.as_mut_slice();
-        let chunk_size = total_inputs.div_ceil(rayon::current_num_threads());
+        let chunk_size = trace_batch_size(total_inputs);
 
         flat_buffer.par_chunks_mut(chunk_size).zip(flat_inputs.par_chunks(chunk_size)).for_each(
             |(trace_slice, input_slice)| {
@@ -7,7 +7,7 @@ use std::{
     fs::File,
     io::Read,
 };
-use zisk_common::ChunkId;
+use zisk_common::{memmap, ChunkId};
 
 use crate::{MemAlignCounters, MemHelpers};
 use std::fmt;
@@ -75,10 +75,10 @@ impl MemCounters {
         addr_vector.par_sort_by_key(|(key, _)| *key);
 
         // Divide the original vector into three parts
-        let point = addr_vector.partition_point(|x| x.0 < (0xA000_0000 / 8));
+        let point = addr_vector.partition_point(|x| x.0 < (memmap::RAM_ADDR / 8) as u32);
         self.addr_sorted[2] = addr_vector.split_off(point);
 
-        let point = addr_vector.partition_point(|x| x.0 < (0x9000_0000 / 8));
+        let point = addr_vector.partition_point(|x| x.0 < (memmap::INPUT_ADDR / 8) as u32);
         self.addr_sorted[1] = addr_vector.split_off(point);
 
         self.addr_sorted[0] = addr_vector;
@@ -0,0 +1,168 @@
+//! Global constructors
+//!
+//! There is no loader in the Zisk zkVM, so with the `init-array` feature the entrypoint runs the
+//! global constructors itself, before calling the guest main, if the program has any constructor
+//! or destructor table: first the `.preinit_array` entries and then the `.init_array` ones, in
+//! order, as glibc does.  The feature is opt-in, since running them changes the execution, and
+//! with it the proof, of every existing guest that links an init table, e.g. through a C
+//! dependency; guests that rely on their constructors, e.g. C++ ones, must enable it.
+//! Constructors are called with `argc = 0` and empty, but valid, `argv` and `envp` arrays, since
+//! there are no arguments nor environment.
+//!
+//! Destructors (`.fini_array`) never run, since the program halts when main returns.
+//!
+//! With the `strict-init` feature, which implies `init-array`, the entrypoint also reports, as a warning record with target
+//! `INIT_LOG_TARGET` in the guest log channel, every entry that cannot be honored: constructors
+//! that are skipped because their address is not in the program ROM, i.e. in
+//! `[ROM_ADDR, ROM_ADDR_MAX]` of `ziskos_config` (e.g. the legacy `-1` sentinel), and
+//! destructors that will never run.
+
+use crate::ziskos_definitions::ziskos_config::{ROM_ADDR, ROM_ADDR_MAX};
+
+/// Target of the guest log records reporting unsupported constructors
+pub const INIT_LOG_TARGET: &str = "ziskos::init";
+
+/// Global constructor or destructor that cannot be honored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitDiagnostic {
+    /// Constructor skipped because its address is not in the ROM
+    InvalidConstructor { section: &'static str, index: usize, addr: usize },
+    /// Destructor that will never run
+    IgnoredDestructor { index: usize, addr: usize },
+}
+
+impl core::fmt::Display for InitDiagnostic {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            InitDiagnostic::InvalidConstructor { section, index, addr } => {
+                write!(f, "{section}[{index}] = {addr:#x} is not a valid constructor, skipped")
+            }
+            InitDiagnostic::IgnoredDestructor { index, addr } => {
+                write!(f, ".fini_array[{index}] = {addr:#x} destructor will never run")
+            }
+        }
+    }
+}
+
+/// Returns true if the constructor address can be called
+fn is_valid_constructor(addr: usize) -> bool {
+    (ROM_ADDR..=ROM_ADDR_MAX).contains(&(addr as u64))
+}
+
+/// Returns the diagnostics of the constructors and destructors tables
+pub fn init_diagnostics(
+    preinit_array: &[usize],
+    init_array: &[usize],
+    fini_array: &[usize],
+) -> Vec<InitDiagnostic> {
+    let constructors = [(".preinit_array", preinit_array), (".init_array", init_array)];
+    let invalid = constructors.into_iter().flat_map(|(section, entries)| {
+        entries.iter().enumerate().filter(|(_, addr)| !is_valid_constructor(**addr)).map(
+            move |(index, addr)| InitDiagnostic::InvalidConstructor { section, index, addr: *addr },
+        )
+    });
+    let ignored = fini_array
+        .iter()
+        .enumerate()
+        .filter(|(_, addr)| **addr != 0)
+        .map(|(index, addr)| InitDiagnostic::IgnoredDestructor { index, addr: *addr });
+    invalid.chain(ignored).collect()
+}
+
+#[cfg(all(target_os = "zkvm", target_vendor = "zisk", feature = "init-array"))]
+mod tables {
+    // The bounds are weak, so they resolve to zero, i.e. an empty table, if the program has no
+    // such section
+    core::arch::global_asm!(
+        ".section .rodata.ziskos_init_tables, \"a\"",
+        ".p2align 3",
+        ".weak __preinit_array_start",
+        ".weak __preinit_array_end",
+        ".weak __init_array_start",
+        ".weak __init_array_end",
+        ".weak __fini_array_start",
+        ".weak __fini_array_end",
+        ".globl _ziskos_init_tables",
+        "_ziskos_init_tables:",
+        ".dword __preinit_array_start",
+        ".dword __preinit_array_end",
+        ".dword __init_array_start",
+        ".dword __init_array_end",
+        ".dword __fini_array_start",
+        ".dword __fini_array_end",
+    );
+
+    extern "C" {
+        static _ziskos_init_tables: [usize; 6];
+    }
+
+    /// Returns the table of the given index: 0 = preinit, 1 = init, 2 = fini
+    pub(super) fn table(index: usize) -> &'static [usize] {
+        let (start, end) =
+            unsafe { (_ziskos_init_tables[2 * index], _ziskos_init_tables[2 * index + 1]) };
+        if start == 0 || end <= start {
+            return &[];
+        }
+        unsafe { core::slice::from_raw_parts(start as *const usize, (end - start) / 8) }
+    }
+}
+
+/// Returns true if the program has any constructor or destructor table, e.g. C or C++ guests
+#[cfg(all(target_os = "zkvm", target_vendor = "zisk", feature = "init-array"))]
+pub(crate) fn has_init_tables() -> bool {
+    (0..3).any(|index| !tables::table(index).is_empty())
+}
+
+/// Runs the global constructors; called by the entrypoint before the guest main
+#[cfg(all(target_os = "zkvm", target_vendor = "zisk", feature = "init-array"))]
+pub(crate) fn run_init_array() {
+    type Constructor = unsafe extern "C" fn(i32, *const *const u8, *const *const u8);
+    let empty: [*const u8; 1] = [core::ptr::null()];
+
+    // Report before running the constructors, in case any of them fails
+    #[cfg(feature = "strict-init")]
+    for diagnostic in init_diagnostics(tables::table(0), tables::table(1), tables::table(2)) {
+        crate::log::log(crate::log::LogLevel::Warn, INIT_LOG_TARGET, &format!("{diagnostic}"));
+    }
+
+    for entries in [tables::table(0), tables::table(1)] {
+        for addr in entries.iter().copied().filter(|addr| is_valid_constructor(*addr)) {
+            let constructor: Constructor = unsafe { core::mem::transmute(addr) };
+            unsafe { constructor(0, empty.as_ptr(), empty.as_ptr()) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_init_diagnostics() {
+        let diagnostics = init_diagnostics(
+            &[0x80001000],
+            &[0x80002000, usize::MAX, 0x88000000],
+            &[0, 0x80003000],
+        );
+        assert_eq!(
+            diagnostics,
+            vec![
+                InitDiagnostic::InvalidConstructor {
+                    section: ".init_array",
+                    index: 1,
+                    addr: usize::MAX
+                },
+                InitDiagnostic::InvalidConstructor {
+                    section: ".init_array",
+                    index: 2,
+                    addr: 0x88000000
+                },
+                InitDiagnostic::IgnoredDestructor { index: 1, addr: 0x80003000 },
+            ]
+        );
+        assert_eq!(
+            diagnostics[2].to_string(),
+            ".fini_array[1] = 0x80003000 destructor will never run"
+        );
+    }
+}
@@ -0,0 +1,69 @@
+//! Named input channels
+//!
+//! When the host provides the input as named channels (see `InputChannels` in the zisk-common
+//! crate), the whole input is a container with a header listing, for every channel, its name and
+//! the offset and length of its data.  `zisk_input(name)` returns the data of a channel, without
+//! copying it.
+//!
+//! The container is read once, through `read_input_slice()`, so the input commitment, if enabled,
+//! hashes the whole container.
+
+use lazy_static::lazy_static;
+
+/// Magic word at the beginning of an input channels container ("ZISKCHAN")
+pub const INPUT_CHANNELS_MAGIC: u64 = u64::from_le_bytes(*b"ZISKCHAN");
+
+lazy_static! {
+    /// Channels of the input, as (name, data) pairs
+    static ref INPUT_CHANNELS: Vec<(&'static str, &'static [u8])> = parse_input_channels(input());
+}
+
+#[cfg(all(target_os = "zkvm", target_vendor = "zisk"))]
+fn input() -> &'static [u8] {
+    crate::read_input_slice()
+}
+
+#[cfg(not(all(target_os = "zkvm", target_vendor = "zisk")))]
+fn input() -> &'static [u8] {
+    Box::leak(crate::read_input_slice())
+}
+
+/// Parses an input channels container
+fn parse_input_channels(input: &'static [u8]) -> Vec<(&'static str, &'static [u8])> {
+    let word = |pos: usize| {
+        let bytes = input.get(pos..pos + 8).expect("parse_input_channels() truncated input");
+        u64::from_le_bytes(bytes.try_into().unwrap()) as usize
+    };
+    assert!(
+        word(0) as u64 == INPUT_CHANNELS_MAGIC,
+        "parse_input_channels() input is not an input channels container"
+    );
+
+    let mut pos = 16;
+    (0..word(8))
+        .map(|_| {
+            let name_len = word(pos);
+            let name = core::str::from_utf8(&input[pos + 8..pos + 8 + name_len])
+                .expect("parse_input_channels() invalid channel name");
+            pos += 8 + name_len.div_ceil(8) * 8;
+            let (offset, len) = (word(pos), word(pos + 8));
+            pos += 16;
+            (name, &input[offset..offset + len])
+        })
+        .collect()
+}
+
+/// Returns the data of the input channel `name`, or None if there is no such channel
+pub fn zisk_input_opt(name: &str) -> Option<&'static [u8]> {
+    INPUT_CHANNELS.iter().find(|(channel, _)| *channel == name).map(|(_, data)| *data)
+}
+
+/// Returns the data of the input channel `name`; it panics if there is no such channel
+pub fn zisk_input(name: &str) -> &'static [u8] {
+    zisk_input_opt(name).unwrap_or_else(|| panic!("zisk_input() input channel '{name}' not found"))
+}
+
+/// Returns the names of the input channels, in the order provided by the host
+pub fn zisk_input_names() -> impl Iterator<Item = &'static str> {
+    INPUT_CHANNELS.iter().map(|(name, _)| *name)
+}
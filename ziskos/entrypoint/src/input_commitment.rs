@@ -0,0 +1,146 @@
+//! Input commitment
+//!
+//! A program can commit to its input, so that a proof generated with an input different from the
+//! one used in a previous execution is detected early, by comparing the public outputs, instead of
+//! after a full proving run:
+//! * `commit_input(first_output_id)` hashes the input returned by `read_input_slice()` with
+//!   SHA-256 and writes the digest right away as 8 public outputs, starting at `first_output_id`,
+//!   each of them a big-endian word of the digest, i.e. a word of the SHA-256 state
+//! * `input_digest()` and `input_digest_from_file()` compute the same digest on the host, from the
+//!   input bytes or the input file
+//!
+//! Inside the Zisk zkVM the hash uses the sha256f precompile through `sha256f_compress()`, and
+//! outside of it the compression function of the `sha2` crate, as the emulator does.
+
+/// Number of public outputs used by the input digest
+pub const INPUT_DIGEST_OUTPUTS: usize = 8;
+
+/// SHA-256 initial state
+const SHA256_INITIAL_STATE: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// Incremental SHA-256 hasher
+#[derive(Debug, Clone)]
+pub struct InputHasher {
+    state: [u32; 8],
+    block: [u8; 64],
+    block_len: usize,
+    total_len: u64,
+}
+
+impl Default for InputHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputHasher {
+    pub fn new() -> Self {
+        Self { state: SHA256_INITIAL_STATE, block: [0; 64], block_len: 0, total_len: 0 }
+    }
+
+    /// Hashes the provided bytes
+    pub fn update(&mut self, mut bytes: &[u8]) {
+        self.total_len += bytes.len() as u64;
+        while !bytes.is_empty() {
+            let n = (64 - self.block_len).min(bytes.len());
+            self.block[self.block_len..self.block_len + n].copy_from_slice(&bytes[..n]);
+            self.block_len += n;
+            bytes = &bytes[n..];
+            if self.block_len == 64 {
+                compress(&mut self.state, &self.block);
+                self.block_len = 0;
+            }
+        }
+    }
+
+    /// Returns the digest of all the hashed bytes, as SHA-256 state words
+    pub fn finalize_words(mut self) -> [u32; 8] {
+        let bit_len = self.total_len * 8;
+        self.update(&[0x80]);
+        if self.block_len > 56 {
+            self.update(&[0u8; 64][..64 - self.block_len]);
+        }
+        self.update(&[0u8; 56][..56 - self.block_len]);
+        self.block[56..].copy_from_slice(&bit_len.to_be_bytes());
+        compress(&mut self.state, &self.block);
+        self.state
+    }
+
+    /// Returns the digest of all the hashed bytes
+    pub fn finalize(self) -> [u8; 32] {
+        let mut digest = [0u8; 32];
+        for (bytes, word) in digest.chunks_exact_mut(4).zip(self.finalize_words()) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+}
+
+#[cfg(all(target_os = "zkvm", target_vendor = "zisk"))]
+fn compress(state: &mut [u32; 8], block: &[u8; 64]) {
+    crate::zisklib::sha256f_compress(state, &[*block]);
+}
+
+#[cfg(not(all(target_os = "zkvm", target_vendor = "zisk")))]
+fn compress(state: &mut [u32; 8], block: &[u8; 64]) {
+    sha2::compress256(state, &[(*block).into()]);
+}
+
+/// Writes the digest of the input as the public outputs `first_output_id..first_output_id + 8`
+pub fn commit_input(first_output_id: usize) {
+    assert!(
+        first_output_id + INPUT_DIGEST_OUTPUTS <= 64,
+        "commit_input() the input digest does not fit in the 64 public outputs"
+    );
+    let input = crate::read_input_slice();
+    for (i, word) in input_digest_outputs(&input).into_iter().enumerate() {
+        crate::set_output(first_output_id + i, word);
+    }
+}
+
+/// Returns the digest of the input, as committed by `commit_input()`
+pub fn input_digest(input: &[u8]) -> [u8; 32] {
+    let mut hasher = InputHasher::new();
+    hasher.update(input);
+    hasher.finalize()
+}
+
+/// Returns the public outputs written by `commit_input()`
+pub fn input_digest_outputs(input: &[u8]) -> [u32; INPUT_DIGEST_OUTPUTS] {
+    let mut hasher = InputHasher::new();
+    hasher.update(input);
+    hasher.finalize_words()
+}
+
+/// Returns the digest of the input file, as committed by `commit_input()`
+#[cfg(not(all(target_os = "zkvm", target_vendor = "zisk")))]
+pub fn input_digest_from_file(path: impl AsRef<std::path::Path>) -> std::io::Result<[u8; 32]> {
+    Ok(input_digest(&std::fs::read(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_digest() {
+        let hex = |digest: [u8; 32]| digest.iter().map(|b| format!("{b:02x}")).collect::<String>();
+        assert_eq!(
+            hex(input_digest(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex(input_digest(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(input_digest_outputs(b"abc")[0], 0xba7816bf);
+
+        // Incremental hashing, crossing block boundaries
+        let input: Vec<u8> = (0..1000u32).map(|i| i as u8).collect();
+        let mut hasher = InputHasher::new();
+        input.chunks(7).for_each(|chunk| hasher.update(chunk));
+        assert_eq!(hasher.finalize(), input_digest(&input));
+    }
+}
@@ -5,15 +5,35 @@
 use core::arch::asm;
 #[cfg(all(target_os = "zkvm", target_vendor = "zisk"))]
 mod fcall;
+mod init_array;
+mod input_channels;
+mod input_commitment;
+mod paged_input;
+#[cfg(feature = "panic-info")]
+mod panic_info;
 mod profile;
+mod region;
+#[cfg(feature = "soft-float")]
+mod soft_float;
 #[cfg(all(target_os = "zkvm", target_vendor = "zisk"))]
 pub use fcall::*;
+pub use init_array::*;
+pub use input_channels::*;
+pub use input_commitment::*;
+pub use paged_input::*;
+#[cfg(feature = "panic-info")]
+pub use panic_info::*;
 pub use profile::*;
+pub use region::*;
+#[cfg(feature = "soft-float")]
+pub use soft_float::*;
 
 pub mod zisklib;
 
 pub mod syscalls;
 
+pub mod log;
+
 pub mod ziskos_definitions;
 
 #[macro_export]
@@ -148,12 +168,13 @@ mod ziskos {
           "j 2f",
 
           // Zisk exit
-          "1: li   a7, 93",
+          "1: li   a7, {exit_id}",
           "ecall",
 
           "2: j 2b",
 
           _zisk_main = sym _zisk_main, // {entry} refers to the function [entry] below
+          exit_id = const crate::syscalls::ECALL_EXIT_ID,
           options(noreturn) // we must handle "returning" from assembly
         );
 
@@ -170,6 +191,12 @@ mod ziskos {
 
     #[no_mangle]
     unsafe extern "C" fn _zisk_main() {
+        #[cfg(feature = "panic-info")]
+        crate::install_panic_hook();
+        #[cfg(feature = "init-array")]
+        if crate::init_array::has_init_tables() {
+            crate::init_array::run_init_array();
+        }
         {
             extern "C" {
                 fn main();
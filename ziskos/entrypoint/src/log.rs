@@ -0,0 +1,142 @@
+//! Guest log channel
+//!
+//! Log records are written to `LOG_ADDR`, separately from the standard output written to
+//! `UART_ADDR`, so that the host can capture them as structured records (level, target and
+//! message) without mixing them with the program output.
+//!
+//! Every record is written as a sequence of 8-byte words:
+//! * A header word: level in bits 56..64, target length in bits 32..48, and message length in
+//!   bits 0..32
+//! * The target bytes followed by the message bytes, packed in little-endian words, with the last
+//!   word padded with zeros
+//!
+//! Outside the Zisk zkVM the records are printed to the standard error.
+
+#[allow(unused_imports)]
+use crate::ziskos_definitions::ziskos_config::*;
+
+/// Log record level, from most to least severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum LogLevel {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
+
+impl LogLevel {
+    /// Returns the level name, as printed by the guest and by the emulator
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Error => "ERROR",
+            LogLevel::Warn => "WARN",
+            LogLevel::Info => "INFO",
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Trace => "TRACE",
+        }
+    }
+}
+
+impl TryFrom<u8> for LogLevel {
+    type Error = u8;
+
+    /// Decodes the level of a record header, returning the value back if it is not a level
+    fn try_from(level: u8) -> Result<Self, u8> {
+        match level {
+            1 => Ok(LogLevel::Error),
+            2 => Ok(LogLevel::Warn),
+            3 => Ok(LogLevel::Info),
+            4 => Ok(LogLevel::Debug),
+            5 => Ok(LogLevel::Trace),
+            _ => Err(level),
+        }
+    }
+}
+
+/// Returns the header word of a record with the given level and target and message lengths
+pub fn log_header(level: LogLevel, target_len: usize, message_len: usize) -> u64 {
+    ((level as u64) << 56) | ((target_len as u64) << 32) | (message_len as u64)
+}
+
+/// Writes a log record to the guest log channel
+#[cfg(all(target_os = "zkvm", target_vendor = "zisk"))]
+pub fn log(level: LogLevel, target: &str, message: &str) {
+    let target = &target.as_bytes()[..target.len().min(u16::MAX as usize)];
+    let message = &message.as_bytes()[..message.len().min(u32::MAX as usize)];
+    let header = log_header(level, target.len(), message.len());
+
+    let addr = LOG_ADDR as *mut u64;
+    unsafe {
+        core::ptr::write_volatile(addr, header);
+        let mut bytes = target.iter().chain(message.iter());
+        loop {
+            let mut word = [0u8; 8];
+            let mut n = 0;
+            for byte in word.iter_mut() {
+                match bytes.next() {
+                    Some(b) => {
+                        *byte = *b;
+                        n += 1;
+                    }
+                    None => break,
+                }
+            }
+            if n == 0 {
+                break;
+            }
+            core::ptr::write_volatile(addr, u64::from_le_bytes(word));
+        }
+    }
+}
+
+/// Writes a log record to the guest log channel
+#[cfg(not(all(target_os = "zkvm", target_vendor = "zisk")))]
+pub fn log(level: LogLevel, target: &str, message: &str) {
+    eprintln!("[{}] {}: {}", level.as_str(), target, message);
+}
+
+/// Writes a formatted log record, using the current module path as target
+#[macro_export]
+macro_rules! zisk_log {
+    ($level:expr, $($arg:tt)+) => {
+        $crate::log::log($level, module_path!(), &format!($($arg)+))
+    };
+}
+
+/// Asserts that a condition is true; otherwise writes an error log record and panics
+#[macro_export]
+macro_rules! zisk_assert {
+    ($cond:expr $(,)?) => {
+        $crate::zisk_assert!($cond, "assertion failed: {}", stringify!($cond))
+    };
+    ($cond:expr, $($arg:tt)+) => {
+        if !$cond {
+            let message = format!($($arg)+);
+            $crate::log::log($crate::log::LogLevel::Error, module_path!(), &message);
+            panic!("{}", message);
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_header() {
+        // Every level is decoded back from the header, and the other values are not levels
+        for level in
+            [LogLevel::Error, LogLevel::Warn, LogLevel::Info, LogLevel::Debug, LogLevel::Trace]
+        {
+            let header = log_header(level, 0x1234, 0x89abcdef);
+            assert_eq!(LogLevel::try_from((header >> 56) as u8), Ok(level));
+            assert_eq!((header >> 32) & 0xffff, 0x1234);
+            assert_eq!(header & 0xffffffff, 0x89abcdef);
+        }
+        assert_eq!(LogLevel::try_from(0), Err(0));
+        assert_eq!(LogLevel::try_from(6), Err(6));
+        assert_eq!(LogLevel::try_from(4).map(|level| level.as_str()), Ok("DEBUG"));
+    }
+}
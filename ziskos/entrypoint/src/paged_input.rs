@@ -0,0 +1,266 @@
+//! Paged input
+//!
+//! `read_input()` requires the whole input to be resident in the input section of the guest
+//! address space, which limits the input size.  The paged input interface instead loads the input
+//! on demand, in pages of `INPUT_PAGE_SIZE` bytes obtained through the input page fcall, and keeps
+//! the most recently used ones in a bounded guest-side cache, so that a program can process
+//! inputs larger than its RAM, e.g. full block witness archives:
+//! * `input_len()` returns the input size, and `read_chunk(offset, len)` any range of it
+//! * `set_input_cache_pages(pages)` bounds the cache, which keeps `DEFAULT_INPUT_CACHE_PAGES`
+//!   pages by default
+//!
+//! The functions above use the process-wide input session, whose pages are read through the fcall
+//! in the Zisk zkVM, and from the same input file used by `read_input()` outside it.  Outside the
+//! Zisk zkVM an `InputSession` can also be created from a file or from memory, so that independent
+//! executions, e.g. the cases of a test harness, do not share their input.
+
+use lazy_static::lazy_static;
+use std::sync::Mutex;
+
+use crate::zisklib::{INPUT_PAGE_SIZE, INPUT_PAGE_WORDS};
+
+/// Default number of pages kept in the input cache
+pub const DEFAULT_INPUT_CACHE_PAGES: usize = 64;
+
+/// Input file read by the process-wide input session outside the Zisk zkVM
+#[cfg(not(all(target_os = "zkvm", target_vendor = "zisk")))]
+const INPUT_FILE: &str = "build/input.bin";
+
+/// Source of the input pages
+enum PageSource {
+    /// Input page fcall
+    #[cfg(all(target_os = "zkvm", target_vendor = "zisk"))]
+    Fcall,
+    /// Input file, opened on the first page load and kept open along with its size
+    #[cfg(not(all(target_os = "zkvm", target_vendor = "zisk")))]
+    File { path: std::path::PathBuf, handle: Option<(std::fs::File, u64)> },
+    /// Input in memory
+    #[cfg(not(all(target_os = "zkvm", target_vendor = "zisk")))]
+    Bytes(Vec<u8>),
+}
+
+impl PageSource {
+    /// Loads an input page, returning the input size
+    #[cfg(all(target_os = "zkvm", target_vendor = "zisk"))]
+    fn load_page(&mut self, page: u64, words: &mut [u64; INPUT_PAGE_WORDS]) -> u64 {
+        match self {
+            PageSource::Fcall => crate::zisklib::fcall_input_page(page, words),
+        }
+    }
+
+    /// Loads an input page, returning the input size
+    #[cfg(not(all(target_os = "zkvm", target_vendor = "zisk")))]
+    fn load_page(&mut self, page: u64, words: &mut [u64; INPUT_PAGE_WORDS]) -> u64 {
+        use std::{
+            fs::File,
+            io::{Read, Seek, SeekFrom},
+        };
+
+        match self {
+            PageSource::File { path, handle } => {
+                let (file, input_len) = handle.get_or_insert_with(|| {
+                    let file = File::open(&*path).unwrap_or_else(|e| {
+                        panic!("Error opening input file at: {}: {e}", path.display())
+                    });
+                    let input_len = file.metadata().unwrap().len();
+                    (file, input_len)
+                });
+                let input_len = *input_len;
+                let mut bytes = [0u8; INPUT_PAGE_SIZE];
+                let start = (page * INPUT_PAGE_SIZE as u64).min(input_len);
+                file.seek(SeekFrom::Start(start)).unwrap();
+                let n = ((input_len - start) as usize).min(INPUT_PAGE_SIZE);
+                file.read_exact(&mut bytes[..n]).unwrap();
+                for (word, chunk) in words.iter_mut().zip(bytes.chunks_exact(8)) {
+                    *word = u64::from_le_bytes(chunk.try_into().unwrap());
+                }
+                input_len
+            }
+            PageSource::Bytes(input) => {
+                let mut results = vec![0u64; 1 + INPUT_PAGE_WORDS];
+                crate::zisklib::fcall_input_page_from(input, &[page], &mut results);
+                words.copy_from_slice(&results[1..]);
+                results[0]
+            }
+        }
+    }
+}
+
+/// Cached input page
+struct CachedPage {
+    page: u64,
+    last_use: u64,
+    words: Box<[u64; INPUT_PAGE_WORDS]>,
+}
+
+/// Paged input with its own bounded cache of input pages, evicting the least recently used one
+pub struct InputSession {
+    source: PageSource,
+    pages: Vec<CachedPage>,
+    capacity: usize,
+    input_len: Option<u64>,
+    uses: u64,
+}
+
+impl InputSession {
+    fn new(source: PageSource) -> Self {
+        Self {
+            source,
+            pages: Vec::new(),
+            capacity: DEFAULT_INPUT_CACHE_PAGES,
+            input_len: None,
+            uses: 0,
+        }
+    }
+
+    /// Creates a session reading the input pages from a file
+    #[cfg(not(all(target_os = "zkvm", target_vendor = "zisk")))]
+    pub fn from_file(path: impl Into<std::path::PathBuf>) -> Self {
+        Self::new(PageSource::File { path: path.into(), handle: None })
+    }
+
+    /// Creates a session reading the input pages from memory
+    #[cfg(not(all(target_os = "zkvm", target_vendor = "zisk")))]
+    pub fn from_bytes(input: Vec<u8>) -> Self {
+        Self::new(PageSource::Bytes(input))
+    }
+
+    /// Returns the cached page, loading it if needed
+    fn page(&mut self, page: u64) -> &[u64; INPUT_PAGE_WORDS] {
+        self.uses += 1;
+        let index = match self.pages.iter().position(|cached| cached.page == page) {
+            Some(index) => index,
+            None => {
+                if self.pages.len() >= self.capacity {
+                    let lru = (0..self.pages.len()).min_by_key(|&i| self.pages[i].last_use);
+                    self.pages.swap_remove(lru.unwrap());
+                }
+                let mut words = Box::new([0u64; INPUT_PAGE_WORDS]);
+                self.input_len = Some(self.source.load_page(page, &mut words));
+                self.pages.push(CachedPage { page, last_use: 0, words });
+                self.pages.len() - 1
+            }
+        };
+        self.pages[index].last_use = self.uses;
+        &self.pages[index].words
+    }
+
+    /// Sets the maximum number of pages kept in the cache, evicting the exceeding ones
+    pub fn set_cache_pages(&mut self, pages: usize) {
+        assert!(pages > 0, "InputSession::set_cache_pages() the cache must keep at least one page");
+        self.capacity = pages;
+        if self.pages.len() > pages {
+            self.pages.sort_by_key(|cached| std::cmp::Reverse(cached.last_use));
+            self.pages.truncate(pages);
+        }
+    }
+
+    /// Returns the input size in bytes, loading the first page if it is not known yet
+    pub fn input_len(&mut self) -> u64 {
+        match self.input_len {
+            Some(input_len) => input_len,
+            None => {
+                self.page(0);
+                self.input_len.unwrap()
+            }
+        }
+    }
+
+    /// Reads `buffer.len()` input bytes starting at `offset`; it panics if the range exceeds the
+    /// input
+    pub fn read_chunk_into(&mut self, offset: u64, buffer: &mut [u8]) {
+        let input_len = self.input_len();
+        assert!(
+            offset.checked_add(buffer.len() as u64).is_some_and(|end| end <= input_len),
+            "InputSession::read_chunk_into() range offset={} len={} exceeds the input size={}",
+            offset,
+            buffer.len(),
+            input_len
+        );
+
+        let mut pos = 0;
+        while pos < buffer.len() {
+            let addr = offset + pos as u64;
+            let page = self.page(addr / INPUT_PAGE_SIZE as u64);
+            let page_offset = (addr % INPUT_PAGE_SIZE as u64) as usize;
+            let n = (INPUT_PAGE_SIZE - page_offset).min(buffer.len() - pos);
+            for (i, byte) in buffer[pos..pos + n].iter_mut().enumerate() {
+                let byte_offset = page_offset + i;
+                *byte = (page[byte_offset / 8] >> (8 * (byte_offset % 8))) as u8;
+            }
+            pos += n;
+        }
+    }
+
+    /// Returns `len` input bytes starting at `offset`; it panics if the range exceeds the input
+    pub fn read_chunk(&mut self, offset: u64, len: usize) -> Vec<u8> {
+        let mut buffer = vec![0u8; len];
+        self.read_chunk_into(offset, &mut buffer);
+        buffer
+    }
+}
+
+lazy_static! {
+    static ref INPUT_SESSION: Mutex<InputSession> = {
+        #[cfg(all(target_os = "zkvm", target_vendor = "zisk"))]
+        let source = PageSource::Fcall;
+        #[cfg(not(all(target_os = "zkvm", target_vendor = "zisk")))]
+        let source = PageSource::File { path: INPUT_FILE.into(), handle: None };
+        Mutex::new(InputSession::new(source))
+    };
+}
+
+/// Sets the maximum number of pages kept in the input cache, evicting the exceeding ones
+pub fn set_input_cache_pages(pages: usize) {
+    assert!(pages > 0, "set_input_cache_pages() the cache must keep at least one page");
+    INPUT_SESSION.lock().unwrap().set_cache_pages(pages);
+}
+
+/// Returns the input size in bytes
+pub fn input_len() -> u64 {
+    INPUT_SESSION.lock().unwrap().input_len()
+}
+
+/// Reads `buffer.len()` input bytes starting at `offset`; it panics if the range exceeds the input
+pub fn read_chunk_into(offset: u64, buffer: &mut [u8]) {
+    INPUT_SESSION.lock().unwrap().read_chunk_into(offset, buffer);
+}
+
+/// Returns `len` input bytes starting at `offset`; it panics if the range exceeds the input
+pub fn read_chunk(offset: u64, len: usize) -> Vec<u8> {
+    let mut buffer = vec![0u8; len];
+    read_chunk_into(offset, &mut buffer);
+    buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_session() {
+        let input_a: Vec<u8> = (0..3 * INPUT_PAGE_SIZE + 5).map(|i| (i % 251) as u8).collect();
+        let mut session_a = InputSession::from_bytes(input_a.clone());
+        let mut session_b = InputSession::from_bytes(vec![7; 10]);
+        session_a.set_cache_pages(1);
+
+        assert_eq!(session_a.input_len(), input_a.len() as u64);
+        assert_eq!(session_b.input_len(), 10);
+        let offset = INPUT_PAGE_SIZE - 3;
+        assert_eq!(
+            session_a.read_chunk(offset as u64, INPUT_PAGE_SIZE + 6),
+            &input_a[offset..offset + INPUT_PAGE_SIZE + 6]
+        );
+        assert_eq!(session_a.read_chunk(1, 2), &input_a[1..3]);
+        assert_eq!(session_b.read_chunk(8, 2), [7, 7]);
+
+        // The file source keeps the file open across page loads
+        let path = std::env::temp_dir().join(format!("zisk_paged_input_{}", std::process::id()));
+        std::fs::write(&path, &input_a).unwrap();
+        let mut session_c = InputSession::from_file(&path);
+        session_c.set_cache_pages(1);
+        assert_eq!(session_c.read_chunk(offset as u64, 8), &input_a[offset..offset + 8]);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(session_c.read_chunk(2 * INPUT_PAGE_SIZE as u64, 5), &input_a[8192..8197]);
+    }
+}
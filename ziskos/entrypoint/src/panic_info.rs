@@ -0,0 +1,120 @@
+//! Structured panic information
+//!
+//! With the `panic-info` feature, ziskos installs a panic hook before calling the guest main, so
+//! that a panic is reported through the guest log channel before the program halts, instead of
+//! as an opaque trap.  The report is an error record with target `PANIC_LOG_TARGET`, whose
+//! message is made of three lines:
+//! * The location, as `file:line:column`, or empty if unknown
+//! * A shallow backtrace, as space-separated hexadecimal return addresses, from the innermost
+//!   frame; it is only available if the guest is built with frame pointers
+//!   (`-C force-frame-pointers=yes`), and empty otherwise
+//! * The panic message, which can span several lines
+//!
+//! The emulator decodes it with `GuestPanicInfo`.
+
+use crate::log::{log, LogLevel};
+
+/// Target of the guest log records reporting a panic
+pub const PANIC_LOG_TARGET: &str = "ziskos::panic";
+
+/// Maximum number of return addresses reported
+pub const PANIC_BACKTRACE_DEPTH: usize = 16;
+
+/// Returns the return addresses of the calling frames, walking the frame pointer chain
+#[cfg(all(target_os = "zkvm", target_vendor = "zisk"))]
+fn backtrace() -> Vec<u64> {
+    use core::arch::asm;
+
+    // Frames must be inside the RAM, i.e. [0xa0000000, 0xc0000000)
+    const RAM_START: u64 = 0xa000_0000;
+    const RAM_END: u64 = 0xc000_0000;
+
+    let mut fp: u64;
+    unsafe { asm!("mv {}, s0", out(reg) fp) };
+
+    let mut return_addresses = Vec::new();
+    while return_addresses.len() < PANIC_BACKTRACE_DEPTH
+        && (fp & 7) == 0
+        && fp >= RAM_START + 16
+        && fp <= RAM_END
+    {
+        // The return address and the caller frame pointer are stored right below the frame pointer
+        let (ra, caller_fp) = unsafe { (*((fp - 8) as *const u64), *((fp - 16) as *const u64)) };
+        if ra == 0 {
+            break;
+        }
+        return_addresses.push(ra);
+        if caller_fp <= fp {
+            break;
+        }
+        fp = caller_fp;
+    }
+    return_addresses
+}
+
+#[cfg(not(all(target_os = "zkvm", target_vendor = "zisk")))]
+fn backtrace() -> Vec<u64> {
+    Vec::new()
+}
+
+/// Encodes a panic report as the message of its guest log record
+pub fn encode_panic_report(
+    location: Option<(&str, u32, u32)>,
+    backtrace: &[u64],
+    message: &str,
+) -> String {
+    let location = location.map(|(file, line, column)| format!("{file}:{line}:{column}"));
+    let backtrace: Vec<String> = backtrace.iter().map(|ra| format!("{ra:x}")).collect();
+    format!("{}\n{}\n{}", location.unwrap_or_default(), backtrace.join(" "), message)
+}
+
+/// Installs the panic hook that reports the panics through the guest log channel
+pub fn install_panic_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        let message = if let Some(message) = info.payload().downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = info.payload().downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "Box<dyn Any>".to_string()
+        };
+        let location = info.location().map(|l| (l.file(), l.line(), l.column()));
+        let report = encode_panic_report(location, &backtrace(), &message);
+        log(LogLevel::Error, PANIC_LOG_TARGET, &report);
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Splits a report into its location, backtrace and message lines, as the emulator decodes it
+    fn decode(report: &str) -> (&str, &str, &str) {
+        let mut lines = report.splitn(3, '\n');
+        (lines.next().unwrap(), lines.next().unwrap(), lines.next().unwrap())
+    }
+
+    #[test]
+    fn test_encode_panic_report() {
+        let report = encode_panic_report(
+            Some(("src/main.rs", 12, 5)),
+            &[0x80001010, 0x80000200],
+            "index out of bounds\nlen is 3",
+        );
+        assert_eq!(
+            decode(&report),
+            ("src/main.rs:12:5", "80001010 80000200", "index out of bounds\nlen is 3")
+        );
+
+        // An unknown location or backtrace leaves its line empty, and so does an empty message
+        assert_eq!(
+            decode(&encode_panic_report(None, &[], "explicit panic")),
+            ("", "", "explicit panic")
+        );
+        let report = encode_panic_report(Some(("C:\\guest\\main.rs", 1, 2)), &[0xa], "");
+        assert_eq!(decode(&report), ("C:\\guest\\main.rs:1:2", "a", ""));
+
+        // The frame pointer chain is only walked in the guest
+        assert!(backtrace().is_empty());
+    }
+}
@@ -0,0 +1,61 @@
+//! Scoped profiling regions
+//!
+//! * `zisk_region!("name", { ... })` executes the block inside a named region, whose steps are
+//!   attributed to it by the emulator when run with `--regions`, without the overhead of the full
+//!   statistics.
+//! * A region is identified by the address of its name, a static NUL-terminated string, so the
+//!   ids are unique without any registry, and the regions with the same name share it.
+//! * The begin and end markers are hints, i.e. `addi x0, reg, mark` instructions executed as nops,
+//!   with the address of the name in reg and `REGION_BEGIN_MARK` or `REGION_END_MARK` as mark.
+//! * The end marker is emitted by a guard, so it is emitted also when the block is left early,
+//!   e.g. through `return` or `?`.
+
+#[cfg(all(target_os = "zkvm", target_vendor = "zisk"))]
+use core::arch::asm;
+
+/// Immediate of the hint that marks the beginning of a region
+pub const REGION_BEGIN_MARK: u16 = 0x500;
+/// Immediate of the hint that marks the end of a region
+pub const REGION_END_MARK: u16 = 0x600;
+
+/// Executes the block inside the region with the provided name, returning its value
+#[macro_export]
+macro_rules! zisk_region {
+    ($name:literal, $body:block) => {{
+        let _region = $crate::RegionGuard::begin(concat!($name, "\0"));
+        $body
+    }};
+}
+
+/// Guard of an active region, which emits the end marker when dropped
+pub struct RegionGuard {
+    name: &'static str,
+}
+
+impl RegionGuard {
+    /// Emits the begin marker of the region, whose name must be NUL-terminated
+    #[inline(always)]
+    pub fn begin(name: &'static str) -> Self {
+        region_mark::<REGION_BEGIN_MARK>(name.as_ptr());
+        Self { name }
+    }
+}
+
+impl Drop for RegionGuard {
+    #[inline(always)]
+    fn drop(&mut self) {
+        region_mark::<REGION_END_MARK>(self.name.as_ptr());
+    }
+}
+
+#[cfg(all(target_os = "zkvm", target_vendor = "zisk"))]
+#[inline(always)]
+fn region_mark<const MARK: u16>(name: *const u8) {
+    unsafe {
+        asm!("addi x0, {}, {}", in(reg) name, const MARK, options(nomem, nostack));
+    }
+}
+
+#[cfg(not(all(target_os = "zkvm", target_vendor = "zisk")))]
+#[inline(always)]
+fn region_mark<const MARK: u16>(_name: *const u8) {}
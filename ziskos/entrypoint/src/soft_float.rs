@@ -0,0 +1,446 @@
+//! Soft-float shims
+//!
+//! With the `soft-float` feature, ziskos provides its own implementation of the f64 arithmetic,
+//! comparison and conversion intrinsics that the compiler calls on soft-float targets
+//! (`__adddf3`, `__muldf3`, `__ltdf2`, `__floatdidf`, `__fixdfdi`, etc.), so that every guest
+//! crate, including the float formatting code pulled in by third-party crates, uses the same code,
+//! whose behavior is fully specified:
+//! * Results are correctly rounded to nearest, ties to even, as required by IEEE 754; the rounding
+//!   mode cannot be changed and no exception flags are raised
+//! * Every NaN result is the canonical NaN (`0x7ff8000000000000`), as on RISC-V, so that NaN
+//!   payloads never depend on the operands order
+//! * Float to integer conversions truncate towards zero and saturate, and NaN converts to zero, as
+//!   the Rust `as` casts do
+//!
+//! The compiler-builtins intrinsics are weak symbols, so these definitions take precedence.  The
+//! same functions are available on the host, as `f64_add()`, `f64_mul()`, etc., operating on the
+//! f64 bit patterns, so their results can be compared with the native ones.
+
+use core::cmp::Ordering;
+
+const SIGN: u64 = 1 << 63;
+const FRAC_MASK: u64 = (1 << 52) - 1;
+const INF: u64 = 0x7ff0_0000_0000_0000;
+
+/// Canonical NaN, returned by every operation whose result is NaN
+pub const CANONICAL_NAN: u64 = 0x7ff8_0000_0000_0000;
+
+#[inline(always)]
+fn is_nan(a: u64) -> bool {
+    (a & !SIGN) > INF
+}
+
+#[inline(always)]
+fn is_inf(a: u64) -> bool {
+    (a & !SIGN) == INF
+}
+
+#[inline(always)]
+fn is_zero(a: u64) -> bool {
+    (a & !SIGN) == 0
+}
+
+/// Returns the significand and exponent of a finite non-zero value, i.e. `value = sig * 2^exp`,
+/// with the significand normalized to have its leading bit at bit 52
+fn unpack(a: u64) -> (u64, i32) {
+    let biased_exp = ((a >> 52) & 0x7ff) as i32;
+    let frac = a & FRAC_MASK;
+    if biased_exp == 0 {
+        let shift = frac.leading_zeros() as i32 - 11;
+        (frac << shift, -1074 - shift)
+    } else {
+        (frac | (1 << 52), biased_exp - 1075)
+    }
+}
+
+/// Shifts right, or-ing the shifted out bits into the least significant bit (sticky bit)
+fn shift_right_jam(a: u128, dist: u32) -> u128 {
+    if dist == 0 {
+        a
+    } else if dist < 128 {
+        (a >> dist) | ((a << (128 - dist)) != 0) as u128
+    } else {
+        (a != 0) as u128
+    }
+}
+
+/// Rounds `sig * 2^exp` to nearest, ties to even, and packs it with the provided sign
+fn round_pack(negative: bool, sig: u128, exp: i32) -> u64 {
+    let sign = if negative { SIGN } else { 0 };
+    if sig == 0 {
+        return sign;
+    }
+
+    // Normalize to 64 bits with the leading bit at bit 62, i.e. 10 rounding bits
+    let leading = 127 - sig.leading_zeros() as i32;
+    let mut sig = if leading > 62 {
+        shift_right_jam(sig, (leading - 62) as u32) as u64
+    } else {
+        (sig << (62 - leading)) as u64
+    };
+
+    // Biased exponent minus one, since the leading bit is added when packing
+    let mut biased_exp = exp + leading + 1022;
+    if biased_exp < 0 {
+        // Subnormal result
+        sig = shift_right_jam(sig as u128, (-biased_exp) as u32) as u64;
+        biased_exp = 0;
+    } else if biased_exp >= 0x7fd && (biased_exp > 0x7fd || sig + 0x200 >= SIGN) {
+        return sign | INF;
+    }
+
+    let round_bits = sig & 0x3ff;
+    let mut sig = (sig + 0x200) >> 10;
+    if round_bits == 0x200 {
+        sig &= !1;
+    }
+    if sig == 0 {
+        return sign;
+    }
+    sign + ((biased_exp as u64) << 52) + sig
+}
+
+/// Returns `a + b`
+pub fn f64_add(a: u64, b: u64) -> u64 {
+    if is_nan(a) || is_nan(b) {
+        return CANONICAL_NAN;
+    }
+    if is_inf(a) || is_inf(b) {
+        if is_inf(a) && is_inf(b) && ((a ^ b) & SIGN) != 0 {
+            return CANONICAL_NAN;
+        }
+        return if is_inf(a) { a } else { b };
+    }
+    if is_zero(a) || is_zero(b) {
+        return match (is_zero(a), is_zero(b)) {
+            (true, true) => a & b,
+            (true, false) => b,
+            _ => a,
+        };
+    }
+
+    // Align the operands, keeping 64 extra bits below the least significant bit of the largest
+    let (mut sig_a, exp_a) = unpack(a);
+    let (mut sig_b, exp_b) = unpack(b);
+    let (mut neg_a, mut neg_b) = ((a & SIGN) != 0, (b & SIGN) != 0);
+    let (mut exp_a, mut exp_b) = (exp_a, exp_b);
+    if (exp_a, sig_a) < (exp_b, sig_b) {
+        core::mem::swap(&mut sig_a, &mut sig_b);
+        core::mem::swap(&mut exp_a, &mut exp_b);
+        core::mem::swap(&mut neg_a, &mut neg_b);
+    }
+    let big = (sig_a as u128) << 64;
+    let small = shift_right_jam((sig_b as u128) << 64, (exp_a - exp_b) as u32);
+
+    if neg_a == neg_b {
+        round_pack(neg_a, big + small, exp_a - 64)
+    } else {
+        // The largest magnitude determines the sign; an exact zero is positive
+        round_pack(neg_a && big != small, big - small, exp_a - 64)
+    }
+}
+
+/// Returns `a - b`
+pub fn f64_sub(a: u64, b: u64) -> u64 {
+    if is_nan(b) {
+        return CANONICAL_NAN;
+    }
+    f64_add(a, b ^ SIGN)
+}
+
+/// Returns `a * b`
+pub fn f64_mul(a: u64, b: u64) -> u64 {
+    let negative = ((a ^ b) & SIGN) != 0;
+    if is_nan(a) || is_nan(b) {
+        return CANONICAL_NAN;
+    }
+    if is_inf(a) || is_inf(b) {
+        if is_zero(a) || is_zero(b) {
+            return CANONICAL_NAN;
+        }
+        return ((a ^ b) & SIGN) | INF;
+    }
+    if is_zero(a) || is_zero(b) {
+        return (a ^ b) & SIGN;
+    }
+
+    let (sig_a, exp_a) = unpack(a);
+    let (sig_b, exp_b) = unpack(b);
+    round_pack(negative, sig_a as u128 * sig_b as u128, exp_a + exp_b)
+}
+
+/// Returns `a / b`
+pub fn f64_div(a: u64, b: u64) -> u64 {
+    let sign = (a ^ b) & SIGN;
+    if is_nan(a) || is_nan(b) {
+        return CANONICAL_NAN;
+    }
+    if is_inf(a) {
+        return if is_inf(b) { CANONICAL_NAN } else { sign | INF };
+    }
+    if is_inf(b) {
+        return sign;
+    }
+    if is_zero(b) {
+        return if is_zero(a) { CANONICAL_NAN } else { sign | INF };
+    }
+    if is_zero(a) {
+        return sign;
+    }
+
+    // The quotient of the 53-bit significands has at least 75 significant bits, plus a sticky
+    // bit for the remainder
+    let (sig_a, exp_a) = unpack(a);
+    let (sig_b, exp_b) = unpack(b);
+    let dividend = (sig_a as u128) << 75;
+    let quotient = dividend / sig_b as u128;
+    let sticky = !dividend.is_multiple_of(sig_b as u128) as u128;
+    round_pack(sign != 0, quotient | sticky, exp_a - exp_b - 75)
+}
+
+/// Compares `a` and `b`, returning None if any of them is NaN; both zeros are equal
+pub fn f64_cmp(a: u64, b: u64) -> Option<Ordering> {
+    if is_nan(a) || is_nan(b) {
+        return None;
+    }
+    if is_zero(a) && is_zero(b) {
+        return Some(Ordering::Equal);
+    }
+    // Map the sign-magnitude representation into a monotonic integer one
+    let key = |x: u64| if (x & SIGN) != 0 { !x } else { x | SIGN };
+    Some(key(a).cmp(&key(b)))
+}
+
+/// Returns the f64 nearest to `value`
+pub fn f64_from_i64(value: i64) -> u64 {
+    round_pack(value < 0, value.unsigned_abs() as u128, 0)
+}
+
+/// Returns the f64 nearest to `value`
+pub fn f64_from_u64(value: u64) -> u64 {
+    round_pack(false, value as u128, 0)
+}
+
+/// Returns `a` truncated towards zero, saturated to the [min, max] range, or zero if it is NaN
+fn f64_to_int(a: u64, min: i128, max: i128) -> i128 {
+    if is_nan(a) {
+        return 0;
+    }
+    let negative = (a & SIGN) != 0;
+    if is_inf(a) {
+        return if negative { min } else { max };
+    }
+    if is_zero(a) {
+        return 0;
+    }
+    let (sig, exp) = unpack(a);
+    let magnitude: i128 = if exp >= 0 {
+        if exp > 64 {
+            i128::MAX
+        } else {
+            (sig as i128) << exp
+        }
+    } else if exp > -64 {
+        (sig >> (-exp)) as i128
+    } else {
+        0
+    };
+    let value = if negative { -magnitude } else { magnitude };
+    value.clamp(min, max)
+}
+
+/// Returns `a` truncated towards zero and saturated, or zero if it is NaN
+pub fn f64_to_i64(a: u64) -> i64 {
+    f64_to_int(a, i64::MIN as i128, i64::MAX as i128) as i64
+}
+
+/// Returns `a` truncated towards zero and saturated, or zero if it is NaN
+pub fn f64_to_u64(a: u64) -> u64 {
+    f64_to_int(a, 0, u64::MAX as i128) as u64
+}
+
+/// Returns `a` truncated towards zero and saturated, or zero if it is NaN
+pub fn f64_to_i32(a: u64) -> i32 {
+    f64_to_int(a, i32::MIN as i128, i32::MAX as i128) as i32
+}
+
+/// Returns `a` truncated towards zero and saturated, or zero if it is NaN
+pub fn f64_to_u32(a: u64) -> u32 {
+    f64_to_int(a, 0, u32::MAX as i128) as u32
+}
+
+#[cfg(all(target_os = "zkvm", target_vendor = "zisk"))]
+mod intrinsics {
+    use super::*;
+
+    /// Comparison result expected by the intrinsics: -1, 0 or 1, or `unordered` for NaN
+    fn cmp_result(a: f64, b: f64, unordered: i32) -> i32 {
+        match f64_cmp(a.to_bits(), b.to_bits()) {
+            Some(Ordering::Less) => -1,
+            Some(Ordering::Equal) => 0,
+            Some(Ordering::Greater) => 1,
+            None => unordered,
+        }
+    }
+
+    #[export_name = "__adddf3"]
+    extern "C" fn adddf3(a: f64, b: f64) -> f64 {
+        f64::from_bits(f64_add(a.to_bits(), b.to_bits()))
+    }
+
+    #[export_name = "__subdf3"]
+    extern "C" fn subdf3(a: f64, b: f64) -> f64 {
+        f64::from_bits(f64_sub(a.to_bits(), b.to_bits()))
+    }
+
+    #[export_name = "__muldf3"]
+    extern "C" fn muldf3(a: f64, b: f64) -> f64 {
+        f64::from_bits(f64_mul(a.to_bits(), b.to_bits()))
+    }
+
+    #[export_name = "__divdf3"]
+    extern "C" fn divdf3(a: f64, b: f64) -> f64 {
+        f64::from_bits(f64_div(a.to_bits(), b.to_bits()))
+    }
+
+    #[export_name = "__eqdf2"]
+    extern "C" fn eqdf2(a: f64, b: f64) -> i32 {
+        cmp_result(a, b, 1)
+    }
+
+    #[export_name = "__nedf2"]
+    extern "C" fn nedf2(a: f64, b: f64) -> i32 {
+        cmp_result(a, b, 1)
+    }
+
+    #[export_name = "__ltdf2"]
+    extern "C" fn ltdf2(a: f64, b: f64) -> i32 {
+        cmp_result(a, b, 1)
+    }
+
+    #[export_name = "__ledf2"]
+    extern "C" fn ledf2(a: f64, b: f64) -> i32 {
+        cmp_result(a, b, 1)
+    }
+
+    #[export_name = "__gtdf2"]
+    extern "C" fn gtdf2(a: f64, b: f64) -> i32 {
+        cmp_result(a, b, -1)
+    }
+
+    #[export_name = "__gedf2"]
+    extern "C" fn gedf2(a: f64, b: f64) -> i32 {
+        cmp_result(a, b, -1)
+    }
+
+    #[export_name = "__unorddf2"]
+    extern "C" fn unorddf2(a: f64, b: f64) -> i32 {
+        f64_cmp(a.to_bits(), b.to_bits()).is_none() as i32
+    }
+
+    #[export_name = "__floatsidf"]
+    extern "C" fn floatsidf(a: i32) -> f64 {
+        f64::from_bits(f64_from_i64(a as i64))
+    }
+
+    #[export_name = "__floatdidf"]
+    extern "C" fn floatdidf(a: i64) -> f64 {
+        f64::from_bits(f64_from_i64(a))
+    }
+
+    #[export_name = "__floatunsidf"]
+    extern "C" fn floatunsidf(a: u32) -> f64 {
+        f64::from_bits(f64_from_u64(a as u64))
+    }
+
+    #[export_name = "__floatundidf"]
+    extern "C" fn floatundidf(a: u64) -> f64 {
+        f64::from_bits(f64_from_u64(a))
+    }
+
+    #[export_name = "__fixdfsi"]
+    extern "C" fn fixdfsi(a: f64) -> i32 {
+        f64_to_i32(a.to_bits())
+    }
+
+    #[export_name = "__fixdfdi"]
+    extern "C" fn fixdfdi(a: f64) -> i64 {
+        f64_to_i64(a.to_bits())
+    }
+
+    #[export_name = "__fixunsdfsi"]
+    extern "C" fn fixunsdfsi(a: f64) -> u32 {
+        f64_to_u32(a.to_bits())
+    }
+
+    #[export_name = "__fixunsdfdi"]
+    extern "C" fn fixunsdfdi(a: f64) -> u64 {
+        f64_to_u64(a.to_bits())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the native result bits, with NaNs canonicalized
+    fn native(value: f64) -> u64 {
+        if value.is_nan() {
+            CANONICAL_NAN
+        } else {
+            value.to_bits()
+        }
+    }
+
+    #[test]
+    fn test_soft_float() {
+        // Interesting values, plus pseudo-random bit patterns covering all the exponents
+        let mut values: Vec<u64> = [
+            0.0,
+            -0.0,
+            1.0,
+            -1.0,
+            0.1,
+            3.0,
+            1e308,
+            -1e308,
+            5e-324,
+            2.2250738585072014e-308,
+            f64::MAX,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::NAN,
+            9007199254740993.0,
+        ]
+        .iter()
+        .map(|v: &f64| v.to_bits())
+        .collect();
+        let mut seed = 0x2545f4914f6cdd1du64;
+        for _ in 0..300 {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            values.push(seed);
+            values.push(seed & 0x800f_ffff_ffff_ffff);
+            values.push((seed & 0x801f_ffff_ffff_ffff) | 0x3ff0_0000_0000_0000);
+        }
+
+        for &a in &values {
+            let fa = f64::from_bits(a);
+            for &b in &values {
+                let fb = f64::from_bits(b);
+                assert_eq!(f64_add(a, b), native(fa + fb), "{fa:e} + {fb:e}");
+                assert_eq!(f64_sub(a, b), native(fa - fb), "{fa:e} - {fb:e}");
+                assert_eq!(f64_mul(a, b), native(fa * fb), "{fa:e} * {fb:e}");
+                assert_eq!(f64_div(a, b), native(fa / fb), "{fa:e} / {fb:e}");
+                assert_eq!(f64_cmp(a, b), fa.partial_cmp(&fb), "{fa:e} <=> {fb:e}");
+            }
+            assert_eq!(f64_to_i64(a), fa as i64);
+            assert_eq!(f64_to_u64(a), fa as u64);
+            assert_eq!(f64_to_i32(a), fa as i32);
+            assert_eq!(f64_to_u32(a), fa as u32);
+            assert_eq!(f64_from_i64(a as i64), (a as i64 as f64).to_bits());
+            assert_eq!(f64_from_u64(a), (a as f64).to_bits());
+        }
+    }
+}
@@ -0,0 +1,40 @@
+//! Ecall syscalls
+//!
+//! The syscall number is passed in a7, and its arguments in a0 - a2:
+//! * `ECALL_EXIT_ID` ends the program.  `_start` issues it when `main()` returns, and the Zisk trap
+//!   handler serves it.
+//! * `ECALL_READ_ID` and `ECALL_WRITE_ID` are the read and write syscalls of the RISC-V Linux ABI
+//!   followed by the exit syscall, as issued e.g. by the guests built on a libc.  The Zisk trap
+//!   handler ignores them, so they are only served by the emulator on the host.
+//! * `ECALL_HINT_ID`, issued by `syscall_emit_hint()`, is the first number after the precompile
+//!   syscalls range (0x800 - 0x84F).  It is also ignored by the Zisk trap handler.
+
+#[cfg(all(target_os = "zkvm", target_vendor = "zisk"))]
+use core::arch::asm;
+
+/// Reads up to a2 bytes of input data into the buffer at a1, returning the number of bytes read
+pub const ECALL_READ_ID: u64 = 63;
+/// Writes a2 bytes of the buffer at a1 to the file descriptor a0: 1 is stdout and 2 is stderr
+pub const ECALL_WRITE_ID: u64 = 64;
+/// Ends the program with the exit code a0
+pub const ECALL_EXIT_ID: u64 = 93;
+/// Emits the a1 bytes of the buffer at a0 as a hint
+pub const ECALL_HINT_ID: u64 = 0x850;
+
+/// Emits a hint, collected by the emulator when it serves the ecall syscalls on the host.  The
+/// proven execution ignores it.
+#[allow(unused_variables)]
+pub fn syscall_emit_hint(data: &[u8]) {
+    #[cfg(all(target_os = "zkvm", target_vendor = "zisk"))]
+    unsafe {
+        asm!(
+            "ecall",
+            in("a7") ECALL_HINT_ID,
+            inlateout("a0") data.as_ptr() => _,
+            in("a1") data.len(),
+            clobber_abi("C"),
+        );
+    }
+    #[cfg(not(all(target_os = "zkvm", target_vendor = "zisk")))]
+    unreachable!()
+}
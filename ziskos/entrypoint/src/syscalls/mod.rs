@@ -13,6 +13,7 @@ mod bn254_complex_sub;
 mod bn254_curve_add;
 mod bn254_curve_dbl;
 mod complex;
+mod ecall;
 mod keccakf;
 mod point;
 mod secp256k1_add;
@@ -35,6 +36,7 @@ pub use bn254_complex_sub::*;
 pub use bn254_curve_add::*;
 pub use bn254_curve_dbl::*;
 pub use complex::*;
+pub use ecall::*;
 pub use keccakf::*;
 pub use point::*;
 pub use secp256k1_add::*;
@@ -0,0 +1,32 @@
+use cfg_if::cfg_if;
+cfg_if! {
+    if #[cfg(all(target_os = "zkvm", target_vendor = "zisk"))] {
+        use core::arch::asm;
+        use crate::{ziskos_fcall, ziskos_fcall_get, ziskos_fcall_param};
+        use super::FCALL_INPUT_PAGE_ID;
+    }
+}
+
+/// Size in bytes of an input page
+pub const INPUT_PAGE_SIZE: usize = 4096;
+/// Size in u64 words of an input page
+pub const INPUT_PAGE_WORDS: usize = INPUT_PAGE_SIZE / 8;
+
+/// Loads the input page `page`, i.e. the input bytes starting at `page * INPUT_PAGE_SIZE`, packed
+/// in little-endian words and padded with zeros, and returns the total input size in bytes.
+#[allow(unused_variables)]
+pub fn fcall_input_page(page: u64, words: &mut [u64; INPUT_PAGE_WORDS]) -> u64 {
+    #[cfg(not(all(target_os = "zkvm", target_vendor = "zisk")))]
+    unreachable!();
+    #[cfg(all(target_os = "zkvm", target_vendor = "zisk"))]
+    {
+        ziskos_fcall_param!(page, 1);
+        ziskos_fcall!(FCALL_INPUT_PAGE_ID);
+
+        let input_len = ziskos_fcall_get();
+        for word in words.iter_mut() {
+            *word = ziskos_fcall_get();
+        }
+        input_len
+    }
+}
@@ -18,6 +18,7 @@ pub const FCALL_MSB_POS_384_ID: u16 = 15;
 pub const FCALL_BIG_INT256_DIV_ID: u16 = 16;
 pub const FCALL_BIG_INT_DIV_ID: u16 = 17;
 pub const FCALL_BIN_DECOMP_ID: u16 = 18;
+pub const FCALL_INPUT_PAGE_ID: u16 = 19;
 
 mod big_int256_div;
 mod big_int_div;
@@ -29,6 +30,7 @@ mod bls12_381_twist;
 mod bn254_fp;
 mod bn254_fp2;
 mod bn254_twist;
+mod input_page;
 mod msb_pos_256;
 mod msb_pos_384;
 mod secp256k1_fn_inv;
@@ -45,6 +47,7 @@ pub use bls12_381_twist::*;
 pub use bn254_fp::*;
 pub use bn254_fp2::*;
 pub use bn254_twist::*;
+pub use input_page::*;
 pub use msb_pos_256::*;
 pub use msb_pos_384::*;
 pub use secp256k1_fn_inv::*;
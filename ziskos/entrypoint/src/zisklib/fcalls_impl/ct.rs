@@ -0,0 +1,122 @@
+//! Constant-time primitives for the fcalls handling secret data
+//!
+//! * Some fcalls are computed on the host over values that can be secret, e.g. the square root
+//!   of a secp256k1 coordinate derived from a private key, so their control flow should not
+//!   depend on those values.
+//! * The primitives take choices in {0, 1} as `u64` instead of `bool`, and hide them from the
+//!   optimizer with `black_box`, so that the selections are not compiled back into branches.
+//!   They do not make the big integer arithmetic constant-time, only the decisions taken on its
+//!   results.
+//! * With the `ct-audit` feature, `ct_timing_test()` runs a dudect-style statistical test: it
+//!   times a function over a fixed input and over random inputs, interleaved at random, and
+//!   returns the Welch's t statistic of both timing distributions.  A value whose absolute value
+//!   is above `CT_TIMING_THRESHOLD` indicates that the timing depends on the input.
+
+use std::hint::black_box;
+
+/// Returns a mask with all the bits set if `choice` is 1, or with none if it is 0
+#[inline(always)]
+pub fn ct_mask(choice: u64) -> u64 {
+    0u64.wrapping_sub(black_box(choice & 1))
+}
+
+/// Returns `b` if `choice` is 1, or `a` if it is 0
+#[inline(always)]
+pub fn ct_select_u64(a: u64, b: u64, choice: u64) -> u64 {
+    a ^ (ct_mask(choice) & (a ^ b))
+}
+
+/// Returns the limbs of `b` if `choice` is 1, or the limbs of `a` if it is 0
+#[inline(always)]
+pub fn ct_select<const N: usize>(a: &[u64; N], b: &[u64; N], choice: u64) -> [u64; N] {
+    let mask = ct_mask(choice);
+    core::array::from_fn(|i| a[i] ^ (mask & (a[i] ^ b[i])))
+}
+
+/// Returns 1 if all the limbs are equal, or 0 otherwise, examining all of them
+#[inline(always)]
+pub fn ct_eq<const N: usize>(a: &[u64; N], b: &[u64; N]) -> u64 {
+    let diff = a.iter().zip(b.iter()).fold(0, |diff, (a, b)| diff | (a ^ b));
+    ((black_box(diff) | diff.wrapping_neg()) >> 63) ^ 1
+}
+
+/// Absolute t statistic above which `ct_timing_test()` reports a timing leak, as in dudect
+#[cfg(feature = "ct-audit")]
+pub const CT_TIMING_THRESHOLD: f64 = 4.5;
+
+/// Times `f` over `samples` executions, each one on `fixed` or on a `random()` input chosen at
+/// random, and returns the Welch's t statistic of the two timing distributions.  The slowest 10%
+/// of the measurements is discarded, since it is dominated by interruptions.
+#[cfg(feature = "ct-audit")]
+pub fn ct_timing_test<T, R>(
+    f: impl Fn(&T) -> R,
+    fixed: &T,
+    mut random: impl FnMut() -> T,
+    samples: usize,
+) -> f64 {
+    use std::time::Instant;
+
+    // Prepare the inputs in advance, so that their generation is not measured
+    let inputs: Vec<Option<T>> =
+        (0..samples).map(|_| if rand::random::<bool>() { Some(random()) } else { None }).collect();
+    let mut timings: Vec<(bool, u64)> = inputs
+        .iter()
+        .map(|input| {
+            let class = input.is_some();
+            let input = input.as_ref().unwrap_or(fixed);
+            let start = Instant::now();
+            black_box(f(black_box(input)));
+            (class, start.elapsed().as_nanos() as u64)
+        })
+        .collect();
+
+    let mut sorted: Vec<u64> = timings.iter().map(|(_, time)| *time).collect();
+    sorted.sort_unstable();
+    let cutoff = sorted[(sorted.len() * 9) / 10];
+    timings.retain(|(_, time)| *time <= cutoff);
+
+    // Mean and variance of every class
+    let stats = |class: bool| {
+        let values: Vec<f64> =
+            timings.iter().filter(|(c, _)| *c == class).map(|(_, time)| *time as f64).collect();
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let variance = values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / (n - 1.0);
+        (n, mean, variance)
+    };
+    let (n0, mean0, variance0) = stats(false);
+    let (n1, mean1, variance1) = stats(true);
+    (mean0 - mean1) / ((variance0 / n0) + (variance1 / n1)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ct_select_eq() {
+        let a = [1, 2, 3, 4];
+        let b = [5, 6, 7, 8];
+        assert_eq!(ct_select(&a, &b, 0), a);
+        assert_eq!(ct_select(&a, &b, 1), b);
+        assert_eq!((ct_select_u64(9, 10, 0), ct_select_u64(9, 10, 1)), (9, 10));
+
+        assert_eq!(ct_eq(&a, &a), 1);
+        assert_eq!(ct_eq(&a, &[1, 2, 3, 5]), 0);
+        assert_eq!(ct_eq(&[u64::MAX; 4], &[0; 4]), 0);
+        assert_eq!(ct_eq(&[0x8000000000000000], &[0]), 0);
+    }
+
+    #[cfg(feature = "ct-audit")]
+    #[test]
+    fn test_ct_timing() {
+        let fixed = [0u64; 4];
+        let t = ct_timing_test(
+            |a: &[u64; 4]| ct_eq(a, &fixed),
+            &fixed,
+            || core::array::from_fn(|_| rand::random::<u64>()),
+            100_000,
+        );
+        assert!(t.abs() < CT_TIMING_THRESHOLD, "ct_eq() timing depends on the input: t={t}");
+    }
+}
@@ -0,0 +1,46 @@
+use crate::zisklib::{INPUT_PAGE_SIZE, INPUT_PAGE_WORDS};
+
+/// Serves the input page fcall from the host copy of the input; it is not dispatched by
+/// `fcall_proxy()`, since the input is owned by the emulator
+pub fn fcall_input_page_from(input: &[u8], parameters: &[u64], results: &mut [u64]) -> i64 {
+    let page = parameters[0] as usize;
+    results[0] = input.len() as u64;
+
+    let start = page.saturating_mul(INPUT_PAGE_SIZE).min(input.len());
+    let end = (start + INPUT_PAGE_SIZE).min(input.len());
+    let page_bytes = &input[start..end];
+    for (i, result) in results[1..=INPUT_PAGE_WORDS].iter_mut().enumerate() {
+        let mut word = [0u8; 8];
+        let from = (8 * i).min(page_bytes.len());
+        let to = (8 * i + 8).min(page_bytes.len());
+        word[..to - from].copy_from_slice(&page_bytes[from..to]);
+        *result = u64::from_le_bytes(word);
+    }
+
+    1 + INPUT_PAGE_WORDS as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fcall_input_page_from() {
+        let input: Vec<u8> = (0..INPUT_PAGE_SIZE + 10).map(|i| i as u8).collect();
+        let mut results = vec![0u64; 1 + INPUT_PAGE_WORDS];
+
+        assert_eq!(fcall_input_page_from(&input, &[0], &mut results), 1 + INPUT_PAGE_WORDS as i64);
+        assert_eq!(results[0], input.len() as u64);
+        assert_eq!(results[1], 0x0706050403020100);
+
+        // Last page, padded with zeros
+        fcall_input_page_from(&input, &[1], &mut results);
+        assert_eq!(results[1], 0x0706050403020100);
+        assert_eq!(results[2], 0x0908);
+        assert!(results[3..].iter().all(|word| *word == 0));
+
+        // Page beyond the input
+        fcall_input_page_from(&input, &[5], &mut results);
+        assert!(results[1..].iter().all(|word| *word == 0));
+    }
+}
@@ -8,12 +8,18 @@ mod bls12_381_twist;
 mod bn254_fp;
 mod bn254_fp2;
 mod bn254_twist;
+mod ct;
+mod input_page;
 mod msb_pos_256;
 mod msb_pos_384;
 mod proxy;
+mod registry;
 mod secp256k1_fn_inv;
 mod secp256k1_fp_inv;
 mod secp256k1_fp_sqrt;
 mod utils;
 
+pub use ct::*;
+pub use input_page::*;
 pub use proxy::*;
+pub use registry::*;
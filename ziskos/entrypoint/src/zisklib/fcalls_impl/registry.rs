@@ -0,0 +1,319 @@
+//! Free-input call registry
+//!
+//! Every free-input call (fcall) is registered in `FCALL_REGISTRY` with its id, name, version,
+//! the layout of its parameters and results, and its host handler.  `fcall_proxy()` dispatches
+//! through the registry, so the layouts documented here are the ones the emulator serves.
+//!
+//! `fcall_spec_json()` and `fcall_spec_table()` render the registry as a machine-readable JSON
+//! spec and as a markdown table, for the implementers of fcall producers and consumers outside
+//! this repository.
+
+use crate::zisklib::{
+    FCALL_BIG_INT256_DIV_ID, FCALL_BIG_INT_DIV_ID, FCALL_BIN_DECOMP_ID, FCALL_BLS12_381_FP2_INV_ID,
+    FCALL_BLS12_381_FP_INV_ID, FCALL_BLS12_381_FP_SQRT_ID,
+    FCALL_BLS12_381_TWIST_ADD_LINE_COEFFS_ID, FCALL_BLS12_381_TWIST_DBL_LINE_COEFFS_ID,
+    FCALL_BN254_FP2_INV_ID, FCALL_BN254_FP_INV_ID, FCALL_BN254_TWIST_ADD_LINE_COEFFS_ID,
+    FCALL_BN254_TWIST_DBL_LINE_COEFFS_ID, FCALL_INPUT_PAGE_ID, FCALL_MSB_POS_256_ID,
+    FCALL_MSB_POS_384_ID, FCALL_SECP256K1_FN_INV_ID, FCALL_SECP256K1_FP_INV_ID,
+    FCALL_SECP256K1_FP_SQRT_ID, INPUT_PAGE_WORDS,
+};
+
+use super::{
+    big_int256_div::*, big_int_div::*, bin_decomp::*, bls12_381_fp2_inv::*, bls12_381_fp_inv::*,
+    bls12_381_fp_sqrt::*, bls12_381_twist::*, bn254_fp::*, bn254_fp2::*, bn254_twist::*,
+    msb_pos_256::*, msb_pos_384::*, secp256k1_fn_inv::*, secp256k1_fp_inv::*, secp256k1_fp_sqrt::*,
+};
+
+/// Version of the spec format emitted by `fcall_spec_json()`
+pub const FCALL_SPEC_FORMAT_VERSION: u32 = 1;
+
+/// Host handler of a free-input call: fills the results from the parameters, and returns the
+/// number of result words
+pub type FcallHandler = fn(&[u64], &mut [u64]) -> i64;
+
+/// Length of a field of the parameters or results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FcallFieldLen {
+    /// Fixed number of 64-bit words
+    Words(usize),
+    /// Variable number of 64-bit words, preceded by a word with that number
+    Prefixed,
+}
+
+/// Field of the parameters or results of a free-input call, in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FcallField {
+    pub name: &'static str,
+    pub len: FcallFieldLen,
+}
+
+/// Registered free-input call
+#[derive(Debug, Clone, Copy)]
+pub struct FcallSpec {
+    pub id: u16,
+    pub name: &'static str,
+    /// Version of the parameters and results layout, increased on every incompatible change
+    pub version: u32,
+    pub params: &'static [FcallField],
+    pub results: &'static [FcallField],
+    /// Host handler, or None if the emulator serves the call itself (e.g. input pages)
+    pub handler: Option<FcallHandler>,
+}
+
+/// Builds a fixed length field
+const fn words(name: &'static str, len: usize) -> FcallField {
+    FcallField { name, len: FcallFieldLen::Words(len) }
+}
+
+/// Builds a length-prefixed field
+const fn prefixed(name: &'static str) -> FcallField {
+    FcallField { name, len: FcallFieldLen::Prefixed }
+}
+
+/// Builds a version 1 fcall spec with a host handler
+const fn spec(
+    id: u16,
+    name: &'static str,
+    params: &'static [FcallField],
+    results: &'static [FcallField],
+    handler: FcallHandler,
+) -> FcallSpec {
+    FcallSpec { id, name, version: 1, params, results, handler: Some(handler) }
+}
+
+/// Registered free-input calls, by id
+pub const FCALL_REGISTRY: &[FcallSpec] = &[
+    spec(
+        FCALL_SECP256K1_FP_INV_ID,
+        "secp256k1_fp_inv",
+        &[words("a", 4)],
+        &[words("inv", 4)],
+        fcall_secp256k1_fp_inv,
+    ),
+    spec(
+        FCALL_SECP256K1_FN_INV_ID,
+        "secp256k1_fn_inv",
+        &[words("a", 4)],
+        &[words("inv", 4)],
+        fcall_secp256k1_fn_inv,
+    ),
+    spec(
+        FCALL_SECP256K1_FP_SQRT_ID,
+        "secp256k1_fp_sqrt",
+        &[words("a", 4), words("parity", 1)],
+        &[words("has_sqrt", 1), words("sqrt", 4)],
+        fcall_secp256k1_fp_sqrt,
+    ),
+    spec(
+        FCALL_MSB_POS_256_ID,
+        "msb_pos_256",
+        &[words("x", 4), words("y", 4)],
+        &[words("limb", 1), words("bit", 1)],
+        fcall_msb_pos_256,
+    ),
+    spec(
+        FCALL_BN254_FP_INV_ID,
+        "bn254_fp_inv",
+        &[words("a", 4)],
+        &[words("inv", 4)],
+        fcall_bn254_fp_inv,
+    ),
+    spec(
+        FCALL_BN254_FP2_INV_ID,
+        "bn254_fp2_inv",
+        &[words("a", 8)],
+        &[words("inv", 8)],
+        fcall_bn254_fp2_inv,
+    ),
+    spec(
+        FCALL_BN254_TWIST_ADD_LINE_COEFFS_ID,
+        "bn254_twist_add_line_coeffs",
+        &[words("x1", 8), words("y1", 8), words("x2", 8), words("y2", 8)],
+        &[words("lambda", 8), words("mu", 8)],
+        fcall_bn254_twist_add_line_coeffs,
+    ),
+    spec(
+        FCALL_BN254_TWIST_DBL_LINE_COEFFS_ID,
+        "bn254_twist_dbl_line_coeffs",
+        &[words("x", 8), words("y", 8)],
+        &[words("lambda", 8), words("mu", 8)],
+        fcall_bn254_twist_dbl_line_coeffs,
+    ),
+    spec(
+        FCALL_BLS12_381_FP_INV_ID,
+        "bls12_381_fp_inv",
+        &[words("a", 6)],
+        &[words("inv", 6)],
+        fcall_bls12_381_fp_inv,
+    ),
+    spec(
+        FCALL_BLS12_381_FP_SQRT_ID,
+        "bls12_381_fp_sqrt",
+        &[words("a", 6)],
+        &[words("has_sqrt", 1), words("sqrt", 6)],
+        fcall_bls12_381_fp_sqrt,
+    ),
+    spec(
+        FCALL_BLS12_381_FP2_INV_ID,
+        "bls12_381_fp2_inv",
+        &[words("a", 12)],
+        &[words("inv", 12)],
+        fcall_bls12_381_fp2_inv,
+    ),
+    spec(
+        FCALL_BLS12_381_TWIST_ADD_LINE_COEFFS_ID,
+        "bls12_381_twist_add_line_coeffs",
+        &[words("x1", 12), words("y1", 12), words("x2", 12), words("y2", 12)],
+        &[words("lambda", 12), words("mu", 12)],
+        fcall_bls12_381_twist_add_line_coeffs,
+    ),
+    spec(
+        FCALL_BLS12_381_TWIST_DBL_LINE_COEFFS_ID,
+        "bls12_381_twist_dbl_line_coeffs",
+        &[words("x", 12), words("y", 12)],
+        &[words("lambda", 12), words("mu", 12)],
+        fcall_bls12_381_twist_dbl_line_coeffs,
+    ),
+    // x and y are padded to 8 words
+    spec(
+        FCALL_MSB_POS_384_ID,
+        "msb_pos_384",
+        &[words("x", 8), words("y", 8)],
+        &[words("limb", 1), words("bit", 1)],
+        fcall_msb_pos_384,
+    ),
+    spec(
+        FCALL_BIG_INT256_DIV_ID,
+        "big_int256_div",
+        &[words("a", 4), words("b", 4)],
+        &[words("quotient", 4), words("remainder", 4)],
+        fcall_big_int256_div,
+    ),
+    spec(
+        FCALL_BIG_INT_DIV_ID,
+        "big_int_div",
+        &[prefixed("a"), prefixed("b")],
+        &[prefixed("quotient"), prefixed("remainder")],
+        fcall_big_int_div,
+    ),
+    spec(
+        FCALL_BIN_DECOMP_ID,
+        "bin_decomp",
+        &[prefixed("x")],
+        &[prefixed("bits")],
+        fcall_bin_decomp,
+    ),
+    FcallSpec {
+        id: FCALL_INPUT_PAGE_ID,
+        name: "input_page",
+        version: 1,
+        params: &[words("page", 1)],
+        results: &[words("input_len", 1), words("data", INPUT_PAGE_WORDS)],
+        handler: None,
+    },
+];
+
+/// Returns the registered free-input call with the given id
+pub fn fcall_spec(id: u16) -> Option<&'static FcallSpec> {
+    FCALL_REGISTRY.iter().find(|spec| spec.id == id)
+}
+
+/// Returns the fields as a JSON array
+fn fields_json(fields: &[FcallField]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| match field.len {
+            FcallFieldLen::Words(len) => format!("{{\"name\":\"{}\",\"words\":{len}}}", field.name),
+            FcallFieldLen::Prefixed => {
+                format!("{{\"name\":\"{}\",\"words\":\"length-prefixed\"}}", field.name)
+            }
+        })
+        .collect();
+    format!("[{}]", fields.join(","))
+}
+
+/// Returns the registry as a JSON document:
+/// `{"format_version":1,"fcalls":[{"id":1,"name":"...","version":1,"params":[...],"results":[...]}]}`
+/// where every field is `{"name":"...","words":4}`, or `"words":"length-prefixed"`
+pub fn fcall_spec_json() -> String {
+    let fcalls: Vec<String> = FCALL_REGISTRY
+        .iter()
+        .map(|spec| {
+            format!(
+                "{{\"id\":{},\"name\":\"{}\",\"version\":{},\"params\":{},\"results\":{}}}",
+                spec.id,
+                spec.name,
+                spec.version,
+                fields_json(spec.params),
+                fields_json(spec.results)
+            )
+        })
+        .collect();
+    format!("{{\"format_version\":{FCALL_SPEC_FORMAT_VERSION},\"fcalls\":[{}]}}", fcalls.join(","))
+}
+
+/// Returns the fields as a comma-separated list, e.g. `a[4], b[n]`
+fn fields_text(fields: &[FcallField]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| match field.len {
+            FcallFieldLen::Words(len) => format!("{}[{len}]", field.name),
+            FcallFieldLen::Prefixed => format!("{}[n]", field.name),
+        })
+        .collect();
+    fields.join(", ")
+}
+
+/// Returns the registry as a markdown table, with `[n]` denoting a length-prefixed field
+pub fn fcall_spec_table() -> String {
+    let mut table =
+        "| id | name | version | params | results |\n|---|---|---|---|---|\n".to_string();
+    for spec in FCALL_REGISTRY {
+        table += &format!(
+            "| {} | {} | {} | {} | {} |\n",
+            spec.id,
+            spec.name,
+            spec.version,
+            fields_text(spec.params),
+            fields_text(spec.results)
+        );
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fcall_registry() {
+        // Ids are unique
+        let mut ids: Vec<u16> = FCALL_REGISTRY.iter().map(|spec| spec.id).collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), FCALL_REGISTRY.len());
+
+        // The registered layout matches the number of words written by the handler
+        let spec = fcall_spec(FCALL_BIG_INT256_DIV_ID).unwrap();
+        let mut results = [0u64; 8];
+        let written = (spec.handler.unwrap())(&[7, 0, 0, 0, 2, 0, 0, 0], &mut results);
+        let words: usize = spec
+            .results
+            .iter()
+            .map(|field| match field.len {
+                FcallFieldLen::Words(len) => len,
+                FcallFieldLen::Prefixed => unreachable!(),
+            })
+            .sum();
+        assert_eq!(written as usize, words);
+        assert_eq!(results, [3, 0, 0, 0, 1, 0, 0, 0]);
+
+        let json = fcall_spec_json();
+        assert!(json.starts_with("{\"format_version\":1,\"fcalls\":[{\"id\":1,"));
+        assert!(json.contains(
+            "{\"id\":17,\"name\":\"big_int_div\",\"version\":1,\"params\":[{\"name\":\"a\",\
+             \"words\":\"length-prefixed\"},{\"name\":\"b\",\"words\":\"length-prefixed\"}]"
+        ));
+        assert!(fcall_spec_table().contains("| 3 | secp256k1_fp_sqrt | 1 | a[4], parity[1] |"));
+    }
+}
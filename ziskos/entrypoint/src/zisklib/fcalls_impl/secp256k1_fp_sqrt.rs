@@ -1,7 +1,10 @@
 use lazy_static::lazy_static;
 use num_bigint::BigUint;
 
-use super::utils::{biguint_from_u64_digits, n_u64_digits_from_biguint};
+use super::{
+    ct::{ct_eq, ct_select},
+    utils::{biguint_from_u64_digits, n_u64_digits_from_biguint},
+};
 
 lazy_static! {
     pub static ref P: BigUint = BigUint::parse_bytes(
@@ -40,32 +43,25 @@ fn secp256k1_fp_sqrt(a: &[u64; 4], parity: u64, results: &mut [u64]) {
     let a_big = biguint_from_u64_digits(a);
 
     // Attempt to compute the square root of a
-    let mut sqrt = a_big.modpow(&P_DIV_4, &P);
+    let sqrt = a_big.modpow(&P_DIV_4, &P);
 
     // Check if a is a quadratic residue
-    let square = (&sqrt * &sqrt) % &*P;
-    let a_is_qr = square == a_big;
-    results[0] = a_is_qr as u64;
-    if !a_is_qr {
-        // To check that a is indeed a non-quadratic residue, we check that
-        // a * NQR is a quadratic residue for some fixed known non-quadratic residue NQR
-        let a_nqr = (a_big * &*NQR) % &*P;
-
-        // Compute the square root of a * NQR
-        let sqrt_nqr = a_nqr.modpow(&P_DIV_4, &P);
-
-        results[1..5].copy_from_slice(&n_u64_digits_from_biguint::<4>(&sqrt_nqr));
-        return;
-    }
+    let square = n_u64_digits_from_biguint::<4>(&((&sqrt * &sqrt) % &*P));
+    let a_is_qr = ct_eq(&square, a);
+    results[0] = a_is_qr;
+
+    // To check that a is indeed a non-quadratic residue, we check that a * NQR is a quadratic
+    // residue for some fixed known non-quadratic residue NQR.  Its square root is computed even
+    // if a is a quadratic residue, so that the work done does not depend on a
+    let a_nqr = (a_big * &*NQR) % &*P;
+    let sqrt_nqr = n_u64_digits_from_biguint::<4>(&a_nqr.modpow(&P_DIV_4, &P));
 
     // Flip the sqrt if needed to match the requested parity
-    let sqrt_r = n_u64_digits_from_biguint::<4>(&sqrt);
-    let sqrt_parity = (sqrt_r[0] & 1) as u64;
-    if parity != sqrt_parity {
-        sqrt = (&*P - &sqrt) % &*P;
-    }
+    let sqrt_neg = n_u64_digits_from_biguint::<4>(&((&*P - &sqrt) % &*P));
+    let sqrt = n_u64_digits_from_biguint::<4>(&sqrt);
+    let sqrt = ct_select(&sqrt, &sqrt_neg, (parity ^ sqrt[0]) & 1);
 
-    results[1..5].copy_from_slice(&n_u64_digits_from_biguint::<4>(&sqrt));
+    results[1..5].copy_from_slice(&ct_select(&sqrt_nqr, &sqrt, a_is_qr));
 }
 
 #[cfg(test)]
@@ -0,0 +1,408 @@
+//! EVM precompiles
+//!
+//! Maps the Ethereum precompiled contracts at addresses 0x01..0x0a to the Zisk precompiles that
+//! accelerate them, and parses their calldata into the operands of the zisklib functions, so that
+//! the guests executing EVM code share one shim instead of each decoding the calldata on its own.
+//!
+//! The calldata follows the EVM rules: a short input is read as if it were right-padded with
+//! zeros, except for the precompiles that require an exact input size (blake2f, point evaluation)
+//! or a multiple of an element size (pairing), which reject any other size.  The 32-byte big-endian
+//! integers are converted to the little-endian limbs used by zisklib, and the BN254 G2 points from
+//! the EVM order (x.imaginary, x.real, y.imaginary, y.real) to the zisklib one (x.real,
+//! x.imaginary, y.real, y.imaginary).
+
+use crate::syscalls::{
+    SYSCALL_ADD256_ID, SYSCALL_ARITH256_ID, SYSCALL_ARITH256_MOD_ID, SYSCALL_ARITH384_MOD_ID,
+    SYSCALL_BLS12_381_COMPLEX_ADD_ID, SYSCALL_BLS12_381_COMPLEX_MUL_ID,
+    SYSCALL_BLS12_381_COMPLEX_SUB_ID, SYSCALL_BLS12_381_CURVE_ADD_ID,
+    SYSCALL_BLS12_381_CURVE_DBL_ID, SYSCALL_BN254_COMPLEX_ADD_ID, SYSCALL_BN254_COMPLEX_MUL_ID,
+    SYSCALL_BN254_COMPLEX_SUB_ID, SYSCALL_BN254_CURVE_ADD_ID, SYSCALL_BN254_CURVE_DBL_ID,
+    SYSCALL_KECCAKF_ID, SYSCALL_SECP256K1_ADD_ID, SYSCALL_SECP256K1_DBL_ID, SYSCALL_SHA256F_ID,
+};
+
+/// Size of a BN254 pairing input element: a G1 point and a G2 point
+pub const EVM_BN254_PAIRING_ELEMENT_SIZE: usize = 192;
+/// Size of the blake2f input
+pub const EVM_BLAKE2F_INPUT_SIZE: usize = 213;
+/// Size of the point evaluation input
+pub const EVM_POINT_EVALUATION_INPUT_SIZE: usize = 192;
+
+/// Ethereum precompiled contract, by address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EvmPrecompile {
+    Ecrecover = 0x01,
+    Sha256 = 0x02,
+    Ripemd160 = 0x03,
+    Identity = 0x04,
+    Modexp = 0x05,
+    Bn254Add = 0x06,
+    Bn254Mul = 0x07,
+    Bn254Pairing = 0x08,
+    Blake2f = 0x09,
+    PointEvaluation = 0x0a,
+}
+
+impl EvmPrecompile {
+    pub const ALL: [EvmPrecompile; 10] = [
+        EvmPrecompile::Ecrecover,
+        EvmPrecompile::Sha256,
+        EvmPrecompile::Ripemd160,
+        EvmPrecompile::Identity,
+        EvmPrecompile::Modexp,
+        EvmPrecompile::Bn254Add,
+        EvmPrecompile::Bn254Mul,
+        EvmPrecompile::Bn254Pairing,
+        EvmPrecompile::Blake2f,
+        EvmPrecompile::PointEvaluation,
+    ];
+
+    /// Returns the precompile at the address, or None if it is not a precompile address
+    pub fn from_address(address: &[u8; 20]) -> Option<Self> {
+        if address[..19].iter().any(|&byte| byte != 0) {
+            return None;
+        }
+        Self::ALL.into_iter().find(|precompile| *precompile as u8 == address[19])
+    }
+
+    /// Returns the address of the precompile
+    pub fn address(&self) -> [u8; 20] {
+        let mut address = [0u8; 20];
+        address[19] = *self as u8;
+        address
+    }
+
+    /// Returns the name of the precompile
+    pub fn name(&self) -> &'static str {
+        match self {
+            EvmPrecompile::Ecrecover => "ecrecover",
+            EvmPrecompile::Sha256 => "sha256",
+            EvmPrecompile::Ripemd160 => "ripemd160",
+            EvmPrecompile::Identity => "identity",
+            EvmPrecompile::Modexp => "modexp",
+            EvmPrecompile::Bn254Add => "bn254_add",
+            EvmPrecompile::Bn254Mul => "bn254_mul",
+            EvmPrecompile::Bn254Pairing => "bn254_pairing",
+            EvmPrecompile::Blake2f => "blake2f",
+            EvmPrecompile::PointEvaluation => "point_evaluation",
+        }
+    }
+
+    /// Returns the ids of the Zisk precompile syscalls that accelerate the precompile; empty if it
+    /// runs as plain RISC-V code
+    pub fn zisk_syscalls(&self) -> &'static [u16] {
+        match self {
+            // Public key recovery, and keccak of the public key for the address
+            EvmPrecompile::Ecrecover => &[
+                SYSCALL_ARITH256_MOD_ID,
+                SYSCALL_SECP256K1_ADD_ID,
+                SYSCALL_SECP256K1_DBL_ID,
+                SYSCALL_KECCAKF_ID,
+            ],
+            EvmPrecompile::Sha256 => &[SYSCALL_SHA256F_ID],
+            EvmPrecompile::Ripemd160 | EvmPrecompile::Identity | EvmPrecompile::Blake2f => &[],
+            EvmPrecompile::Modexp => &[SYSCALL_ARITH256_ID, SYSCALL_ADD256_ID],
+            EvmPrecompile::Bn254Add | EvmPrecompile::Bn254Mul => {
+                &[SYSCALL_ARITH256_MOD_ID, SYSCALL_BN254_CURVE_ADD_ID, SYSCALL_BN254_CURVE_DBL_ID]
+            }
+            EvmPrecompile::Bn254Pairing => &[
+                SYSCALL_ARITH256_MOD_ID,
+                SYSCALL_BN254_CURVE_ADD_ID,
+                SYSCALL_BN254_CURVE_DBL_ID,
+                SYSCALL_BN254_COMPLEX_ADD_ID,
+                SYSCALL_BN254_COMPLEX_SUB_ID,
+                SYSCALL_BN254_COMPLEX_MUL_ID,
+            ],
+            // KZG proof verification, and sha256 of the commitment for the versioned hash
+            EvmPrecompile::PointEvaluation => &[
+                SYSCALL_ARITH384_MOD_ID,
+                SYSCALL_BLS12_381_CURVE_ADD_ID,
+                SYSCALL_BLS12_381_CURVE_DBL_ID,
+                SYSCALL_BLS12_381_COMPLEX_ADD_ID,
+                SYSCALL_BLS12_381_COMPLEX_SUB_ID,
+                SYSCALL_BLS12_381_COMPLEX_MUL_ID,
+                SYSCALL_SHA256F_ID,
+            ],
+        }
+    }
+
+    /// Parses the calldata of a call to the precompile
+    pub fn parse_call<'a>(
+        &self,
+        input: &'a [u8],
+    ) -> Result<EvmPrecompileCall<'a>, EvmPrecompileError> {
+        match self {
+            EvmPrecompile::Ecrecover => {
+                let v = word(input, 1);
+                let recovery_id = match (v[..31].iter().all(|&byte| byte == 0), v[31]) {
+                    (true, 27) => Some(0),
+                    (true, 28) => Some(1),
+                    _ => None,
+                };
+                Ok(EvmPrecompileCall::Ecrecover {
+                    hash: be_to_limbs(&word(input, 0)),
+                    recovery_id,
+                    r: be_to_limbs(&word(input, 2)),
+                    s: be_to_limbs(&word(input, 3)),
+                })
+            }
+            EvmPrecompile::Sha256 => Ok(EvmPrecompileCall::Sha256(input)),
+            EvmPrecompile::Ripemd160 => Ok(EvmPrecompileCall::Ripemd160(input)),
+            EvmPrecompile::Identity => Ok(EvmPrecompileCall::Identity(input)),
+            EvmPrecompile::Modexp => {
+                let base_len = length(&word(input, 0))?;
+                let exponent_len = length(&word(input, 1))?;
+                let modulus_len = length(&word(input, 2))?;
+                let base_start = 96;
+                let exponent_start = base_start + base_len;
+                let modulus_start = exponent_start + exponent_len;
+                Ok(EvmPrecompileCall::Modexp {
+                    base: padded(input, base_start, base_len),
+                    exponent: padded(input, exponent_start, exponent_len),
+                    modulus: padded(input, modulus_start, modulus_len),
+                })
+            }
+            EvmPrecompile::Bn254Add => Ok(EvmPrecompileCall::Bn254Add {
+                p1: bn254_g1(&padded(input, 0, 64)),
+                p2: bn254_g1(&padded(input, 64, 64)),
+            }),
+            EvmPrecompile::Bn254Mul => Ok(EvmPrecompileCall::Bn254Mul {
+                p: bn254_g1(&padded(input, 0, 64)),
+                k: be_to_limbs(&word(input, 2)),
+            }),
+            EvmPrecompile::Bn254Pairing => {
+                if !input.len().is_multiple_of(EVM_BN254_PAIRING_ELEMENT_SIZE) {
+                    return Err(EvmPrecompileError::InvalidInputLength {
+                        precompile: *self,
+                        len: input.len(),
+                    });
+                }
+                let elements = input.chunks_exact(EVM_BN254_PAIRING_ELEMENT_SIZE);
+                Ok(EvmPrecompileCall::Bn254Pairing {
+                    g1: elements.clone().map(|element| bn254_g1(&element[..64])).collect(),
+                    g2: elements.map(|element| bn254_g2(&element[64..])).collect(),
+                })
+            }
+            EvmPrecompile::Blake2f => {
+                if input.len() != EVM_BLAKE2F_INPUT_SIZE {
+                    return Err(EvmPrecompileError::InvalidInputLength {
+                        precompile: *self,
+                        len: input.len(),
+                    });
+                }
+                let le_words = |start: usize, out: &mut [u64]| {
+                    for (i, word) in out.iter_mut().enumerate() {
+                        let bytes = &input[start + 8 * i..start + 8 * i + 8];
+                        *word = u64::from_le_bytes(bytes.try_into().unwrap());
+                    }
+                };
+                let mut h = [0u64; 8];
+                let mut m = [0u64; 16];
+                let mut t = [0u64; 2];
+                le_words(4, &mut h);
+                le_words(68, &mut m);
+                le_words(196, &mut t);
+                let f = match input[212] {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(EvmPrecompileError::InvalidFinalFlag),
+                };
+                let rounds = u32::from_be_bytes(input[0..4].try_into().unwrap());
+                Ok(EvmPrecompileCall::Blake2f { rounds, h, m, t, f })
+            }
+            EvmPrecompile::PointEvaluation => {
+                if input.len() != EVM_POINT_EVALUATION_INPUT_SIZE {
+                    return Err(EvmPrecompileError::InvalidInputLength {
+                        precompile: *self,
+                        len: input.len(),
+                    });
+                }
+                Ok(EvmPrecompileCall::PointEvaluation {
+                    versioned_hash: input[0..32].try_into().unwrap(),
+                    z: be_to_limbs(&word(input, 1)),
+                    y: be_to_limbs(&word(input, 2)),
+                    commitment: input[96..144].try_into().unwrap(),
+                    proof: input[144..192].try_into().unwrap(),
+                })
+            }
+        }
+    }
+}
+
+/// Parsed call to an EVM precompile, with the operands in the zisklib format
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvmPrecompileCall<'a> {
+    /// Signature recovery; `recovery_id` is None if `v` is neither 27 nor 28, in which case the
+    /// EVM returns an empty output
+    Ecrecover {
+        hash: [u64; 4],
+        recovery_id: Option<u8>,
+        r: [u64; 4],
+        s: [u64; 4],
+    },
+    Sha256(&'a [u8]),
+    Ripemd160(&'a [u8]),
+    Identity(&'a [u8]),
+    /// Big-endian base, exponent and modulus
+    Modexp {
+        base: Vec<u8>,
+        exponent: Vec<u8>,
+        modulus: Vec<u8>,
+    },
+    /// Affine points, (0, 0) being the point at infinity
+    Bn254Add {
+        p1: [u64; 8],
+        p2: [u64; 8],
+    },
+    Bn254Mul {
+        p: [u64; 8],
+        k: [u64; 4],
+    },
+    Bn254Pairing {
+        g1: Vec<[u64; 8]>,
+        g2: Vec<[u64; 16]>,
+    },
+    /// BLAKE2 compression function F
+    Blake2f {
+        rounds: u32,
+        h: [u64; 8],
+        m: [u64; 16],
+        t: [u64; 2],
+        f: bool,
+    },
+    /// KZG proof verification, with the compressed BLS12-381 commitment and proof
+    PointEvaluation {
+        versioned_hash: [u8; 32],
+        z: [u64; 4],
+        y: [u64; 4],
+        commitment: [u8; 48],
+        proof: [u8; 48],
+    },
+}
+
+/// Errors of the precompile calldata parsing; the EVM call fails on any of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvmPrecompileError {
+    /// The input size is not valid for the precompile
+    InvalidInputLength { precompile: EvmPrecompile, len: usize },
+    /// The blake2f final block flag is neither 0 nor 1
+    InvalidFinalFlag,
+    /// A modexp length does not fit in the guest address space
+    LengthOverflow,
+}
+
+/// Returns the 32-byte word at the index, right-padded with zeros
+fn word(input: &[u8], index: usize) -> [u8; 32] {
+    padded(input, 32 * index, 32).try_into().unwrap()
+}
+
+/// Returns `len` bytes starting at `start`, right-padded with zeros
+fn padded(input: &[u8], start: usize, len: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; len];
+    if start < input.len() {
+        let n = (input.len() - start).min(len);
+        bytes[..n].copy_from_slice(&input[start..start + n]);
+    }
+    bytes
+}
+
+/// Converts a 32-byte big-endian integer to little-endian limbs
+fn be_to_limbs(bytes: &[u8; 32]) -> [u64; 4] {
+    let mut limbs = [0u64; 4];
+    for (i, limb) in limbs.iter_mut().enumerate() {
+        *limb = u64::from_be_bytes(bytes[24 - 8 * i..32 - 8 * i].try_into().unwrap());
+    }
+    limbs
+}
+
+/// Converts a 32-byte big-endian modexp length, failing if it does not fit in 32 bits
+fn length(bytes: &[u8; 32]) -> Result<usize, EvmPrecompileError> {
+    if bytes[..28].iter().any(|&byte| byte != 0) {
+        return Err(EvmPrecompileError::LengthOverflow);
+    }
+    Ok(u32::from_be_bytes(bytes[28..].try_into().unwrap()) as usize)
+}
+
+/// Converts a 64-byte EVM G1 point (x, y)
+fn bn254_g1(bytes: &[u8]) -> [u64; 8] {
+    let mut point = [0u64; 8];
+    point[0..4].copy_from_slice(&be_to_limbs(bytes[0..32].try_into().unwrap()));
+    point[4..8].copy_from_slice(&be_to_limbs(bytes[32..64].try_into().unwrap()));
+    point
+}
+
+/// Converts a 128-byte EVM G2 point (x.imaginary, x.real, y.imaginary, y.real)
+fn bn254_g2(bytes: &[u8]) -> [u64; 16] {
+    let mut point = [0u64; 16];
+    for (i, coordinate) in [1, 0, 3, 2].into_iter().enumerate() {
+        let word = bytes[32 * coordinate..32 * coordinate + 32].try_into().unwrap();
+        point[4 * i..4 * i + 4].copy_from_slice(&be_to_limbs(word));
+    }
+    point
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evm_precompiles() {
+        for precompile in EvmPrecompile::ALL {
+            assert_eq!(EvmPrecompile::from_address(&precompile.address()), Some(precompile));
+        }
+        let mut address = EvmPrecompile::Modexp.address();
+        address[0] = 1;
+        assert_eq!(EvmPrecompile::from_address(&address), None);
+
+        // Short ecrecover input, padded with zeros
+        let mut input = vec![0u8; 64];
+        input[31] = 5;
+        input[63] = 28;
+        let call = EvmPrecompile::Ecrecover.parse_call(&input).unwrap();
+        let expected = EvmPrecompileCall::Ecrecover {
+            hash: [5, 0, 0, 0],
+            recovery_id: Some(1),
+            r: [0; 4],
+            s: [0; 4],
+        };
+        assert_eq!(call, expected);
+
+        // modexp 3^2 mod 5, with a truncated modulus
+        let mut input = vec![0u8; 96];
+        input[31] = 1;
+        input[63] = 1;
+        input[95] = 2;
+        input.extend([3, 2, 5]);
+        let call = EvmPrecompile::Modexp.parse_call(&input).unwrap();
+        let expected =
+            EvmPrecompileCall::Modexp { base: vec![3], exponent: vec![2], modulus: vec![5, 0] };
+        assert_eq!(call, expected);
+
+        // G2 coordinates are reordered to real, imaginary
+        let mut input = vec![0u8; EVM_BN254_PAIRING_ELEMENT_SIZE];
+        for (i, value) in [1u8, 2, 3, 4, 5, 6].into_iter().enumerate() {
+            input[32 * i + 31] = value;
+        }
+        let EvmPrecompileCall::Bn254Pairing { g1, g2 } =
+            EvmPrecompile::Bn254Pairing.parse_call(&input).unwrap()
+        else {
+            panic!("expected a pairing call");
+        };
+        assert_eq!((g1[0][0], g1[0][4]), (1, 2));
+        assert_eq!((g2[0][0], g2[0][4], g2[0][8], g2[0][12]), (4, 3, 6, 5));
+        assert_eq!(
+            EvmPrecompile::Bn254Pairing.parse_call(&input[1..]),
+            Err(EvmPrecompileError::InvalidInputLength {
+                precompile: EvmPrecompile::Bn254Pairing,
+                len: EVM_BN254_PAIRING_ELEMENT_SIZE - 1
+            })
+        );
+
+        let mut input = vec![0u8; EVM_BLAKE2F_INPUT_SIZE];
+        input[3] = 12;
+        input[212] = 2;
+        assert_eq!(
+            EvmPrecompile::Blake2f.parse_call(&input),
+            Err(EvmPrecompileError::InvalidFinalFlag)
+        );
+    }
+}
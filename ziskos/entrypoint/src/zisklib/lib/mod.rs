@@ -1,7 +1,10 @@
 mod array_lib;
 mod bigint256;
+mod blake2f_compress;
 mod bls12_381;
 mod bn254;
+mod evm_precompiles;
+mod ripemd160_compress;
 mod secp256k1;
 mod sha256f_compress;
 mod utils;
@@ -9,8 +12,11 @@ mod utils;
 // For public consumption
 pub use array_lib::*;
 pub use bigint256::*;
+pub use blake2f_compress::*;
 pub use bls12_381::*;
 pub use bn254::*;
+pub use evm_precompiles::*;
+pub use ripemd160_compress::*;
 pub use secp256k1::*;
 pub use sha256f_compress::*;
 pub use utils::*;
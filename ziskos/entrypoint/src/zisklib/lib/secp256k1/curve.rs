@@ -2,16 +2,19 @@ use crate::{
     syscalls::{
         syscall_secp256k1_add, syscall_secp256k1_dbl, SyscallPoint256, SyscallSecp256k1AddParams,
     },
-    zisklib::{eq, fcall_msb_pos_256},
+    zisklib::{eq, fcall_msb_pos_256, lt},
 };
 
 use super::{
-    constants::{E_B, G_X, G_Y},
+    constants::{E_B, G_X, G_Y, N},
     field::{
         secp256k1_fp_add, secp256k1_fp_inv, secp256k1_fp_mul, secp256k1_fp_sqrt,
         secp256k1_fp_square,
     },
-    scalar::{secp256k1_fn_inv, secp256k1_fn_mul, secp256k1_fn_reduce},
+    scalar::{
+        secp256k1_fn_batch_inv, secp256k1_fn_inv, secp256k1_fn_is_zero, secp256k1_fn_mul,
+        secp256k1_fn_neg, secp256k1_fn_reduce,
+    },
 };
 
 /// Converts a non-zero point `p` on the Secp256k1 curve from projective coordinates to affine coordinates
@@ -297,10 +300,47 @@ pub fn secp256k1_ecdsa_verify(
     r: &[u64; 4],
     s: &[u64; 4],
 ) -> bool {
-    let s_inv = secp256k1_fn_inv(s);
+    // A zero s has no inverse, and the signature is invalid
+    if secp256k1_fn_is_zero(s) {
+        return false;
+    }
+    ecdsa_verify_with_s_inv(pk, z, r, &secp256k1_fn_inv(s))
+}
 
-    let u1 = secp256k1_fn_mul(z, &s_inv);
-    let u2 = secp256k1_fn_mul(r, &s_inv);
+/// ECDSA signature to verify, with its public key and message hash
+#[derive(Debug)]
+pub struct Secp256k1EcdsaInput {
+    pub pk: SyscallPoint256,
+    pub z: [u64; 4],
+    pub r: [u64; 4],
+    pub s: [u64; 4],
+}
+
+/// Verifies a batch of ECDSA signatures, computing the inverses of every `s` with a single hinted
+/// inverse, and returns the result of every signature, in order. The signatures with a zero `s`
+/// are invalid, without affecting the others
+pub fn secp256k1_ecdsa_verify_batch(inputs: &[Secp256k1EcdsaInput]) -> Vec<bool> {
+    let s: Vec<[u64; 4]> = inputs.iter().map(|input| input.s).collect();
+    let s_inv = secp256k1_fn_batch_inv(&s);
+
+    inputs
+        .iter()
+        .zip(&s_inv)
+        .map(|(input, s_inv)| match s_inv {
+            Some(s_inv) => ecdsa_verify_with_s_inv(&input.pk, &input.z, &input.r, s_inv),
+            None => false,
+        })
+        .collect()
+}
+
+fn ecdsa_verify_with_s_inv(
+    pk: &SyscallPoint256,
+    z: &[u64; 4],
+    r: &[u64; 4],
+    s_inv: &[u64; 4],
+) -> bool {
+    let u1 = secp256k1_fn_mul(z, s_inv);
+    let u2 = secp256k1_fn_mul(r, s_inv);
 
     let (is_infinity, res) = secp256k1_double_scalar_mul_with_g(&u1, &u2, pk);
     if is_infinity {
@@ -310,6 +350,78 @@ pub fn secp256k1_ecdsa_verify(
     eq(&secp256k1_fn_reduce(&res.x), r)
 }
 
+/// ECDSA signature to recover the public key of, with its message hash
+#[derive(Debug)]
+pub struct Secp256k1EcrecoverInput {
+    pub z: [u64; 4],
+    pub r: [u64; 4],
+    pub s: [u64; 4],
+    /// Parity of the y-coordinate of the point with x-coordinate `r`, 0 or 1
+    pub recovery_id: u8,
+}
+
+impl Secp256k1EcrecoverInput {
+    /// Returns whether `r` and `s` are in [1, N-1] and `recovery_id` is 0 or 1
+    fn is_valid(&self) -> bool {
+        let in_range = |x: &[u64; 4]| !secp256k1_fn_is_zero(x) && lt(x, &N);
+        in_range(&self.r) && in_range(&self.s) && self.recovery_id <= 1
+    }
+}
+
+/// Recovers the public key of an ECDSA signature, or returns None if the signature is invalid
+pub fn secp256k1_ecrecover(input: &Secp256k1EcrecoverInput) -> Option<SyscallPoint256> {
+    if !input.is_valid() {
+        return None;
+    }
+    ecrecover_with_r_inv(input, &secp256k1_fn_inv(&input.r))
+}
+
+/// Recovers the public keys of a batch of ECDSA signatures, computing the inverses of every `r`
+/// with a single hinted inverse, and returns the result of every signature, in order. The invalid
+/// signatures, e.g. with a zero `r` or `s`, get None without affecting the others
+pub fn secp256k1_ecrecover_batch(
+    inputs: &[Secp256k1EcrecoverInput],
+) -> Vec<Option<SyscallPoint256>> {
+    // Leave the invalid signatures out of the batch inversion
+    let r: Vec<[u64; 4]> =
+        inputs.iter().map(|input| if input.is_valid() { input.r } else { [0, 0, 0, 0] }).collect();
+    let r_inv = secp256k1_fn_batch_inv(&r);
+
+    inputs
+        .iter()
+        .zip(&r_inv)
+        .map(|(input, r_inv)| r_inv.as_ref().and_then(|r_inv| ecrecover_with_r_inv(input, r_inv)))
+        .collect()
+}
+
+/// Computes the public key Q = r⁻¹·(s·R - z·G) of a valid signature, where R is the point with
+/// x-coordinate r, or returns None if there is no such point or Q is 𝒪
+fn ecrecover_with_r_inv(
+    input: &Secp256k1EcrecoverInput,
+    r_inv: &[u64; 4],
+) -> Option<SyscallPoint256> {
+    // Since r < N < P, r is the x-coordinate of R
+    let mut r_bytes = [0u8; 32];
+    for (i, limb) in input.r.iter().enumerate() {
+        r_bytes[24 - 8 * i..32 - 8 * i].copy_from_slice(&limb.to_be_bytes());
+    }
+    let ((x, y), has_point) = secp256k1_decompress(&r_bytes, input.recovery_id == 1);
+    if !has_point {
+        return None;
+    }
+
+    // Q = u1·G + u2·R, with u1 = -z·r⁻¹ and u2 = s·r⁻¹ != 0; u1 is zero if z ≡ 0, in which case
+    // the double scalar multiplication only adds multiples of R
+    let u1 = secp256k1_fn_neg(&secp256k1_fn_mul(&input.z, r_inv));
+    let u2 = secp256k1_fn_mul(&input.s, r_inv);
+    let (is_infinity, q) = secp256k1_double_scalar_mul_with_g(&u1, &u2, &SyscallPoint256 { x, y });
+    if is_infinity {
+        return None;
+    }
+
+    Some(q)
+}
+
 /// # Safety
 /// - `p_ptr` must point to 12 u64s (projective point: x[4], y[4], z[4])
 /// - `out_ptr` must point to at least 8 u64s (will write affine x[4], y[4])
@@ -421,3 +533,36 @@ pub unsafe extern "C" fn secp256k1_ecdsa_verify_c(
 
     secp256k1_ecdsa_verify(&pk, z, r, s)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_rejects_zero_scalars() {
+        // Signatures with a zero scalar are rejected per entry, as by the single-entry functions,
+        // without inverting anything
+        let (z, r) = ([1, 2, 3, 4], [5, 6, 7, 8]);
+        let zero_s = [[0, 0, 0, 0], N];
+        let inputs: Vec<_> = zero_s
+            .iter()
+            .map(|&s| Secp256k1EcdsaInput { pk: SyscallPoint256 { x: G_X, y: G_Y }, z, r, s })
+            .collect();
+        let expected: Vec<_> =
+            inputs.iter().map(|i| secp256k1_ecdsa_verify(&i.pk, &i.z, &i.r, &i.s)).collect();
+        assert_eq!(secp256k1_ecdsa_verify_batch(&inputs), expected);
+        assert_eq!(expected, [false, false]);
+
+        let invalid = [
+            Secp256k1EcrecoverInput { z, r, s: [0, 0, 0, 0], recovery_id: 0 },
+            Secp256k1EcrecoverInput { z, r: [0, 0, 0, 0], s: r, recovery_id: 1 },
+            Secp256k1EcrecoverInput { z, r: N, s: r, recovery_id: 0 },
+            Secp256k1EcrecoverInput { z, r, s: r, recovery_id: 2 },
+        ];
+        let recovered = secp256k1_ecrecover_batch(&invalid);
+        assert_eq!(recovered.len(), invalid.len());
+        for (input, q) in invalid.iter().zip(&recovered) {
+            assert!(q.is_none() && secp256k1_ecrecover(input).is_none(), "{input:?}");
+        }
+    }
+}
@@ -83,6 +83,50 @@ pub fn secp256k1_fn_inv(x: &[u64; 4]) -> [u64; 4] {
     x_inv
 }
 
+/// Returns whether `x` is zero modulo N, i.e. whether it is 0 or N
+pub fn secp256k1_fn_is_zero(x: &[u64; 4]) -> bool {
+    *x == [0, 0, 0, 0] || *x == N
+}
+
+/// Inverts a batch of elements with a single hinted inverse (Montgomery's trick), at the cost of
+/// 3·(n-1) multiplications for n non-zero elements. The zero elements have no inverse: they get
+/// `None` and are left out of the product, so they do not affect the inverses of the others
+pub fn secp256k1_fn_batch_inv(xs: &[[u64; 4]]) -> Vec<Option<[u64; 4]>> {
+    batch_inv(xs, secp256k1_fn_is_zero, secp256k1_fn_mul, secp256k1_fn_inv)
+}
+
+/// Montgomery's trick, given the zero test, the multiplication and the inversion of the field
+fn batch_inv(
+    xs: &[[u64; 4]],
+    is_zero: impl Fn(&[u64; 4]) -> bool,
+    mul: impl Fn(&[u64; 4], &[u64; 4]) -> [u64; 4],
+    inv: impl Fn(&[u64; 4]) -> [u64; 4],
+) -> Vec<Option<[u64; 4]>> {
+    let mut result = vec![None; xs.len()];
+    let nonzero: Vec<usize> = (0..xs.len()).filter(|&i| !is_zero(&xs[i])).collect();
+    if nonzero.is_empty() {
+        return result;
+    }
+
+    // prefix[j] = product of the first j+1 non-zero elements
+    let mut prefix = Vec::with_capacity(nonzero.len());
+    prefix.push(xs[nonzero[0]]);
+    for &i in &nonzero[1..] {
+        let last = prefix.last().unwrap();
+        prefix.push(mul(last, &xs[i]));
+    }
+
+    // Walk back from the inverse of the whole product, peeling one element at a time
+    let mut acc = inv(prefix.last().unwrap());
+    for j in (1..nonzero.len()).rev() {
+        result[nonzero[j]] = Some(mul(&acc, &prefix[j - 1]));
+        acc = mul(&acc, &xs[nonzero[j]]);
+    }
+    result[nonzero[0]] = Some(acc);
+
+    result
+}
+
 /// # Safety
 /// - `x_ptr` must point to 4 u64s
 /// - `out_ptr` must point to at least 4 u64s
@@ -230,3 +274,50 @@ pub unsafe extern "C" fn secp256k1_fn_inv_c(x_ptr: *const u64, out_ptr: *mut u64
     *out_ptr.add(2) = x_inv[2];
     *out_ptr.add(3) = x_inv[3];
 }
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigUint;
+
+    use super::*;
+
+    fn to_big(x: &[u64; 4]) -> BigUint {
+        BigUint::from_bytes_le(&x.iter().flat_map(|limb| limb.to_le_bytes()).collect::<Vec<_>>())
+    }
+
+    fn from_big(x: &BigUint) -> [u64; 4] {
+        let mut limbs = [0u64; 4];
+        limbs[..x.to_u64_digits().len()].copy_from_slice(&x.to_u64_digits());
+        limbs
+    }
+
+    fn mul(x: &[u64; 4], y: &[u64; 4]) -> [u64; 4] {
+        from_big(&(to_big(x) * to_big(y) % to_big(&N)))
+    }
+
+    fn inv(x: &[u64; 4]) -> [u64; 4] {
+        from_big(&to_big(x).modinv(&to_big(&N)).expect("non-zero element"))
+    }
+
+    #[test]
+    fn test_batch_inv() {
+        // Every entry is inverted as by the single inversion, and the zero entries, 0 and N, get
+        // no inverse without affecting the others
+        let xs = [
+            [0xf9ee4256a589409f, 0xa21a3985f17502d0, 0xb3eb393d00dc480c, 0x142def02c537eced],
+            [0, 0, 0, 0],
+            [1, 0, 0, 0],
+            N_MINUS_ONE,
+            N,
+            [0x1234, 0x5678, 0x9abc, 0xdef0],
+        ];
+        let batch = batch_inv(&xs, secp256k1_fn_is_zero, mul, inv);
+        for (x, x_inv) in xs.iter().zip(&batch) {
+            let expected = if secp256k1_fn_is_zero(x) { None } else { Some(inv(x)) };
+            assert_eq!(*x_inv, expected, "{x:x?}");
+        }
+
+        assert!(batch_inv(&[[0, 0, 0, 0]], secp256k1_fn_is_zero, mul, inv)[0].is_none());
+        assert!(batch_inv(&[], secp256k1_fn_is_zero, mul, inv).is_empty());
+    }
+}